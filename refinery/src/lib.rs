@@ -31,8 +31,28 @@ embedded::migrations::runner().run(&mut conn).unwrap();
 for more examples refer to the [examples](https://github.com/rust-db/refinery/tree/master/examples)
 */
 
+pub use refinery_core::analyze;
 pub use refinery_core::config;
-pub use refinery_core::{error, load_sql_migrations, Error, Migration, Report, Runner, Target};
+pub use refinery_core::introspect;
+pub use refinery_core::lint;
+pub use refinery_core::lockfile;
+pub use refinery_core::manifest;
+#[cfg(feature = "metrics")]
+pub use refinery_core::metrics;
+pub use refinery_core::registry;
+#[cfg(feature = "sea-query")]
+pub use refinery_core::sea_query;
+
+#[cfg(feature = "diff")]
+pub use refinery_core::diff;
+pub use refinery_core::{
+    error, load_sql_migrations, load_sql_migrations_with_depth, rerun_if_migrations_changed,
+    AppliedMigrationRecord, AsyncExecutor, CancelHandle, ChecksumAlgorithm, ChecksumMismatch,
+    DefaultSchemaHistoryDdl, Dialect, Error, FailedStatement, IntegrityIssue, Migration,
+    MigrationLoader, MigrationNaming, MultiTenantRunner, RefineryConnection, Report, Rollout,
+    RolloutStage, Runner, SchemaHistoryDdl, SchemaHistoryRow, ShardFailurePolicy, ShardResults,
+    ShardRunner, Splitter, Target, ValidationIssue, ValidationReport,
+};
 #[doc(hidden)]
 pub use refinery_core::{AsyncMigrate, Migrate};
-pub use refinery_macros::embed_migrations;
+pub use refinery_macros::{collect_migrations, embed_migrations, migration};
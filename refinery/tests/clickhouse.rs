@@ -0,0 +1,178 @@
+#[cfg(feature = "clickhouse")]
+mod clickhouse {
+    use futures::FutureExt;
+    use refinery::{AsyncMigrate, Migration, Runner, Target};
+    use refinery_core::clickhouse::Client;
+    use std::panic::AssertUnwindSafe;
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    fn client(database: &str) -> Client {
+        Client::default()
+            .with_url("http://localhost:8123")
+            .with_user("refinery")
+            .with_password("root")
+            .with_database(database)
+    }
+
+    fn get_migrations() -> Vec<Migration> {
+        let migration1 = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id Int32, name String, city String) ENGINE = MergeTree ORDER BY id;",
+        )
+        .unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_table",
+            "CREATE TABLE cars (id Int32, name String) ENGINE = MergeTree ORDER BY id;",
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            "ALTER TABLE cars ADD COLUMN brand String;",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3]
+    }
+
+    async fn run_test<T: std::future::Future<Output = ()>>(t: T) {
+        let result = AssertUnwindSafe(t).catch_unwind().await;
+        clean_database().await;
+        assert!(result.is_ok());
+    }
+
+    async fn clean_database() {
+        let client = client("default");
+        client
+            .query("DROP DATABASE IF EXISTS refinery_test")
+            .execute()
+            .await
+            .unwrap();
+        client
+            .query("CREATE DATABASE refinery_test")
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn report_contains_applied_migrations() {
+        run_test(async {
+            let mut client = client("refinery_test");
+            let migrations = get_migrations();
+            let report = Runner::new(&migrations)
+                .run_async(&mut client)
+                .await
+                .unwrap();
+
+            let applied_migrations = report.applied_migrations();
+            assert_eq!(3, applied_migrations.len());
+            for (migration, applied) in migrations.iter().zip(applied_migrations) {
+                assert_eq!(migration.version(), applied.version());
+                assert_eq!(migration.name(), applied.name());
+                assert_eq!(migration.checksum(), applied.checksum());
+            }
+
+            let applied_migrations = client
+                .get_applied_migrations(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap();
+            assert_eq!(migrations, applied_migrations);
+
+            let applied_by = client
+                .query(
+                    "SELECT ifNull(applied_by, '') FROM refinery_schema_history WHERE version = 1",
+                )
+                .fetch_one::<String>()
+                .await
+                .unwrap();
+            assert_eq!("refinery", applied_by);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn leaves_failed_migration_partially_applied() {
+        run_test(async {
+            let mut client = client("refinery_test");
+            let mut migrations = get_migrations();
+            migrations[2] = Migration::unapplied(
+                "V3__add_brand_to_cars_table",
+                "ALTER TABLE cars ADD COLUMN brand String;\nALTER TABLE motos ADD COLUMN brand String;",
+            )
+            .unwrap();
+
+            let err = Runner::new(&migrations)
+                .run_async(&mut client)
+                .await
+                .unwrap_err();
+            assert_eq!(2, err.report().unwrap().applied_migrations().len());
+
+            let current = client
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(2, current.version());
+
+            let columns = client
+                .query("SELECT count() FROM system.columns WHERE database = 'refinery_test' AND table = 'cars' AND name = 'brand'")
+                .fetch_one::<u64>()
+                .await
+                .unwrap();
+            assert_eq!(1, columns);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn migrates_to_target_migration() {
+        run_test(async {
+            let mut client = client("refinery_test");
+            let report = Runner::new(&get_migrations())
+                .set_target(Target::Version(2))
+                .run_async(&mut client)
+                .await
+                .unwrap();
+            assert_eq!(2, report.applied_migrations().len());
+
+            let current = client
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(2, current.version());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn applies_migrations_with_question_marks() {
+        run_test(async {
+            let mut client = client("refinery_test");
+            let mut migrations = get_migrations();
+            migrations.push(
+                Migration::unapplied(
+                    "V4__add_person",
+                    "INSERT INTO persons (id, name, city) VALUES (1, 'who?', 'Lisbon');",
+                )
+                .unwrap(),
+            );
+            Runner::new(&migrations)
+                .run_async(&mut client)
+                .await
+                .unwrap();
+
+            let (id, name) = client
+                .query("SELECT id, name FROM persons")
+                .fetch_one::<(i32, String)>()
+                .await
+                .unwrap();
+            assert_eq!(1, id);
+            assert_eq!("who?", name);
+        })
+        .await;
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "duckdb")]
+mod duckdb {
+    use refinery::{
+        config::{Config, ConfigDbType},
+        Migrate, Migration, Runner, Target,
+    };
+    use refinery_core::duckdb::Connection;
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    fn get_migrations() -> Vec<Migration> {
+        let migration1 = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id INTEGER PRIMARY KEY, name VARCHAR(255), city VARCHAR(255));",
+        )
+        .unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_and_motos_table",
+            "CREATE TABLE cars (id INTEGER, name VARCHAR(255));
+            CREATE TABLE motos (id INTEGER, name VARCHAR(255));",
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            "ALTER TABLE cars ADD COLUMN brand VARCHAR(255);",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3]
+    }
+
+    fn broken_migrations() -> Vec<Migration> {
+        let mut migrations = get_migrations();
+        migrations[2] = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            "ALTER TABLE cars ADD COLUMN brand VARCHAR(255);
+            ALTER TABLE trucks ADD COLUMN brand VARCHAR(255);",
+        )
+        .unwrap();
+        migrations
+    }
+
+    #[test]
+    fn report_contains_applied_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let applied_migrations = report.applied_migrations();
+        assert_eq!(3, applied_migrations.len());
+        for (migration, applied) in migrations.iter().zip(applied_migrations) {
+            assert_eq!(migration.version(), applied.version());
+            assert_eq!(migration.name(), applied.name());
+            assert_eq!(migration.checksum(), applied.checksum());
+        }
+
+        let applied_migrations = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+        assert_eq!(migrations, applied_migrations);
+    }
+
+    #[test]
+    fn applies_migration_grouped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        Runner::new(&get_migrations())
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap();
+
+        conn.execute(
+            "INSERT INTO cars (id, name, brand) VALUES (1, 'Model 3', 'Tesla')",
+            [],
+        )
+        .unwrap();
+        let (name, brand): (String, String) = conn
+            .query_row("SELECT name, brand FROM cars", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!("Model 3", name);
+        assert_eq!("Tesla", brand);
+    }
+
+    #[test]
+    fn updates_to_last_working_if_not_grouped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = Runner::new(&broken_migrations())
+            .run(&mut conn)
+            .unwrap_err();
+        assert_eq!(2, err.report().unwrap().applied_migrations().len());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+
+    #[test]
+    fn doesnt_update_to_last_working_if_grouped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let result = Runner::new(&broken_migrations())
+            .set_grouped(true)
+            .run(&mut conn);
+        assert!(result.is_err());
+
+        let tables: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM information_schema.tables WHERE table_name = 'cars'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(0, tables);
+    }
+
+    #[test]
+    fn migrates_to_target_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = Runner::new(&get_migrations())
+            .set_target(Target::Version(2))
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+
+    #[test]
+    fn rolls_back_migrations_failing_checks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id INTEGER, active BOOLEAN);
+            INSERT INTO persons VALUES (1, false), (2, false);",
+        )
+        .unwrap();
+        let backfill = Migration::unapplied(
+            "V2__backfill",
+            "UPDATE persons SET active = true WHERE id = 1;
+            -- refinery:check
+            SELECT NOT EXISTS (SELECT 1 FROM persons WHERE NOT active);",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[initial.clone(), backfill])
+            .run(&mut conn)
+            .unwrap_err();
+        assert_eq!(1, err.report().unwrap().applied_migrations().len());
+        assert!(err.to_string().contains("refinery_check_1"));
+        let active: i64 = conn
+            .query_row("SELECT count(*) FROM persons WHERE active", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(0, active);
+
+        let backfill = Migration::unapplied(
+            "V2__backfill",
+            "UPDATE persons SET active = true;
+            -- refinery:check
+            SELECT NOT EXISTS (SELECT 1 FROM persons WHERE NOT active);",
+        )
+        .unwrap();
+        let report = Runner::new(&[initial, backfill]).run(&mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+    }
+
+    #[test]
+    fn migrates_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data").join("analytics.duckdb");
+        let mut config = Config::new(ConfigDbType::DuckDb)
+            .set_db_path(path.to_str().unwrap())
+            .set_create_db_if_missing(true);
+
+        let runner = Runner::new(&get_migrations());
+        let report = runner.run(&mut config).unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+        assert!(path.exists());
+
+        let conn = Connection::open(&path).unwrap();
+        let applied: i64 = conn
+            .query_row("SELECT count(*) FROM refinery_schema_history", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(3, applied);
+        drop(conn);
+
+        let applied = runner.get_applied_migrations(&mut config).unwrap();
+        assert_eq!(3, applied.len());
+
+        // without the option a missing database isn't created
+        let missing = dir.path().join("missing.duckdb");
+        let mut config = Config::new(ConfigDbType::DuckDb).set_db_path(missing.to_str().unwrap());
+        assert!(runner.run(&mut config).is_err());
+        assert!(!missing.exists());
+    }
+}
@@ -0,0 +1,146 @@
+use barrel::backend::Sqlite as Sql;
+
+#[cfg(feature = "libsql")]
+mod libsql {
+    use refinery::{embed_migrations, AsyncMigrate, Migration, Target};
+    use refinery_core::libsql::{Builder, Connection};
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    fn get_migrations() -> Vec<Migration> {
+        embed_migrations!("./tests/migrations");
+
+        let migration1 =
+            Migration::unapplied("V1__initial.rs", &migrations::V1__initial::migration()).unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_and_motos_table.sql",
+            include_str!("./migrations/V1-2/V2__add_cars_and_motos_table.sql"),
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            include_str!("./migrations/V3/V3__add_brand_to_cars_table.sql"),
+        )
+        .unwrap();
+
+        let migration4 = Migration::unapplied(
+            "V4__add_year_to_motos_table.rs",
+            &migrations::V4__add_year_to_motos_table::migration(),
+        )
+        .unwrap();
+
+        let migration5 = Migration::unapplied(
+            "V5__add_year_field_to_cars",
+            "ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3, migration4, migration5]
+    }
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations");
+    }
+
+    mod broken {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_broken");
+    }
+
+    // the driver runs the same on a local database as on a remote one built with `Builder::new_remote`
+    async fn connection() -> Connection {
+        Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap()
+            .connect()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn report_contains_applied_migrations() {
+        let mut conn = connection().await;
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        let migrations = get_migrations();
+        let applied_migrations = report.applied_migrations();
+
+        assert_eq!(4, applied_migrations.len());
+        for (migration, applied) in migrations.iter().zip(applied_migrations) {
+            assert_eq!(migration.version(), applied.version());
+            assert_eq!(migration.name(), applied.name());
+            assert_eq!(migration.checksum(), applied.checksum());
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_migration_grouped() {
+        let mut conn = connection().await;
+        embedded::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        conn.execute(
+            "INSERT INTO persons (name, city) VALUES ('John Legend', 'New York')",
+            (),
+        )
+        .await
+        .unwrap();
+        let mut rows = conn
+            .query("SELECT name, city FROM persons", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!("John Legend", row.get::<String>(0).unwrap());
+        assert_eq!("New York", row.get::<String>(1).unwrap());
+    }
+
+    #[tokio::test]
+    async fn updates_to_last_working_if_not_grouped() {
+        let mut conn = connection().await;
+        let result = broken::migrations::runner().run_async(&mut conn).await;
+        assert!(result.is_err());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        let err = result.unwrap_err();
+        assert_eq!(2, current.version());
+        assert_eq!(2, err.report().unwrap().applied_migrations().len());
+    }
+
+    #[tokio::test]
+    async fn migrates_to_target_migration() {
+        let mut conn = connection().await;
+        let migrations = get_migrations();
+        let report = conn
+            .migrate(
+                &migrations,
+                true,
+                true,
+                false,
+                Target::Version(3),
+                DEFAULT_TABLE_NAME,
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+
+        let applied_migrations = conn
+            .get_applied_migrations(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap();
+        assert_eq!(3, applied_migrations.len());
+        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+    }
+}
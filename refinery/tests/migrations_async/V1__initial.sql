@@ -0,0 +1,5 @@
+CREATE TABLE persons (
+    id INTEGER PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    city VARCHAR(255) NOT NULL
+);
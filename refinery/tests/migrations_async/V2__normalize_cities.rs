@@ -0,0 +1,28 @@
+use refinery::error::WrapMigrationError;
+use refinery::{AsyncExecutor, Error};
+use refinery_core::sqlx::{self, Row, SqliteConnection};
+
+pub async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), Error> {
+    executor
+        .execute("INSERT INTO persons (name, city) VALUES ('John Legend', 'new york'), ('Ada Lovelace', 'london')")
+        .await?;
+
+    let conn = executor
+        .downcast_mut::<SqliteConnection>()
+        .expect("migration must run on a sqlite connection");
+    let rows = sqlx::query("SELECT id, city FROM persons")
+        .fetch_all(&mut *conn)
+        .await
+        .migration_err("error querying persons", None)?;
+    for row in rows {
+        let id: i64 = row.get(0);
+        let city: String = row.get(1);
+        sqlx::query("UPDATE persons SET city = ? WHERE id = ?")
+            .bind(city.to_uppercase())
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .migration_err("error normalizing city", None)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,3 @@
+CREATE TABLE users(id int);
+-- refinery:down
+DROP TABLE users;
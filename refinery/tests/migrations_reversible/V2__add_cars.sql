@@ -0,0 +1,3 @@
+CREATE TABLE cars(id int);
+-- refinery:down
+DROP TABLE cars;
@@ -117,6 +117,7 @@ mod mysql_async {
 
             embedded::migrations::runner()
                 .set_grouped(true)
+                .set_grouped_fallback(true)
                 .run_async(&mut pool)
                 .await
                 .unwrap();
@@ -207,6 +208,7 @@ mod mysql_async {
 
             embedded::migrations::runner()
                 .set_grouped(true)
+                .set_grouped_fallback(true)
                 .run_async(&mut pool)
                 .await
                 .unwrap();
@@ -261,6 +263,7 @@ mod mysql_async {
 
             embedded::migrations::runner()
                 .set_grouped(true)
+                .set_grouped_fallback(true)
                 .run_async(&mut pool)
                 .await
                 .unwrap();
@@ -398,6 +401,7 @@ mod mysql_async {
 
             let report = embedded::migrations::runner()
                 .set_grouped(true)
+                .set_grouped_fallback(true)
                 .set_target(Target::Version(3))
                 .run_async(&mut pool)
                 .await
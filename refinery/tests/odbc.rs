@@ -0,0 +1,125 @@
+#[cfg(feature = "odbc")]
+mod odbc {
+    use refinery::{Migrate, Migration, Runner, Target};
+    use refinery_core::odbc_api::{Connection, ConnectionOptions, Cursor, Environment};
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    // migrated through the SQLite ODBC driver, registered as `SQLite3` by libsqliteodbc
+    fn connect<'env>(env: &'env Environment, dir: &tempfile::TempDir) -> Connection<'env> {
+        let path = dir.path().join("refinery.db");
+        env.connect_with_connection_string(
+            &format!("Driver={{SQLite3}};Database={}", path.display()),
+            ConnectionOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn count(conn: &Connection<'_>, query: &str) -> i64 {
+        let mut cursor = conn.execute(query, (), None).unwrap().unwrap();
+        let mut row = cursor.next_row().unwrap().unwrap();
+        let mut count = 0i64;
+        row.get_data(1, &mut count).unwrap();
+        count
+    }
+
+    fn get_migrations() -> Vec<Migration> {
+        let migration1 = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id INTEGER PRIMARY KEY, name VARCHAR(255), city VARCHAR(255));",
+        )
+        .unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_and_motos_table",
+            "CREATE TABLE cars (id INTEGER, name VARCHAR(255));
+            CREATE TABLE motos (id INTEGER, name VARCHAR(255));",
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            "ALTER TABLE cars ADD COLUMN brand VARCHAR(255);",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3]
+    }
+
+    #[test]
+    fn report_contains_applied_migrations() {
+        let env = Environment::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = connect(&env, &dir);
+        let migrations = get_migrations();
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let applied_migrations = report.applied_migrations();
+        assert_eq!(3, applied_migrations.len());
+        for (migration, applied) in migrations.iter().zip(applied_migrations) {
+            assert_eq!(migration.version(), applied.version());
+            assert_eq!(migration.name(), applied.name());
+            assert_eq!(migration.checksum(), applied.checksum());
+        }
+
+        let applied_migrations = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+        assert_eq!(migrations, applied_migrations);
+    }
+
+    #[test]
+    fn creates_migration_table_once() {
+        let env = Environment::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = connect(&env, &dir);
+        Runner::new(&get_migrations()).run(&mut conn).unwrap();
+        let report = Runner::new(&get_migrations()).run(&mut conn).unwrap();
+        assert!(report.applied_migrations().is_empty());
+
+        assert_eq!(
+            3,
+            count(&conn, "SELECT count(*) FROM refinery_schema_history")
+        );
+    }
+
+    #[test]
+    fn rolls_back_failing_migration() {
+        let env = Environment::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = connect(&env, &dir);
+        let mut migrations = get_migrations();
+        migrations[2] = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            "INSERT INTO cars (id, name) VALUES (1, 'Model 3');
+            ALTER TABLE trucks ADD COLUMN brand VARCHAR(255);",
+        )
+        .unwrap();
+
+        let err = Runner::new(&migrations).run(&mut conn).unwrap_err();
+        assert_eq!(2, err.report().unwrap().applied_migrations().len());
+        assert_eq!(0, count(&conn, "SELECT count(*) FROM cars"));
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+
+    #[test]
+    fn migrates_to_target_migration() {
+        let env = Environment::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = connect(&env, &dir);
+        let report = Runner::new(&get_migrations())
+            .set_target(Target::Version(2))
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+}
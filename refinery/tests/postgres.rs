@@ -5,7 +5,10 @@ mod postgres {
     use assert_cmd::prelude::*;
     use predicates::str::contains;
     use refinery::{
-        config::Config, embed_migrations, error::Kind, Migrate, Migration, Runner, Target,
+        config::{Config, SslMode},
+        embed_migrations,
+        error::Kind,
+        IntegrityIssue, Migrate, Migration, MultiTenantRunner, Runner, Target,
     };
     use refinery_core::postgres::{Client, NoTls};
     use std::process::Command;
@@ -548,6 +551,157 @@ mod postgres {
         });
     }
 
+    #[test]
+    fn migrates_from_native_config() {
+        run_test(|| {
+            let mut config = refinery_core::postgres::Config::from_str(&db_uri()).unwrap();
+
+            let report = embedded::migrations::runner().run(&mut config).unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let last = embedded::migrations::runner()
+                .get_last_applied_migration(&mut config)
+                .unwrap()
+                .unwrap();
+            assert_eq!(4, last.version());
+        })
+    }
+
+    #[test]
+    fn creates_migration_table_on_schema() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            client
+                .batch_execute("DROP SCHEMA IF EXISTS audit CASCADE; CREATE SCHEMA audit;")
+                .unwrap();
+            let runner = embedded::migrations::runner().set_migration_table_schema("audit");
+
+            let report = runner.run(&mut client).unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let schemas: Vec<String> = client
+                .query(
+                    "SELECT table_schema::text FROM information_schema.tables WHERE table_name = $1",
+                    &[&DEFAULT_TABLE_NAME],
+                )
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            assert_eq!(vec!["audit".to_string()], schemas);
+            assert_eq!(4, runner.get_applied_migrations(&mut client).unwrap().len());
+
+            client.batch_execute("DROP SCHEMA audit CASCADE").unwrap();
+        })
+    }
+
+    #[test]
+    fn migrates_tenant_schemas() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            client
+                .batch_execute(
+                    "DROP SCHEMA IF EXISTS tenant_a CASCADE; CREATE SCHEMA tenant_a;
+                     DROP SCHEMA IF EXISTS \"tenant-b\" CASCADE; CREATE SCHEMA \"tenant-b\";",
+                )
+                .unwrap();
+            let runner =
+                MultiTenantRunner::new(embedded::migrations::runner(), &["tenant_a", "tenant-b"]);
+
+            let reports = runner.run(&mut client).unwrap();
+            assert_eq!(2, reports.len());
+            assert_eq!("tenant_a", reports[0].0);
+            assert_eq!(4, reports[0].1.applied_migrations().len());
+            assert_eq!("tenant-b", reports[1].0);
+            assert_eq!(4, reports[1].1.applied_migrations().len());
+
+            let schemas: Vec<String> = client
+                .query(
+                    "SELECT table_schema::text FROM information_schema.tables WHERE table_name = $1 ORDER BY 1",
+                    &[&DEFAULT_TABLE_NAME],
+                )
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            assert_eq!(
+                vec!["tenant-b".to_string(), "tenant_a".to_string()],
+                schemas
+            );
+            let persons: i64 = client
+                .query_one(
+                    "SELECT count(*) FROM information_schema.tables WHERE table_name = 'persons'",
+                    &[],
+                )
+                .unwrap()
+                .get(0);
+            assert_eq!(2, persons);
+
+            // the tenants already migrated have nothing pending
+            let reports = runner.run(&mut client).unwrap();
+            assert!(reports
+                .iter()
+                .all(|(_, report)| report.applied_migrations().is_empty()));
+
+            let err = MultiTenantRunner::new(embedded::migrations::runner(), &["tenant_c"])
+                .run(&mut client)
+                .unwrap_err();
+            assert!(matches!(err.kind(), Kind::TenantAborted(tenant, _) if tenant == "tenant_c"));
+
+            client
+                .batch_execute("DROP SCHEMA tenant_a CASCADE; DROP SCHEMA \"tenant-b\" CASCADE;")
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn creates_database_if_missing_from_config() {
+        let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+        client
+            .execute("DROP DATABASE IF EXISTS refinery_created_test", &[])
+            .unwrap();
+        let mut config = Config::from_str(&db_uri())
+            .unwrap()
+            .set_db_name("refinery_created_test")
+            .set_create_db_if_missing(true);
+
+        let report = embedded::migrations::runner().run(&mut config).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        // the database already exists now
+        let report = embedded::migrations::runner().run(&mut config).unwrap();
+        assert!(report.applied_migrations().is_empty());
+
+        client
+            .execute("DROP DATABASE refinery_created_test", &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn fails_creating_database_without_permission() {
+        let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+        client
+            .batch_execute(
+                "DROP ROLE IF EXISTS refinery_no_createdb;
+                CREATE ROLE refinery_no_createdb LOGIN NOCREATEDB;",
+            )
+            .unwrap();
+        let mut config = Config::from_str(&db_uri())
+            .unwrap()
+            .set_db_user("refinery_no_createdb")
+            .set_db_name("refinery_denied_test")
+            .set_create_db_if_missing(true);
+
+        let err = embedded::migrations::runner().run(&mut config).unwrap_err();
+        assert!(
+            matches!(err.kind(), Kind::CreateDatabaseDenied(name, _) if name == "refinery_denied_test")
+        );
+
+        client
+            .execute("DROP ROLE refinery_no_createdb", &[])
+            .unwrap();
+    }
+
     #[test]
     fn migrates_from_config() {
         run_test(|| {
@@ -584,6 +738,27 @@ mod postgres {
         })
     }
 
+    #[test]
+    fn migrates_from_config_with_ssl_mode() {
+        run_test(|| {
+            let mut config = Config::from_str(&db_uri())
+                .unwrap()
+                .set_ssl_mode(SslMode::Disable);
+            let report = Runner::new(&get_migrations()).run(&mut config).unwrap();
+            assert_eq!(5, report.applied_migrations().len());
+
+            // the client certificate is useless without its key
+            let mut config = Config::from_str(&format!("{}?sslcert=client.crt", db_uri())).unwrap();
+            let err = Runner::new(&get_migrations()).run(&mut config).unwrap_err();
+            match err.kind() {
+                Kind::ConfigError(msg) => {
+                    assert!(msg.contains("ssl_cert and ssl_key must be set together"))
+                }
+                _ => panic!("test failed"),
+            }
+        })
+    }
+
     #[test]
     fn migrate_from_config_report_contains_migrations() {
         run_test(|| {
@@ -713,6 +888,306 @@ mod postgres {
         });
     }
 
+    #[test]
+    fn reports_integrity_issues() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            client
+                .batch_execute(
+                    "CREATE TABLE owners (id int PRIMARY KEY);
+                    CREATE TABLE pets (id int PRIMARY KEY, owner_id int, name text);
+                    INSERT INTO owners (id) VALUES (1);
+                    INSERT INTO pets (id, owner_id, name) VALUES (1, 1, 'rex'), (2, 2, 'rex');
+                    ALTER TABLE pets ADD CONSTRAINT pets_owner_id_fkey FOREIGN KEY (owner_id) REFERENCES owners(id) NOT VALID;",
+                )
+                .unwrap();
+            // a failed concurrent build leaves the index invalid
+            assert!(client
+                .batch_execute("CREATE UNIQUE INDEX CONCURRENTLY pets_name ON pets (name)")
+                .is_err());
+
+            let report = embedded::migrations::runner()
+                .set_integrity_checks(true)
+                .run(&mut client)
+                .unwrap();
+            assert_eq!(
+                &vec![
+                    IntegrityIssue::ForeignKeyViolation {
+                        table: "pets".into(),
+                        references: "owners".into()
+                    },
+                    IntegrityIssue::UnvalidatedConstraint {
+                        table: "pets".into(),
+                        constraint: "pets_owner_id_fkey".into()
+                    },
+                    IntegrityIssue::InvalidIndex {
+                        table: "pets".into(),
+                        index: "pets_name".into()
+                    },
+                ],
+                report.integrity_issues()
+            );
+
+            client
+                .batch_execute(
+                    "DELETE FROM pets WHERE id = 2;
+                    ALTER TABLE pets VALIDATE CONSTRAINT pets_owner_id_fkey;
+                    REINDEX INDEX pets_name;",
+                )
+                .unwrap();
+            let report = embedded::migrations::runner()
+                .set_integrity_checks(true)
+                .run(&mut client)
+                .unwrap();
+            assert!(report.integrity_issues().is_empty());
+        })
+    }
+
+    #[test]
+    fn rolls_back_migrations_failing_checks() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            let initial = Migration::unapplied(
+                "V1__initial",
+                "CREATE TABLE people (id int, active boolean); INSERT INTO people VALUES (1, false), (2, false);",
+            )
+            .unwrap();
+            let backfill = Migration::unapplied(
+                "V2__backfill",
+                "UPDATE people SET active = true WHERE id = 1;
+                -- refinery:check
+                SELECT count(*) FROM people;
+                SELECT bool_and(active) FROM people;",
+            )
+            .unwrap();
+
+            let err = Runner::new(&[initial.clone(), backfill])
+                .run(&mut client)
+                .unwrap_err();
+            assert_eq!(1, err.report().unwrap().applied_migrations().len());
+            assert!(format!("{:?}", err).contains("refinery_check_2"));
+            let active: i64 = client
+                .query_one("SELECT count(*) FROM people WHERE active", &[])
+                .unwrap()
+                .get(0);
+            assert_eq!(0, active);
+
+            let backfill = Migration::unapplied(
+                "V2__backfill",
+                "UPDATE people SET active = true;
+                -- refinery:check
+                SELECT count(*) FROM people;
+                SELECT bool_and(active) FROM people;",
+            )
+            .unwrap();
+            let report = Runner::new(&[initial, backfill]).run(&mut client).unwrap();
+            assert_eq!(1, report.applied_migrations().len());
+        })
+    }
+
+    #[test]
+    fn cancels_migrations_exceeding_timeout() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            let migrations = [
+                Migration::unapplied("V1__initial", "CREATE TABLE people (id int);").unwrap(),
+                Migration::unapplied(
+                    "V2__slow",
+                    "-- refinery:timeout=100ms\nINSERT INTO people VALUES (1);\nSELECT pg_sleep(2);",
+                )
+                .unwrap(),
+            ];
+
+            let err = Runner::new(&migrations).run(&mut client).unwrap_err();
+            assert!(format!("{:?}", err).contains("statement timeout"));
+            assert_eq!(1, err.report().unwrap().applied_migrations().len());
+            let count: i64 = client
+                .query_one("SELECT count(*) FROM people", &[])
+                .unwrap()
+                .get(0);
+            assert_eq!(0, count);
+        })
+    }
+
+    #[test]
+    fn introspects_schema() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            embedded::migrations::runner().run(&mut client).unwrap();
+            client
+                .batch_execute(
+                    "CREATE TABLE owners (id int PRIMARY KEY, email text NOT NULL UNIQUE);
+                    CREATE TABLE pets (id int PRIMARY KEY, owner_id int REFERENCES owners(id), name text DEFAULT 'rex');
+                    CREATE INDEX pets_name ON pets (name);",
+                )
+                .unwrap();
+
+            let schema = refinery::introspect::snapshot(&mut client).unwrap();
+            assert!(schema.table(DEFAULT_TABLE_NAME).is_some());
+
+            let owners = schema.table("owners").unwrap();
+            assert_eq!("PRIMARY KEY (id)", owners.constraints["owners_pkey"]);
+            assert_eq!("UNIQUE (email)", owners.constraints["owners_email_key"]);
+            assert!(!owners.column("email").unwrap().nullable);
+            assert!(owners.indexes.is_empty());
+
+            let pets = schema.table("pets").unwrap();
+            assert_eq!(
+                Some("'rex'::text"),
+                pets.column("name").unwrap().default.as_deref()
+            );
+            assert_eq!(
+                "FOREIGN KEY (owner_id) REFERENCES owners(id)",
+                pets.constraints["pets_owner_id_fkey"]
+            );
+            assert_eq!(
+                "CREATE INDEX pets_name ON pets USING btree (name)",
+                pets.indexes["pets_name"]
+            );
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "diff")]
+    fn diffs_schema_sql() {
+        use refinery::diff::{Diff, DiffTarget};
+
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            embedded::migrations::runner().run(&mut client).unwrap();
+            client
+                .batch_execute("CREATE TABLE people (id int PRIMARY KEY, name varchar(255));")
+                .unwrap();
+
+            let schema_sql =
+                "CREATE TABLE people (id int PRIMARY KEY, name text NOT NULL, city varchar(255));
+                CREATE INDEX people_city ON people (city);";
+            let sql = Diff::new()
+                .sql(&mut client, DiffTarget::SchemaSql(schema_sql))
+                .unwrap()
+                .unwrap();
+            assert!(sql.contains("ALTER TABLE people ALTER COLUMN name TYPE text;"));
+            assert!(sql.contains("ALTER TABLE people ALTER COLUMN name SET NOT NULL;"));
+            assert!(sql.contains("ALTER TABLE people ADD COLUMN city character varying(255);"));
+            assert!(sql.contains("CREATE INDEX people_city ON people USING btree (city);"));
+            // tables created by the migrations aren't on the schema sql
+            assert!(sql.contains("DROP TABLE cars;"));
+            assert!(sql.contains("DROP TABLE persons;"));
+            assert!(!sql.contains(DEFAULT_TABLE_NAME));
+
+            client.batch_execute(&sql).unwrap();
+            let sql = Diff::new()
+                .sql(&mut client, DiffTarget::SchemaSql(schema_sql))
+                .unwrap();
+            assert_eq!(None, sql);
+        })
+    }
+
+    #[test]
+    fn records_connection_user_as_applied_by() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history(
+                         version INT4 NOT NULL,
+                         name VARCHAR(255) NOT NULL,
+                         applied_on VARCHAR(255),
+                         checksum VARCHAR(255),
+                         PRIMARY KEY (version, name));",
+                )
+                .unwrap();
+
+            // the columns are added to the tables of previous versions
+            embedded::migrations::runner().run(&mut client).unwrap();
+            let rows = client
+                .query(
+                    "SELECT execution_time_ms, applied_by, CURRENT_USER::text
+                     FROM refinery_schema_history",
+                    &[],
+                )
+                .unwrap();
+            assert_eq!(4, rows.len());
+            for row in rows {
+                assert!(row.get::<_, Option<i64>>(0).is_some());
+                assert_eq!(Some(row.get::<_, String>(2)), row.get(1));
+            }
+        })
+    }
+
+    #[test]
+    fn migrates_inside_transaction() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+
+            // the migrations are rolled back with the transaction they are applied on
+            let mut transaction = client.transaction().unwrap();
+            let report = embedded::migrations::runner()
+                .run(&mut transaction)
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+            transaction.rollback().unwrap();
+            let tables: i64 = client
+                .query_one(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = 'public'",
+                    &[],
+                )
+                .unwrap()
+                .get(0);
+            assert_eq!(0, tables);
+
+            // a failing migration only rolls back its savepoint, leaving the transaction usable
+            let mut transaction = client.transaction().unwrap();
+            let err = broken::migrations::runner()
+                .run(&mut transaction)
+                .unwrap_err();
+            assert_eq!(2, err.report().unwrap().applied_migrations().len());
+            transaction.commit().unwrap();
+            let current = client
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .unwrap()
+                .unwrap();
+            assert_eq!(2, current.version());
+        })
+    }
+
+    #[test]
+    fn creates_indexes_concurrently_outside_of_a_transaction() {
+        run_test(|| {
+            let mut client = Client::connect(&db_uri(), NoTls).unwrap();
+            let initial =
+                Migration::unapplied("V1__initial", "CREATE TABLE persons (id int, name text);")
+                    .unwrap();
+            let index = Migration::unapplied(
+                "V2__index_persons",
+                "CREATE INDEX CONCURRENTLY persons_name ON persons (name);",
+            )
+            .unwrap();
+
+            let err = Runner::new(&[initial.clone(), index])
+                .run(&mut client)
+                .unwrap_err();
+            assert_eq!(1, err.report().unwrap().applied_migrations().len());
+
+            let index = Migration::unapplied(
+                "V2__index_persons",
+                "-- refinery:no-transaction\nCREATE INDEX CONCURRENTLY persons_name ON persons (name);",
+            )
+            .unwrap();
+            let report = Runner::new(&[initial, index]).run(&mut client).unwrap();
+            assert_eq!(1, report.applied_migrations().len());
+
+            let indexes: i64 = client
+                .query_one(
+                    "SELECT COUNT(*) FROM pg_indexes WHERE indexname = 'persons_name'",
+                    &[],
+                )
+                .unwrap()
+                .get(0);
+            assert_eq!(1, indexes);
+        })
+    }
+
     #[test]
     fn migrates_from_cli() {
         run_test(|| {
@@ -8,12 +8,17 @@ mod rusqlite {
         config::{Config, ConfigDbType},
         embed_migrations,
         error::Kind,
-        Migrate, Migration, Runner, Target,
+        CancelHandle, ChecksumAlgorithm, DefaultSchemaHistoryDdl, Dialect, IntegrityIssue, Migrate,
+        Migration, MultiTenantRunner, RefineryConnection, Rollout, RolloutStage, Runner,
+        SchemaHistoryDdl, SchemaHistoryRow, ShardFailurePolicy, ShardRunner, Splitter, Target,
+        ValidationIssue,
     };
     use refinery_core::rusqlite::Error;
     use refinery_core::rusqlite::{Connection, OptionalExtension};
+    use std::collections::HashMap;
     use std::fs::{self, File};
     use std::process::Command;
+    use std::sync::{Arc, Mutex};
     use time::OffsetDateTime;
 
     const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
@@ -28,11 +33,51 @@ mod rusqlite {
         embed_migrations!("./tests/migrations_broken");
     }
 
+    mod top_level {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations", max_depth = 0);
+    }
+
+    mod several {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations/V1-2", "./tests/migrations/V3");
+    }
+
+    mod timestamp {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_timestamp", naming = "timestamp");
+    }
+
     mod missing {
         use refinery::embed_migrations;
         embed_migrations!("./tests/migrations_missing");
     }
 
+    mod reversible {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_reversible");
+    }
+
+    mod omit_down {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_reversible", omit_down = true);
+    }
+
+    mod registered {
+        use refinery::{migration, Dialect};
+
+        #[migration(version = 5, name = "add_colour_to_cars_table")]
+        fn colour() -> String {
+            "ALTER TABLE cars ADD colour varchar(20);".into()
+        }
+
+        #[migration(version = 6)]
+        fn add_doors_to_cars_table(dialect: Dialect) -> String {
+            assert_eq!(Dialect::Sqlite, dialect);
+            "ALTER TABLE cars ADD doors int;".into()
+        }
+    }
+
     fn run_test<T>(test: T)
     where
         T: FnOnce() + std::panic::UnwindSafe,
@@ -151,6 +196,81 @@ mod rusqlite {
         assert_eq!(DEFAULT_TABLE_NAME, table_name);
     }
 
+    #[test]
+    fn creates_migration_table_with_custom_ddl() {
+        struct IndexedDdl;
+
+        impl SchemaHistoryDdl for IndexedDdl {
+            fn create_table_statements(
+                &self,
+                migration_table_name: &str,
+                dialect: Option<Dialect>,
+            ) -> Vec<String> {
+                let mut statements =
+                    DefaultSchemaHistoryDdl.create_table_statements(migration_table_name, dialect);
+                statements.push(format!(
+                    "CREATE INDEX IF NOT EXISTS {0}_applied_on ON {0}(applied_on)",
+                    migration_table_name
+                ));
+                statements
+            }
+        }
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = embedded::migrations::runner()
+            .set_schema_history_ddl(IndexedDdl)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        let index: String = conn
+            .query_row(
+                "SELECT tbl_name FROM sqlite_master WHERE type = 'index' AND name = ?1",
+                [format!("{}_applied_on", DEFAULT_TABLE_NAME)],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(DEFAULT_TABLE_NAME, index);
+
+        // the statements don't fail on the existing table
+        let report = embedded::migrations::runner()
+            .set_schema_history_ddl(IndexedDdl)
+            .run(&mut conn)
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+    }
+
+    #[test]
+    fn yields_schema_history_ddl_error_from_iter() {
+        struct BrokenDdl;
+
+        impl SchemaHistoryDdl for BrokenDdl {
+            fn create_table_statements(
+                &self,
+                _migration_table_name: &str,
+                _dialect: Option<Dialect>,
+            ) -> Vec<String> {
+                vec!["NOT VALID SQL".into()]
+            }
+        }
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = embedded::migrations::runner().set_schema_history_ddl(BrokenDdl);
+        let mut iter = runner.run_iter(&mut conn);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn yields_invalid_migration_table_name_from_iter() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut runner = embedded::migrations::runner().set_migration_table_schema("main");
+        runner.set_migration_table_name("main.history").unwrap();
+        let mut iter = runner.run_iter(&mut conn);
+        let err = iter.next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationTableName(..)));
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn creates_migration_table_iter() {
         let mut conn = Connection::open_in_memory().unwrap();
@@ -496,154 +616,1709 @@ mod rusqlite {
     }
 
     #[test]
-    fn migrates_to_target_migration_iter() {
+    fn migrates_single_migration() {
         let mut conn = Connection::open_in_memory().unwrap();
+        let runner = embedded::migrations::runner();
 
-        let applied_migrations = embedded::migrations::runner()
-            .set_target(Target::Version(3))
-            .run_iter(&mut conn)
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap();
+        let err = runner.run_single(2, &mut conn).unwrap_err();
+        match err.kind() {
+            Kind::UnappliedPredecessor(predecessor, migration) => {
+                assert_eq!(1, predecessor.version());
+                assert_eq!(2, migration.version());
+            }
+            _ => panic!("failed test"),
+        }
+
+        let report = runner.run_single(1, &mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        let report = runner.run_single(2, &mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert_eq!(2, report.applied_migrations()[0].version());
 
         let current = conn
             .get_last_applied_migration(DEFAULT_TABLE_NAME)
             .unwrap()
             .unwrap();
+        assert_eq!(2, current.version());
 
-        let migrations = get_migrations();
+        let err = runner.run_single(2, &mut conn).unwrap_err();
+        assert!(matches!(err.kind(), Kind::NotPending(_)));
+        let err = runner.run_single(42, &mut conn).unwrap_err();
+        assert!(matches!(err.kind(), Kind::VersionNotFound(42)));
+    }
 
-        assert_eq!(3, current.version());
+    #[test]
+    fn force_reruns_applied_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied("V2__view", "CREATE VIEW ids AS SELECT id FROM persons;").unwrap(),
+        ];
+        Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let fixed = [
+            migrations[0].clone(),
+            Migration::unapplied(
+                "V2__view",
+                "DROP VIEW ids; CREATE VIEW ids AS SELECT id FROM persons WHERE id > 0;",
+            )
+            .unwrap(),
+        ];
+        let runner = Runner::new(&fixed);
+        let err = runner.run(&mut conn).unwrap_err();
+        assert!(matches!(err.kind(), Kind::DivergentVersion(_, _)));
+
+        let report = runner.force_rerun(2, &mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(fixed[1].checksum(), current.checksum());
+        runner.run(&mut conn).unwrap();
 
-        assert_eq!(3, applied_migrations.len());
+        let err = Runner::new(&[Migration::unapplied("V3__new", "SELECT 1;").unwrap()])
+            .force_rerun(3, &mut conn)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::NotApplied(_)));
+    }
 
-        assert_eq!(migrations[0].version(), applied_migrations[0].version());
-        assert_eq!(migrations[1].version(), applied_migrations[1].version());
-        assert_eq!(migrations[2].version(), applied_migrations[2].version());
+    #[test]
+    fn records_execution_time_and_applied_by() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = embedded::migrations::runner()
+            .set_applied_by("deploy-pipeline")
+            .run(&mut conn)
+            .unwrap();
+        for applied in report.applied_migrations() {
+            assert_eq!(Some("deploy-pipeline"), applied.applied_by());
+            assert!(applied.execution_time().is_some());
+            assert!(applied.started_on().unwrap() <= applied.finished_on().unwrap());
+        }
+        let migrations = report.applied_migrations();
+        assert!(migrations[0].finished_on().unwrap() <= migrations[1].started_on().unwrap());
 
-        assert_eq!(migrations[0].name(), migrations[0].name());
-        assert_eq!(migrations[1].name(), applied_migrations[1].name());
-        assert_eq!(migrations[2].name(), applied_migrations[2].name());
+        let rows: Vec<(Option<i64>, Option<String>)> = conn
+            .prepare("SELECT execution_time_ms, applied_by FROM refinery_schema_history")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(4, rows.len());
+        for (execution_time_ms, applied_by) in rows {
+            assert!(execution_time_ms.is_some());
+            assert_eq!(Some("deploy-pipeline".to_string()), applied_by);
+        }
 
-        assert_eq!(migrations[0].checksum(), applied_migrations[0].checksum());
-        assert_eq!(migrations[1].checksum(), applied_migrations[1].checksum());
-        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+        // sqlite has no users to record by default, and grouped runs don't record execution times
+        let mut conn = Connection::open_in_memory().unwrap();
+        embedded::migrations::runner()
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap();
+        let recorded: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM refinery_schema_history
+                 WHERE execution_time_ms IS NOT NULL OR applied_by IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(0, recorded);
     }
 
     #[test]
-    fn migrates_to_target_migration_grouped() {
+    fn upgrades_schema_history_table_of_previous_versions() {
         let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        conn.execute_batch(&format!(
+            "CREATE TABLE refinery_schema_history(
+                 version INT4 NOT NULL,
+                 name VARCHAR(255) NOT NULL,
+                 applied_on VARCHAR(255),
+                 checksum VARCHAR(255),
+                 PRIMARY KEY (version, name));
+             CREATE TABLE persons (id int, name varchar(255), city varchar(255));
+             INSERT INTO refinery_schema_history (version, name, applied_on, checksum)
+                 VALUES (1, 'initial', '2024-01-01T00:00:00Z', '{}');",
+            migrations[0].checksum()
+        ))
+        .unwrap();
 
         let report = embedded::migrations::runner()
-            .set_target(Target::Version(3))
-            .set_grouped(true)
+            .set_applied_by("alice")
             .run(&mut conn)
             .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+
+        let rows: Vec<(Option<i64>, Option<String>, Option<i64>)> = conn
+            .prepare(
+                "SELECT execution_time_ms, applied_by, skipped FROM refinery_schema_history ORDER BY version",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!((None, None, None), rows[0]);
+        assert!(rows[1..]
+            .iter()
+            .all(|row| row.0.is_some() && row.1.as_deref() == Some("alice") && row.2.is_none()));
+    }
+
+    #[test]
+    fn rolls_back_migrations_failing_checks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id int, active int); INSERT INTO persons VALUES (1, 0), (2, 0);",
+        )
+        .unwrap();
+        let backfill = Migration::unapplied(
+            "V2__backfill",
+            "UPDATE persons SET active = 1 WHERE id = 1;
+            -- refinery:check
+            SELECT count(*) = 2 FROM persons;
+            -- every person is active
+            SELECT NOT EXISTS (SELECT 1 FROM persons WHERE active = 0);",
+        )
+        .unwrap();
 
+        let err = Runner::new(&[initial.clone(), backfill])
+            .run(&mut conn)
+            .unwrap_err();
+        assert_eq!(1, err.report().unwrap().applied_migrations().len());
+        assert!(err.to_string().contains("refinery_check_2"));
+        let active: i64 = conn
+            .query_row("SELECT count(*) FROM persons WHERE active = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(0, active);
+
+        let backfill = Migration::unapplied(
+            "V2__backfill",
+            "UPDATE persons SET active = 1;
+            -- refinery:check
+            SELECT count(*) = 2 FROM persons;
+            SELECT NOT EXISTS (SELECT 1 FROM persons WHERE active = 0);",
+        )
+        .unwrap();
+        let report = Runner::new(&[initial, backfill]).run(&mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
         let current = conn
             .get_last_applied_migration(DEFAULT_TABLE_NAME)
             .unwrap()
             .unwrap();
+        assert_eq!(2, current.version());
+    }
 
-        let applied_migrations = report.applied_migrations();
-        let migrations = get_migrations();
+    #[test]
+    fn retries_failing_migrations() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let mut conn = Connection::open(db.path()).unwrap();
+        conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+        Runner::new(&[
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+        ])
+        .run(&mut conn)
+        .unwrap();
 
-        assert_eq!(3, current.version());
+        // another connection holds a write lock for a while
+        let locker = Connection::open(db.path()).unwrap();
+        locker
+            .execute_batch("BEGIN IMMEDIATE; INSERT INTO persons VALUES (1);")
+            .unwrap();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            locker.execute_batch("COMMIT;").unwrap();
+        });
+
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied(
+                "V2__add_person",
+                "-- refinery:retries=20 backoff=50ms\nINSERT INTO persons VALUES (2);",
+            )
+            .unwrap(),
+        ];
+        assert_eq!(20, migrations[1].retries());
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        handle.join().unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM persons", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(2, count);
+    }
 
-        assert_eq!(3, applied_migrations.len());
+    #[test]
+    fn times_out_migration_run() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let mut conn = Connection::open(db.path()).unwrap();
+        conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+        let mut migrations =
+            vec![Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap()];
+        Runner::new(&migrations).run(&mut conn).unwrap();
+
+        // the second migration waits on another connection's write lock past the run timeout
+        let locker = Connection::open(db.path()).unwrap();
+        locker
+            .execute_batch("BEGIN IMMEDIATE; INSERT INTO persons VALUES (1);")
+            .unwrap();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            locker.execute_batch("COMMIT;").unwrap();
+        });
+
+        migrations.extend([
+            Migration::unapplied(
+                "V2__add_person",
+                "-- refinery:retries=20 backoff=50ms\nINSERT INTO persons VALUES (2);",
+            )
+            .unwrap(),
+            Migration::unapplied("V3__add_person", "INSERT INTO persons VALUES (3);").unwrap(),
+        ]);
+        let err = Runner::new(&migrations)
+            .set_run_timeout(std::time::Duration::from_millis(100))
+            .run(&mut conn)
+            .unwrap_err();
+        handle.join().unwrap();
+        assert!(matches!(err.kind(), Kind::RunTimeout(_)));
+        let applied_migrations = err.report().unwrap().applied_migrations();
+        assert_eq!(1, applied_migrations.len());
+        assert_eq!(2, applied_migrations[0].version());
 
-        assert_eq!(migrations[0].version(), applied_migrations[0].version());
-        assert_eq!(migrations[1].version(), applied_migrations[1].version());
-        assert_eq!(migrations[2].version(), applied_migrations[2].version());
+        // the rest of the migrations are applied on the next run
+        let mut config = Config::new(ConfigDbType::Sqlite).set_db_path(db.path().to_str().unwrap());
+        let report = Runner::new(&migrations)
+            .set_run_timeout(std::time::Duration::from_secs(60))
+            .run(&mut config)
+            .unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert_eq!(3, report.applied_migrations()[0].version());
+    }
 
-        assert_eq!(migrations[0].name(), migrations[0].name());
-        assert_eq!(migrations[1].name(), applied_migrations[1].name());
-        assert_eq!(migrations[2].name(), applied_migrations[2].name());
+    #[test]
+    fn times_out_migrations_waiting_on_locks() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let mut conn = Connection::open(db.path()).unwrap();
+        let mut migrations =
+            vec![Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap()];
+        Runner::new(&migrations).run(&mut conn).unwrap();
+
+        // the busy timeout of the connection is set to the timeout, so the migration waits on another
+        // connection's write lock until it's exceeded
+        let locker = Connection::open(db.path()).unwrap();
+        locker
+            .execute_batch("BEGIN IMMEDIATE; INSERT INTO persons VALUES (1);")
+            .unwrap();
+        migrations.push(
+            Migration::unapplied("V2__add_person", "INSERT INTO persons VALUES (2);").unwrap(),
+        );
+        let started = std::time::Instant::now();
+        let err = Runner::new(&migrations)
+            .set_timeout(std::time::Duration::from_millis(200))
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+        match err.kind() {
+            Kind::Timeout(migration, timeout) => {
+                assert_eq!("V2__add_person", migration.to_string());
+                assert_eq!(std::time::Duration::from_millis(200), *timeout);
+            }
+            kind => panic!("unexpected error {}", kind),
+        }
+        assert_eq!(0, err.report().unwrap().applied_migrations().len());
+        assert_eq!(1, err.statement().unwrap().index());
+        locker.execute_batch("COMMIT;").unwrap();
 
-        assert_eq!(migrations[0].checksum(), applied_migrations[0].checksum());
-        assert_eq!(migrations[1].checksum(), applied_migrations[1].checksum());
-        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+        let report = Runner::new(&migrations)
+            .set_timeout(std::time::Duration::from_millis(200))
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(1, report.applied_migrations().len());
     }
 
     #[test]
-    fn aborts_on_missing_migration_on_filesystem() {
+    fn cancels_migration_run() {
         let mut conn = Connection::open_in_memory().unwrap();
+        let cancel_handle = CancelHandle::new();
+        cancel_handle.clone().cancel();
 
-        embedded::migrations::runner().run(&mut conn).unwrap();
-
-        let migration = Migration::unapplied(
-            "V4__add_year_field_to_cars",
-            "ALTER TABLE cars ADD year INTEGER;",
-        )
-        .unwrap();
-        let err = conn
-            .migrate(
-                &[migration],
-                true,
-                true,
-                false,
-                Target::Latest,
-                DEFAULT_TABLE_NAME,
-            )
+        let err = embedded::migrations::runner()
+            .set_cancel_handle(cancel_handle)
+            .run(&mut conn)
             .unwrap_err();
-
-        match err.kind() {
-            Kind::MissingVersion(missing) => {
-                assert_eq!(1, missing.version());
-                assert_eq!("initial", missing.name());
-            }
-            _ => panic!("failed test"),
-        }
+        assert!(matches!(err.kind(), Kind::Cancelled));
+        assert!(err.report().unwrap().applied_migrations().is_empty());
+        assert!(conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .is_none());
     }
 
     #[test]
-    fn aborts_on_divergent_migration() {
+    fn creates_schema_qualified_migration_table() {
         let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("ATTACH DATABASE ':memory:' AS audit", [])
+            .unwrap();
+        let mut runner = embedded::migrations::runner();
+        runner
+            .set_migration_table_name("audit.\"refinery history\"")
+            .unwrap();
+        runner.run(&mut conn).unwrap();
 
-        embedded::migrations::runner().run(&mut conn).unwrap();
+        let applied: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM audit.\"refinery history\"",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(4, applied);
+        assert_eq!(4, runner.get_applied_migrations(&mut conn).unwrap().len());
+    }
 
-        let migration = Migration::unapplied(
-            "V2__add_year_field_to_cars",
-            "ALTER TABLE cars ADD year INTEGER;",
+    #[test]
+    fn reports_down_sql_of_applied_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&[Migration::unapplied(
+            "V1__add_users",
+            "CREATE TABLE users(id int);\n-- refinery:down\nDROP TABLE users;\n",
         )
-        .unwrap();
-        let err = conn
-            .migrate(
-                &[migration.clone()],
-                true,
-                false,
-                false,
-                Target::Latest,
-                DEFAULT_TABLE_NAME,
+        .unwrap()]);
+
+        let report = runner.run(&mut conn).unwrap();
+        let applied = &report.applied_migrations()[0];
+        assert_eq!(Some("DROP TABLE users;\n"), applied.down_sql());
+        assert!(!applied.sql().unwrap().contains("DROP TABLE users"));
+        let users: u32 = conn
+            .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(0, users);
+    }
+
+    fn reversible_migrations() -> Vec<Migration> {
+        vec![
+            Migration::unapplied(
+                "V1__add_users",
+                "CREATE TABLE users(id int);\n-- refinery:down\nDROP TABLE users;",
             )
-            .unwrap_err();
+            .unwrap(),
+            Migration::unapplied(
+                "V2__add_cars",
+                "CREATE TABLE cars(id int);\n-- refinery:down\nDROP TABLE cars;",
+            )
+            .unwrap(),
+            Migration::unapplied(
+                "V3__add_motos",
+                "CREATE TABLE motos(id int);\n-- refinery:down\nDROP TABLE motos;",
+            )
+            .unwrap(),
+        ]
+    }
 
-        match err.kind() {
-            Kind::DivergentVersion(applied, divergent) => {
-                assert_eq!(&migration, divergent);
-                assert_eq!(2, applied.version());
-                assert_eq!("add_cars_and_motos_table", applied.name());
-            }
-            _ => panic!("failed test"),
-        }
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get::<_, u32>(0),
+        )
+        .unwrap()
+            == 1
     }
 
     #[test]
-    fn aborts_on_missing_migration_on_database() {
+    fn rolls_back_to_version() {
         let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&reversible_migrations());
+        runner.run(&mut conn).unwrap();
+
+        let report = runner.rollback(&mut conn, Target::Version(1)).unwrap();
+        let rolled_back: Vec<u32> = report
+            .applied_migrations()
+            .iter()
+            .map(Migration::version)
+            .collect();
+        assert_eq!(vec![3, 2], rolled_back);
+        assert!(table_exists(&conn, "users"));
+        assert!(!table_exists(&conn, "cars"));
+        assert!(!table_exists(&conn, "motos"));
+
+        let applied = runner.get_applied_migrations(&mut conn).unwrap();
+        assert_eq!(1, applied.len());
+        assert_eq!(1, applied[0].version());
+
+        // the rolled back migrations are applied again on the next run
+        let report = runner.run(&mut conn).unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+        assert!(table_exists(&conn, "motos"));
+    }
 
-        missing::migrations::runner().run(&mut conn).unwrap();
+    #[test]
+    fn embeds_migrations_without_down_section() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = omit_down::migrations::runner();
+        for migration in runner.get_migrations() {
+            assert!(migration.down_sql().is_none());
+            assert!(!migration.sql().unwrap().contains("DROP TABLE"));
+        }
+        runner.run(&mut conn).unwrap();
 
-        let migration1 = Migration::unapplied(
-            "V1__initial",
-            concat!(
-                "CREATE TABLE persons (",
-                "id int,",
-                "name varchar(255),",
-                "city varchar(255)",
-                ");"
-            ),
-        )
-        .unwrap();
+        // the checksums are the ones of the migrations with their down section
+        let reversible = reversible::migrations::runner();
+        assert!(reversible.verify_checksums(&mut conn).unwrap().is_empty());
+        assert!(reversible
+            .run(&mut conn)
+            .unwrap()
+            .applied_migrations()
+            .is_empty());
+
+        let err = runner.rollback(&mut conn, Target::Version(1)).unwrap_err();
+        assert!(matches!(err.kind(), Kind::DownOmitted(m) if m.version() == 2));
+        assert!(table_exists(&conn, "cars"));
+
+        let report = reversible.rollback(&mut conn, Target::Version(1)).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert!(!table_exists(&conn, "cars"));
+    }
+
+    #[test]
+    fn rolls_back_steps_grouped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&reversible_migrations()).set_grouped(true);
+        runner.run(&mut conn).unwrap();
+
+        let report = runner.rollback(&mut conn, Target::StepsForward(1)).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert_eq!(3, report.applied_migrations()[0].version());
+        assert!(!table_exists(&conn, "motos"));
+        assert!(table_exists(&conn, "cars"));
+    }
+
+    #[test]
+    fn fake_rolls_back_only_schema_history() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&reversible_migrations());
+        runner.run(&mut conn).unwrap();
+
+        let report = runner.rollback(&mut conn, Target::FakeVersion(2)).unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert!(table_exists(&conn, "motos"));
+        let last = runner
+            .get_last_applied_migration(&mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, last.version());
+    }
+
+    #[test]
+    fn doesnt_roll_back_irreversible_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut migrations = reversible_migrations();
+        migrations.push(
+            Migration::unapplied("V4__seed_users", "INSERT INTO users(id) VALUES (1);").unwrap(),
+        );
+        let runner = Runner::new(&migrations);
+        runner.run(&mut conn).unwrap();
+
+        let err = runner.rollback(&mut conn, Target::Version(1)).unwrap_err();
+        match err.kind() {
+            Kind::Irreversible(migration) => assert_eq!(4, migration.version()),
+            _ => panic!("failed test"),
+        }
+        // nothing is rolled back
+        assert!(table_exists(&conn, "motos"));
+        assert_eq!(4, runner.get_applied_migrations(&mut conn).unwrap().len());
+
+        let err = runner.rollback(&mut conn, Target::Latest).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            Kind::InvalidRollbackTarget(Target::Latest)
+        ));
+    }
+
+    #[test]
+    fn downgrades_to_target_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&reversible_migrations());
+        runner.run(&mut conn).unwrap();
+
+        let report = Runner::new(&reversible_migrations())
+            .set_target(Target::Version(1))
+            .run(&mut conn)
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+        let rolled_back: Vec<u32> = report
+            .rolled_back_migrations()
+            .iter()
+            .map(Migration::version)
+            .collect();
+        assert_eq!(vec![3, 2], rolled_back);
+        assert!(table_exists(&conn, "users"));
+        assert!(!table_exists(&conn, "cars"));
+
+        // migrating up to the target again is a no-op, and a later one applies the rolled back migrations
+        let report = Runner::new(&reversible_migrations())
+            .set_target(Target::Version(1))
+            .run(&mut conn)
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert!(report.rolled_back_migrations().is_empty());
+        let report = Runner::new(&reversible_migrations())
+            .set_target(Target::Version(2))
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert!(table_exists(&conn, "cars"));
+    }
+
+    #[test]
+    fn doesnt_downgrade_irreversible_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut migrations = reversible_migrations();
+        migrations.push(
+            Migration::unapplied("V4__seed_users", "INSERT INTO users(id) VALUES (1);").unwrap(),
+        );
+        Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let err = Runner::new(&migrations)
+            .set_target(Target::Version(2))
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::Irreversible(migration) if migration.version() == 4));
+        assert!(table_exists(&conn, "motos"));
+    }
+
+    #[test]
+    fn reports_downgrade_when_run_fails_afterwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        Runner::new(&reversible_migrations())
+            .run(&mut conn)
+            .unwrap();
+
+        let err = Runner::new(&reversible_migrations())
+            .set_target(Target::Version(1))
+            .set_snapshot_path(dir.path().join("missing").join("schema.sql"))
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidSnapshot(..)));
+        let rolled_back: Vec<u32> = err
+            .report()
+            .unwrap()
+            .rolled_back_migrations()
+            .iter()
+            .map(Migration::version)
+            .collect();
+        assert_eq!(vec![3, 2], rolled_back);
+        assert!(!table_exists(&conn, "cars"));
+    }
+
+    #[test]
+    fn migrates_inside_transaction() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        // the migrations are rolled back with the transaction they are applied on
+        let mut transaction = conn.transaction().unwrap();
+        let report = embedded::migrations::runner()
+            .run(&mut transaction)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        transaction.rollback().unwrap();
+        assert!(!table_exists(&conn, "persons"));
+        assert!(!table_exists(&conn, DEFAULT_TABLE_NAME));
+
+        // a failing migration only rolls back its savepoint, leaving the transaction usable
+        let mut transaction = conn.transaction().unwrap();
+        let err = broken::migrations::runner()
+            .run(&mut transaction)
+            .unwrap_err();
+        assert_eq!(2, err.report().unwrap().applied_migrations().len());
+        transaction.commit().unwrap();
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+
+    // a connection refinery has no driver for, migrated through RefineryConnection
+    struct Wrapped(Connection);
+
+    impl RefineryConnection for Wrapped {
+        type Error = Error;
+
+        fn execute_in_transaction(&mut self, queries: &[&str]) -> Result<(), Error> {
+            let transaction = self.0.transaction()?;
+            for query in queries {
+                transaction.execute_batch(query)?;
+            }
+            transaction.commit()
+        }
+
+        fn query_rows(&mut self, query: &str) -> Result<Vec<SchemaHistoryRow>, Error> {
+            let mut stmt = self.0.prepare(query)?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?;
+            rows.collect()
+        }
+
+        fn dialect(&self) -> Option<Dialect> {
+            Some(Dialect::Sqlite)
+        }
+    }
+
+    #[test]
+    fn migrates_refinery_connection() {
+        let mut conn = Wrapped(Connection::open_in_memory().unwrap());
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        let migrations = get_migrations();
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+
+        let applied_migrations = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+        assert_eq!(migrations, applied_migrations);
+    }
+
+    // a connection whose first transactions applying migrations fail with a serialization failure
+    struct Contended {
+        inner: Wrapped,
+        failures: u32,
+    }
+
+    impl RefineryConnection for Contended {
+        type Error = Error;
+
+        fn execute_in_transaction(&mut self, queries: &[&str]) -> Result<(), Error> {
+            if self.failures > 0 && queries.len() > 1 {
+                self.failures -= 1;
+                return Err(Error::InvalidQuery);
+            }
+            self.inner.execute_in_transaction(queries)
+        }
+
+        fn query_rows(&mut self, query: &str) -> Result<Vec<SchemaHistoryRow>, Error> {
+            self.inner.query_rows(query)
+        }
+
+        fn dialect(&self) -> Option<Dialect> {
+            RefineryConnection::dialect(&self.inner)
+        }
+
+        fn is_serialization_failure(error: &Error) -> bool {
+            matches!(error, Error::InvalidQuery)
+        }
+    }
+
+    #[test]
+    fn retries_migrations_on_serialization_failure() {
+        let mut conn = Contended {
+            inner: Wrapped(Connection::open_in_memory().unwrap()),
+            failures: 2,
+        };
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(0, conn.failures);
+
+        let mut conn = Contended {
+            inner: Wrapped(Connection::open_in_memory().unwrap()),
+            failures: 1,
+        };
+        let report = embedded::migrations::runner()
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(0, conn.failures);
+    }
+
+    #[test]
+    fn replaces_placeholders_of_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE ${prefix}_persons (id int, city varchar(255) DEFAULT '${city}');",
+        )
+        .unwrap()];
+        let placeholders = HashMap::from([
+            ("prefix".to_string(), "staging".to_string()),
+            ("city".to_string(), "Lisbon".to_string()),
+        ]);
+        Runner::new(&migrations)
+            .set_placeholders(placeholders)
+            .set_strict_placeholders(true)
+            .run(&mut conn)
+            .unwrap();
+
+        conn.execute("INSERT INTO staging_persons (id) VALUES (1)", [])
+            .unwrap();
+        let city: String = conn
+            .query_row("SELECT city FROM staging_persons", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!("Lisbon", city);
+
+        // the checksum is the one of the migration as written, whatever the values
+        let applied = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+        assert_eq!(migrations[0].checksum(), applied[0].checksum());
+
+        let err = Runner::new(&migrations)
+            .set_strict_placeholders(true)
+            .run(&mut Connection::open_in_memory().unwrap())
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownPlaceholder(name, _) if name == "prefix"));
+    }
+
+    #[test]
+    fn splits_migrations_into_statements() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied(
+                "V1__add_cars",
+                "CREATE TABLE cars(id int, brand text, updated int);
+CREATE TRIGGER cars_updated AFTER UPDATE OF brand ON cars BEGIN
+  UPDATE cars SET updated = 1 WHERE id = NEW.id;
+END;
+INSERT INTO cars VALUES (1, 'a;b', 0); -- the last statement",
+            )
+            .unwrap(),
+            Migration::unapplied(
+                "V2__update_cars",
+                "UPDATE cars SET brand = 'Ford' WHERE id = 1\nGO\nINSERT INTO cars VALUES (2, 'Audi', 0)",
+            )
+            .unwrap(),
+        ];
+
+        Runner::new(&migrations[..1]).run(&mut conn).unwrap();
+        // `GO` isn't sql, so the migration only runs split with a separator
+        let err = Runner::new(&migrations).run(&mut conn).unwrap_err();
+        assert!(matches!(err.kind(), Kind::Connection(..)));
+        Runner::new(&migrations)
+            .set_splitter(Splitter::Separator("GO".into()))
+            .run(&mut conn)
+            .unwrap();
+
+        let cars: Vec<(String, i32)> = conn
+            .prepare("SELECT brand, updated FROM cars ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![("Ford".to_string(), 1), ("Audi".to_string(), 0)], cars);
+    }
+
+    // a Runner recording the migrations its callbacks are invoked with
+    fn hooked_runner(runner: Runner, calls: &Arc<Mutex<Vec<String>>>) -> Runner {
+        let before = calls.clone();
+        let after = calls.clone();
+        runner
+            .set_before_migration(move |migration| {
+                before
+                    .lock()
+                    .unwrap()
+                    .push(format!("before {}", migration.version()))
+            })
+            .set_after_migration(move |migration, _duration| {
+                assert!(migration.applied_on().is_some());
+                after
+                    .lock()
+                    .unwrap()
+                    .push(format!("after {}", migration.version()))
+            })
+    }
+
+    #[test]
+    fn invokes_callbacks_around_each_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let report = hooked_runner(
+            embedded::migrations::runner().set_target(Target::Version(3)),
+            &calls,
+        )
+        .run(&mut conn)
+        .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+        assert_eq!(
+            vec!["before 1", "after 1", "before 2", "after 2", "before 3", "after 3"],
+            *calls.lock().unwrap()
+        );
+
+        // the failing migration is only passed to the before callback
+        let mut conn = Connection::open_in_memory().unwrap();
+        calls.lock().unwrap().clear();
+        hooked_runner(broken::migrations::runner(), &calls)
+            .run(&mut conn)
+            .unwrap_err();
+        assert_eq!(
+            vec!["before 1", "after 1", "before 2", "after 2", "before 3"],
+            *calls.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn invokes_callbacks_around_grouped_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        hooked_runner(embedded::migrations::runner().set_grouped(true), &calls)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(
+            vec![
+                "before 1", "before 2", "before 3", "before 4", "after 1", "after 2", "after 3",
+                "after 4"
+            ],
+            *calls.lock().unwrap()
+        );
+
+        // fake runs don't invoke them
+        let mut conn = Connection::open_in_memory().unwrap();
+        calls.lock().unwrap().clear();
+        hooked_runner(
+            embedded::migrations::runner().set_target(Target::Fake),
+            &calls,
+        )
+        .run(&mut conn)
+        .unwrap();
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sets_up_sqlite_pragmas_before_migrating() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let mut conn = Connection::open(db.path()).unwrap();
+        let runner = Runner::new(&[
+            Migration::unapplied(
+                "V1__add_users",
+                "CREATE TABLE users(id int PRIMARY KEY);
+                CREATE TABLE emails(user_id int REFERENCES users(id));",
+            )
+            .unwrap(),
+            Migration::unapplied("V2__add_email", "INSERT INTO emails(user_id) VALUES (1);")
+                .unwrap(),
+        ])
+        .set_sqlite_pragmas(vec!["journal_mode=WAL".into(), "foreign_keys=ON".into()]);
+
+        let err = runner.run(&mut conn).unwrap_err();
+        assert_eq!(1, err.report().unwrap().applied_migrations().len());
+        let journal_mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!("wal", journal_mode);
+
+        let err = Runner::new(&[])
+            .set_sqlite_extensions(vec!["./missing_extension".into()])
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(err.to_string().contains("missing_extension"));
+    }
+
+    #[test]
+    fn sets_up_sqlite_pragmas_from_config() {
+        run_test(|| {
+            let mut config = Config::new(ConfigDbType::Sqlite)
+                .set_db_path("tests/db.sql")
+                .set_sqlite_pragmas(vec!["foreign_keys=ON".into()]);
+            let runner = Runner::new(&[
+                Migration::unapplied(
+                    "V1__add_users",
+                    "CREATE TABLE users(id int PRIMARY KEY);
+                    CREATE TABLE emails(user_id int REFERENCES users(id));",
+                )
+                .unwrap(),
+                Migration::unapplied("V2__add_email", "INSERT INTO emails(user_id) VALUES (1);")
+                    .unwrap(),
+            ]);
+
+            assert!(runner.run(&mut config).is_err());
+            let applied = runner.get_applied_migrations(&mut config).unwrap();
+            assert_eq!(1, applied.len());
+        })
+    }
+
+    #[test]
+    fn creates_database_if_missing_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data").join("db.sql");
+        let mut config = Config::new(ConfigDbType::Sqlite)
+            .set_db_path(path.to_str().unwrap())
+            .set_create_db_if_missing(true);
+
+        let report = embedded::migrations::runner().run(&mut config).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert!(path.exists());
+
+        // without the option a missing database isn't created
+        let mut config = Config::new(ConfigDbType::Sqlite)
+            .set_db_path(dir.path().join("missing.sql").to_str().unwrap());
+        assert!(embedded::migrations::runner().run(&mut config).is_err());
+    }
+
+    #[test]
+    fn fails_migrating_tenants_on_sqlite() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = MultiTenantRunner::new(embedded::migrations::runner(), &["tenant_a"])
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            Kind::UnsupportedTenantDialect(Some(Dialect::Sqlite))
+        ));
+        assert!(!table_exists(&conn, DEFAULT_TABLE_NAME));
+    }
+
+    #[test]
+    fn runs_namespaces_independently() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let billing = Runner::new(&[Migration::unapplied(
+            "V1__add_invoices",
+            "CREATE TABLE invoices(id int);",
+        )
+        .unwrap()])
+        .set_namespace("billing")
+        .unwrap();
+        let orders = Runner::new(&[
+            Migration::unapplied("V1__add_orders", "CREATE TABLE orders(id int);").unwrap(),
+            Migration::unapplied("V2__add_total", "ALTER TABLE orders ADD total int;").unwrap(),
+        ])
+        .set_namespace("orders")
+        .unwrap();
+
+        embedded::migrations::runner().run(&mut conn).unwrap();
+        let report = billing.run(&mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        let report = orders.run(&mut conn).unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+        assert!(billing
+            .run(&mut conn)
+            .unwrap()
+            .applied_migrations()
+            .is_empty());
+
+        let applied = billing.get_applied_migrations(&mut conn).unwrap();
+        assert_eq!(1, applied.len());
+        assert_eq!(Some("billing"), applied[0].namespace());
+        assert_eq!("add_invoices", applied[0].name());
+        let last = orders
+            .get_last_applied_migration(&mut conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!("orders/V2__add_total", last.to_string());
+        assert_eq!(
+            4,
+            embedded::migrations::runner()
+                .get_applied_migrations(&mut conn)
+                .unwrap()
+                .len()
+        );
+
+        let name: String = conn
+            .query_row(
+                "SELECT name FROM refinery_schema_history WHERE version = 1 AND name LIKE 'billing/%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!("billing/add_invoices", name);
+    }
+
+    #[test]
+    fn rolls_back_namespaces_with_underscores_independently() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = |namespace: &str, table: &str| {
+            let sql = format!(
+                "CREATE TABLE {0}(id int);\n-- refinery:down\nDROP TABLE {0};",
+                table
+            );
+            Runner::new(&[Migration::unapplied("V1__initial", &sql).unwrap()])
+                .set_namespace(namespace)
+                .unwrap()
+        };
+        let billing_eu = runner("billing_eu", "invoices_eu");
+        let billingxeu = runner("billingxeu", "invoices_xeu");
+        billing_eu.run(&mut conn).unwrap();
+        billingxeu.run(&mut conn).unwrap();
+
+        // `_` isn't a wildcard matching the rows of the other namespace
+        billing_eu.rollback(&mut conn, Target::Version(0)).unwrap();
+        assert!(billing_eu
+            .get_applied_migrations(&mut conn)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            1,
+            billingxeu.get_applied_migrations(&mut conn).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn fails_setting_invalid_namespace() {
+        for namespace in ["", "billing/eu", "billing eu", "billing'"] {
+            let result = Runner::new(&[]).set_namespace(namespace);
+            assert!(
+                matches!(result, Err(err) if matches!(err.kind(), Kind::InvalidNamespace(n) if n == namespace))
+            );
+        }
+    }
+
+    #[test]
+    fn fails_namespaces_on_legacy_primary_key() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE {}(version INT4 PRIMARY KEY, name VARCHAR(255), applied_on VARCHAR(255), \
+             checksum VARCHAR(255));",
+            DEFAULT_TABLE_NAME
+        ))
+        .unwrap();
+        let migrations =
+            [Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap()];
+
+        let result = Runner::new(&migrations)
+            .set_namespace("billing")
+            .unwrap()
+            .run(&mut conn);
+        assert!(matches!(
+            result,
+            Err(err) if matches!(err.kind(), Kind::LegacyPrimaryKey(table) if table == DEFAULT_TABLE_NAME)
+        ));
+        assert!(!table_exists(&conn, "persons"));
+        // runs without a namespace still work on it
+        Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        Runner::new(&migrations)
+            .set_namespace("billing")
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn applies_seeds_only_when_set() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied("S1__add_persons", "INSERT INTO persons VALUES (1, 'seed');")
+                .unwrap(),
+            Migration::unapplied("V1__initial", "CREATE TABLE persons(id int, name text);")
+                .unwrap(),
+            Migration::unapplied("V2__add_cars", "CREATE TABLE cars(id int);").unwrap(),
+        ];
+
+        let report = Runner::new(&migrations[1..]).run(&mut conn).unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        assert!(report.applied_migrations().is_empty());
+
+        let report = Runner::new(&migrations)
+            .set_apply_seeds(true)
+            .run(&mut conn)
+            .unwrap();
+        let applied = report.applied_migrations();
+        assert_eq!(1, applied.len());
+        assert_eq!("seeds/S1__add_persons", applied[0].to_string());
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM persons", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(1, count);
+
+        // the applied seeds are ignored by runs without them
+        let runner = Runner::new(&migrations[1..]);
+        assert!(runner
+            .run(&mut conn)
+            .unwrap()
+            .applied_migrations()
+            .is_empty());
+        assert_eq!(2, runner.get_applied_migrations(&mut conn).unwrap().len());
+
+        // seeds are applied after the other migrations
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = Runner::new(&migrations)
+            .set_apply_seeds(true)
+            .run(&mut conn)
+            .unwrap();
+        let applied: Vec<String> = report
+            .applied_migrations()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec!["V1__initial", "V2__add_cars", "seeds/S1__add_persons"],
+            applied
+        );
+    }
+
+    #[test]
+    fn rolls_out_to_canary_first() {
+        let mut canary = Connection::open_in_memory().unwrap();
+        let mut targets = vec![
+            Connection::open_in_memory().unwrap(),
+            Connection::open_in_memory().unwrap(),
+        ];
+
+        let rollout = Rollout::new(embedded::migrations::runner())
+            .set_checks(vec!["SELECT COUNT(*) = 1 FROM persons".into()]);
+        let err = rollout.run(&mut canary, &mut targets).unwrap_err();
+        match err.kind() {
+            Kind::RolloutAborted(RolloutStage::Checks, _) => {}
+            _ => panic!("failed test"),
+        }
+        assert_eq!(
+            4,
+            canary
+                .get_applied_migrations(DEFAULT_TABLE_NAME)
+                .unwrap()
+                .len()
+        );
+        for target in &targets {
+            let tables: u32 = target
+                .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(0, tables);
+        }
+
+        let rollout = Rollout::new(embedded::migrations::runner())
+            .set_checks(vec!["SELECT COUNT(*) = 0 FROM persons".into()]);
+        let reports = rollout.run(&mut canary, &mut targets).unwrap();
+        assert_eq!(3, reports.len());
+        assert!(reports[0].applied_migrations().is_empty());
+        assert_eq!(4, reports[1].applied_migrations().len());
+        assert_eq!(4, reports[2].applied_migrations().len());
+    }
+
+    #[test]
+    fn exports_and_imports_history() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = embedded::migrations::runner();
+        runner.run(&mut conn).unwrap();
+        let records = runner.export_history(&mut conn).unwrap();
+        assert_eq!(4, records.len());
+        assert_eq!("initial", records[0].name);
+
+        // importing into a table with another name, and again, only inserts the records once
+        let mut runner = embedded::migrations::runner();
+        runner.set_migration_table_name("history").unwrap();
+        assert_eq!(4, runner.import_history(&mut conn, &records).unwrap());
+        assert_eq!(0, runner.import_history(&mut conn, &records).unwrap());
+        assert_eq!(records, runner.export_history(&mut conn).unwrap());
+        assert!(runner.get_pending_migrations(&mut conn).unwrap().is_empty());
+
+        let mut conflicting = records.clone();
+        conflicting[1].checksum = "1".into();
+        let err = runner.import_history(&mut conn, &conflicting).unwrap_err();
+        assert!(matches!(err.kind(), Kind::ConflictingHistoryRecord(..)));
+    }
+
+    #[test]
+    fn migrates_shards_by_failure_policy() {
+        let shards = || {
+            let shards = vec![
+                Connection::open_in_memory().unwrap(),
+                Connection::open_in_memory().unwrap(),
+                Connection::open_in_memory().unwrap(),
+            ];
+            shards[1]
+                .execute("CREATE TABLE persons (id int)", [])
+                .unwrap();
+            shards
+        };
+
+        let mut stopped = shards();
+        let results = ShardRunner::new(embedded::migrations::runner()).run(&mut stopped);
+        assert_eq!(2, results.len());
+        assert_eq!(0, results[0].0);
+        assert_eq!(4, results[0].1.as_ref().unwrap().applied_migrations().len());
+        assert_eq!(1, results[1].0);
+        assert!(results[1].1.is_err());
+        let tables: u32 = stopped[2]
+            .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(0, tables);
+
+        let mut continued = shards();
+        let results = ShardRunner::new(embedded::migrations::runner())
+            .set_concurrency(2)
+            .set_failure_policy(ShardFailurePolicy::Continue)
+            .run(&mut continued);
+        let indexes: Vec<usize> = results.iter().map(|(index, _)| *index).collect();
+        assert_eq!(vec![0, 1, 2], indexes);
+        assert!(results[1].1.is_err());
+        assert_eq!(4, results[2].1.as_ref().unwrap().applied_migrations().len());
+    }
+
+    #[test]
+    fn embeds_migrations_up_to_max_depth() {
+        let mut versions: Vec<u32> = embedded::migrations::runner()
+            .get_migrations()
+            .iter()
+            .map(Migration::version)
+            .collect();
+        versions.sort();
+        assert_eq!(vec![1, 2, 3, 4], versions);
+
+        let runner = top_level::migrations::runner();
+        assert_eq!(1, runner.get_migrations().len());
+        assert_eq!("add_year_to_motos_table", runner.get_migrations()[0].name());
+    }
+
+    #[test]
+    fn embeds_migrations_of_several_locations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = several::migrations::runner().run(&mut conn).unwrap();
+        let names: Vec<String> = report
+            .applied_migrations()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "V1__initial",
+                "V2__add_cars_and_motos_table",
+                "V3__add_brand_to_cars_table"
+            ],
+            names
+        );
+
+        let manifest = several::migrations::manifest();
+        assert!(manifest
+            .matches_locations(&["./tests/migrations/V1-2", "./tests/migrations/V3"])
+            .unwrap());
+        assert!(!manifest
+            .matches_location("./tests/migrations/V1-2")
+            .unwrap());
+    }
+
+    #[test]
+    fn runs_timestamp_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = timestamp::migrations::runner().run(&mut conn).unwrap();
+        let names: Vec<String> = report
+            .applied_migrations()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec!["20250501_000000_initial", "20250614_093000_add_cars_table"],
+            names
+        );
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(1749893400, current.version());
+        assert_eq!("add_cars_table", current.name());
+
+        let migrations = refinery::load_sql_migrations("./tests/migrations_timestamp").unwrap();
+        assert_eq!(1746057600, migrations[0].version());
+    }
+
+    #[test]
+    fn embeds_migrations_manifest() {
+        let manifest = embedded::migrations::manifest();
+        let migrations = get_migrations();
+
+        assert_eq!(4, manifest.migrations.len());
+        for (entry, migration) in manifest.migrations.iter().zip(&migrations) {
+            assert_eq!(migration.version(), entry.version);
+        }
+        // the sql of Rust migrations is only generated when they are run
+        assert_eq!(None, manifest.migrations[0].checksum);
+        assert_eq!(
+            Some(migrations[1].checksum()),
+            manifest.migrations[1].checksum
+        );
+        assert!(manifest.matches_location("./tests/migrations").unwrap());
+        assert!(!manifest
+            .matches_location("./tests/migrations_broken")
+            .unwrap());
+    }
+
+    #[test]
+    fn fails_on_invalid_directives() {
+        let err = Migration::unapplied(
+            "V2__add_person",
+            "-- refinery:retries=many\nINSERT INTO persons VALUES (2);",
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err.kind(), Kind::InvalidDirective(_, directive) if directive == "retries=many")
+        );
+    }
+
+    #[test]
+    fn migrates_steps_forward() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        for grouped in [false, true] {
+            let report = embedded::migrations::runner()
+                .set_grouped(grouped)
+                .set_target("steps:2".parse().unwrap())
+                .run(&mut conn)
+                .unwrap();
+            assert_eq!(2, report.applied_migrations().len());
+        }
+
+        let applied_migrations = embedded::migrations::runner()
+            .set_target(Target::StepsForward(2))
+            .run_iter(&mut conn)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(applied_migrations.is_empty());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(4, current.version());
+    }
+
+    #[test]
+    fn migrates_next_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        for version in 1..=4 {
+            let report = embedded::migrations::runner()
+                .set_target(Target::Next)
+                .run(&mut conn)
+                .unwrap();
+            let applied = report.applied_migrations();
+            assert_eq!(1, applied.len());
+            assert_eq!(version, applied[0].version());
+        }
+        let report = embedded::migrations::runner()
+            .set_target(Target::Next)
+            .run(&mut conn)
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+    }
+
+    #[test]
+    fn rolls_back_next_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let runner = Runner::new(&reversible_migrations());
+        runner.run(&mut conn).unwrap();
+
+        let report = runner.rollback(&mut conn, Target::Next).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert_eq!(3, report.applied_migrations()[0].version());
+        assert!(!table_exists(&conn, "motos"));
+        assert!(table_exists(&conn, "cars"));
+    }
+
+    #[test]
+    fn reports_integrity_issues() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // bundled sqlite enforces foreign keys by default
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+            CREATE TABLE owners (id integer PRIMARY KEY);
+            CREATE TABLE pets (id integer PRIMARY KEY, owner_id integer REFERENCES owners(id));
+            INSERT INTO pets (id, owner_id) VALUES (1, 1), (2, 1), (3, NULL);",
+        )
+        .unwrap();
+
+        let report = embedded::migrations::runner()
+            .set_target(Target::Version(1))
+            .run(&mut conn)
+            .unwrap();
+        assert!(report.integrity_issues().is_empty());
+
+        let report = embedded::migrations::runner()
+            .set_integrity_checks(true)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+        assert_eq!(
+            &vec![IntegrityIssue::ForeignKeyViolation {
+                table: "pets".into(),
+                references: "owners".into()
+            }],
+            report.integrity_issues()
+        );
+    }
+
+    #[test]
+    fn writes_schema_snapshot_after_run() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let snapshot = dir.path().join("schema.sql");
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int PRIMARY KEY);")
+                .unwrap(),
+            Migration::unapplied(
+                "V2__add_name",
+                "ALTER TABLE persons ADD name varchar(255) NOT NULL DEFAULT '';
+                CREATE INDEX persons_name ON persons (name);",
+            )
+            .unwrap(),
+        ];
+        Runner::new(&migrations[..1])
+            .set_snapshot_path(&snapshot)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(
+            "CREATE TABLE persons (\n    id INT,\n    CONSTRAINT persons_pkey PRIMARY KEY (id)\n);\n",
+            fs::read_to_string(&snapshot).unwrap()
+        );
+
+        Runner::new(&migrations)
+            .set_snapshot_path(&snapshot)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(
+            concat!(
+                "CREATE TABLE persons (\n",
+                "    id INT,\n",
+                "    name varchar(255) NOT NULL DEFAULT '',\n",
+                "    CONSTRAINT persons_pkey PRIMARY KEY (id)\n",
+                ");\n",
+                "CREATE INDEX persons_name ON persons (name);\n",
+            ),
+            fs::read_to_string(&snapshot).unwrap()
+        );
+
+        let err = Runner::new(&migrations)
+            .set_snapshot_path(dir.path().join("missing").join("schema.sql"))
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidSnapshot(..)));
+    }
+
+    #[test]
+    fn introspects_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        embedded::migrations::runner().run(&mut conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE owners (id integer PRIMARY KEY, email text NOT NULL UNIQUE);
+            CREATE TABLE pets (id integer, owner_id integer REFERENCES owners(id), name text DEFAULT 'rex', PRIMARY KEY (id));
+            CREATE INDEX pets_name ON pets (name);",
+        )
+        .unwrap();
+
+        let schema = refinery::introspect::snapshot(&mut conn).unwrap();
+        assert!(schema.table(DEFAULT_TABLE_NAME).is_some());
+        assert!(schema.table("persons").is_some());
+
+        let owners = schema.table("owners").unwrap();
+        assert_eq!("PRIMARY KEY (id)", owners.constraints["owners_pkey"]);
+        assert_eq!("UNIQUE (email)", owners.constraints["owners_email_key"]);
+        assert!(!owners.column("email").unwrap().nullable);
+        assert!(owners.indexes.is_empty());
+
+        let pets = schema.table("pets").unwrap();
+        let names: Vec<&str> = pets.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(vec!["id", "owner_id", "name"], names);
+        assert_eq!(
+            Some("'rex'"),
+            pets.column("name").unwrap().default.as_deref()
+        );
+        assert_eq!(
+            "FOREIGN KEY (owner_id) REFERENCES owners(id)",
+            pets.constraints["pets_owner_id_fkey"]
+        );
+        assert_eq!(
+            "CREATE INDEX pets_name ON pets (name)",
+            pets.indexes["pets_name"]
+        );
+    }
+
+    #[test]
+    fn migrates_to_target_migration_iter() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let applied_migrations = embedded::migrations::runner()
+            .set_target(Target::Version(3))
+            .run_iter(&mut conn)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+
+        let migrations = get_migrations();
+
+        assert_eq!(3, current.version());
+
+        assert_eq!(3, applied_migrations.len());
+
+        assert_eq!(migrations[0].version(), applied_migrations[0].version());
+        assert_eq!(migrations[1].version(), applied_migrations[1].version());
+        assert_eq!(migrations[2].version(), applied_migrations[2].version());
+
+        assert_eq!(migrations[0].name(), migrations[0].name());
+        assert_eq!(migrations[1].name(), applied_migrations[1].name());
+        assert_eq!(migrations[2].name(), applied_migrations[2].name());
+
+        assert_eq!(migrations[0].checksum(), applied_migrations[0].checksum());
+        assert_eq!(migrations[1].checksum(), applied_migrations[1].checksum());
+        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+    }
+
+    #[test]
+    fn migrates_to_target_migration_grouped() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let report = embedded::migrations::runner()
+            .set_target(Target::Version(3))
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap();
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+
+        let applied_migrations = report.applied_migrations();
+        let migrations = get_migrations();
+
+        assert_eq!(3, current.version());
+
+        assert_eq!(3, applied_migrations.len());
+
+        assert_eq!(migrations[0].version(), applied_migrations[0].version());
+        assert_eq!(migrations[1].version(), applied_migrations[1].version());
+        assert_eq!(migrations[2].version(), applied_migrations[2].version());
+
+        assert_eq!(migrations[0].name(), migrations[0].name());
+        assert_eq!(migrations[1].name(), applied_migrations[1].name());
+        assert_eq!(migrations[2].name(), applied_migrations[2].name());
+
+        assert_eq!(migrations[0].checksum(), applied_migrations[0].checksum());
+        assert_eq!(migrations[1].checksum(), applied_migrations[1].checksum());
+        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+    }
+
+    #[test]
+    fn aborts_on_missing_migration_on_filesystem() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        embedded::migrations::runner().run(&mut conn).unwrap();
+
+        let migration = Migration::unapplied(
+            "V4__add_year_field_to_cars",
+            "ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = conn
+            .migrate(
+                &[migration],
+                true,
+                true,
+                false,
+                Target::Latest,
+                DEFAULT_TABLE_NAME,
+            )
+            .unwrap_err();
+
+        match err.kind() {
+            Kind::MissingVersion(missing) => {
+                assert_eq!(1, missing.version());
+                assert_eq!("initial", missing.name());
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[test]
+    fn aborts_on_divergent_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        embedded::migrations::runner().run(&mut conn).unwrap();
+
+        let migration = Migration::unapplied(
+            "V2__add_year_field_to_cars",
+            "ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = conn
+            .migrate(
+                &[migration.clone()],
+                true,
+                false,
+                false,
+                Target::Latest,
+                DEFAULT_TABLE_NAME,
+            )
+            .unwrap_err();
+
+        match err.kind() {
+            Kind::DivergentVersion(applied, divergent) => {
+                assert_eq!(&migration, divergent);
+                assert_eq!(2, applied.version());
+                assert_eq!("add_cars_and_motos_table", applied.name());
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[test]
+    fn verifies_legacy_checksums_after_switching_algorithm() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        Runner::new(&migrations[..3]).run(&mut conn).unwrap();
+
+        let report = Runner::new(&migrations)
+            .set_checksum_algorithm(ChecksumAlgorithm::Sha256)
+            .set_abort_divergent(true)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let checksums: Vec<String> = conn
+            .prepare("SELECT checksum FROM refinery_schema_history ORDER BY version")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(checksums[..3].iter().all(|c| c.parse::<u64>().is_ok()));
+        assert!(checksums[3..].iter().all(|c| c.starts_with("sha256:")));
+
+        let applied_migrations = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+        let algorithms: Vec<_> = applied_migrations
+            .iter()
+            .map(|migration| migration.checksum_algorithm())
+            .collect();
+        assert_eq!(
+            vec![
+                ChecksumAlgorithm::Sip13Legacy,
+                ChecksumAlgorithm::Sip13Legacy,
+                ChecksumAlgorithm::Sip13Legacy,
+                ChecksumAlgorithm::Sha256,
+                ChecksumAlgorithm::Sha256
+            ],
+            algorithms
+        );
+        assert_eq!(migrations[4], applied_migrations[4]);
+    }
+
+    #[test]
+    fn aborts_on_missing_migration_on_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        missing::migrations::runner().run(&mut conn).unwrap();
+
+        let migration1 = Migration::unapplied(
+            "V1__initial",
+            concat!(
+                "CREATE TABLE persons (",
+                "id int,",
+                "name varchar(255),",
+                "city varchar(255)",
+                ");"
+            ),
+        )
+        .unwrap();
 
         let migration2 = Migration::unapplied(
             "V2__add_cars_table",
@@ -827,6 +2502,584 @@ mod rusqlite {
         assert!(matches!(err.unwrap_err(), Error::QueryReturnedNoRows));
     }
 
+    #[test]
+    fn generates_migration_sql_for_connection_dialect() {
+        fn migration(dialect: Dialect) -> String {
+            assert_eq!(Dialect::Sqlite, dialect);
+            "CREATE TABLE persons (id int, name varchar(255));".into()
+        }
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [Migration::unapplied_for_dialect("V1__initial", migration).unwrap()];
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+
+        let applied_migrations = report.applied_migrations();
+        assert_eq!(1, applied_migrations.len());
+        assert_eq!(
+            Some("CREATE TABLE persons (id int, name varchar(255));"),
+            applied_migrations[0].sql()
+        );
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(applied_migrations[0].checksum(), current.checksum());
+    }
+
+    #[test]
+    fn records_skipped_migrations_without_executing_them() {
+        for grouped in [false, true] {
+            let mut conn = Connection::open_in_memory().unwrap();
+            let migrations = [
+                Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+                Migration::unapplied("V2__superseded", "-- refinery:skip\nNOT VALID SQL;").unwrap(),
+                Migration::unapplied("V3__add_name", "ALTER TABLE persons ADD name varchar(255);")
+                    .unwrap(),
+            ];
+            let report = Runner::new(&migrations)
+                .set_grouped(grouped)
+                .run(&mut conn)
+                .unwrap();
+            assert_eq!(3, report.applied_migrations().len());
+            assert!(report.applied_migrations()[1].is_skipped());
+
+            let applied_migrations = conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap();
+            assert_eq!(3, applied_migrations.len());
+            assert_eq!("superseded", applied_migrations[1].name());
+        }
+    }
+
+    #[test]
+    fn skips_versions_and_reruns_them_once_repaired() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied("V2__add_cars", "CREATE TABLE cars (id int); NOT VALID SQL;")
+                .unwrap(),
+            Migration::unapplied("V3__add_name", "ALTER TABLE persons ADD name varchar(255);")
+                .unwrap(),
+        ];
+        let report = Runner::new(&migrations)
+            .set_skip_versions(&[2])
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+        assert!(report.applied_migrations()[1].is_skipped());
+        assert!(conn.execute("SELECT * FROM cars", []).is_err());
+
+        let skipped = Runner::new(&migrations)
+            .get_skipped_migrations(&mut conn)
+            .unwrap();
+        assert_eq!(1, skipped.len());
+        assert_eq!("add_cars", skipped[0].name());
+
+        let repaired = Migration::unapplied("V2__add_cars", "CREATE TABLE cars (id int);").unwrap();
+        Runner::new(&[migrations[0].clone(), repaired, migrations[2].clone()])
+            .force_rerun(2, &mut conn)
+            .unwrap();
+        conn.execute("SELECT * FROM cars", []).unwrap();
+        assert!(Runner::new(&migrations)
+            .get_skipped_migrations(&mut conn)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn skips_versions_set_repeatedly_and_on_added_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied("V2__add_cars", "NOT VALID SQL;").unwrap(),
+        ];
+        let added = [
+            Migration::unapplied("V3__add_name", "ALTER TABLE persons ADD name varchar(255);")
+                .unwrap(),
+            Migration::unapplied("V4__add_brand", "NOT VALID SQL EITHER;").unwrap(),
+        ];
+        let report = Runner::new(&migrations)
+            .set_skip_versions(&[2])
+            .set_skip_versions(&[4])
+            .add_migrations(&added)
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+        let skipped: Vec<bool> = report
+            .applied_migrations()
+            .iter()
+            .map(Migration::is_skipped)
+            .collect();
+        assert_eq!(vec![false, true, false, true], skipped);
+        conn.execute("SELECT name FROM persons", []).unwrap();
+    }
+
+    #[test]
+    fn applies_settings_to_added_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE cars (id int, brand varchar(255));",
+        )
+        .unwrap()];
+        let added = [Migration::unapplied(
+            "V2__add_brands",
+            "INSERT INTO cars VALUES (1, 'Ford')\nGO\nINSERT INTO cars VALUES (2, 'Audi')",
+        )
+        .unwrap()];
+        let report = Runner::new(&migrations)
+            .set_checksum_algorithm(ChecksumAlgorithm::Sha256)
+            .set_splitter(Splitter::Separator("GO".into()))
+            .set_applied_by("deploy-pipeline")
+            .add_migrations(&added)
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let rows: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT checksum, applied_by FROM refinery_schema_history ORDER BY version")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        for (checksum, applied_by) in rows {
+            assert!(checksum.starts_with("sha256:"));
+            assert_eq!(Some("deploy-pipeline".to_string()), applied_by);
+        }
+        let cars: i64 = conn
+            .query_row("SELECT COUNT(*) FROM cars", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(2, cars);
+    }
+
+    #[test]
+    fn gets_pending_migrations_without_applying_them() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        Runner::new(&migrations[..2]).run(&mut conn).unwrap();
+
+        let pending = Runner::new(&migrations)
+            .get_pending_migrations(&mut conn)
+            .unwrap();
+        let versions: Vec<u32> = pending.iter().map(Migration::version).collect();
+        assert_eq!(vec![3, 4, 5], versions);
+        let pending = Runner::new(&migrations)
+            .set_target(Target::Version(3))
+            .get_pending_migrations(&mut conn)
+            .unwrap();
+        assert_eq!(1, pending.len());
+        assert_eq!(
+            2,
+            conn.get_applied_migrations(DEFAULT_TABLE_NAME)
+                .unwrap()
+                .len()
+        );
+
+        // verified the same way as when running them
+        let mut divergent = migrations.clone();
+        divergent[1] =
+            Migration::unapplied("V2__add_trucks_table", "CREATE TABLE trucks (id int);").unwrap();
+        let err = Runner::new(&divergent)
+            .get_pending_migrations(&mut conn)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::DivergentVersion(_, _)));
+    }
+
+    #[test]
+    fn verifies_checksums_of_applied_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        Runner::new(&migrations[..3]).run(&mut conn).unwrap();
+        assert!(Runner::new(&migrations)
+            .verify_checksums(&mut conn)
+            .unwrap()
+            .is_empty());
+
+        // every mismatch is returned, unlike a run that aborts on the first divergent migration
+        let mut edited = migrations.to_vec();
+        edited[0] = Migration::unapplied(
+            "V1__initial",
+            "CREATE TABLE persons (id int, name varchar(255));",
+        )
+        .unwrap();
+        edited[2] = Migration::unapplied("V3__add_brand_to_cars_table", "SELECT 1;").unwrap();
+        let mismatches = Runner::new(&edited[1..])
+            .set_checksum_algorithm(ChecksumAlgorithm::Sha256)
+            .verify_checksums(&mut conn)
+            .unwrap();
+        assert_eq!(1, mismatches.len());
+        assert_eq!(3, mismatches[0].applied().version());
+        assert_eq!(
+            mismatches[0].applied().checksum().to_string(),
+            mismatches[0].applied_checksum()
+        );
+        assert_ne!(mismatches[0].applied_checksum(), mismatches[0].checksum());
+        assert!(!mismatches[0].checksum().starts_with("sha256:"));
+
+        let mismatches = Runner::new(&edited).verify_checksums(&mut conn).unwrap();
+        let versions: Vec<u32> = mismatches
+            .iter()
+            .map(|mismatch| mismatch.applied().version())
+            .collect();
+        assert_eq!(vec![1, 3], versions);
+    }
+
+    #[test]
+    fn validates_migrations_without_applying_them() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = get_migrations();
+        Runner::new(&migrations[..3]).run(&mut conn).unwrap();
+
+        let report = Runner::new(&migrations).validate(&mut conn).unwrap();
+        assert!(report.is_valid());
+        let versions: Vec<u32> = report
+            .pending_migrations()
+            .iter()
+            .map(Migration::version)
+            .collect();
+        assert_eq!(vec![4, 5], versions);
+
+        // every issue is collected, even the ones a run doesn't abort on
+        let mut inconsistent = migrations[1..].to_vec();
+        inconsistent[0] =
+            Migration::unapplied("V2__add_trucks_table", "CREATE TABLE trucks (id int);").unwrap();
+        let report = Runner::new(&inconsistent).validate(&mut conn).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            &vec![
+                ValidationIssue::Missing(
+                    conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap()[0].clone()
+                ),
+                ValidationIssue::Divergent {
+                    applied: conn.get_applied_migrations(DEFAULT_TABLE_NAME).unwrap()[1].clone(),
+                    migration: inconsistent[0].clone(),
+                },
+            ],
+            report.issues()
+        );
+        assert_eq!(
+            3,
+            conn.get_applied_migrations(DEFAULT_TABLE_NAME)
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn runs_no_transaction_migrations_outside_of_a_transaction() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial =
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap();
+        let vacuum = Migration::unapplied("V2__vacuum", "VACUUM;").unwrap();
+
+        // Sqlite can't VACUUM inside of a transaction
+        let err = Runner::new(&[initial.clone(), vacuum])
+            .run(&mut conn)
+            .unwrap_err();
+        assert_eq!(1, err.report().unwrap().applied_migrations().len());
+
+        let vacuum =
+            Migration::unapplied("V2__vacuum", "-- refinery:no-transaction\nVACUUM;").unwrap();
+        let err = Runner::new(&[initial.clone(), vacuum.clone()])
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap_err();
+        assert!(
+            matches!(err.kind(), Kind::GroupedNoTransaction(migration) if migration.version() == 2)
+        );
+
+        let report = Runner::new(&[initial, vacuum]).run(&mut conn).unwrap();
+        assert_eq!(1, report.applied_migrations().len());
+        assert!(!report.applied_migrations()[0].is_transactional());
+    }
+
+    #[test]
+    fn leaves_failed_no_transaction_migrations_partially_applied() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migration = Migration::unapplied(
+            "V1__initial",
+            "-- refinery:no-transaction\nCREATE TABLE persons (id int);\nNOT VALID SQL;",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration]).run(&mut conn).unwrap_err();
+        assert!(err.report().unwrap().applied_migrations().is_empty());
+        assert!(conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .unwrap()
+            .is_none());
+        let tables: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'persons'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(1, tables);
+        let statement = err.statement().unwrap();
+        assert_eq!((2, 3), (statement.index(), statement.line()));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn emits_migration_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use refinery::metrics::{
+            MIGRATIONS_APPLIED, MIGRATION_DURATION, MIGRATION_FAILURES, PENDING_MIGRATIONS,
+        };
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial =
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap();
+        let broken = Migration::unapplied("V2__broken", "NOT VALID SQL;").unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            Runner::new(&[initial, broken]).run(&mut conn).unwrap_err();
+        });
+
+        let metrics: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<String> = key
+                    .labels()
+                    .map(|label| label.value().to_string())
+                    .collect();
+                (key.name().to_string(), labels, value)
+            })
+            .collect();
+        let find = |name: &str| {
+            metrics
+                .iter()
+                .find(|(metric, _, _)| metric == name)
+                .map(|(_, labels, value)| (labels.as_slice(), value))
+                .unwrap()
+        };
+        let no_labels: &[String] = &[];
+        assert_eq!(
+            (no_labels, &DebugValue::Counter(1)),
+            find(MIGRATIONS_APPLIED)
+        );
+        assert_eq!(
+            (
+                ["V2__broken".to_string()].as_slice(),
+                &DebugValue::Counter(1)
+            ),
+            find(MIGRATION_FAILURES)
+        );
+        assert_eq!(["V1__initial"], find(MIGRATION_DURATION).0);
+        assert!(
+            matches!(find(MIGRATION_DURATION).1, DebugValue::Histogram(durations) if durations.len() == 1)
+        );
+        assert_eq!(&DebugValue::Gauge(1.0.into()), find(PENDING_MIGRATIONS).1);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn emits_migration_spans() {
+        use std::io;
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let output = output.clone();
+                move || output.clone()
+            })
+            .with_span_events(FmtSpan::CLOSE)
+            .with_ansi(false)
+            .finish();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial =
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap();
+        let divergent = Migration::unapplied("V1__initial", "CREATE TABLE cars (id int);").unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            Runner::new(std::slice::from_ref(&initial))
+                .run(&mut conn)
+                .unwrap();
+            Runner::new(&[divergent]).run(&mut conn).unwrap_err();
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains(&format!(
+            "migration{{version=1 name=V1__initial checksum={} duration_ms=",
+            initial.checksum()
+        )));
+        assert!(output.contains("issue=\"divergent\" version=1 name=V1__initial"));
+    }
+
+    #[cfg(feature = "enums")]
+    #[test]
+    fn enumerates_embedded_migrations() {
+        use embedded::migrations::EmbeddedMigration;
+        use std::convert::TryFrom;
+
+        let migrations: Vec<(u32, &str)> = EmbeddedMigration::all()
+            .iter()
+            .map(|migration| (migration.version(), migration.name()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, "initial"),
+                (2, "add_cars_and_motos_table"),
+                (3, "add_brand_to_cars_table"),
+                (4, "add_year_to_motos_table"),
+            ],
+            migrations
+        );
+        assert!(matches!(
+            EmbeddedMigration::try_from(3),
+            Ok(EmbeddedMigration::AddBrandToCarsTable(migration)) if migration.version() == 3
+        ));
+        assert_eq!(42, EmbeddedMigration::try_from(42).unwrap_err());
+    }
+
+    #[test]
+    fn reports_the_failing_statement() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let initial =
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap();
+        let broken = Migration::unapplied(
+            "V2__add_cars",
+            "-- cars and their owners\nCREATE TABLE cars (id int);\n\nINSERT INTO cars (id)\n  VALUES (1, 2);\nCREATE TABLE owners (id int);",
+        )
+        .unwrap();
+
+        for grouped in [false, true] {
+            let err = Runner::new(&[initial.clone(), broken.clone()])
+                .set_grouped(grouped)
+                .run(&mut conn)
+                .unwrap_err();
+            let statement = err.statement().unwrap();
+            assert_eq!("V2__add_cars", statement.migration());
+            assert_eq!((2, 4), (statement.index(), statement.line()));
+            assert_eq!("INSERT INTO cars (id)\n  VALUES (1, 2);", statement.sql());
+            assert!(err
+                .to_string()
+                .ends_with(", on statement 2 of migration V2__add_cars at line 4"));
+        }
+    }
+
+    #[test]
+    fn runs_registered_migrations() {
+        let migrations = refinery::collect_migrations!();
+        let names: Vec<String> = migrations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "V5__add_colour_to_cars_table",
+                "V6__add_doors_to_cars_table"
+            ],
+            names
+        );
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let report = embedded::migrations::runner()
+            .add_migrations(&migrations)
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(6, report.applied_migrations().len());
+        conn.execute(
+            "INSERT INTO cars (name, brand, colour, doors) VALUES ('Beetle', 'Volkswagen', 'yellow', 2)",
+            [],
+        )
+        .unwrap();
+
+        let result = embedded::migrations::runner()
+            .add_migrations(&migrations)
+            .unwrap()
+            .add_migrations(&migrations);
+        assert!(matches!(
+            result.map(|_| ()).unwrap_err().kind(),
+            Kind::RepeatedVersion(_)
+        ));
+    }
+
+    #[test]
+    fn migrates_from_cli_with_table_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_config = |name: &str| {
+            let db_path = dir.path().join(format!("{}.sql", name));
+            File::create(&db_path).unwrap();
+            let config_path = dir.path().join(format!("{}.toml", name));
+            let config = format!(
+                "[main]\ndb_type = \"Sqlite\"\ndb_path = \"{}\"\ntable_name = \"tenant_a_schema_history\"\n",
+                db_path.display()
+            );
+            fs::write(&config_path, config).unwrap();
+            (db_path, config_path.display().to_string())
+        };
+
+        let (db_path, config_path) = write_config("tenant_a");
+        Command::new("refinery")
+            .args(["migrate", "-c", &config_path, "-p", "tests/migrations"])
+            .unwrap()
+            .assert()
+            .stdout(contains("applying migration: V3__add_brand_to_cars_table"));
+        let conn = Connection::open(&db_path).unwrap();
+        assert!(table_exists(&conn, "tenant_a_schema_history"));
+        assert!(!table_exists(&conn, DEFAULT_TABLE_NAME));
+        Command::new("refinery")
+            .args(["status", "-c", &config_path, "-p", "tests/migrations"])
+            .unwrap()
+            .assert()
+            .stdout(contains("applied migration: V3__add_brand_to_cars_table"));
+
+        // the flag overrides the table name of the config file
+        let (db_path, config_path) = write_config("tenant_b");
+        Command::new("refinery")
+            .args(["migrate", "-c", &config_path, "-p", "tests/migrations"])
+            .args(["--table-name", "tenant_b_schema_history"])
+            .unwrap()
+            .assert()
+            .stdout(contains("applying migration: V3__add_brand_to_cars_table"));
+        let conn = Connection::open(&db_path).unwrap();
+        assert!(table_exists(&conn, "tenant_b_schema_history"));
+        assert!(!table_exists(&conn, "tenant_a_schema_history"));
+    }
+
+    #[test]
+    fn validates_from_cli() {
+        run_test(|| {
+            Command::new("refinery")
+                .args(["migrate", "-c", "tests/sqlite_refinery.toml"])
+                .args(["-p", "tests/migrations"])
+                .unwrap()
+                .assert()
+                .success();
+            Command::new("refinery")
+                .args(["validate", "-c", "tests/sqlite_refinery.toml"])
+                .args(["-p", "tests/migrations"])
+                .unwrap()
+                .assert()
+                .stdout(contains("migrations are consistent with the applied ones"));
+            Command::new("refinery")
+                .args(["validate", "-c", "tests/sqlite_refinery.toml"])
+                .args(["-p", "tests/migrations_broken"])
+                .assert()
+                .failure()
+                .stdout(contains("is different than V2__add_cars_table"));
+        })
+    }
+
     #[test]
     fn migrates_from_cli() {
         run_test(|| {
@@ -0,0 +1,180 @@
+use barrel::backend::Pg as Sql;
+
+#[cfg(feature = "sqlx-postgres")]
+mod sqlx_postgres {
+    use futures::FutureExt;
+    use refinery::{embed_migrations, AsyncMigrate, Migration, Target};
+    use refinery_core::sqlx::{self, Connection, Executor, Row};
+    use std::panic::AssertUnwindSafe;
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    fn get_migrations() -> Vec<Migration> {
+        embed_migrations!("./tests/migrations");
+
+        let migration1 =
+            Migration::unapplied("V1__initial.rs", &migrations::V1__initial::migration()).unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_and_motos_table.sql",
+            include_str!("./migrations/V1-2/V2__add_cars_and_motos_table.sql"),
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            include_str!("./migrations/V3/V3__add_brand_to_cars_table.sql"),
+        )
+        .unwrap();
+
+        let migration4 = Migration::unapplied(
+            "V4__add_year_to_motos_table.rs",
+            &migrations::V4__add_year_to_motos_table::migration(),
+        )
+        .unwrap();
+
+        let migration5 = Migration::unapplied(
+            "V5__add_year_field_to_cars",
+            "ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3, migration4, migration5]
+    }
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations");
+    }
+
+    mod broken {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_broken");
+    }
+
+    async fn run_test<T: std::future::Future<Output = ()>>(t: T) {
+        let result = AssertUnwindSafe(t).catch_unwind().await;
+        clean_database().await;
+        assert!(result.is_ok());
+    }
+
+    async fn clean_database() {
+        let mut conn = sqlx::PgConnection::connect("postgres://postgres@localhost:5432/template1")
+            .await
+            .unwrap();
+
+        conn.execute(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE datname='postgres'",
+        )
+        .await
+        .unwrap();
+        conn.execute("DROP DATABASE postgres").await.unwrap();
+        conn.execute("CREATE DATABASE POSTGRES").await.unwrap();
+    }
+
+    async fn connection() -> sqlx::PgConnection {
+        sqlx::PgConnection::connect("postgres://postgres@localhost:5432/postgres")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn report_contains_applied_migrations() {
+        run_test(async {
+            let mut conn = connection().await;
+            let report = embedded::migrations::runner()
+                .run_async(&mut conn)
+                .await
+                .unwrap();
+
+            let migrations = get_migrations();
+            let applied_migrations = report.applied_migrations();
+
+            assert_eq!(4, applied_migrations.len());
+            for (migration, applied) in migrations.iter().zip(applied_migrations) {
+                assert_eq!(migration.version(), applied.version());
+                assert_eq!(migration.name(), applied.name());
+                assert_eq!(migration.checksum(), applied.checksum());
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn applies_migration_grouped() {
+        run_test(async {
+            let mut conn = connection().await;
+            embedded::migrations::runner()
+                .set_grouped(true)
+                .run_async(&mut conn)
+                .await
+                .unwrap();
+
+            conn.execute("INSERT INTO persons (name, city) VALUES ('John Legend', 'New York')")
+                .await
+                .unwrap();
+            let row = conn
+                .fetch_one("SELECT name, city FROM persons")
+                .await
+                .unwrap();
+            assert_eq!("John Legend", row.get::<String, _>(0));
+            assert_eq!("New York", row.get::<String, _>(1));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn updates_to_last_working_if_not_grouped() {
+        run_test(async {
+            let mut conn = connection().await;
+            let result = broken::migrations::runner().run_async(&mut conn).await;
+            assert!(result.is_err());
+
+            let current = conn
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .unwrap();
+            let err = result.unwrap_err();
+            assert_eq!(2, current.version());
+            assert_eq!(2, err.report().unwrap().applied_migrations().len());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn migrates_with_pool() {
+        run_test(async {
+            let mut pool = sqlx::PgPool::connect("postgres://postgres@localhost:5432/postgres")
+                .await
+                .unwrap();
+
+            embedded::migrations::runner()
+                .run_async(&mut pool)
+                .await
+                .unwrap();
+
+            let migrations = get_migrations();
+            pool.migrate(
+                &migrations,
+                true,
+                true,
+                false,
+                Target::Latest,
+                DEFAULT_TABLE_NAME,
+            )
+            .await
+            .unwrap();
+
+            let current = pool
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(5, current.version());
+            assert_eq!(migrations[4].checksum(), current.checksum());
+            pool.close().await;
+        })
+        .await;
+    }
+}
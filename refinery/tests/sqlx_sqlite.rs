@@ -0,0 +1,320 @@
+use barrel::backend::Sqlite as Sql;
+
+#[cfg(feature = "sqlx-sqlite")]
+mod sqlx_sqlite {
+    use futures::{StreamExt, TryStreamExt};
+    use refinery::{embed_migrations, error::Kind, AsyncMigrate, Migration, Runner, Target};
+    use refinery_core::sqlx::{self, Connection, Row};
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+    fn get_migrations() -> Vec<Migration> {
+        embed_migrations!("./tests/migrations");
+
+        let migration1 =
+            Migration::unapplied("V1__initial.rs", &migrations::V1__initial::migration()).unwrap();
+
+        let migration2 = Migration::unapplied(
+            "V2__add_cars_and_motos_table.sql",
+            include_str!("./migrations/V1-2/V2__add_cars_and_motos_table.sql"),
+        )
+        .unwrap();
+
+        let migration3 = Migration::unapplied(
+            "V3__add_brand_to_cars_table",
+            include_str!("./migrations/V3/V3__add_brand_to_cars_table.sql"),
+        )
+        .unwrap();
+
+        let migration4 = Migration::unapplied(
+            "V4__add_year_to_motos_table.rs",
+            &migrations::V4__add_year_to_motos_table::migration(),
+        )
+        .unwrap();
+
+        let migration5 = Migration::unapplied(
+            "V5__add_year_field_to_cars",
+            "ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+
+        vec![migration1, migration2, migration3, migration4, migration5]
+    }
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations");
+    }
+
+    mod broken {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_broken");
+    }
+
+    mod r#async {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/migrations_async");
+    }
+
+    async fn connection() -> sqlx::SqliteConnection {
+        sqlx::SqliteConnection::connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn report_contains_applied_migrations() {
+        let mut conn = connection().await;
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        let migrations = get_migrations();
+        let applied_migrations = report.applied_migrations();
+
+        assert_eq!(4, applied_migrations.len());
+        for (migration, applied) in migrations.iter().zip(applied_migrations) {
+            assert_eq!(migration.version(), applied.version());
+            assert_eq!(migration.name(), applied.name());
+            assert_eq!(migration.checksum(), applied.checksum());
+        }
+    }
+
+    #[tokio::test]
+    async fn report_contains_applied_migrations_iter() {
+        let mut conn = connection().await;
+        let applied_migrations: Vec<Migration> = embedded::migrations::runner()
+            .run_iter_async(&mut conn)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let migrations = get_migrations();
+
+        assert_eq!(4, applied_migrations.len());
+        for (migration, applied) in migrations.iter().zip(&applied_migrations) {
+            assert_eq!(migration.version(), applied.version());
+            assert_eq!(migration.name(), applied.name());
+            assert_eq!(migration.checksum(), applied.checksum());
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_migration_grouped() {
+        let mut conn = connection().await;
+        embedded::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        sqlx::query("INSERT INTO persons (name, city) VALUES ('John Legend', 'New York')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let row = sqlx::query("SELECT name, city FROM persons")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!("John Legend", row.get::<String, _>(0));
+        assert_eq!("New York", row.get::<String, _>(1));
+    }
+
+    #[tokio::test]
+    async fn updates_to_last_working_if_not_grouped() {
+        let mut conn = connection().await;
+        let result = broken::migrations::runner().run_async(&mut conn).await;
+        assert!(result.is_err());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        let err = result.unwrap_err();
+        assert_eq!(2, current.version());
+        assert_eq!(2, err.report().unwrap().applied_migrations().len());
+    }
+
+    #[tokio::test]
+    async fn stops_iter_on_failed_migration() {
+        let mut conn = connection().await;
+        let results: Vec<_> = broken::migrations::runner()
+            .run_iter_async(&mut conn)
+            .collect()
+            .await;
+
+        assert_eq!(3, results.len());
+        assert_eq!(1, results[0].as_ref().unwrap().version());
+        assert_eq!(2, results[1].as_ref().unwrap().version());
+        assert!(results[2].is_err());
+
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+    }
+
+    #[tokio::test]
+    async fn migrates_to_target_migration() {
+        let mut conn = connection().await;
+        let migrations = get_migrations();
+        let report = conn
+            .migrate(
+                &migrations,
+                true,
+                true,
+                false,
+                Target::Version(3),
+                DEFAULT_TABLE_NAME,
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, report.applied_migrations().len());
+
+        let applied_migrations = conn
+            .get_applied_migrations(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap();
+        assert_eq!(3, applied_migrations.len());
+        assert_eq!(migrations[2].checksum(), applied_migrations[2].checksum());
+    }
+
+    #[tokio::test]
+    async fn gets_pending_migrations() {
+        let mut conn = connection().await;
+        let migrations = get_migrations();
+        let runner = Runner::new(&migrations[..2]);
+        runner.run_async(&mut conn).await.unwrap();
+
+        let runner = Runner::new(&migrations).set_target(Target::Version(4));
+        let pending = runner
+            .get_pending_migrations_async(&mut conn)
+            .await
+            .unwrap();
+        let versions: Vec<u32> = pending.iter().map(Migration::version).collect();
+        assert_eq!(vec![3, 4], versions);
+        assert_eq!(
+            2,
+            conn.get_applied_migrations(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn migrates_with_pool() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let options =
+            sqlx::sqlite::SqliteConnectOptions::from_str(db.path().to_str().unwrap()).unwrap();
+        let mut pool = sqlx::SqlitePool::connect_with(options).await.unwrap();
+
+        embedded::migrations::runner()
+            .run_async(&mut pool)
+            .await
+            .unwrap();
+
+        let migrations = get_migrations();
+        pool.migrate(
+            &migrations,
+            true,
+            true,
+            false,
+            Target::Latest,
+            DEFAULT_TABLE_NAME,
+        )
+        .await
+        .unwrap();
+
+        let current = pool
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(5, current.version());
+        assert_eq!(migrations[4].checksum(), current.checksum());
+    }
+
+    #[tokio::test]
+    async fn invokes_callbacks_around_each_migration() {
+        let mut conn = connection().await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let before = calls.clone();
+        let after = calls.clone();
+
+        embedded::migrations::runner()
+            .set_before_migration(move |migration| {
+                before.lock().unwrap().push((migration.version(), None))
+            })
+            .set_after_migration(move |migration, duration| {
+                after
+                    .lock()
+                    .unwrap()
+                    .push((migration.version(), Some(duration)))
+            })
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        let calls = calls.lock().unwrap();
+        let versions: Vec<_> = calls.iter().map(|(version, _)| *version).collect();
+        assert_eq!(vec![1, 1, 2, 2, 3, 3, 4, 4], versions);
+        assert!(calls.iter().skip(1).step_by(2).all(|(_, d)| d.is_some()));
+    }
+
+    #[tokio::test]
+    async fn runs_async_rust_migrations() {
+        let mut conn = connection().await;
+        let runner = r#async::migrations::runner();
+        let report = runner.run_async(&mut conn).await.unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let rows = sqlx::query("SELECT name, city FROM persons ORDER BY id")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        let cities: Vec<String> = rows.iter().map(|row| row.get(1)).collect();
+        assert_eq!(vec!["NEW YORK", "LONDON"], cities);
+
+        // the checksum of the async migration is formed from its source
+        let migration = &runner.get_migrations()[1];
+        assert!(migration.sql().is_none());
+        let current = conn
+            .get_last_applied_migration(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, current.version());
+        assert_eq!(migration.checksum(), current.checksum());
+        assert_eq!(
+            Some(migration.checksum()),
+            r#async::migrations::manifest().migrations[1].checksum
+        );
+    }
+
+    #[tokio::test]
+    async fn doesnt_group_async_rust_migrations() {
+        let mut conn = connection().await;
+        let err = r#async::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut conn)
+            .await
+            .unwrap_err();
+        match err.kind() {
+            Kind::UnsupportedAsyncMigration(migration) => assert_eq!(2, migration.version()),
+            kind => panic!("unexpected error {}", kind),
+        }
+
+        let applied = conn
+            .get_applied_migrations(DEFAULT_TABLE_NAME)
+            .await
+            .unwrap();
+        assert!(applied.is_empty());
+    }
+}
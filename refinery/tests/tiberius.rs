@@ -102,6 +102,36 @@ mod tiberius {
         assert!(result.is_ok());
     }
 
+    #[cfg(feature = "bb8-tiberius")]
+    #[tokio::test]
+    async fn migrates_from_bb8_pool() {
+        use refinery_core::{bb8, bb8_tiberius::ConnectionManager};
+
+        run_test(async {
+            let config = Config::from_str(CONFIG).unwrap();
+            let mut tconfig: TConfig = (&config).try_into().unwrap();
+            tconfig.trust_cert();
+            let mut pool = bb8::Pool::builder()
+                .build(ConnectionManager::new(tconfig))
+                .await
+                .unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut pool)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let last = embedded::migrations::runner()
+                .get_last_applied_migration_async(&mut pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(4, last.version());
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn aborts_on_missing_migration_on_filesystem() {
         run_test(async {
@@ -7,11 +7,12 @@ mod tokio_postgres {
         config::{Config, ConfigDbType},
         embed_migrations,
         error::Kind,
-        AsyncMigrate, Migration, Runner, Target,
+        AsyncMigrate, CancelHandle, Migration, Runner, Target,
     };
     use refinery_core::tokio_postgres;
     use refinery_core::tokio_postgres::NoTls;
     use std::panic::AssertUnwindSafe;
+    use std::str::FromStr;
     use time::OffsetDateTime;
 
     const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
@@ -135,6 +136,70 @@ mod tokio_postgres {
         .await;
     }
 
+    #[cfg(feature = "deadpool-postgres")]
+    #[tokio::test]
+    async fn migrates_from_deadpool_pool() {
+        use refinery_core::deadpool_postgres::{Manager, Pool};
+
+        run_test(async {
+            let config =
+                tokio_postgres::Config::from_str("postgres://postgres@localhost:5432/postgres")
+                    .unwrap();
+            let mut pool = Pool::builder(Manager::new(config, NoTls))
+                .max_size(2)
+                .build()
+                .unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut pool)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let mut object = pool.get().await.unwrap();
+            let last = embedded::migrations::runner()
+                .get_last_applied_migration_async(&mut object)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(4, last.version());
+        })
+        .await;
+    }
+
+    #[cfg(feature = "bb8-postgres")]
+    #[tokio::test]
+    async fn migrates_from_bb8_pool() {
+        use refinery_core::{bb8, bb8_postgres::PostgresConnectionManager};
+
+        run_test(async {
+            let manager = PostgresConnectionManager::new_from_stringlike(
+                "postgres://postgres@localhost:5432/postgres",
+                NoTls,
+            )
+            .unwrap();
+            let mut pool = bb8::Pool::builder()
+                .max_size(2)
+                .build(manager)
+                .await
+                .unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut pool)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let last = embedded::migrations::runner()
+                .get_last_applied_migration_async(&mut pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(4, last.version());
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn creates_migration_table() {
         run_test(async {
@@ -555,6 +620,94 @@ mod tokio_postgres {
         .await;
     }
 
+    #[tokio::test]
+    async fn cancels_migration_run_exceeding_timeout() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            let migrations = [
+                Migration::unapplied("V1__initial", "CREATE TABLE people (id int);").unwrap(),
+                Migration::unapplied(
+                    "V2__slow",
+                    "INSERT INTO people VALUES (1);\nSELECT pg_sleep(2);",
+                )
+                .unwrap(),
+            ];
+            let err = Runner::new(&migrations)
+                .set_run_timeout(std::time::Duration::from_millis(500))
+                .run_async(&mut client)
+                .await
+                .unwrap_err();
+
+            assert!(matches!(err.kind(), Kind::RunTimeout(_)));
+            let applied_migrations = err.report().unwrap().applied_migrations();
+            assert_eq!(1, applied_migrations.len());
+            assert_eq!(1, applied_migrations[0].version());
+            let count: i64 = client
+                .query_one("SELECT count(*) FROM people", &[])
+                .await
+                .unwrap()
+                .get(0);
+            assert_eq!(0, count);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn cancels_migration_run_after_current_migration() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            let migrations = [
+                Migration::unapplied("V1__initial", "CREATE TABLE people (id int);").unwrap(),
+                Migration::unapplied(
+                    "V2__slow",
+                    "SELECT pg_sleep(1);\nINSERT INTO people VALUES (1);",
+                )
+                .unwrap(),
+                Migration::unapplied("V3__add_person", "INSERT INTO people VALUES (2);").unwrap(),
+            ];
+            let cancel_handle = CancelHandle::new();
+            let canceller = cancel_handle.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+                canceller.cancel();
+            });
+
+            let err = Runner::new(&migrations)
+                .set_cancel_handle(cancel_handle)
+                .run_async(&mut client)
+                .await
+                .unwrap_err();
+
+            assert!(matches!(err.kind(), Kind::Cancelled));
+            let applied_migrations = err.report().unwrap().applied_migrations();
+            assert_eq!(2, applied_migrations.len());
+            assert_eq!(2, applied_migrations[1].version());
+            let count: i64 = client
+                .query_one("SELECT count(*) FROM people", &[])
+                .await
+                .unwrap()
+                .get(0);
+            assert_eq!(1, count);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn migrates_to_target_migration_grouped() {
         run_test(async {
@@ -750,6 +903,144 @@ mod tokio_postgres {
         .await;
     }
 
+    #[tokio::test]
+    async fn migrates_inside_transaction() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            // the migrations are rolled back with the transaction they are applied on
+            let mut transaction = client.transaction().await.unwrap();
+            let report = embedded::migrations::runner()
+                .run_async(&mut transaction)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+            transaction.rollback().await.unwrap();
+            let tables: i64 = client
+                .query_one(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = 'public'",
+                    &[],
+                )
+                .await
+                .unwrap()
+                .get(0);
+            assert_eq!(0, tables);
+
+            // a failing migration only rolls back its savepoint, leaving the transaction usable
+            let mut transaction = client.transaction().await.unwrap();
+            let err = broken::migrations::runner()
+                .run_async(&mut transaction)
+                .await
+                .unwrap_err();
+            assert_eq!(2, err.report().unwrap().applied_migrations().len());
+            transaction.commit().await.unwrap();
+            let current = client
+                .get_last_applied_migration(DEFAULT_TABLE_NAME)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(2, current.version());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rolls_back_from_config() {
+        run_test(async {
+            let mut config =
+                Config::from_str("postgres://postgres@localhost:5432/postgres").unwrap();
+            let runner = Runner::new(&[
+                Migration::unapplied(
+                    "V1__add_users",
+                    "CREATE TABLE users(id int);\n-- refinery:down\nDROP TABLE users;",
+                )
+                .unwrap(),
+                Migration::unapplied(
+                    "V2__add_cars",
+                    "CREATE TABLE cars(id int);\n-- refinery:down\nDROP TABLE cars;",
+                )
+                .unwrap(),
+            ]);
+            runner.run_async(&mut config).await.unwrap();
+
+            let report = runner
+                .rollback_async(&mut config, Target::Version(0))
+                .await
+                .unwrap();
+            assert_eq!(2, report.applied_migrations().len());
+            let applied = runner
+                .get_applied_migrations_async(&mut config)
+                .await
+                .unwrap();
+            assert!(applied.is_empty());
+
+            // the tables were dropped, so the migrations can be applied again
+            let report = runner.run_async(&mut config).await.unwrap();
+            assert_eq!(2, report.applied_migrations().len());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn creates_database_if_missing_from_config() {
+        let (client, connection) =
+            tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                .await
+                .unwrap();
+        tokio::spawn(async move {
+            connection.await.unwrap();
+        });
+        client
+            .execute("DROP DATABASE IF EXISTS refinery_created_async_test", &[])
+            .await
+            .unwrap();
+        let mut config = Config::from_str("postgres://postgres@localhost:5432/postgres")
+            .unwrap()
+            .set_db_name("refinery_created_async_test")
+            .set_create_db_if_missing(true);
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut config)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        client
+            .execute("DROP DATABASE refinery_created_async_test", &[])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrates_from_native_config() {
+        run_test(async {
+            let mut config: tokio_postgres::Config = "postgres://postgres@localhost:5432/postgres"
+                .parse()
+                .unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut config)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let last = embedded::migrations::runner()
+                .get_last_applied_migration_async(&mut config)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(4, last.version());
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn migrates_from_config() {
         run_test(async {
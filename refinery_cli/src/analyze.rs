@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use anyhow::Context;
+use refinery_core::analyze::analyze;
+use refinery_core::config::ConfigDbType;
+use refinery_core::{load_sql_migrations, Dialect, Migration};
+
+use crate::cli::AnalyzeArgs;
+use crate::migrate::{config, migration_table_name};
+
+pub fn handle_analyze_command(args: AnalyzeArgs) -> anyhow::Result<()> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+
+    let (migrations, dialect) = if args.config.is_some() || args.env_var.is_some() {
+        let location = args
+            .config
+            .as_deref()
+            .unwrap_or(Path::new("./refinery.toml"));
+        pending_migrations(
+            location,
+            args.env_var.as_deref(),
+            args.env.as_deref(),
+            &migrations,
+            args.table_name.as_deref(),
+        )?
+    } else {
+        (migrations, args.dialect)
+    };
+
+    println!("{}", analyze(&migrations, dialect));
+    Ok(())
+}
+
+// the migrations pending on the database of the config, and its dialect
+fn pending_migrations(
+    location: &Path,
+    env_var: Option<&str>,
+    env: Option<&str>,
+    migrations: &[Migration],
+    table_name: Option<&str>,
+) -> anyhow::Result<(Vec<Migration>, Option<Dialect>)> {
+    let mut config = config(location, env_var, env)?;
+    let dialect = Some(config.db_type().into());
+    let table_name = migration_table_name(table_name, &config);
+    let table_name = match config.table_schema() {
+        Some(schema) => format!("{}.{}", schema, table_name),
+        None => table_name,
+    };
+    let table_name = table_name.as_str();
+    let pending = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to query the applied migrations
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use refinery_core::AsyncMigrate;
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(AsyncMigrate::get_unapplied_migrations(
+                        &mut config,
+                        migrations,
+                        false,
+                        false,
+                        table_name,
+                    ))?
+                } else {
+                    panic!("tried to analyze migrations from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    refinery_core::Migrate::get_unapplied_migrations(
+                        &mut config,
+                        migrations,
+                        false,
+                        false,
+                        table_name,
+                    )?
+                } else {
+                    panic!("tried to analyze migrations from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok((pending, dialect))
+}
@@ -2,16 +2,77 @@
 
 use std::path::PathBuf;
 
-use clap::{Args, Parser};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use refinery_core::scaffold::MigrationTemplate;
+use refinery_core::{Dialect, MigrationNaming};
 
 #[derive(Parser)]
 #[clap(version)]
-pub enum Cli {
+pub struct Cli {
+    /// Output format of the reports of migrate, status, validate, verify and rollback, json for deployment pipelines to parse them,
+    /// and of history export, json to be imported with history import
+    #[clap(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
     /// Run the refinery setup hooks to generate the config file
     Setup,
 
     /// Refinery's main migrate operation
     Migrate(MigrateArgs),
+
+    /// List the applied and pending migrations of the database
+    Status(StatusArgs),
+
+    /// Validate the migrations against the applied ones without applying them, failing on divergent,
+    /// missing and repeated migrations
+    Validate(ValidateArgs),
+
+    /// Compare the checksums of the applied migrations against the migrations directory, failing on
+    /// the ones that differ
+    Verify(VerifyArgs),
+
+    /// Roll back the applied migrations after the target version, or the last applied ones with --steps,
+    /// executing their down section
+    Rollback(RollbackArgs),
+
+    /// Generate a new migration file with the next version available
+    Generate(GenerateArgs),
+
+    /// Lint the migrations directory, failing on errors
+    Lint(LintArgs),
+
+    /// Apply the pending migrations to a canary database first, asserting the verification checks on it,
+    /// and only then to the target databases
+    Rollout(RolloutArgs),
+
+    /// Classify the migrations as reversible, destructive or blocking, producing a risk summary for change review
+    Analyze(AnalyzeArgs),
+
+    /// Validate the migrations directory and bundle it with a manifest of their checksums,
+    /// to be applied with migrate --bundle
+    Package(PackageArgs),
+
+    /// Write the lockfile with the checksums of the migrations, or check them against it with --check
+    Lock(LockArgs),
+
+    /// Export the schema history table of the database, or import an exported one into it
+    History(HistoryArgs),
+
+    /// Generate a draft migration from the difference between the live database schema and a target one
+    #[cfg(feature = "diff")]
+    Diff(DiffArgs),
 }
 
 #[derive(Args)]
@@ -28,6 +89,10 @@ pub struct MigrateArgs {
     #[clap(short)]
     pub env_var: Option<String>,
 
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
     /// Run migrations grouped in a single transaction
     #[clap(short)]
     pub grouped: bool,
@@ -40,9 +105,26 @@ pub struct MigrateArgs {
     #[clap(short)]
     pub target: Option<u32>,
 
-    /// Set migration table name
-    #[clap(long, default_value = "refinery_schema_history")]
-    pub table_name: String,
+    /// Apply at most the specified number of pending migrations from the current version, i.e. 1 to apply
+    /// them one at a time, verifying the database between the steps
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["target", "fake"])]
+    pub steps: Option<u32>,
+
+    /// Apply only the specified pending migration version, i.e. V42, the migrations before it must be applied
+    #[clap(long, value_parser = parse_version, conflicts_with_all = ["target", "steps"])]
+    pub only: Option<u32>,
+
+    /// Re-run the specified already applied migration version, updating its checksum, requires --force
+    #[clap(long, value_parser = parse_version, requires = "force", conflicts_with_all = ["target", "only", "fake", "steps"])]
+    pub rerun: Option<u32>,
+
+    /// Confirm force re-running an applied migration with --rerun
+    #[clap(long)]
+    pub force: bool,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
 
     /// Should abort if divergent migrations are found
     #[clap(short)]
@@ -51,4 +133,424 @@ pub struct MigrateArgs {
     /// Should abort if missing migrations are found
     #[clap(short)]
     pub missing: bool,
+
+    /// Create the database if it doesn't exist before migrating it
+    #[clap(long)]
+    pub create_db: bool,
+
+    /// Also apply the seed migrations, S{version}__{name}, i.e. test or staging data
+    #[clap(long)]
+    pub seeds: bool,
+
+    /// Apply the migrations of a bundle built with `refinery package` instead of the migrations directory,
+    /// verifying them against its manifest
+    #[clap(long, conflicts_with = "path")]
+    pub bundle: Option<PathBuf>,
+
+    /// Apply the migrations of the Rust crate at the given path, Rust migrations included, running its `migrations`
+    /// binary, or the one set as `package.metadata.refinery.bin` on its manifest, which exports them with
+    /// `refinery::archive::export_bundle`
+    #[clap(long = "crate", conflicts_with_all = ["path", "bundle"])]
+    pub crate_path: Option<PathBuf>,
+
+    /// Write the schema of the database after migrating it to the given file, as canonical sql or as json if its
+    /// extension is json, so it can be tracked to detect drift
+    #[clap(long, conflicts_with_all = ["only", "rerun", "dsn_file"])]
+    pub snapshot: Option<PathBuf>,
+
+    /// Apply the migrations to each database url of the given file instead of the database of the config file,
+    /// i.e. the shards of a database, one url per line, blank lines and lines starting with `#` are skipped
+    #[clap(long, conflicts_with_all = ["env_var", "env", "only", "rerun"])]
+    pub dsn_file: Option<PathBuf>,
+
+    /// How many databases of --dsn-file are migrated at once
+    #[clap(long, default_value_t = 1, requires = "dsn_file")]
+    pub concurrency: usize,
+
+    /// Keep migrating the remaining databases of --dsn-file after one of them fails, by default they are left
+    /// unmigrated
+    #[clap(long, requires = "dsn_file")]
+    pub continue_on_failure: bool,
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("rollback_target").required(true).args(["target", "steps"])))]
+pub struct RollbackArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Roll back the migrations in a single transaction
+    #[clap(short)]
+    pub grouped: bool,
+
+    /// Do not actually roll back migrations, just delete them from refinery's schema migration table
+    #[clap(short)]
+    pub fake: bool,
+
+    /// Roll back the migrations applied after the specified target version
+    #[clap(short, value_parser = parse_version)]
+    pub target: Option<u32>,
+
+    /// Roll back the specified number of the last applied migrations
+    #[clap(long, conflicts_with = "fake")]
+    pub steps: Option<u32>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+// parses a migration version with an optional prefix, i.e. `V42` or `42`
+fn parse_version(version: &str) -> Result<u32, String> {
+    version
+        .strip_prefix(['V', 'U'])
+        .unwrap_or(version)
+        .parse()
+        .map_err(|_| format!("invalid migration version {}", version))
+}
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Migration name
+    pub name: String,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Generate an unversioned migration
+    #[clap(short)]
+    pub unversioned: bool,
+
+    /// Generate a Rust migration instead of a SQL one
+    #[clap(short)]
+    pub rust: bool,
+
+    /// Template of the migration, plain, up-down, rust or the path of a template file where {{version}}, {{name}}
+    /// and {{date}} are replaced, plain by default
+    #[clap(long, conflicts_with = "rust")]
+    pub template: Option<MigrationTemplate>,
+
+    /// Naming scheme of the migration, versioned or timestamp, by default the one of the migrations on the directory
+    #[clap(long, conflicts_with = "unversioned")]
+    pub naming: Option<MigrationNaming>,
+}
+
+#[derive(Args)]
+pub struct LintArgs {
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Dialect the migrations are checked for, migration variants are always checked for their own
+    #[clap(long, value_parser = parse_dialect)]
+    pub dialect: Option<Dialect>,
+
+    /// Statement the migrations must not contain, can be repeated, DROP DATABASE by default
+    #[clap(long)]
+    pub forbid: Vec<String>,
+
+    /// Report gaps between migration versions as warnings instead of errors
+    #[clap(long)]
+    pub allow_gaps: bool,
+
+    /// Fail on warnings too
+    #[clap(long)]
+    pub deny_warnings: bool,
+
+    /// Only lint the migrations added or modified relative to the given git ref, i.e. origin/main,
+    /// also failing if migrations of the ref were modified or new ones don't have a greater version
+    #[clap(long)]
+    pub since: Option<String>,
+}
+
+fn parse_dialect(dialect: &str) -> Result<Dialect, String> {
+    match dialect {
+        "postgres" => Ok(Dialect::Postgres),
+        "mysql" => Ok(Dialect::Mysql),
+        "sqlite" => Ok(Dialect::Sqlite),
+        "mssql" => Ok(Dialect::Mssql),
+        "cockroach" => Ok(Dialect::Cockroach),
+        "clickhouse" => Ok(Dialect::ClickHouse),
+        "duckdb" => Ok(Dialect::DuckDb),
+        "oracle" => Ok(Dialect::Oracle),
+        _ => Err(format!(
+            "invalid dialect {}, it must be postgres, mysql, sqlite, mssql, cockroach, clickhouse, duckdb or oracle",
+            dialect
+        )),
+    }
+}
+
+#[derive(Args)]
+pub struct RolloutArgs {
+    /// Config file location of the canary database
+    #[clap(long)]
+    pub canary: PathBuf,
+
+    /// Config file locations of the target databases, migrated in order after the canary one
+    #[clap(required = true)]
+    pub targets: Vec<PathBuf>,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Verification check asserted on the canary database after migrating it, can be repeated,
+    /// a SELECT returning a single value that must be true, i.e. not false, 0 or NULL
+    #[clap(long)]
+    pub check: Vec<String>,
+
+    /// Run migrations grouped in a single transaction
+    #[clap(short)]
+    pub grouped: bool,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Config file location of the database, to only analyze the migrations pending on it for its dialect
+    #[clap(short)]
+    pub config: Option<PathBuf>,
+
+    /// Load the database from the given environment variable, to only analyze the migrations pending on it
+    #[clap(short, conflicts_with = "config")]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, requires = "config")]
+    pub env: Option<String>,
+
+    /// Dialect the migrations are analyzed for when there's no database, migration variants are always analyzed
+    /// for their own
+    #[clap(long, value_parser = parse_dialect, conflicts_with_all = ["config", "env_var"])]
+    pub dialect: Option<Dialect>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    #[clap(subcommand)]
+    pub command: HistoryCommand,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// Print the rows of the schema history table, with --format json to be imported with history import
+    Export(HistoryExportArgs),
+
+    /// Import the schema history rows exported to a file with history export --format json, skipping the ones
+    /// already applied, i.e. on a database restored from a backup or on a schema history table with another name
+    Import(HistoryImportArgs),
+}
+
+#[derive(Args)]
+pub struct HistoryExportArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct HistoryImportArgs {
+    /// Config file location
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Load database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Set migration table name, overriding `table_name` on the config file [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+
+    /// The file with the exported schema history rows
+    pub file: PathBuf,
+}
+
+#[derive(Args)]
+pub struct LockArgs {
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Lockfile location
+    #[clap(short, default_value = "./refinery.lock")]
+    pub lockfile: PathBuf,
+
+    /// Fail if any locked migration was edited, renamed or removed, instead of writing the lockfile
+    #[clap(long)]
+    pub check: bool,
+}
+
+#[derive(Args)]
+pub struct PackageArgs {
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Bundle location, a gzipped tarball
+    #[clap(short, default_value = "./migrations.tar.gz")]
+    pub output: PathBuf,
+
+    /// Lockfile location, the migrations must match it if given
+    #[clap(short)]
+    pub lockfile: Option<PathBuf>,
+
+    /// Dialect the migrations are checked for, migration variants are always checked for their own
+    #[clap(long, value_parser = parse_dialect)]
+    pub dialect: Option<Dialect>,
+
+    /// Report gaps between migration versions as warnings instead of errors
+    #[clap(long)]
+    pub allow_gaps: bool,
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("diff_target").required(true).args(["target_config", "schema_file"])))]
+pub struct DiffArgs {
+    /// Config file location of the live database
+    #[clap(short, default_value = "./refinery.toml")]
+    pub config: PathBuf,
+
+    /// Load the live database from the given environment variable
+    #[clap(short)]
+    pub env_var: Option<String>,
+
+    /// Environment of the config file, its [environments.<ENV>] section overrides the [main] one
+    #[clap(long, conflicts_with = "env_var")]
+    pub env: Option<String>,
+
+    /// Migrations directory path
+    #[clap(short, default_value = "./migrations")]
+    pub path: PathBuf,
+
+    /// Config file location of the target database
+    #[clap(long)]
+    pub target_config: Option<PathBuf>,
+
+    /// Declarative schema file with the target schema
+    #[clap(long)]
+    pub schema_file: Option<PathBuf>,
+
+    /// Postgres schema to compare
+    #[clap(long, default_value = "public")]
+    pub schema: String,
+
+    /// Migration table name, excluded from the comparison, overriding `table_name` on the config file
+    /// [default: refinery_schema_history]
+    #[clap(long)]
+    pub table_name: Option<String>,
+
+    /// Print the migration sql instead of creating the migration file
+    #[clap(long)]
+    pub dry_run: bool,
 }
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use anyhow::Context;
+use refinery_core::diff::{connect, Diff, DiffTarget};
+
+use crate::cli::DiffArgs;
+use crate::migrate::{config, migration_table_name};
+
+pub fn handle_diff_command(args: DiffArgs) -> anyhow::Result<()> {
+    let live_config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let diff = Diff::new()
+        .set_schema(&args.schema)
+        .set_migration_table_name(&migration_table_name(
+            args.table_name.as_deref(),
+            &live_config,
+        ));
+    let mut live = connect(&live_config).context("could not connect to the live database")?;
+
+    let schema_sql;
+    let mut target_client;
+    let target = match (&args.target_config, &args.schema_file) {
+        (Some(target_config), _) => {
+            let target_config = config(target_config, None, None)?;
+            target_client =
+                connect(&target_config).context("could not connect to the target database")?;
+            DiffTarget::Database(&mut target_client)
+        }
+        (None, Some(schema_file)) => {
+            schema_sql = std::fs::read_to_string(schema_file)
+                .with_context(|| format!("could not read schema file {}", schema_file.display()))?;
+            DiffTarget::SchemaSql(&schema_sql)
+        }
+        (None, None) => unreachable!("clap requires a diff target"),
+    };
+
+    generate_diff(&diff, &mut live, target, &args.path, args.dry_run)
+}
+
+fn generate_diff(
+    diff: &Diff,
+    live: &mut refinery_core::postgres::Client,
+    target: DiffTarget,
+    path: &Path,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if dry_run {
+        match diff.sql(live, target).context("could not diff schemas")? {
+            Some(sql) => print!("{}", sql),
+            None => println!("schemas don't differ"),
+        }
+    } else {
+        match diff
+            .generate(path, live, target)
+            .context("could not generate migration")?
+        {
+            Some(path) => println!("created migration file: {}", path.display()),
+            None => println!("schemas don't differ, no migration created"),
+        }
+    }
+
+    Ok(())
+}
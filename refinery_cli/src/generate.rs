@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use anyhow::Context;
+use refinery_core::scaffold::{new_migration_from_template, MigrationTemplate};
+use refinery_core::{MigrationNaming, Type};
+
+use crate::cli::GenerateArgs;
+
+pub fn handle_generate_command(args: GenerateArgs) -> anyhow::Result<()> {
+    let template = match args.template {
+        Some(template) => template,
+        None if args.rust => MigrationTemplate::Rust,
+        None => MigrationTemplate::Plain,
+    };
+    generate_migration(
+        &args.path,
+        &args.name,
+        args.unversioned,
+        &template,
+        args.naming,
+    )
+}
+
+fn generate_migration(
+    path: &Path,
+    name: &str,
+    unversioned: bool,
+    template: &MigrationTemplate,
+    naming: Option<MigrationNaming>,
+) -> anyhow::Result<()> {
+    let kind = if unversioned {
+        Type::Unversioned
+    } else {
+        Type::Versioned
+    };
+
+    let paths = new_migration_from_template(path, name, kind, template, naming)
+        .with_context(|| format!("could not generate migration {}", name))?;
+    for path in paths {
+        println!("created migration file: {}", path.display());
+    }
+
+    Ok(())
+}
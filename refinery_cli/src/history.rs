@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use anyhow::Context;
+use refinery_core::config::{Config, ConfigDbType};
+use refinery_core::{AppliedMigrationRecord, Runner};
+use time::format_description::well_known::Rfc3339;
+
+use crate::cli::{Format, HistoryArgs, HistoryCommand, HistoryExportArgs, HistoryImportArgs};
+use crate::migrate::{config, migration_table_name};
+
+pub fn handle_history_command(args: HistoryArgs, format: Format) -> anyhow::Result<()> {
+    match args.command {
+        HistoryCommand::Export(args) => {
+            let records = export(&args)?;
+            match format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&records)?),
+                Format::Text => {
+                    for record in records {
+                        println!(
+                            "{} {} applied on {} checksum {}",
+                            record.version,
+                            record.name,
+                            record.applied_on.format(&Rfc3339)?,
+                            record.checksum
+                        );
+                    }
+                }
+            }
+        }
+        HistoryCommand::Import(args) => {
+            let records = read_records(&args.file)?;
+            let imported = import(&args, &records)?;
+            println!(
+                "imported {} of {} schema history records",
+                imported,
+                records.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn read_records(file: &Path) -> anyhow::Result<Vec<AppliedMigrationRecord>> {
+    let records = std::fs::read_to_string(file)
+        .with_context(|| format!("could not read schema history file {}", file.display()))?;
+    serde_json::from_str(&records)
+        .with_context(|| format!("could not parse schema history file {}", file.display()))
+}
+
+// a Runner without migrations, for the schema history table of the config
+fn runner(config: &Config, table_name: Option<&str>) -> anyhow::Result<Runner> {
+    let mut runner = Runner::new(&[]);
+    if let Some(schema) = config.table_schema() {
+        runner = runner.set_migration_table_schema(schema);
+    }
+    runner.set_migration_table_name(migration_table_name(table_name, config))?;
+    Ok(runner)
+}
+
+fn export(args: &HistoryExportArgs) -> anyhow::Result<Vec<AppliedMigrationRecord>> {
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let runner = runner(&config, args.table_name.as_deref())?;
+    let records = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to query the schema history
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(runner.export_history_async(&mut config))?
+                } else {
+                    panic!("tried to export the schema history from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    runner.export_history(&mut config)?
+                } else {
+                    panic!("tried to export the schema history from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok(records)
+}
+
+fn import(args: &HistoryImportArgs, records: &[AppliedMigrationRecord]) -> anyhow::Result<usize> {
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let runner = runner(&config, args.table_name.as_deref())?;
+    let imported = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to import the schema history
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(runner.import_history_async(&mut config, records))?
+                } else {
+                    panic!("tried to import the schema history from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    runner.import_history(&mut config, records)?
+                } else {
+                    panic!("tried to import the schema history from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok(imported)
+}
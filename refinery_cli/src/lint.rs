@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use refinery_core::lint::{Linter, MigrationChange, Severity};
+use refinery_core::{parse_migration_name, Type};
+
+use crate::cli::LintArgs;
+
+pub fn handle_lint_command(args: LintArgs) -> anyhow::Result<()> {
+    let mut linter = Linter::new().set_contiguous_versions(!args.allow_gaps);
+    if !args.forbid.is_empty() {
+        linter = linter.set_forbidden_statements(&args.forbid);
+    }
+    if let Some(dialect) = args.dialect {
+        linter = linter.set_dialect(dialect);
+    }
+
+    let issues = match &args.since {
+        Some(base) => {
+            let (changes, base_version) = git_changes(&args.path, base)?;
+            linter.lint_changes(&args.path, &changes, base_version)
+        }
+        None => linter.lint(&args.path),
+    }
+    .with_context(|| format!("could not lint migrations on {}", args.path.display()))?;
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    let failing = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error || args.deny_warnings)
+        .count();
+    if failing > 0 {
+        bail!("found {} issues on the migrations", failing);
+    }
+    println!("migrations are valid");
+    Ok(())
+}
+
+fn git(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("could not run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("git output is not valid UTF-8")
+}
+
+// the migration files on `path` changed since the merge base of `base` and the working tree, including untracked ones,
+// and the highest version of the versioned migrations on the merge base
+fn git_changes(path: &Path, base: &str) -> anyhow::Result<(Vec<MigrationChange>, Option<i32>)> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+    let merge_base = git(&["merge-base", base, "HEAD"])?;
+    let merge_base = merge_base.trim();
+    let path = path
+        .to_str()
+        .context("migrations path is not valid UTF-8")?;
+
+    let mut changes = Vec::new();
+    let diff = git(&[
+        "diff",
+        "--name-status",
+        "--no-renames",
+        merge_base,
+        "--",
+        path,
+    ])?;
+    for line in diff.lines() {
+        let Some((status, file)) = line.split_once('\t') else {
+            continue;
+        };
+        let file = root.join(file);
+        changes.push(match status {
+            "A" => MigrationChange::Added(file),
+            "D" => MigrationChange::Removed(file),
+            _ => MigrationChange::Modified(file),
+        });
+    }
+    let untracked = git(&[
+        "ls-files",
+        "--others",
+        "--exclude-standard",
+        "--full-name",
+        "--",
+        path,
+    ])?;
+    changes.extend(
+        untracked
+            .lines()
+            .map(|file| MigrationChange::Added(root.join(file))),
+    );
+
+    let base_files = git(&["ls-tree", "-r", "--name-only", merge_base, "--", path])?;
+    let base_version = base_files
+        .lines()
+        .filter_map(|file| Path::new(file).file_stem()?.to_str())
+        .filter_map(|stem| match parse_migration_name(stem) {
+            Ok((Type::Versioned, version, _)) => Some(version),
+            _ => None,
+        })
+        .max();
+
+    Ok((changes, base_version))
+}
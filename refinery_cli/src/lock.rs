@@ -0,0 +1,27 @@
+use anyhow::{bail, Context};
+use refinery_core::lockfile::{check_lockfile, write_lockfile};
+
+use crate::cli::LockArgs;
+
+pub fn handle_lock_command(args: LockArgs) -> anyhow::Result<()> {
+    if !args.check {
+        write_lockfile(&args.path, &args.lockfile).context("could not write the lockfile")?;
+        println!("wrote lockfile: {}", args.lockfile.display());
+        return Ok(());
+    }
+
+    let mismatches =
+        check_lockfile(&args.path, &args.lockfile).context("could not check the lockfile")?;
+    if mismatches.is_empty() {
+        println!("migrations match the lockfile");
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    bail!(
+        "{} locked migrations don't match the lockfile {}",
+        mismatches.len(),
+        args.lockfile.display()
+    )
+}
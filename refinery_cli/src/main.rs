@@ -1,8 +1,22 @@
 //! Main entry point for the refinery cli tool
 
+mod analyze;
 mod cli;
+#[cfg(feature = "diff")]
+mod diff;
+mod generate;
+mod history;
+mod lint;
+mod lock;
 mod migrate;
+mod output;
+mod package;
+mod rollback;
+mod rollout;
 mod setup;
+mod status;
+mod validate;
+mod verify;
 
 use anyhow::Error;
 use clap::Parser;
@@ -10,23 +24,41 @@ use env_logger::{Builder, Target};
 use log::LevelFilter;
 use std::io::Write;
 
-use cli::Cli;
+use cli::{Cli, Command, Format};
 
 fn main() -> Result<(), Error> {
     human_panic::setup_panic!();
 
+    let cli = Cli::parse();
+
+    // the log lines go to stderr when stdout has the json report
+    let target = match cli.format {
+        Format::Text => Target::Stdout,
+        Format::Json => Target::Stderr,
+    };
     let mut builder = Builder::new();
     builder
         .format(|buf, record| writeln!(buf, "{}", record.args()))
         .filter(Some("refinery_core::traits"), LevelFilter::Info)
-        .target(Target::Stdout)
+        .target(target)
         .init();
 
-    let cli = Cli::parse();
-
-    match cli {
-        Cli::Setup => setup::handle_setup()?,
-        Cli::Migrate(args) => migrate::handle_migration_command(args)?,
+    match cli.command {
+        Command::Setup => setup::handle_setup()?,
+        Command::Migrate(args) => migrate::handle_migration_command(args, cli.format)?,
+        Command::Status(args) => status::handle_status_command(args, cli.format)?,
+        Command::Validate(args) => validate::handle_validate_command(args, cli.format)?,
+        Command::Verify(args) => verify::handle_verify_command(args, cli.format)?,
+        Command::Rollback(args) => rollback::handle_rollback_command(args, cli.format)?,
+        Command::Generate(args) => generate::handle_generate_command(args)?,
+        Command::Lint(args) => lint::handle_lint_command(args)?,
+        Command::Analyze(args) => analyze::handle_analyze_command(args)?,
+        Command::Rollout(args) => rollout::handle_rollout_command(args)?,
+        Command::Package(args) => package::handle_package_command(args)?,
+        Command::Lock(args) => lock::handle_lock_command(args)?,
+        Command::History(args) => history::handle_history_command(args, cli.format)?,
+        #[cfg(feature = "diff")]
+        Command::Diff(args) => diff::handle_diff_command(args)?,
     }
 
     Ok(())
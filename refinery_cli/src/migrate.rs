@@ -1,70 +1,105 @@
-use std::path::Path;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::str::FromStr;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use refinery_core::{
+    archive::{BUNDLE_DIALECT_VAR, BUNDLE_PATH_VAR},
     config::{Config, ConfigDbType},
-    find_migration_files, Migration, MigrationType, Runner, Target,
+    error::Kind,
+    find_migration_files, migration_file_name, CancelHandle, Dialect, Error, Migration,
+    MigrationType, Report, Runner, ShardFailurePolicy, ShardRunner, Target,
 };
+use serde::Deserialize;
 
-use crate::cli::MigrateArgs;
+use crate::cli::{Format, MigrateArgs};
+use crate::output::{print_report, report_error, Durations, State};
 
-pub fn handle_migration_command(args: MigrateArgs) -> anyhow::Result<()> {
-    run_migrations(
+const DEFAULT_TABLE_NAME: &str = "refinery_schema_history";
+
+// the binary of a crate run by `migrate --crate` unless its manifest sets `package.metadata.refinery.bin`
+const DEFAULT_MIGRATIONS_BIN: &str = "migrations";
+
+// exit code of a run interrupted with Ctrl-C, 128 + SIGINT as shells report it
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+pub fn handle_migration_command(args: MigrateArgs, format: Format) -> anyhow::Result<()> {
+    if let Some(dsn_file) = &args.dsn_file {
+        if format == Format::Json {
+            bail!("migrating the databases of --dsn-file is only reported as text");
+        }
+        return migrate_shards(&args, dsn_file);
+    }
+    let durations = Durations::default();
+    let result = run_migrations(
+        (format == Format::Json).then_some(&durations),
         &args.config,
         args.grouped,
         args.divergent,
         args.missing,
         args.fake,
         args.target,
+        args.steps,
+        args.only,
+        args.rerun,
         args.env_var.as_deref(),
+        args.env.as_deref(),
         &args.path,
-        &args.table_name,
-    )?;
-    Ok(())
+        args.table_name.as_deref(),
+        args.create_db,
+        args.bundle.as_deref(),
+        args.crate_path.as_deref(),
+        args.seeds,
+        args.snapshot.as_deref(),
+    );
+    let result = report_error(format, "migrate", result)?;
+    let result = print_report(format, "migrate", State::Applied, result, &durations);
+    exit_if_interrupted(result, format)
 }
 
 #[allow(clippy::too_many_arguments)]
 fn run_migrations(
+    durations: Option<&Durations>,
     config_location: &Path,
     grouped: bool,
     divergent: bool,
     missing: bool,
     fake: bool,
     target: Option<u32>,
+    steps: Option<u32>,
+    only: Option<u32>,
+    rerun: Option<u32>,
     env_var_opt: Option<&str>,
+    env: Option<&str>,
     path: &Path,
-    table_name: &str,
-) -> anyhow::Result<()> {
-    let migration_files_path = find_migration_files(path, MigrationType::Sql)?;
-    let mut migrations = Vec::new();
-    for path in migration_files_path {
-        let sql = std::fs::read_to_string(path.as_path())
-            .with_context(|| format!("could not read migration file name {}", path.display()))?;
-
-        //safe to call unwrap as find_migration_filenames returns canonical paths
-        let filename = path
-            .file_stem()
-            .and_then(|file| file.to_os_string().into_string().ok())
-            .unwrap();
-
-        let migration = Migration::unapplied(&filename, &sql)
-            .with_context(|| format!("could not read migration file name {}", path.display()))?;
-        migrations.push(migration);
+    table_name: Option<&str>,
+    create_db: bool,
+    bundle: Option<&Path>,
+    crate_path: Option<&Path>,
+    seeds: bool,
+    snapshot: Option<&Path>,
+) -> anyhow::Result<Result<Report, Error>> {
+    let mut config = config(config_location, env_var_opt, env)?;
+    let migrations = match (bundle, crate_path) {
+        (Some(bundle), _) => load_bundle(bundle)?,
+        (None, Some(crate_path)) => load_crate(crate_path, config.db_type().into())?,
+        (None, None) => load_migrations(path)?,
+    };
+    if create_db {
+        config = config.set_create_db_if_missing(true);
     }
-    let mut config = config(config_location, env_var_opt)?;
+    let table_name = migration_table_name(table_name, &config);
+    let cancel_handle = interrupt_handle()?;
 
-    let target = match (fake, target) {
-        (true, None) => Target::Fake,
-        (false, None) => Target::Latest,
-        (true, Some(version)) => Target::FakeVersion(version),
-        (false, Some(version)) => Target::Version(version),
-    };
+    let target = migration_target(fake, target, steps);
 
-    match config.db_type() {
-        ConfigDbType::Mssql => {
+    let result = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
             cfg_if::cfg_if! {
-                // tiberius is an async driver so we spawn tokio runtime and run the migrations
-                if #[cfg(feature = "mssql")] {
+                // tiberius and libsql are async drivers so we spawn tokio runtime and run the migrations
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
                     use tokio::runtime::Builder;
 
                     let runtime = Builder::new_current_thread()
@@ -73,30 +108,64 @@ fn run_migrations(
                         .context("Can't start tokio runtime")?;
 
                     runtime.block_on(async {
-                        Runner::new(&migrations)
+                        let mut runner = Runner::new(&migrations)
                             .set_grouped(grouped)
                             .set_target(target)
                             .set_abort_divergent(divergent)
                             .set_abort_missing(missing)
-                            .set_migration_table_name(table_name)
-                            .run_async(&mut config)
-                            .await
-                    })?;
+                            .set_apply_seeds(seeds)
+                            .set_cancel_handle(cancel_handle);
+                        if let Some(schema) = config.table_schema() {
+                            runner = runner.set_migration_table_schema(schema);
+                        }
+                        if let Some(durations) = durations {
+                            runner = record_durations(runner, durations);
+                        }
+                        if let Some(snapshot) = snapshot {
+                            runner = runner.set_snapshot_path(snapshot);
+                        }
+                        runner.set_migration_table_name(&table_name)?;
+                        match (only, rerun) {
+                            (Some(version), _) => runner.run_single_async(version, &mut config).await,
+                            (_, Some(version)) => runner.force_rerun_async(version, &mut config).await,
+                            (None, None) => runner.run_async(&mut config).await,
+                        }
+                    })
                 } else {
-                    panic!("tried to migrate async from config for a mssql database, but mssql feature was not enabled!");
+                    panic!("tried to migrate async from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
                 }
             }
         }
-        _db_type @ (ConfigDbType::Mysql | ConfigDbType::Postgres | ConfigDbType::Sqlite) => {
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
             cfg_if::cfg_if! {
-                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite"))] {
-                    Runner::new(&migrations)
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    let mut runner = Runner::new(&migrations)
                         .set_grouped(grouped)
                         .set_abort_divergent(divergent)
                         .set_abort_missing(missing)
+                        .set_apply_seeds(seeds)
                         .set_target(target)
-                        .set_migration_table_name(table_name)
-                        .run(&mut config)?;
+                        .set_cancel_handle(cancel_handle);
+                    if let Some(schema) = config.table_schema() {
+                        runner = runner.set_migration_table_schema(schema);
+                    }
+                    if let Some(durations) = durations {
+                        runner = record_durations(runner, durations);
+                    }
+                    if let Some(snapshot) = snapshot {
+                        runner = runner.set_snapshot_path(snapshot);
+                    }
+                    runner.set_migration_table_name(&table_name)?;
+                    match (only, rerun) {
+                        (Some(version), _) => runner.run_single(version, &mut config),
+                        (_, Some(version)) => runner.force_rerun(version, &mut config),
+                        (None, None) => runner.run(&mut config),
+                    }
                 } else {
                     panic!("tried to migrate async from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
                 }
@@ -104,12 +173,325 @@ fn run_migrations(
         }
     };
 
+    Ok(result)
+}
+
+fn migration_target(fake: bool, target: Option<u32>, steps: Option<u32>) -> Target {
+    match (fake, target, steps) {
+        (_, _, Some(steps)) => Target::StepsForward(steps),
+        (true, None, None) => Target::Fake,
+        (false, None, None) => Target::Latest,
+        (true, Some(version), None) => Target::FakeVersion(version),
+        (false, Some(version), None) => Target::Version(version),
+    }
+}
+
+// the database urls of a --dsn-file, skipping blank lines and `#` comments
+fn read_dsn_file(dsn_file: &Path) -> anyhow::Result<Vec<String>> {
+    let dsns = std::fs::read_to_string(dsn_file)
+        .with_context(|| format!("could not read dsn file {}", dsn_file.display()))?;
+    let dsns: Vec<String> = dsns
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToString::to_string)
+        .collect();
+    if dsns.is_empty() {
+        bail!("dsn file {} has no database urls", dsn_file.display());
+    }
+    Ok(dsns)
+}
+
+// the database url without its password, to be printed
+fn redact_dsn(dsn: &str) -> String {
+    let redacted = dsn.split_once("://").and_then(|(scheme, rest)| {
+        let (user_info, host) = rest.split_once('@')?;
+        let (user, _) = user_info.split_once(':')?;
+        Some(format!("{}://{}:***@{}", scheme, user, host))
+    });
+    redacted.unwrap_or_else(|| dsn.to_string())
+}
+
+fn migrate_shards(args: &MigrateArgs, dsn_file: &Path) -> anyhow::Result<()> {
+    let dsns = read_dsn_file(dsn_file)?;
+    let mut shards = dsns
+        .iter()
+        .map(|dsn| {
+            let config = Config::from_str(dsn)
+                .with_context(|| format!("could not parse database url {}", redact_dsn(dsn)))?;
+            Ok(config.set_create_db_if_missing(args.create_db))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let migrations = match (&args.bundle, &args.crate_path) {
+        (Some(bundle), _) => load_bundle(bundle)?,
+        (None, Some(crate_path)) => load_crate(crate_path, shards[0].db_type().into())?,
+        (None, None) => load_migrations(&args.path)?,
+    };
+    let mut runner = Runner::new(&migrations)
+        .set_grouped(args.grouped)
+        .set_abort_divergent(args.divergent)
+        .set_abort_missing(args.missing)
+        .set_apply_seeds(args.seeds)
+        .set_target(migration_target(args.fake, args.target, args.steps))
+        .set_cancel_handle(interrupt_handle()?);
+    runner
+        .set_migration_table_name(migration_table_name(args.table_name.as_deref(), &shards[0]))?;
+    let failure_policy = if args.continue_on_failure {
+        ShardFailurePolicy::Continue
+    } else {
+        ShardFailurePolicy::Stop
+    };
+    let shard_runner = ShardRunner::new(runner)
+        .set_concurrency(args.concurrency)
+        .set_failure_policy(failure_policy);
+
+    let is_async = |db_type| matches!(db_type, ConfigDbType::Mssql | ConfigDbType::Turso);
+    let async_shards = shards
+        .iter()
+        .filter(|shard| is_async(shard.db_type()))
+        .count();
+    let results = if async_shards == 0 {
+        cfg_if::cfg_if! {
+            if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                shard_runner.run(&mut shards)
+            } else {
+                panic!("tried to migrate shards from config, but no database feature was enabled!");
+            }
+        }
+    } else if async_shards == shards.len() {
+        cfg_if::cfg_if! {
+            // tiberius and libsql are async drivers so we spawn tokio runtime and run the migrations
+            if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                use tokio::runtime::Builder;
+
+                let runtime = Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .context("Can't start tokio runtime")?;
+                runtime.block_on(shard_runner.run_async(&mut shards))
+            } else {
+                panic!("tried to migrate shards from config for a mssql or turso database, but neither mssql nor turso feature was enabled!");
+            }
+        }
+    } else {
+        bail!("mssql and turso databases can't be migrated together with other databases");
+    };
+
+    let mut failed = 0;
+    let mut results = results.into_iter().peekable();
+    for (index, dsn) in dsns.iter().enumerate() {
+        let dsn = redact_dsn(dsn);
+        match results.next_if(|(shard, _)| *shard == index) {
+            Some((_, Ok(report))) => println!(
+                "applied {} migrations to {}",
+                report.applied_migrations().len(),
+                dsn
+            ),
+            Some((_, Err(err))) => {
+                failed += 1;
+                println!("failed to migrate {}: {}", dsn, err);
+            }
+            None => println!("skipped {}", dsn),
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} databases failed to migrate", failed, dsns.len());
+    }
     Ok(())
 }
 
-fn config(config_location: &Path, env_var_opt: Option<&str>) -> anyhow::Result<Config> {
+fn load_migrations(migrations_path: &Path) -> anyhow::Result<Vec<Migration>> {
+    let migration_files_path = find_migration_files(migrations_path, MigrationType::Sql)?;
+    let mut migrations = Vec::new();
+    for path in migration_files_path {
+        let sql = std::fs::read_to_string(path.as_path())
+            .with_context(|| format!("could not read migration file name {}", path.display()))?;
+
+        let filename = migration_file_name(migrations_path, &path);
+        let migration = Migration::unapplied_from(&filename, sql)
+            .with_context(|| format!("could not read migration file name {}", path.display()))?;
+        migrations.push(migration);
+    }
+    Ok(migrations)
+}
+
+// the migrations of a bundle written by `refinery package`, verified against its manifest
+fn load_bundle(bundle: &Path) -> anyhow::Result<Vec<Migration>> {
+    let file = File::open(bundle)
+        .with_context(|| format!("could not open bundle {}", bundle.display()))?;
+    let runner = Runner::from_archive(BufReader::new(file))
+        .with_context(|| format!("could not read bundle {}", bundle.display()))?;
+    Ok(runner.into_migrations())
+}
+
+#[derive(Deserialize)]
+struct CargoMetadata {
+    packages: Vec<CargoPackage>,
+}
+
+#[derive(Deserialize)]
+struct CargoPackage {
+    name: String,
+    manifest_path: PathBuf,
+    targets: Vec<CargoTarget>,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct CargoTarget {
+    name: String,
+    kind: Vec<String>,
+}
+
+impl CargoPackage {
+    // the binary exporting the migrations, `package.metadata.refinery.bin` or the conventional `migrations` one
+    fn migrations_bin(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.pointer("/refinery/bin"))
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or(DEFAULT_MIGRATIONS_BIN)
+    }
+}
+
+// the migrations of a Rust crate, bundled by its migrations binary with `export_bundle` for the given dialect,
+// so the sql of its Rust migrations is generated for the database they are applied on
+fn load_crate(crate_path: &Path, dialect: Dialect) -> anyhow::Result<Vec<Migration>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let manifest_path = crate_path.join("Cargo.toml");
+    let manifest_path = manifest_path
+        .canonicalize()
+        .with_context(|| format!("could not find crate manifest {}", manifest_path.display()))?;
+
+    let output = process::Command::new(&cargo)
+        .args([
+            "metadata",
+            "--no-deps",
+            "--format-version",
+            "1",
+            "--manifest-path",
+        ])
+        .arg(&manifest_path)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("could not run cargo metadata")?;
+    if !output.status.success() {
+        bail!("cargo metadata failed for crate {}", crate_path.display());
+    }
+    let metadata: CargoMetadata =
+        serde_json::from_slice(&output.stdout).context("could not parse cargo metadata")?;
+    let package = metadata
+        .packages
+        .iter()
+        .find(|package| package.manifest_path == manifest_path)
+        .with_context(|| format!("could not find the package of {}", manifest_path.display()))?;
+    let bin = package.migrations_bin();
+    if !package
+        .targets
+        .iter()
+        .any(|target| target.name == bin && target.kind.iter().any(|kind| kind == "bin"))
+    {
+        bail!(
+            "crate {} has no {} binary exporting its migrations, add one or set package.metadata.refinery.bin",
+            package.name,
+            bin
+        );
+    }
+
+    // created with an unpredictable name, and removed once dropped
+    let bundle = tempfile::Builder::new()
+        .prefix("refinery-")
+        .suffix(".tar.gz")
+        .tempfile()
+        .context("could not create the migrations bundle file")?;
+    // the output of the binary goes to stderr, so it doesn't mix with the json report
+    let status = process::Command::new(&cargo)
+        .args(["run", "--quiet", "--manifest-path"])
+        .arg(&manifest_path)
+        .args(["--package", &package.name, "--bin", bin])
+        .env(BUNDLE_PATH_VAR, bundle.path())
+        .env(BUNDLE_DIALECT_VAR, dialect.to_string())
+        .stdout(std::io::stderr())
+        .status()
+        .with_context(|| format!("could not run the {} binary of crate {}", bin, package.name))?;
+    if !status.success() {
+        bail!("the {} binary of crate {} failed", bin, package.name);
+    }
+    load_bundle(bundle.path())
+}
+
+// records the duration of each applied migration for the json report
+fn record_durations(runner: Runner, durations: &Durations) -> Runner {
+    let durations = durations.clone();
+    runner.set_after_migration(move |migration, duration| {
+        durations
+            .lock()
+            .unwrap()
+            .insert(migration.version(), duration);
+    })
+}
+
+// the first Ctrl-C cancels the run once the migration being applied is finished and recorded,
+// the second one exits right away, leaving the database to roll back the migration being applied
+fn interrupt_handle() -> anyhow::Result<CancelHandle> {
+    let cancel_handle = CancelHandle::new();
+    let handle = cancel_handle.clone();
+    ctrlc::set_handler(move || {
+        if handle.is_cancelled() {
+            eprintln!("interrupted again, aborting the migration being applied");
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!("interrupted, stopping after the migration being applied, press Ctrl-C again to abort it");
+        handle.cancel();
+    })
+    .context("could not set the Ctrl-C handler")?;
+    Ok(cancel_handle)
+}
+
+// print the migrations applied before the run was interrupted and exit with a distinct code, or the sql of the
+// statement that failed the run, the json report already has them
+fn exit_if_interrupted(result: Result<Report, Error>, format: Format) -> anyhow::Result<()> {
+    match result {
+        Err(err) if matches!(err.kind(), Kind::Cancelled) => {
+            if format == Format::Text {
+                let applied = err.report().map(Report::applied_migrations);
+                let applied = applied.map(Vec::as_slice).unwrap_or_default();
+                println!("interrupted after applying {} migrations", applied.len());
+                for migration in applied {
+                    println!("applied migration: {}", migration);
+                }
+            }
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        Err(err) => {
+            if let (Format::Text, Some(statement)) = (format, err.statement()) {
+                println!("failed statement:\n{}", statement.sql());
+            }
+            Err(err.into())
+        }
+        Ok(_) => Ok(()),
+    }
+}
+
+// the migration table name set with --table-name, or else on the config file
+pub(crate) fn migration_table_name(table_name: Option<&str>, config: &Config) -> String {
+    table_name
+        .or(config.table_name())
+        .unwrap_or(DEFAULT_TABLE_NAME)
+        .to_string()
+}
+
+pub(crate) fn config(
+    config_location: &Path,
+    env_var_opt: Option<&str>,
+    env: Option<&str>,
+) -> anyhow::Result<Config> {
     if let Some(env_var) = env_var_opt {
         Config::from_env_var(env_var).context("could not environment variable")
+    } else if let Some(env) = env {
+        Config::from_file_with_env(config_location, env).context("could not parse the config file")
     } else {
         Config::from_file_location(config_location).context("could not parse the config file")
     }
@@ -0,0 +1,175 @@
+//! Machine-readable reports of the commands, printed with `--format json`
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use refinery_core::{ChecksumMismatch, Error, FailedStatement, Migration, Report};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+
+use crate::cli::Format;
+
+// the durations of the migrations applied by a run, by version
+pub(crate) type Durations = Arc<Mutex<HashMap<u32, Duration>>>;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum State {
+    Applied,
+    Pending,
+    Missing,
+    RolledBack,
+    Divergent,
+    OutOfOrder,
+    Repeated,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MigrationReport {
+    version: u32,
+    name: String,
+    state: State,
+    // a string, as u64 checksums don't fit on the numbers of most json parsers
+    checksum: String,
+    applied_on: Option<String>,
+    duration_ms: Option<u128>,
+    // the checksum of the migration on the migrations dir, when it differs from the applied one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_checksum: Option<String>,
+}
+
+impl MigrationReport {
+    pub(crate) fn new(migration: &Migration, state: State) -> MigrationReport {
+        MigrationReport {
+            version: migration.version(),
+            name: migration.name().to_string(),
+            state,
+            checksum: migration.checksum().to_string(),
+            applied_on: migration
+                .applied_on()
+                .and_then(|applied_on| applied_on.format(&Rfc3339).ok()),
+            duration_ms: None,
+            local_checksum: None,
+        }
+    }
+
+    // the applied migration of a mismatch with both checksums as they're recorded, with their algorithm
+    pub(crate) fn mismatch(mismatch: &ChecksumMismatch) -> MigrationReport {
+        MigrationReport {
+            checksum: mismatch.applied_checksum(),
+            local_checksum: Some(mismatch.checksum()),
+            ..MigrationReport::new(mismatch.applied(), State::Divergent)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CommandReport<'a> {
+    command: &'a str,
+    success: bool,
+    migrations: Vec<MigrationReport>,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statement: Option<StatementReport<'a>>,
+}
+
+// the statement of the migration that failed to apply
+#[derive(Serialize)]
+struct StatementReport<'a> {
+    migration: &'a str,
+    index: usize,
+    line: usize,
+    sql: &'a str,
+}
+
+impl<'a> StatementReport<'a> {
+    fn new(statement: &'a FailedStatement) -> StatementReport<'a> {
+        StatementReport {
+            migration: statement.migration(),
+            index: statement.index(),
+            line: statement.line(),
+            sql: statement.sql(),
+        }
+    }
+}
+
+pub(crate) fn print_json(command: &str, migrations: Vec<MigrationReport>, error: Option<String>) {
+    print_command_report(command, migrations, error, None);
+}
+
+fn print_command_report(
+    command: &str,
+    migrations: Vec<MigrationReport>,
+    error: Option<String>,
+    statement: Option<&FailedStatement>,
+) {
+    let report = CommandReport {
+        command,
+        success: error.is_none(),
+        migrations,
+        error,
+        statement: statement.map(StatementReport::new),
+    };
+    println!(
+        "{}",
+        serde_json::to_string(&report).expect("report must be serializable")
+    );
+}
+
+// prints the errors that happen before a command gets to run, i.e. parsing the config file, as json
+// and returns the result of the run otherwise
+pub(crate) fn report_error<T>(
+    format: Format,
+    command: &str,
+    result: anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    if let (Format::Json, Err(err)) = (format, &result) {
+        print_json(command, Vec::new(), Some(format!("{:#}", err)));
+    }
+    result
+}
+
+// prints the migrations of the report of a migrate or rollback run as json, also the ones applied before
+// it failed, and returns the result unchanged
+pub(crate) fn print_report(
+    format: Format,
+    command: &str,
+    state: State,
+    result: Result<Report, Error>,
+    durations: &Durations,
+) -> Result<Report, Error> {
+    if format == Format::Json {
+        let report = match &result {
+            Ok(report) => Some(report),
+            Err(err) => err.report(),
+        };
+        let durations = durations.lock().unwrap();
+        // a migrate run to a target version behind the database rolls back the migrations after it first
+        let rolled_back = report
+            .map(Report::rolled_back_migrations)
+            .into_iter()
+            .flatten()
+            .map(|migration| MigrationReport::new(migration, State::RolledBack));
+        let migrations = rolled_back
+            .chain(
+                report
+                    .map(Report::applied_migrations)
+                    .into_iter()
+                    .flatten()
+                    .map(|migration| MigrationReport {
+                        duration_ms: durations.get(&migration.version()).map(Duration::as_millis),
+                        ..MigrationReport::new(migration, state)
+                    }),
+            )
+            .collect();
+        let err = result.as_ref().err();
+        print_command_report(
+            command,
+            migrations,
+            err.map(ToString::to_string),
+            err.and_then(Error::statement),
+        );
+    }
+    result
+}
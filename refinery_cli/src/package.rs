@@ -0,0 +1,60 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use anyhow::{bail, Context};
+use refinery_core::archive::write_bundle;
+use refinery_core::lint::{Linter, Severity};
+use refinery_core::lockfile::check_lockfile;
+
+use crate::cli::PackageArgs;
+
+pub fn handle_package_command(args: PackageArgs) -> anyhow::Result<()> {
+    let mut linter = Linter::new().set_contiguous_versions(!args.allow_gaps);
+    if let Some(dialect) = args.dialect {
+        linter = linter.set_dialect(dialect);
+    }
+    let issues = linter
+        .lint(&args.path)
+        .with_context(|| format!("could not lint migrations on {}", args.path.display()))?;
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let failing = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    if failing > 0 {
+        bail!(
+            "found {} issues on the migrations, not bundling them",
+            failing
+        );
+    }
+
+    if let Some(lockfile) = &args.lockfile {
+        let mismatches =
+            check_lockfile(&args.path, lockfile).context("could not check the lockfile")?;
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
+        }
+        if !mismatches.is_empty() {
+            bail!(
+                "{} locked migrations don't match the lockfile {}, not bundling them",
+                mismatches.len(),
+                lockfile.display()
+            );
+        }
+    }
+
+    let file = File::create(&args.output)
+        .with_context(|| format!("could not create bundle {}", args.output.display()))?;
+    let mut writer = BufWriter::new(file);
+    let digest = write_bundle(&args.path, &mut writer)
+        .with_context(|| format!("could not write bundle {}", args.output.display()))?;
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())
+        .with_context(|| format!("could not write bundle {}", args.output.display()))?;
+    println!("wrote bundle: {}", args.output.display());
+    println!("sha256: {}", digest);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::Context;
+use refinery_core::{config::ConfigDbType, load_sql_migrations, Error, Report, Runner, Target};
+
+use crate::cli::{Format, RollbackArgs};
+use crate::migrate::{config, migration_table_name};
+use crate::output::{print_report, report_error, Durations, State};
+
+pub fn handle_rollback_command(args: RollbackArgs, format: Format) -> anyhow::Result<()> {
+    let result = report_error(format, "rollback", rollback(&args))?;
+    print_report(
+        format,
+        "rollback",
+        State::RolledBack,
+        result,
+        &Durations::default(),
+    )?;
+    Ok(())
+}
+
+fn rollback(args: &RollbackArgs) -> anyhow::Result<Result<Report, Error>> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let target = match (args.target, args.steps, args.fake) {
+        (Some(version), _, true) => Target::FakeVersion(version),
+        (Some(version), _, false) => Target::Version(version),
+        (None, Some(steps), _) => Target::StepsForward(steps),
+        (None, None, _) => unreachable!("clap requires either a target or steps"),
+    };
+    let mut runner = Runner::new(&migrations).set_grouped(args.grouped);
+    if let Some(schema) = config.table_schema() {
+        runner = runner.set_migration_table_schema(schema);
+    }
+    runner.set_migration_table_name(migration_table_name(args.table_name.as_deref(), &config))?;
+
+    let result = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime and roll back the migrations
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(runner.rollback_async(&mut config, target))
+                } else {
+                    panic!("tried to roll back migrations from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    runner.rollback(&mut config, target)
+                } else {
+                    panic!("tried to roll back migrations from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok(result)
+}
@@ -0,0 +1,66 @@
+use anyhow::{bail, Context};
+use refinery_core::{config::ConfigDbType, load_sql_migrations, Report, Rollout, Runner};
+
+use crate::cli::RolloutArgs;
+use crate::migrate::{config, migration_table_name};
+
+pub fn handle_rollout_command(args: RolloutArgs) -> anyhow::Result<()> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+    let mut canary = config(&args.canary, None, None)?;
+    let mut runner = Runner::new(&migrations).set_grouped(args.grouped);
+    // the targets share the schema history table layout of the canary
+    if let Some(schema) = canary.table_schema() {
+        runner = runner.set_migration_table_schema(schema);
+    }
+    runner.set_migration_table_name(migration_table_name(args.table_name.as_deref(), &canary))?;
+    let rollout = Rollout::new(runner).set_checks(args.check);
+
+    let mut targets = args
+        .targets
+        .iter()
+        .map(|target| config(target, None, None))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let is_async = |db_type| matches!(db_type, ConfigDbType::Mssql | ConfigDbType::Turso);
+    let async_targets = targets
+        .iter()
+        .filter(|target| is_async(target.db_type()))
+        .count()
+        + is_async(canary.db_type()) as usize;
+    let reports = if async_targets == 0 {
+        cfg_if::cfg_if! {
+            if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                rollout.run(&mut canary, &mut targets)?
+            } else {
+                panic!("tried to roll out migrations from config, but no database feature was enabled!");
+            }
+        }
+    } else if async_targets == targets.len() + 1 {
+        cfg_if::cfg_if! {
+            // tiberius and libsql are async drivers so we spawn tokio runtime and run the migrations
+            if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                use tokio::runtime::Builder;
+
+                let runtime = Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .context("Can't start tokio runtime")?;
+                runtime.block_on(rollout.run_async(&mut canary, &mut targets))?
+            } else {
+                panic!("tried to roll out migrations from config for a mssql or turso database, but neither mssql nor turso feature was enabled!");
+            }
+        }
+    } else {
+        bail!("mssql and turso databases can't be rolled out together with other databases");
+    };
+
+    let locations = std::iter::once(&args.canary).chain(&args.targets);
+    for (location, report) in locations.zip(reports.iter().map(Report::applied_migrations)) {
+        println!(
+            "applied {} migrations to {}",
+            report.len(),
+            location.display()
+        );
+    }
+    Ok(())
+}
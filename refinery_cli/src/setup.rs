@@ -29,7 +29,7 @@ pub fn handle_setup() -> Result<()> {
 }
 
 fn get_config_from_input() -> Result<Config> {
-    println!("Select database 1) Mysql 2) Postgresql 3) Sqlite 4) Mssql: ");
+    println!("Select database 1) Mysql 2) Postgresql 3) Sqlite 4) Mssql 5) Turso 6) CockroachDB 7) DuckDB 8) Oracle: ");
     print!("Enter a number: ");
     io::stdout().flush()?;
 
@@ -40,13 +40,20 @@ fn get_config_from_input() -> Result<Config> {
         "2" => ConfigDbType::Postgres,
         "3" => ConfigDbType::Sqlite,
         "4" => ConfigDbType::Mssql,
+        "5" => ConfigDbType::Turso,
+        "6" => ConfigDbType::CockroachDb,
+        "7" => ConfigDbType::DuckDb,
+        "8" => ConfigDbType::Oracle,
         _ => return Err(anyhow!("invalid option")),
     };
     let mut config = Config::new(db_type);
 
-    if config.db_type() == ConfigDbType::Sqlite {
+    if matches!(
+        config.db_type(),
+        ConfigDbType::Sqlite | ConfigDbType::DuckDb
+    ) {
         cfg_if::cfg_if! {
-            if #[cfg(feature = "sqlite")] {
+            if #[cfg(any(feature = "sqlite", feature = "duckdb"))] {
                 print!("Enter database path: ");
                 io::stdout().flush()?;
                 let mut db_path = String::new();
@@ -54,7 +61,7 @@ fn get_config_from_input() -> Result<Config> {
                 config = config.set_db_path(db_path.trim());
                 return Ok(config);
             } else {
-                panic!("tried to migrate async from config for a sqlite database, but sqlite feature was not enabled!");
+                panic!("tried to migrate from config for a {:?} database, but its matching feature was not enabled!", config.db_type());
             }
         }
     }
@@ -65,6 +72,14 @@ fn get_config_from_input() -> Result<Config> {
     io::stdin().read_line(&mut db_host)?;
     config = config.set_db_host(db_host.trim());
 
+    if config.db_type() == ConfigDbType::Turso {
+        print!("Enter database auth token: ");
+        io::stdout().flush()?;
+        let mut db_auth_token = String::new();
+        io::stdin().read_line(&mut db_auth_token)?;
+        return Ok(config.set_db_auth_token(db_auth_token.trim()));
+    }
+
     print!("Enter database port: ");
     io::stdout().flush()?;
     let mut db_port = String::new();
@@ -83,7 +98,11 @@ fn get_config_from_input() -> Result<Config> {
     io::stdin().read_line(&mut db_pass)?;
     config = config.set_db_pass(db_pass.trim());
 
-    print!("Enter database name: ");
+    if config.db_type() == ConfigDbType::Oracle {
+        print!("Enter database service name: ");
+    } else {
+        print!("Enter database name: ");
+    }
     io::stdout().flush()?;
     let mut db_name = String::new();
     io::stdin().read_line(&mut db_name)?;
@@ -0,0 +1,110 @@
+use anyhow::Context;
+use refinery_core::config::ConfigDbType;
+use refinery_core::{load_sql_migrations, Migration};
+use time::format_description::well_known::Rfc3339;
+
+use crate::cli::{Format, StatusArgs};
+use crate::migrate::{config, migration_table_name};
+use crate::output::{print_json, report_error, MigrationReport, State};
+
+pub fn handle_status_command(args: StatusArgs, format: Format) -> anyhow::Result<()> {
+    let (migrations, applied, pending) = report_error(format, "status", status(&args))?;
+
+    let mut reports = Vec::new();
+    for migration in &applied {
+        let state = if migrations
+            .iter()
+            .any(|local| local.version() == migration.version())
+        {
+            State::Applied
+        } else {
+            State::Missing
+        };
+        reports.push((migration, state));
+    }
+    reports.extend(pending.iter().map(|migration| (migration, State::Pending)));
+
+    match format {
+        Format::Json => print_json(
+            "status",
+            reports
+                .into_iter()
+                .map(|(migration, state)| MigrationReport::new(migration, state))
+                .collect(),
+            None,
+        ),
+        Format::Text => {
+            for (migration, state) in reports {
+                match (state, migration.applied_on()) {
+                    (State::Missing, _) => println!("missing migration: {}", migration),
+                    (_, Some(applied_on)) => println!(
+                        "applied migration: {} on {}",
+                        migration,
+                        applied_on.format(&Rfc3339)?
+                    ),
+                    _ => println!("pending migration: {}", migration),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// the migrations on the filesystem, the ones applied on the database of the config and the ones pending on it
+fn status(args: &StatusArgs) -> anyhow::Result<(Vec<Migration>, Vec<Migration>, Vec<Migration>)> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let table_name = migration_table_name(args.table_name.as_deref(), &config);
+    let table_name = match config.table_schema() {
+        Some(schema) => format!("{}.{}", schema, table_name),
+        None => table_name,
+    };
+    let (config, table_name) = (&mut config, table_name.as_str());
+    let (applied, pending) = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to query the migrations
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use refinery_core::AsyncMigrate;
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(async {
+                        let pending = AsyncMigrate::get_unapplied_migrations(
+                            config, &migrations, false, false, table_name,
+                        )
+                        .await?;
+                        let applied = AsyncMigrate::get_applied_migrations(config, table_name).await?;
+                        Ok::<_, refinery_core::Error>((applied, pending))
+                    })?
+                } else {
+                    panic!("tried to query migrations from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    use refinery_core::Migrate;
+
+                    let pending = Migrate::get_unapplied_migrations(
+                        config, &migrations, false, false, table_name,
+                    )?;
+                    let applied = Migrate::get_applied_migrations(config, table_name)?;
+                    (applied, pending)
+                } else {
+                    panic!("tried to query migrations from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok((migrations, applied, pending))
+}
@@ -0,0 +1,111 @@
+use anyhow::{bail, Context};
+use refinery_core::config::ConfigDbType;
+use refinery_core::{load_sql_migrations, Runner, ValidationIssue, ValidationReport};
+
+use crate::cli::{Format, ValidateArgs};
+use crate::migrate::{config, migration_table_name};
+use crate::output::{print_json, report_error, MigrationReport, State};
+
+pub fn handle_validate_command(args: ValidateArgs, format: Format) -> anyhow::Result<()> {
+    let report = report_error(format, "validate", validate(&args))?;
+    let issues = report.issues();
+    let error = (!report.is_valid()).then(|| {
+        format!(
+            "{} migrations are inconsistent with the applied ones",
+            issues.len()
+        )
+    });
+
+    match format {
+        Format::Json => {
+            let migrations = issues
+                .iter()
+                .map(|issue| match issue {
+                    ValidationIssue::Divergent { applied, .. } => {
+                        MigrationReport::new(applied, State::Divergent)
+                    }
+                    ValidationIssue::Missing(migration) => {
+                        MigrationReport::new(migration, State::Missing)
+                    }
+                    ValidationIssue::OutOfOrder(migration) => {
+                        MigrationReport::new(migration, State::OutOfOrder)
+                    }
+                    ValidationIssue::Repeated(migration) => {
+                        MigrationReport::new(migration, State::Repeated)
+                    }
+                })
+                .chain(
+                    report
+                        .pending_migrations()
+                        .iter()
+                        .map(|migration| MigrationReport::new(migration, State::Pending)),
+                )
+                .collect();
+            print_json("validate", migrations, error.clone());
+        }
+        Format::Text => {
+            for issue in issues {
+                println!("{}", issue);
+            }
+            for migration in report.pending_migrations() {
+                println!("pending migration: {}", migration);
+            }
+        }
+    }
+
+    match error {
+        Some(error) => bail!(error),
+        None => {
+            if format == Format::Text {
+                println!("migrations are consistent with the applied ones");
+            }
+            Ok(())
+        }
+    }
+}
+
+// the report of the validation of the migrations on the filesystem against the ones applied on the database of the config
+fn validate(args: &ValidateArgs) -> anyhow::Result<ValidationReport> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let table_name = migration_table_name(args.table_name.as_deref(), &config);
+    let mut runner = Runner::new(&migrations);
+    if let Some(schema) = config.table_schema() {
+        runner = runner.set_migration_table_schema(schema);
+    }
+    runner.set_migration_table_name(&table_name)?;
+
+    let report = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to validate the migrations
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(runner.validate_async(&mut config))?
+                } else {
+                    panic!("tried to validate migrations from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    runner.validate(&mut config)?
+                } else {
+                    panic!("tried to validate migrations from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok(report)
+}
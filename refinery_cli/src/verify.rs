@@ -0,0 +1,85 @@
+use anyhow::{bail, Context};
+use refinery_core::config::ConfigDbType;
+use refinery_core::{load_sql_migrations, ChecksumMismatch, Runner};
+
+use crate::cli::{Format, VerifyArgs};
+use crate::migrate::{config, migration_table_name};
+use crate::output::{print_json, report_error, MigrationReport};
+
+pub fn handle_verify_command(args: VerifyArgs, format: Format) -> anyhow::Result<()> {
+    let mismatches = report_error(format, "verify", verify(&args))?;
+    let error = (!mismatches.is_empty()).then(|| {
+        format!(
+            "{} applied migrations have a different checksum than the migrations dir",
+            mismatches.len()
+        )
+    });
+
+    match format {
+        Format::Json => {
+            let migrations = mismatches.iter().map(MigrationReport::mismatch).collect();
+            print_json("verify", migrations, error.clone());
+        }
+        Format::Text => {
+            for mismatch in &mismatches {
+                println!("{}", mismatch);
+            }
+        }
+    }
+
+    match error {
+        Some(error) => bail!(error),
+        None => {
+            if format == Format::Text {
+                println!("checksums of the applied migrations match the migrations dir");
+            }
+            Ok(())
+        }
+    }
+}
+
+// the applied migrations of the database of the config with a different checksum than the migrations on the filesystem
+fn verify(args: &VerifyArgs) -> anyhow::Result<Vec<ChecksumMismatch>> {
+    let migrations = load_sql_migrations(&args.path).context("could not load the migrations")?;
+    let mut config = config(&args.config, args.env_var.as_deref(), args.env.as_deref())?;
+    let table_name = migration_table_name(args.table_name.as_deref(), &config);
+    let mut runner = Runner::new(&migrations);
+    if let Some(schema) = config.table_schema() {
+        runner = runner.set_migration_table_schema(schema);
+    }
+    runner.set_migration_table_name(&table_name)?;
+
+    let mismatches = match config.db_type() {
+        _db_type @ (ConfigDbType::Mssql | ConfigDbType::Turso) => {
+            cfg_if::cfg_if! {
+                // tiberius and libsql are async drivers so we spawn tokio runtime to verify the checksums
+                if #[cfg(any(feature = "mssql", feature = "turso"))] {
+                    use tokio::runtime::Builder;
+
+                    let runtime = Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .context("Can't start tokio runtime")?;
+                    runtime.block_on(runner.verify_checksums_async(&mut config))?
+                } else {
+                    panic!("tried to verify checksums from config for a {:?} database, but it\'s matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+        _db_type @ (ConfigDbType::Mysql
+        | ConfigDbType::Postgres
+        | ConfigDbType::Sqlite
+        | ConfigDbType::CockroachDb
+        | ConfigDbType::DuckDb
+        | ConfigDbType::Oracle) => {
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "mysql", feature = "postgresql", feature = "sqlite", feature = "duckdb", feature = "oracle"))] {
+                    runner.verify_checksums(&mut config)?
+                } else {
+                    panic!("tried to verify checksums from config for a {:?} database, but it's matching feature was not enabled!", _db_type);
+                }
+            }
+        }
+    };
+    Ok(mismatches)
+}
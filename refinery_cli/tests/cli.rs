@@ -27,4 +27,683 @@ mod cli {
             .assert()
             .failure();
     }
+
+    // `refinery generate` with no name should exit with a non-zero code.
+    #[test]
+    fn generate_no_args() {
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["generate"])
+            .assert()
+            .failure();
+    }
+
+    // `refinery generate --template` should create the migration from the template.
+    #[test]
+    fn generate_from_template() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("template.sql"),
+            "-- {{name}}\nBEGIN;\n\nCOMMIT;\n",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["generate", "--template", "up-down", "initial"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(contains("V1__initial.sql"));
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["generate", "--template", "template.sql", "add_cars"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+
+        let migrations = dir.path().join("migrations");
+        assert_eq!(
+            "\n-- refinery:down\n",
+            std::fs::read_to_string(migrations.join("V1__initial.sql")).unwrap()
+        );
+        assert_eq!(
+            "-- add_cars\nBEGIN;\n\nCOMMIT;\n",
+            std::fs::read_to_string(migrations.join("V2__add_cars.sql")).unwrap()
+        );
+    }
+
+    // `refinery migrate --rerun` without --force should exit with a non-zero code.
+    #[test]
+    fn migrate_rerun_requires_force() {
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate", "--rerun", "V2"])
+            .assert()
+            .failure()
+            .stderr(contains("--force"));
+    }
+
+    // `refinery status --env` with an environment missing from the config file should exit with a non-zero code.
+    #[test]
+    fn status_missing_env() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = dir.path().join("refinery.toml");
+        std::fs::write(
+            &config,
+            "[main]\ndb_type = \"Postgres\"\n\n[environments.staging]\ndb_host = \"staging.db\"\n",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["status", "--env", "prod", "-p"])
+            .arg(dir.path())
+            .arg("-c")
+            .arg(&config)
+            .assert()
+            .failure()
+            .stderr(contains("environment prod not found in config file"));
+    }
+
+    // `refinery lock --check` without a lockfile should exit with a non-zero code.
+    #[test]
+    fn lock_check_requires_lockfile() {
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["lock", "--check", "-l", "./missing.lock"])
+            .assert()
+            .failure()
+            .stderr(contains("could not check the lockfile"));
+    }
+
+    // `refinery lint` with an invalid dialect should exit with a non-zero code.
+    #[test]
+    fn lint_invalid_dialect() {
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["lint", "--dialect", "db2"])
+            .assert()
+            .failure()
+            .stderr(contains("invalid dialect db2"));
+    }
+
+    fn git(dir: &std::path::Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=refinery",
+                "-c",
+                "user.email=refinery@localhost",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    // `refinery lint --since` should fail on merged migrations that were modified and new ones with lower versions.
+    #[test]
+    fn lint_since_base_ref() {
+        let repo = tempfile::TempDir::new().unwrap();
+        let migrations = repo.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(
+            migrations.join("V3__cars.sql"),
+            "CREATE TABLE cars(id int);",
+        )
+        .unwrap();
+        git(repo.path(), &["init", "-q"]);
+        git(repo.path(), &["add", "."]);
+        git(repo.path(), &["commit", "-q", "-m", "initial"]);
+        git(repo.path(), &["branch", "base"]);
+
+        std::fs::write(
+            migrations.join("V4__motos.sql"),
+            "CREATE TABLE motos(id int);",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["lint", "--allow-gaps", "--since", "base"])
+            .current_dir(repo.path())
+            .assert()
+            .success();
+
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id bigint);",
+        )
+        .unwrap();
+        std::fs::write(migrations.join("V2__late.sql"), "SELECT 1;").unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["lint", "--since", "base"])
+            .current_dir(repo.path())
+            .assert()
+            .failure()
+            .stdout(contains(
+                "V1__initial.sql: migration was modified after being merged",
+            ))
+            .stdout(contains("version 2 must be greater than 3"));
+    }
+
+    // `refinery rollout` should abort when the canary checks fail, without migrating the targets.
+    #[test]
+    fn rollout_aborts_on_canary_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        for db in ["canary", "primary"] {
+            std::fs::write(dir.path().join(format!("{}.db", db)), "").unwrap();
+            std::fs::write(
+                dir.path().join(format!("{}.toml", db)),
+                format!("[main]\ndb_type = \"Sqlite\"\ndb_path = \"./{}.db\"\n", db),
+            )
+            .unwrap();
+        }
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["rollout", "--canary", "./canary.toml", "./primary.toml"])
+            .args(["--check", "SELECT COUNT(*) = 1 FROM t"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stderr(contains("rollout aborted on the canary checks"));
+        let db_len = |db: &str| std::fs::metadata(dir.path().join(db)).unwrap().len();
+        assert!(db_len("canary.db") > 0);
+        assert_eq!(0, db_len("primary.db"));
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["rollout", "--canary", "./canary.toml", "./primary.toml"])
+            .args(["--check", "SELECT COUNT(*) = 0 FROM t"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(contains("applied 0 migrations to ./canary.toml"))
+            .stdout(contains("applied 1 migrations to ./primary.toml"));
+    }
+
+    // `refinery migrate --dsn-file` should migrate each database of the file, leaving the ones after a failing
+    // one unmigrated unless --continue-on-failure is given.
+    #[test]
+    fn migrate_dsn_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        // the second database isn't a sqlite file, so migrating it fails
+        for (db, contents) in [("a", ""), ("b", "not a database"), ("c", "")] {
+            std::fs::write(dir.path().join(format!("{}.db", db)), contents).unwrap();
+        }
+        let dsns: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|db| {
+                format!(
+                    "sqlite://{}",
+                    dir.path().join(format!("{}.db", db)).display()
+                )
+            })
+            .collect();
+        std::fs::write(
+            dir.path().join("shards.txt"),
+            format!("# shards\n{}\n\n{}\n{}\n", dsns[0], dsns[1], dsns[2]),
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate", "--dsn-file", "shards.txt"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stdout(contains(format!("applied 1 migrations to {}", dsns[0])))
+            .stdout(contains(format!("failed to migrate {}", dsns[1])))
+            .stdout(contains(format!("skipped {}", dsns[2])))
+            .stderr(contains("1 of 3 databases failed to migrate"));
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args([
+                "migrate",
+                "--dsn-file",
+                "shards.txt",
+                "--continue-on-failure",
+            ])
+            .args(["--concurrency", "2"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stdout(contains(format!("applied 0 migrations to {}", dsns[0])))
+            .stdout(contains(format!("applied 1 migrations to {}", dsns[2])));
+    }
+
+    // `refinery history export --format json` should write the schema history rows `history import` inserts into
+    // another database.
+    #[test]
+    fn history_export_import() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        for db in ["old", "new"] {
+            std::fs::write(dir.path().join(format!("{}.db", db)), "").unwrap();
+            std::fs::write(
+                dir.path().join(format!("{}.toml", db)),
+                format!("[main]\ndb_type = \"Sqlite\"\ndb_path = \"./{}.db\"\n", db),
+            )
+            .unwrap();
+        }
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate", "-c", "./old.toml"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["--format", "json", "history", "export", "-c", "./old.toml"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let exported = String::from_utf8(output.stdout).unwrap();
+        assert!(exported.contains("\"name\": \"initial\""));
+        std::fs::write(dir.path().join("history.json"), exported).unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["history", "import", "-c", "./new.toml", "history.json"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(contains("imported 1 of 1 schema history records"));
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["history", "export", "-c", "./new.toml"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(contains("1 initial applied on"));
+    }
+
+    // `refinery verify` should list the applied migrations edited since with both checksums.
+    #[test]
+    fn verify_checksums() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sql"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sql\"\n",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["verify"])
+            .current_dir(dir.path())
+            .assert()
+            .success()
+            .stdout(contains(
+                "checksums of the applied migrations match the migrations dir",
+            ));
+
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int, name text);",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["verify"])
+            .current_dir(dir.path())
+            .assert()
+            .failure()
+            .stdout(contains("applied migration V1__initial has checksum"))
+            .stderr(contains(
+                "1 applied migrations have a different checksum than the migrations dir",
+            ));
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["--format", "json", "verify"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let report = String::from_utf8(output.stdout).unwrap();
+        assert!(report.contains("\"state\":\"divergent\""));
+        assert!(report.contains("\"local_checksum\":"));
+    }
+
+    // `refinery migrate --snapshot` should write the schema of the database after migrating it.
+    #[test]
+    fn migrate_snapshot() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int NOT NULL);",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sql"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sql\"\n",
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate", "--snapshot", "schema.sql"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        assert_eq!(
+            "CREATE TABLE t (\n    id INT NOT NULL\n);\n",
+            std::fs::read_to_string(dir.path().join("schema.sql")).unwrap()
+        );
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["migrate", "--snapshot", "schema.json"])
+            .current_dir(dir.path())
+            .assert()
+            .success();
+        let snapshot = std::fs::read_to_string(dir.path().join("schema.json")).unwrap();
+        assert!(snapshot.contains("\"data_type\": \"INT\""));
+        assert!(!snapshot.contains("refinery_schema_history"));
+    }
+
+    // `refinery analyze` should print the risk summary of the migrations.
+    #[test]
+    fn analyze_risk_summary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("V1__initial.sql"),
+            "CREATE TABLE t(id int);\n-- refinery:down\nDROP TABLE t;",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("V2__index.sql"),
+            "CREATE INDEX t_id ON t(id);",
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(["analyze", "--dialect", "postgres", "-p"])
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stdout(contains("V1__initial: reversible\n"))
+            .stdout(contains("V2__index: irreversible, blocking"))
+            .stdout(contains(
+                "2 migrations: 1 reversible, 0 destructive, 1 blocking",
+            ));
+    }
+
+    // `--format json` should print the reports of migrate, status and rollback as json on stdout.
+    #[test]
+    fn json_reports() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);\n-- refinery:down\nDROP TABLE t;",
+        )
+        .unwrap();
+        std::fs::write(
+            migrations.join("V2__broken.sql"),
+            "CREATE TABLE u(id int);\nBROKEN;",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sqlite"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sqlite\"\n",
+        )
+        .unwrap();
+        let refinery = |args: &[&str]| {
+            let mut command = Command::cargo_bin("refinery").unwrap();
+            command
+                .args(["--format", "json"])
+                .args(args)
+                .current_dir(dir.path());
+            command
+        };
+
+        refinery(&["migrate"])
+            .assert()
+            .failure()
+            .stdout(predicates::str::starts_with(
+                r#"{"command":"migrate","success":false,"migrations":[{"version":1,"name":"initial","state":"applied","#,
+            ))
+            .stdout(contains(r#""error":"`error applying update`"#))
+            .stdout(contains(
+                r#""statement":{"migration":"V2__broken","index":2,"line":2,"sql":"BROKEN;"}}"#,
+            ));
+
+        refinery(&["status"])
+            .assert()
+            .success()
+            .stdout(contains(
+                r#"{"version":1,"name":"initial","state":"applied""#,
+            ))
+            .stdout(contains(
+                r#"{"version":2,"name":"broken","state":"pending""#,
+            ));
+
+        refinery(&["rollback", "-t", "0"])
+            .assert()
+            .success()
+            .stdout(contains(
+                r#"{"command":"rollback","success":true,"migrations":[{"version":1,"name":"initial","state":"rolled_back""#,
+            ));
+
+        refinery(&["status", "-c", "./missing.toml"])
+            .assert()
+            .failure()
+            .stdout(contains(
+                r#"{"command":"status","success":false,"migrations":[],"error":"could not parse the config file"#,
+            ));
+    }
+
+    // `migrate --steps` should apply at most the given number of pending migrations.
+    #[test]
+    fn migrate_steps() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(
+            migrations.join("V2__add_name.sql"),
+            "ALTER TABLE t ADD name text;",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sqlite"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sqlite\"\n",
+        )
+        .unwrap();
+        let refinery = |args: &[&str]| {
+            let mut command = Command::cargo_bin("refinery").unwrap();
+            command.args(args).current_dir(dir.path());
+            command
+        };
+
+        for applied in ["V1__initial", "V2__add_name"] {
+            refinery(&["migrate", "--steps", "1"])
+                .assert()
+                .success()
+                .stdout(contains(applied));
+        }
+        refinery(&["migrate", "--steps", "0"]).assert().failure();
+        refinery(&["migrate", "--steps", "1", "-t", "2"])
+            .assert()
+            .failure();
+    }
+
+    // `package` should bundle the migrations and `migrate --bundle` apply them from it, without the directory.
+    #[test]
+    fn package_and_migrate_bundle() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(
+            migrations.join("V2__add_name.sql"),
+            "ALTER TABLE t ADD name text;",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sqlite"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sqlite\"\n",
+        )
+        .unwrap();
+        let refinery = |args: &[&str]| {
+            let mut command = Command::cargo_bin("refinery").unwrap();
+            command.args(args).current_dir(dir.path());
+            command
+        };
+
+        refinery(&["package", "-o", "release.tar.gz"])
+            .assert()
+            .success()
+            .stdout(contains("wrote bundle: release.tar.gz"))
+            .stdout(contains("sha256: "));
+        std::fs::remove_dir_all(&migrations).unwrap();
+
+        refinery(&["migrate", "--bundle", "release.tar.gz"])
+            .assert()
+            .success()
+            .stdout(contains("V1__initial"))
+            .stdout(contains("V2__add_name"));
+
+        // gaps between versions fail the validation
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(migrations.join("V3__gap.sql"), "SELECT 1;").unwrap();
+        refinery(&["package", "-o", "gap.tar.gz"])
+            .assert()
+            .failure()
+            .stderr(contains("not bundling them"));
+        assert!(!dir.path().join("gap.tar.gz").exists());
+    }
+
+    // `migrate --crate` should apply the migrations exported by the binary the crate sets on its manifest.
+    #[test]
+    fn migrate_crate() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let migrations = dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        std::fs::write(
+            migrations.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.sqlite"), "").unwrap();
+        std::fs::write(
+            dir.path().join("refinery.toml"),
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sqlite\"\n",
+        )
+        .unwrap();
+        let refinery = |args: &[&str]| {
+            let mut command = Command::cargo_bin("refinery").unwrap();
+            command
+                .args(args)
+                .current_dir(dir.path())
+                .env("CARGO_TARGET_DIR", dir.path().join("target"));
+            command
+        };
+        refinery(&["package", "-o", "release.tar.gz"])
+            .assert()
+            .success();
+
+        // a stand-in for a binary calling `export_bundle`, copying the bundle to the path it's given
+        let app = dir.path().join("app");
+        std::fs::create_dir_all(app.join("src/bin")).unwrap();
+        std::fs::write(
+            app.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [package.metadata.refinery]\nbin = \"export\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::write(app.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(
+            app.join("src/bin/export.rs"),
+            format!(
+                "fn main() {{\n    assert_eq!(std::env::var(\"REFINERY_BUNDLE_DIALECT\").unwrap(), \"sqlite\");\n    \
+                 std::fs::copy({:?}, std::env::var(\"REFINERY_BUNDLE_PATH\").unwrap()).unwrap();\n}}\n",
+                dir.path().join("release.tar.gz")
+            ),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&migrations).unwrap();
+
+        refinery(&["migrate", "--crate", "app"])
+            .assert()
+            .success()
+            .stdout(contains("V1__initial"));
+        refinery(&["migrate", "--crate", "app", "--bundle", "release.tar.gz"])
+            .assert()
+            .failure();
+
+        std::fs::write(
+            app.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        refinery(&["migrate", "--crate", "app"])
+            .assert()
+            .failure()
+            .stderr(contains("has no migrations binary"));
+    }
 }
@@ -0,0 +1,327 @@
+//! Analyzes the risk of applying migrations for change review: whether they can be reverted with a
+//! `-- refinery:down` section, destroy data, or are expected to hold long locks on the tables they change.
+use crate::util::{split_down, strip_comments};
+use crate::{Dialect, Migration};
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// The risk of a statement of a migration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Risk {
+    /// A statement that destroys data, i.e. `DROP TABLE` or `TRUNCATE`
+    Destructive,
+    /// A statement expected to hold a long lock on a large table, i.e. building an index without `CONCURRENTLY`
+    Blocking,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Risk::Destructive => write!(fmt, "destructive"),
+            Risk::Blocking => write!(fmt, "blocking"),
+        }
+    }
+}
+
+/// A risky statement found on a migration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub risk: Risk,
+    pub statement: String,
+    pub message: String,
+}
+
+/// The analysis of a migration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationAnalysis {
+    pub migration: String,
+    /// The migration has a `-- refinery:down` section to revert it
+    pub reversible: bool,
+    pub findings: Vec<Finding>,
+}
+
+impl MigrationAnalysis {
+    /// The migration has statements that destroy data
+    pub fn is_destructive(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.risk == Risk::Destructive)
+    }
+
+    /// The migration has statements expected to hold long locks
+    pub fn is_blocking(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.risk == Risk::Blocking)
+    }
+}
+
+/// The analysis of a set of migrations, displayed as a risk summary
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    pub migrations: Vec<MigrationAnalysis>,
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for analysis in &self.migrations {
+            let mut classes = vec![if analysis.reversible {
+                "reversible"
+            } else {
+                "irreversible"
+            }];
+            if analysis.is_destructive() {
+                classes.push("destructive");
+            }
+            if analysis.is_blocking() {
+                classes.push("blocking");
+            }
+            writeln!(fmt, "{}: {}", analysis.migration, classes.join(", "))?;
+            for finding in &analysis.findings {
+                writeln!(
+                    fmt,
+                    "  {}: {}: {}",
+                    finding.risk, finding.message, finding.statement
+                )?;
+            }
+        }
+        let count = |predicate: fn(&MigrationAnalysis) -> bool| {
+            self.migrations
+                .iter()
+                .filter(|analysis| predicate(analysis))
+                .count()
+        };
+        write!(
+            fmt,
+            "{} migrations: {} reversible, {} destructive, {} blocking",
+            self.migrations.len(),
+            count(|analysis| analysis.reversible),
+            count(MigrationAnalysis::is_destructive),
+            count(MigrationAnalysis::is_blocking),
+        )
+    }
+}
+
+// the risk, the dialect the rule applies to, the pattern, the pattern that excludes it and the message
+type Rule = (
+    Risk,
+    Option<Dialect>,
+    &'static str,
+    Option<&'static str>,
+    &'static str,
+);
+
+// statements matching the first pattern and not the second one, matched case insensitively against each statement
+// without comments. Rules with a dialect only apply to migrations for it, or whose dialect is unknown
+const RULES: &[Rule] = &[
+    (
+        Risk::Destructive,
+        None,
+        r"\bDROP\s+(TABLE|SCHEMA|DATABASE)\b",
+        None,
+        "drops all the data it holds",
+    ),
+    (
+        Risk::Destructive,
+        None,
+        r"\bALTER\s+TABLE\b.*\bDROP\s+COLUMN\b",
+        None,
+        "drops the data of the column",
+    ),
+    (
+        Risk::Destructive,
+        None,
+        r"\bTRUNCATE\b",
+        None,
+        "deletes all the rows of the table",
+    ),
+    (
+        Risk::Destructive,
+        None,
+        r"\bDELETE\s+FROM\b",
+        Some(r"\bWHERE\b"),
+        "deletes all the rows of the table",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Postgres),
+        r"\bCREATE\s+(UNIQUE\s+)?INDEX\b",
+        Some(r"\bCONCURRENTLY\b"),
+        "building an index without CONCURRENTLY blocks writes to the table",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Postgres),
+        r"\bALTER\s+TABLE\b.*\bALTER\s+(COLUMN\s+)?\S+\s+(SET\s+DATA\s+)?TYPE\b",
+        None,
+        "changing the type of a column rewrites the table holding an exclusive lock",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Postgres),
+        r"\bADD\s+(CONSTRAINT\s+\S+\s+)?(FOREIGN\s+KEY|CHECK)\b",
+        Some(r"\bNOT\s+VALID\b"),
+        "adding a constraint without NOT VALID scans the table holding a lock",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Postgres),
+        r"\bSET\s+NOT\s+NULL\b",
+        None,
+        "SET NOT NULL scans the table holding an exclusive lock",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Postgres),
+        r"\bVACUUM\s+FULL\b|\bCLUSTER\b",
+        None,
+        "rewrites the table holding an exclusive lock",
+    ),
+    (
+        Risk::Blocking,
+        Some(Dialect::Mysql),
+        r"\bALTER\s+TABLE\b.*\b(MODIFY|CHANGE)\s+(COLUMN\s+)?\S+",
+        None,
+        "changing a column copies the table, blocking writes to it",
+    ),
+    (
+        Risk::Blocking,
+        None,
+        r"\bLOCK\s+TABLES?\b",
+        None,
+        "locks the table explicitly",
+    ),
+];
+
+fn rule_re(pattern: &str) -> Regex {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .dot_matches_new_line(true)
+        .build()
+        .unwrap()
+}
+
+/// Analyzes the given migrations, i.e. the pending ones, for the [`Dialect`] of the database they are going to be
+/// applied on, migration variants are always analyzed for their own.
+/// Migrations whose sql is generated when they are run are analyzed if the dialect is given, without their down section
+pub fn analyze(migrations: &[Migration], dialect: Option<Dialect>) -> Analysis {
+    let rules: Vec<_> = RULES
+        .iter()
+        .map(|(risk, rule_dialect, pattern, unless, message)| {
+            (
+                *risk,
+                *rule_dialect,
+                rule_re(pattern),
+                unless.map(rule_re),
+                *message,
+            )
+        })
+        .collect();
+
+    let migrations = migrations
+        .iter()
+        .map(|migration| {
+            let dialect = migration.dialect().or(dialect);
+            let generated = match (migration.sql(), dialect) {
+                (None, Some(dialect)) => migration.for_dialect(Some(dialect)).ok(),
+                _ => None,
+            };
+            let migration_sql = generated.as_ref().unwrap_or(migration);
+            let reversible = migration_sql.down_sql().is_some();
+            let sql = migration_sql
+                .sql()
+                .map(|sql| strip_comments(split_down(sql).0))
+                .unwrap_or_default();
+
+            let mut findings = Vec::new();
+            for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                for (risk, rule_dialect, pattern, unless, message) in &rules {
+                    let applies = match (rule_dialect, dialect) {
+                        (Some(rule_dialect), Some(dialect)) => *rule_dialect == dialect,
+                        _ => true,
+                    };
+                    if applies
+                        && pattern.is_match(statement)
+                        && !unless
+                            .as_ref()
+                            .is_some_and(|unless| unless.is_match(statement))
+                    {
+                        findings.push(Finding {
+                            risk: *risk,
+                            statement: statement.split_whitespace().collect::<Vec<_>>().join(" "),
+                            message: message.to_string(),
+                        });
+                    }
+                }
+            }
+
+            MigrationAnalysis {
+                migration: migration.to_string(),
+                reversible,
+                findings,
+            }
+        })
+        .collect();
+    Analysis { migrations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, Risk};
+    use crate::{Dialect, Migration};
+
+    #[test]
+    fn classifies_migrations() {
+        let migrations = [
+            Migration::unapplied(
+                "V1__add_users",
+                "CREATE TABLE users(id int);\n-- refinery:down\nDROP TABLE users;",
+            )
+            .unwrap(),
+            Migration::unapplied(
+                "V2__cleanup",
+                "-- drop the legacy table\nDROP TABLE legacy;\nDELETE FROM sessions;\nDELETE FROM users WHERE id = 1;",
+            )
+            .unwrap(),
+            Migration::unapplied(
+                "V3__add_indexes",
+                "CREATE INDEX users_id ON users(id);\nCREATE INDEX CONCURRENTLY users_name ON users(name);",
+            )
+            .unwrap(),
+        ];
+
+        let analysis = analyze(&migrations, Some(Dialect::Postgres));
+        let [users, cleanup, indexes] = analysis.migrations.as_slice() else {
+            panic!("failed test");
+        };
+        assert!(users.reversible);
+        // the down section isn't applied
+        assert!(users.findings.is_empty());
+
+        assert!(!cleanup.reversible);
+        assert!(cleanup.is_destructive());
+        let statements: Vec<&str> = cleanup
+            .findings
+            .iter()
+            .map(|finding| finding.statement.as_str())
+            .collect();
+        assert_eq!(
+            vec!["DROP TABLE legacy", "DELETE FROM sessions"],
+            statements
+        );
+
+        assert_eq!(1, indexes.findings.len());
+        assert_eq!(Risk::Blocking, indexes.findings[0].risk);
+        assert_eq!(
+            "CREATE INDEX users_id ON users(id)",
+            indexes.findings[0].statement
+        );
+        assert!(analysis
+            .to_string()
+            .ends_with("3 migrations: 1 reversible, 1 destructive, 1 blocking"));
+
+        // Postgres rules don't apply to other dialects
+        let analysis = analyze(&migrations, Some(Dialect::Sqlite));
+        assert!(!analysis.migrations[2].is_blocking());
+    }
+}
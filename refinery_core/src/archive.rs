@@ -0,0 +1,553 @@
+//! Bundles the SQL migrations of a location into a gzipped tar archive with a manifest of their checksums, and loads
+//! the migrations of a tar, gzipped tar or zip archive, see [`Runner::from_archive`](crate::Runner::from_archive).
+//!
+//! The manifest of a bundle is a [`BUNDLE_MANIFEST`] file at its root with the SHA-256 digest of each migration file,
+//! in the format `sha256sum` writes, i.e. `3f0a...  postgres/V2__add_email.sql`, so a bundle can also be checked with
+//! `sha256sum -c`. Archives with a manifest only load if every migration file on them matches it.
+//!
+//! The migrations of a [`Runner`], Rust ones included, can also be bundled with [`export_bundle`], so
+//! `refinery migrate --crate` can apply them.
+use crate::error::{Error, Kind};
+use crate::util::{
+    assert_no_conflicts, dialect_file_name, find_migration_files, load_sql_migrations,
+    MigrationType,
+};
+use crate::{Dialect, Migration, Runner};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The name of the manifest file of a bundle
+pub const BUNDLE_MANIFEST: &str = "SHA256SUMS";
+
+/// The environment variable with the path [`export_bundle`] writes the bundle to
+pub const BUNDLE_PATH_VAR: &str = "REFINERY_BUNDLE_PATH";
+
+/// The environment variable with the [`Dialect`] [`export_bundle`] generates the sql of Rust migrations for,
+/// i.e. `postgres`
+pub const BUNDLE_DIALECT_VAR: &str = "REFINERY_BUNDLE_DIALECT";
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+fn invalid_archive(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Error {
+    Error::new(
+        Kind::InvalidArchive(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        None,
+    )
+}
+
+fn read_file(path: &Path, mut file: impl Read) -> Result<String, Error> {
+    let mut sql = String::new();
+    file.read_to_string(&mut sql)
+        .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.to_path_buf(), err), None))?;
+    Ok(sql)
+}
+
+fn digest(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+// the path of an archive entry separated by `/` on every platform, without `.` components
+fn entry_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Collects the `.sql` files of an archive following the migration naming, the other ones are skipped, with a
+/// warning for the `.sql` ones
+#[derive(Default)]
+struct Files {
+    migrations: Vec<Migration>,
+    filenames: Vec<(String, PathBuf)>,
+    digests: Vec<(String, String)>,
+    manifest: Option<(String, String)>,
+}
+
+impl Files {
+    fn is_manifest(path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|file_name| file_name == BUNDLE_MANIFEST)
+    }
+
+    fn is_migration(path: &Path) -> bool {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        if MigrationType::Sql.file_match_re().is_match(file_name) {
+            return true;
+        }
+        if file_name.ends_with(".sql") {
+            log::warn!(
+                "File \"{}\" does not adhere to the migration naming convention, it's skipped.",
+                path.display()
+            );
+        }
+        false
+    }
+
+    fn push_entry(&mut self, path: PathBuf, file: impl Read) -> Result<(), Error> {
+        if Files::is_manifest(&path) {
+            let directory = path.parent().map(entry_path).unwrap_or_default();
+            self.manifest = Some((directory, read_file(&path, file)?));
+        } else if Files::is_migration(&path) {
+            let sql = read_file(&path, file)?;
+            self.push(path, sql)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, path: PathBuf, sql: String) -> Result<(), Error> {
+        // safe to unwrap as is_migration matched the file name
+        let file_stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap()
+            .to_string();
+        let directory = path
+            .parent()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str());
+        let filename = dialect_file_name(file_stem, directory);
+        self.digests
+            .push((entry_path(&path), digest(sql.as_bytes())));
+        self.migrations
+            .push(Migration::unapplied_from(&filename, sql)?);
+        self.filenames.push((filename, path));
+        Ok(())
+    }
+
+    // every migration file must be on the manifest with the same digest, and every file on it must be a migration
+    fn verify_manifest(&self) -> Result<(), Error> {
+        let Some((directory, manifest)) = &self.manifest else {
+            return Ok(());
+        };
+
+        let mut expected = HashMap::new();
+        for line in manifest.lines().filter(|line| !line.trim().is_empty()) {
+            let (digest, path) = line
+                .split_once("  ")
+                .ok_or_else(|| invalid_archive(format!("invalid manifest line {}", line)))?;
+            let path = match directory.as_str() {
+                "" => path.to_string(),
+                directory => format!("{}/{}", directory, path),
+            };
+            expected.insert(path, digest.trim());
+        }
+
+        for (path, digest) in &self.digests {
+            match expected.remove(path.as_str()) {
+                Some(expected) if expected == digest => {}
+                Some(_) => {
+                    return Err(invalid_archive(format!(
+                        "migration {} doesn't match its checksum on the manifest",
+                        path
+                    )))
+                }
+                None => {
+                    return Err(invalid_archive(format!(
+                        "migration {} is not on the manifest",
+                        path
+                    )))
+                }
+            }
+        }
+        if let Some(path) = expected.keys().min() {
+            return Err(invalid_archive(format!(
+                "migration {} on the manifest is missing",
+                path
+            )));
+        }
+        Ok(())
+    }
+
+    fn into_migrations(mut self) -> Result<Vec<Migration>, Error> {
+        self.verify_manifest()?;
+        assert_no_conflicts(&self.filenames)?;
+        self.migrations.sort();
+        Ok(self.migrations)
+    }
+}
+
+fn load_tar(reader: impl Read) -> Result<Vec<Migration>, Error> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = Files::default();
+    for entry in archive.entries().map_err(invalid_archive)? {
+        let entry = entry.map_err(invalid_archive)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(invalid_archive)?.into_owned();
+        files.push_entry(path, entry)?;
+    }
+    files.into_migrations()
+}
+
+fn load_zip(bytes: &[u8]) -> Result<Vec<Migration>, Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(invalid_archive)?;
+    let mut files = Files::default();
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(invalid_archive)?;
+        if !file.is_file() {
+            continue;
+        }
+        let path = PathBuf::from(file.name());
+        files.push_entry(path, file)?;
+    }
+    files.into_migrations()
+}
+
+/// Loads the SQL migrations of an archive, telling zip archives and gzipped tarballs apart from plain tarballs by their
+/// magic bytes. Migrations on a directory named after a [`Dialect`](crate::Dialect) are variants for it, as with
+/// [`migration_file_name`](crate::migration_file_name). The resulting collection is ordered by version.
+pub(crate) fn load_archive_migrations(mut reader: impl Read) -> Result<Vec<Migration>, Error> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|err| Error::new(Kind::InvalidArchive(err), None))?;
+
+    if bytes.starts_with(ZIP_MAGIC) {
+        load_zip(&bytes)
+    } else if bytes.starts_with(GZIP_MAGIC) {
+        load_tar(GzDecoder::new(bytes.as_slice()))
+    } else {
+        load_tar(bytes.as_slice())
+    }
+}
+
+/// Write a gzipped tar bundle of the SQL migrations found on the given location, with their paths relative to it,
+/// and the [`BUNDLE_MANIFEST`] with their digests. The migrations are loaded first, so a location with invalid or
+/// conflicting migration files isn't bundled. Returns the SHA-256 digest of the bundle, to be published next to it.
+pub fn write_bundle(location: impl AsRef<Path>, writer: impl Write) -> Result<String, Error> {
+    let location = location.as_ref();
+    load_sql_migrations(location)?;
+    let root = location.canonicalize().map_err(|err| {
+        Error::new(
+            Kind::InvalidMigrationPath(location.to_path_buf(), err),
+            None,
+        )
+    })?;
+    let mut paths: Vec<PathBuf> = find_migration_files(&root, MigrationType::Sql)?.collect();
+    paths.sort();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let contents = fs::read(&path)
+            .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.clone(), err), None))?;
+        // safe to unwrap as find_migration_files returns the files on the location
+        let relative = entry_path(path.strip_prefix(&root).unwrap());
+        files.push((relative, contents));
+    }
+    write_files(files, writer)
+}
+
+/// Write a gzipped tar bundle of the given migrations, i.e. the ones of a [`Runner`] with Rust migrations, as SQL
+/// migration files with the same checksums and the [`BUNDLE_MANIFEST`] with their digests. The sql of the Rust
+/// migrations that depend on the [`Dialect`] is generated for the given one, async Rust migrations can't be bundled.
+/// Returns the SHA-256 digest of the bundle, as [`write_bundle`].
+pub fn write_migrations_bundle(
+    migrations: &[Migration],
+    dialect: Option<Dialect>,
+    writer: impl Write,
+) -> Result<String, Error> {
+    let mut migrations = migrations.to_vec();
+    migrations.sort();
+    let mut files = Vec::new();
+    for migration in &migrations {
+        let (file_name, sql) = migration.to_file(dialect)?;
+        files.push((file_name, sql.into_bytes()));
+    }
+    write_files(files, writer)
+}
+
+/// Write the bundle of the migrations of a [`Runner`] for `refinery migrate --crate`, which runs the `migrations`
+/// binary of a crate, or the one set as `package.metadata.refinery.bin` on its manifest, so the CLI can apply its
+/// Rust migrations. The bundle is written to the path of the [`BUNDLE_PATH_VAR`] environment variable, or to the
+/// standard output when it's not set, and the sql of the Rust migrations is generated for the Dialect set with
+/// [`Runner::set_dialect`] or the one of the [`BUNDLE_DIALECT_VAR`] environment variable.
+pub fn export_bundle(runner: &Runner) -> Result<(), Error> {
+    let dialect = match (runner.dialect(), std::env::var(BUNDLE_DIALECT_VAR)) {
+        (Some(dialect), _) => Some(dialect),
+        (None, Ok(dialect)) => Some(dialect.parse()?),
+        (None, Err(_)) => None,
+    };
+    match std::env::var_os(BUNDLE_PATH_VAR) {
+        Some(path) => {
+            let file = fs::File::create(&path).map_err(archive_write_err)?;
+            write_migrations_bundle(runner.get_migrations(), dialect, file)?;
+        }
+        None => {
+            write_migrations_bundle(runner.get_migrations(), dialect, std::io::stdout().lock())?;
+        }
+    }
+    Ok(())
+}
+
+// the entries are written in the given order followed by the manifest with their digests
+fn write_files(files: Vec<(String, Vec<u8>)>, writer: impl Write) -> Result<String, Error> {
+    let mut writer = DigestWriter {
+        inner: writer,
+        hasher: Sha256::new(),
+    };
+    let mut builder = tar::Builder::new(GzEncoder::new(&mut writer, Compression::default()));
+    let mut manifest = String::new();
+    for (path, contents) in files {
+        manifest.push_str(&format!("{}  {}\n", digest(&contents), path));
+        append(&mut builder, &path, &contents)?;
+    }
+    append(&mut builder, BUNDLE_MANIFEST, manifest.as_bytes())?;
+
+    let encoder = builder.into_inner().map_err(archive_write_err)?;
+    encoder.finish().map_err(archive_write_err)?;
+    Ok(format!("{:x}", writer.hasher.finalize()))
+}
+
+fn archive_write_err(err: std::io::Error) -> Error {
+    Error::new(Kind::InvalidArchive(err), None)
+}
+
+// entries are appended with fixed metadata, so bundling the same migrations twice produces the same archive
+fn append(
+    builder: &mut tar::Builder<impl Write>,
+    path: &str,
+    contents: &[u8],
+) -> Result<(), Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, path, contents)
+        .map_err(archive_write_err)
+}
+
+struct DigestWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_archive_migrations, write_bundle, write_migrations_bundle, BUNDLE_MANIFEST};
+    use crate::error::Kind;
+    use crate::{Dialect, Migration, Runner};
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use sha2::{Digest, Sha256};
+    use std::fs;
+    use std::io::{Cursor, Read, Write};
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    const FILES: [(&str, &str); 4] = [
+        (
+            "migrations/V1__initial.sql",
+            "CREATE TABLE persons (id int);",
+        ),
+        (
+            "migrations/postgres/V2__add_email.sql",
+            "ALTER TABLE persons ADD email text;",
+        ),
+        ("migrations/README.md", "not a migration"),
+        ("migrations/draft.sql", "SELECT 1;"),
+    ];
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer
+                .start_file(*path, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn assert_migrations(archive: Vec<u8>) {
+        let runner = Runner::from_archive(archive.as_slice()).unwrap();
+        let migrations = runner.get_migrations();
+        assert_eq!(2, migrations.len());
+        assert_eq!("initial", migrations[0].name());
+        assert_eq!(None, migrations[0].dialect());
+        assert_eq!("add_email", migrations[1].name());
+        assert_eq!(Some(Dialect::Postgres), migrations[1].dialect());
+        assert_eq!(
+            Some("ALTER TABLE persons ADD email text;"),
+            migrations[1].sql()
+        );
+    }
+
+    #[test]
+    fn loads_migrations_from_tarball() {
+        assert_migrations(tarball(&FILES));
+    }
+
+    #[test]
+    fn loads_migrations_from_gzipped_tarball() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tarball(&FILES)).unwrap();
+        assert_migrations(encoder.finish().unwrap());
+    }
+
+    #[test]
+    fn loads_migrations_from_zip() {
+        assert_migrations(zip(&FILES));
+    }
+
+    #[test]
+    fn fails_on_conflicting_archive_migrations() {
+        let err = load_archive_migrations(
+            zip(&[
+                ("a/V1__initial.sql", "SELECT 1;"),
+                ("b/V1__other.sql", "SELECT 2;"),
+            ])
+            .as_slice(),
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind(), Kind::ConflictingMigrations(_, _)));
+    }
+
+    #[test]
+    fn fails_on_invalid_archive() {
+        let err = load_archive_migrations(&b"PK\x03\x04 not a zip"[..]).unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidArchive(_)));
+    }
+
+    fn bundle() -> (Vec<u8>, String) {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(tmp_dir.path().join("postgres")).unwrap();
+        for (path, contents) in &FILES {
+            let path = path.trim_start_matches("migrations/");
+            fs::write(tmp_dir.path().join(path), contents).unwrap();
+        }
+        let mut bundle = Vec::new();
+        let digest = write_bundle(tmp_dir.path(), &mut bundle).unwrap();
+        (bundle, digest)
+    }
+
+    #[test]
+    fn writes_bundle_with_manifest() {
+        let (bundle, digest) = bundle();
+        assert_eq!(format!("{:x}", Sha256::digest(&bundle)), digest);
+        assert_eq!(bundle, self::bundle().0);
+
+        let mut archive = tar::Archive::new(GzDecoder::new(bundle.as_slice()));
+        let mut entries = Vec::new();
+        let mut manifest = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_str().unwrap().to_string();
+            if path == BUNDLE_MANIFEST {
+                entry.read_to_string(&mut manifest).unwrap();
+            }
+            entries.push(path);
+        }
+        assert_eq!(
+            vec![
+                "V1__initial.sql",
+                "postgres/V2__add_email.sql",
+                BUNDLE_MANIFEST
+            ],
+            entries
+        );
+        assert_eq!(2, manifest.lines().count());
+        assert!(manifest.ends_with("  postgres/V2__add_email.sql\n"));
+
+        assert_migrations(bundle);
+    }
+
+    #[test]
+    fn fails_on_migrations_not_matching_the_manifest() {
+        let manifest = format!(
+            "{:x}  V1__initial.sql\n",
+            Sha256::digest("CREATE TABLE persons (id int);")
+        );
+        let archive = |files: &[(&str, &str)]| {
+            let mut files = files.to_vec();
+            files.push((BUNDLE_MANIFEST, &manifest));
+            load_archive_migrations(tarball(&files).as_slice())
+        };
+
+        assert!(archive(&[("V1__initial.sql", "CREATE TABLE persons (id int);")]).is_ok());
+        for files in [
+            &[("V1__initial.sql", "CREATE TABLE persons (id bigint);")][..],
+            &[
+                ("V1__initial.sql", "CREATE TABLE persons (id int);"),
+                ("V2__add_email.sql", "ALTER TABLE persons ADD email text;"),
+            ],
+            &[],
+        ] {
+            let err = archive(files).unwrap_err();
+            assert!(matches!(err.kind(), Kind::InvalidArchive(_)));
+        }
+    }
+
+    fn generate_sql(dialect: Dialect) -> String {
+        match dialect {
+            Dialect::Postgres => "ALTER TABLE persons ADD email text;".to_string(),
+            _ => "ALTER TABLE persons ADD email varchar(255);".to_string(),
+        }
+    }
+
+    #[test]
+    fn writes_bundle_of_rust_migrations() {
+        let migrations = vec![
+            Migration::unapplied_for_dialect("V2__add_email", generate_sql).unwrap(),
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied("V1__initial.sqlite", "CREATE TABLE persons (id integer);")
+                .unwrap(),
+        ];
+        let mut bundle = Vec::new();
+        write_migrations_bundle(&migrations, Some(Dialect::Postgres), &mut bundle).unwrap();
+
+        let loaded = load_archive_migrations(bundle.as_slice()).unwrap();
+        let names: Vec<String> = loaded.iter().map(ToString::to_string).collect();
+        assert_eq!(vec!["V1__initial", "V1__initial", "V2__add_email"], names);
+        assert_eq!(Some(Dialect::Sqlite), loaded[1].dialect());
+        assert_eq!(migrations[1].checksum(), loaded[0].checksum());
+        let generated = migrations[0].for_dialect(Some(Dialect::Postgres)).unwrap();
+        assert_eq!(generated.checksum(), loaded[2].checksum());
+        assert_eq!(Some("ALTER TABLE persons ADD email text;"), loaded[2].sql());
+
+        let err = write_migrations_bundle(&migrations, None, Vec::new()).unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownDialect(_)));
+    }
+}
@@ -0,0 +1,179 @@
+use sha2::{Digest, Sha256};
+use siphasher::sip::SipHasher13;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// The algorithm the checksums of the migrations are formed with, see [`Runner::set_checksum_algorithm`].
+/// Checksums are recorded on the schema history table prefixed by their algorithm, i.e. `sha256:3f0a...`,
+/// except the legacy ones, so migrations applied with another algorithm are still verified with theirs.
+///
+/// [`Runner::set_checksum_algorithm`]: crate::Runner::set_checksum_algorithm
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChecksumAlgorithm {
+    /// SipHash 1-3, the checksum of the previous refinery versions and the default one. It hashes the version
+    /// with the endianness of the platform, so it isn't stable across platforms
+    #[default]
+    Sip13Legacy,
+    /// The first 8 bytes of the SHA-256 digest
+    Sha256,
+    /// CRC-32
+    Crc32,
+}
+
+impl ChecksumAlgorithm {
+    // the prefix of the checksums recorded with the algorithm, legacy ones have none
+    fn prefix(&self) -> Option<&'static str> {
+        match self {
+            ChecksumAlgorithm::Sip13Legacy => None,
+            ChecksumAlgorithm::Sha256 => Some("sha256"),
+            ChecksumAlgorithm::Crc32 => Some("crc32"),
+        }
+    }
+
+    /// Parses a checksum recorded on the schema history table, either prefixed by its algorithm or a legacy one,
+    /// `None` if it's invalid
+    pub fn parse_checksum(checksum: &str) -> Option<(ChecksumAlgorithm, u64)> {
+        match checksum.split_once(':') {
+            Some((prefix, checksum)) => {
+                let algorithm = [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Crc32]
+                    .into_iter()
+                    .find(|algorithm| algorithm.prefix() == Some(prefix))?;
+                let checksum = u64::from_str_radix(checksum, 16).ok()?;
+                Some((algorithm, checksum))
+            }
+            None => Some((ChecksumAlgorithm::Sip13Legacy, checksum.parse().ok()?)),
+        }
+    }
+
+    // the checksum as it's recorded on the schema history table
+    pub(crate) fn format_checksum(&self, checksum: u64) -> String {
+        match self.prefix() {
+            Some(prefix) => format!("{}:{:016x}", prefix, checksum),
+            None => checksum.to_string(),
+        }
+    }
+
+    // forms the checksum from the name, version and sql of a migration
+    fn checksum(&self, name: &str, version: i32, sql: &str) -> u64 {
+        match self {
+            // Previously, `std::collections::hash_map::DefaultHasher` was used
+            // to calculate the checksum and the implementation at that time
+            // was SipHasher13. However, that implementation is not guaranteed:
+            // > The internal algorithm is not specified, and so it and its
+            // > hashes should not be relied upon over releases.
+            // We now explicitly use SipHasher13 to both remain compatible with
+            // existing migrations and prevent breaking from possible future
+            // changes to `DefaultHasher`.
+            ChecksumAlgorithm::Sip13Legacy => {
+                let mut hasher = SipHasher13::new();
+                name.hash(&mut hasher);
+                version.hash(&mut hasher);
+                sql.hash(&mut hasher);
+                hasher.finish()
+            }
+            ChecksumAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                hasher.update((name.len() as u64).to_be_bytes());
+                hasher.update(name.as_bytes());
+                hasher.update(version.to_be_bytes());
+                hasher.update(sql.as_bytes());
+                let digest = hasher.finalize();
+                u64::from_be_bytes(digest[..8].try_into().expect("digest must have 8 bytes"))
+            }
+            ChecksumAlgorithm::Crc32 => {
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(&(name.len() as u64).to_be_bytes());
+                hasher.update(name.as_bytes());
+                hasher.update(&version.to_be_bytes());
+                hasher.update(sql.as_bytes());
+                hasher.finalize() as u64
+            }
+        }
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self {
+            ChecksumAlgorithm::Sip13Legacy => "sip13",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Crc32 => "crc32",
+        };
+        fmt.write_str(algorithm)
+    }
+}
+
+// the checksums of an unapplied migration with every algorithm, so the ones applied with another algorithm
+// than the one it's set to can still be verified
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Checksums {
+    sip13: u64,
+    sha256: u64,
+    crc32: u64,
+}
+
+// the checksums with every algorithm in the order of the variants of ChecksumAlgorithm,
+// see `Migration::unapplied_without_down`
+impl From<[u64; 3]> for Checksums {
+    fn from([sip13, sha256, crc32]: [u64; 3]) -> Checksums {
+        Checksums {
+            sip13,
+            sha256,
+            crc32,
+        }
+    }
+}
+
+impl Checksums {
+    pub(crate) fn new(name: &str, version: i32, sql: &str) -> Checksums {
+        Checksums {
+            sip13: ChecksumAlgorithm::Sip13Legacy.checksum(name, version, sql),
+            sha256: ChecksumAlgorithm::Sha256.checksum(name, version, sql),
+            crc32: ChecksumAlgorithm::Crc32.checksum(name, version, sql),
+        }
+    }
+
+    pub(crate) fn get(&self, algorithm: ChecksumAlgorithm) -> u64 {
+        match algorithm {
+            ChecksumAlgorithm::Sip13Legacy => self.sip13,
+            ChecksumAlgorithm::Sha256 => self.sha256,
+            ChecksumAlgorithm::Crc32 => self.crc32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChecksumAlgorithm, Checksums};
+
+    #[test]
+    fn parses_recorded_checksums() {
+        for algorithm in [
+            ChecksumAlgorithm::Sip13Legacy,
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Crc32,
+        ] {
+            let checksum = Checksums::new("initial", 1, "CREATE TABLE t(id int);").get(algorithm);
+            let recorded = algorithm.format_checksum(checksum);
+            assert_eq!(
+                Some((algorithm, checksum)),
+                ChecksumAlgorithm::parse_checksum(&recorded)
+            );
+        }
+        assert_eq!(
+            Some((ChecksumAlgorithm::Sha256, 0x3f)),
+            ChecksumAlgorithm::parse_checksum("sha256:000000000000003f")
+        );
+        assert_eq!(None, ChecksumAlgorithm::parse_checksum("md5:3f"));
+        assert_eq!(None, ChecksumAlgorithm::parse_checksum("not a checksum"));
+    }
+
+    #[test]
+    fn stable_checksums() {
+        // the checksums must never change, or the applied migrations would diverge
+        let checksums = Checksums::new("initial", 1, "CREATE TABLE t(id int);");
+        assert_eq!(0x48fe53de63305fbf, checksums.get(ChecksumAlgorithm::Sha256));
+        assert_eq!(0x6395ce30, checksums.get(ChecksumAlgorithm::Crc32));
+    }
+}
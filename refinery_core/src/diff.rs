@@ -0,0 +1,377 @@
+//! Generates draft migrations from the difference between the schema of a live Postgres database and a target one,
+//! either another database or a declarative `schema.sql`.
+//!
+//! The generated sql is meant to be reviewed before being applied, i.e. renames are generated as a drop and an add,
+//! and sequences, views and functions are not compared.
+use crate::config::{build_db_url, Config, ConfigDbType};
+use crate::drivers::postgres::snapshot_schema;
+use crate::error::{Kind, WrapMigrationError};
+use crate::introspect::{Column, Schema};
+use crate::scaffold::next_version;
+use crate::traits::DEFAULT_MIGRATION_TABLE_NAME;
+use crate::Error;
+use postgres::{Client, NoTls};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// the schema a `schema.sql` is applied to, on a transaction that is rolled back
+const SCRATCH_SCHEMA: &str = "refinery_diff";
+
+/// The target schema a live database is compared to
+pub enum DiffTarget<'a> {
+    /// Another database with the target schema, i.e. a staging one
+    Database(&'a mut Client),
+    /// A declarative schema, which is applied to a scratch schema of the live database on a transaction that is rolled back
+    SchemaSql(&'a str),
+}
+
+/// Struct that compares the schema of a live database with a target one
+pub struct Diff {
+    schema: String,
+    migration_table_name: String,
+}
+
+impl Default for Diff {
+    fn default() -> Self {
+        Diff::new()
+    }
+}
+
+impl Diff {
+    /// instantiate a new Diff, comparing the `public` schema
+    pub fn new() -> Diff {
+        Diff {
+            schema: "public".into(),
+            migration_table_name: DEFAULT_MIGRATION_TABLE_NAME.into(),
+        }
+    }
+
+    /// Set the Postgres schema that is compared, by default this is set to `public`
+    pub fn set_schema(self, schema: &str) -> Diff {
+        Diff {
+            schema: schema.into(),
+            ..self
+        }
+    }
+
+    /// Set the name of the migrations table, which is excluded from the comparison.
+    /// The default name is `refinery_schema_history`
+    pub fn set_migration_table_name(self, migration_table_name: &str) -> Diff {
+        Diff {
+            migration_table_name: migration_table_name.into(),
+            ..self
+        }
+    }
+
+    fn snapshot(&self, client: &mut Client, schema_sql: Option<&str>) -> Result<Schema, Error> {
+        let mut transaction = client
+            .transaction()
+            .migration_err("error starting transaction", None)?;
+        let schema = match schema_sql {
+            Some(schema_sql) => {
+                transaction
+                    .batch_execute(&format!(
+                        "CREATE SCHEMA {0}; SET LOCAL search_path TO {0};",
+                        SCRATCH_SCHEMA
+                    ))
+                    .migration_err("error creating scratch schema", None)?;
+                transaction
+                    .batch_execute(schema_sql)
+                    .migration_err("error applying schema sql", None)?;
+                SCRATCH_SCHEMA
+            }
+            None => self.schema.as_str(),
+        };
+
+        let mut snapshot = snapshot_schema(&mut transaction, schema)
+            .migration_err("error introspecting schema", None)?;
+        snapshot.tables.remove(&self.migration_table_name);
+        transaction
+            .rollback()
+            .migration_err("error rolling back transaction", None)?;
+        Ok(snapshot)
+    }
+
+    /// Get the sql that migrates the schema of the live database to the target one,
+    /// `None` if they don't differ
+    pub fn sql(&self, live: &mut Client, target: DiffTarget) -> Result<Option<String>, Error> {
+        let from = self.snapshot(live, None)?;
+        let to = match target {
+            DiffTarget::Database(client) => self.snapshot(client, None)?,
+            DiffTarget::SchemaSql(schema_sql) => self.snapshot(live, Some(schema_sql))?,
+        };
+
+        let statements = diff_schemas(&from, &to);
+        if statements.is_empty() {
+            return Ok(None);
+        }
+        let mut sql = String::from("-- generated by refinery, review before applying\n");
+        for statement in statements {
+            sql.push_str(&statement);
+            sql.push_str(";\n");
+        }
+        Ok(Some(sql))
+    }
+
+    /// Create a draft `V{n}__auto.sql` migration named after the next version available on the given location,
+    /// with the sql that migrates the schema of the live database to the target one.
+    /// Returns the path of the created file, `None` if the schemas don't differ
+    pub fn generate(
+        &self,
+        location: impl AsRef<Path>,
+        live: &mut Client,
+        target: DiffTarget,
+    ) -> Result<Option<PathBuf>, Error> {
+        let sql = match self.sql(live, target)? {
+            Some(sql) => sql,
+            None => return Ok(None),
+        };
+
+        let location = location.as_ref();
+        let version = next_version(location)?;
+        fs::create_dir_all(location).map_err(|err| {
+            Error::new(
+                Kind::InvalidMigrationPath(location.to_path_buf(), err),
+                None,
+            )
+        })?;
+
+        let path = location.join(format!("V{}__auto.sql", version));
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(sql.as_bytes()))
+            .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.clone(), err), None))?;
+
+        Ok(Some(path))
+    }
+}
+
+/// Connect to the Postgres database of the given [`Config`]
+pub fn connect(config: &Config) -> Result<Client, Error> {
+    if config.db_type() != ConfigDbType::Postgres {
+        return Err(Error::new(
+            Kind::ConfigError("schema diffs are only supported for postgres".into()),
+            None,
+        ));
+    }
+    Client::connect(build_db_url("postgresql", config).as_str(), NoTls)
+        .migration_err("could not connect to database", None)
+}
+
+// quote identifiers that wouldn't be parsed as is
+fn ident(name: &str) -> String {
+    let is_plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if is_plain {
+        name.into()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn column_definition(column: &Column) -> String {
+    let mut definition = format!("{} {}", ident(&column.name), column.data_type);
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        definition.push_str(" DEFAULT ");
+        definition.push_str(default);
+    }
+    definition
+}
+
+// generates the statements that migrate the `from` schema to the `to` one, constraints and indexes
+// are dropped first and created last so they don't depend on the order the tables are changed
+pub(crate) fn diff_schemas(from: &Schema, to: &Schema) -> Vec<String> {
+    let mut drops = Vec::new();
+    let mut changes = Vec::new();
+    let mut creates = Vec::new();
+
+    for (name, table) in &from.tables {
+        let table_ident = ident(name);
+        let Some(target) = to.tables.get(name) else {
+            changes.push(format!("DROP TABLE {}", table_ident));
+            continue;
+        };
+
+        for (constraint, definition) in &table.constraints {
+            if target.constraints.get(constraint) != Some(definition) {
+                drops.push(format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {}",
+                    table_ident,
+                    ident(constraint)
+                ));
+            }
+        }
+        for (index, definition) in &table.indexes {
+            if target.indexes.get(index) != Some(definition) {
+                drops.push(format!("DROP INDEX {}", ident(index)));
+            }
+        }
+
+        for column in &table.columns {
+            let column_ident = ident(&column.name);
+            let Some(target_column) = target.column(&column.name) else {
+                changes.push(format!(
+                    "ALTER TABLE {} DROP COLUMN {}",
+                    table_ident, column_ident
+                ));
+                continue;
+            };
+
+            let alter_column = format!("ALTER TABLE {} ALTER COLUMN {}", table_ident, column_ident);
+            if column.data_type != target_column.data_type {
+                changes.push(format!("{} TYPE {}", alter_column, target_column.data_type));
+            }
+            if column.nullable != target_column.nullable {
+                let nullability = if target_column.nullable {
+                    "DROP NOT NULL"
+                } else {
+                    "SET NOT NULL"
+                };
+                changes.push(format!("{} {}", alter_column, nullability));
+            }
+            if column.default != target_column.default {
+                match &target_column.default {
+                    Some(default) => {
+                        changes.push(format!("{} SET DEFAULT {}", alter_column, default))
+                    }
+                    None => changes.push(format!("{} DROP DEFAULT", alter_column)),
+                }
+            }
+        }
+        for column in &target.columns {
+            if table.column(&column.name).is_none() {
+                changes.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    table_ident,
+                    column_definition(column)
+                ));
+            }
+        }
+    }
+
+    for (name, target) in &to.tables {
+        let table_ident = ident(name);
+        let table = from.tables.get(name);
+        if table.is_none() {
+            let columns: Vec<String> = target
+                .columns
+                .iter()
+                .map(|column| format!("    {}", column_definition(column)))
+                .collect();
+            changes.push(format!(
+                "CREATE TABLE {} (\n{}\n)",
+                table_ident,
+                columns.join(",\n")
+            ));
+        }
+
+        for (constraint, definition) in &target.constraints {
+            if table.and_then(|table| table.constraints.get(constraint)) != Some(definition) {
+                creates.push(format!(
+                    "ALTER TABLE {} ADD CONSTRAINT {} {}",
+                    table_ident,
+                    ident(constraint),
+                    definition
+                ));
+            }
+        }
+        for (index, definition) in &target.indexes {
+            if table.and_then(|table| table.indexes.get(index)) != Some(definition) {
+                creates.push(definition.clone());
+            }
+        }
+    }
+
+    drops.into_iter().chain(changes).chain(creates).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_schemas;
+    use crate::introspect::{Column, Schema, Table};
+
+    fn column(name: &str, data_type: &str, nullable: bool, default: Option<&str>) -> Column {
+        Column {
+            name: name.into(),
+            data_type: data_type.into(),
+            nullable,
+            default: default.map(Into::into),
+        }
+    }
+
+    fn users() -> Table {
+        Table {
+            columns: vec![
+                column("id", "integer", false, None),
+                column("name", "character varying(255)", true, None),
+            ],
+            indexes: Default::default(),
+            constraints: [("users_pkey".to_string(), "PRIMARY KEY (id)".to_string())].into(),
+        }
+    }
+
+    #[test]
+    fn diffs_equal_schemas() {
+        let schema = Schema {
+            tables: [("users".to_string(), users())].into(),
+        };
+        assert!(diff_schemas(&schema, &schema).is_empty());
+    }
+
+    #[test]
+    fn diffs_created_and_dropped_tables() {
+        let from = Schema {
+            tables: [("cars".to_string(), Table::default())].into(),
+        };
+        let to = Schema {
+            tables: [("users".to_string(), users())].into(),
+        };
+
+        assert_eq!(
+            vec![
+                "DROP TABLE cars",
+                "CREATE TABLE users (\n    id integer NOT NULL,\n    name character varying(255)\n)",
+                "ALTER TABLE users ADD CONSTRAINT users_pkey PRIMARY KEY (id)",
+            ],
+            diff_schemas(&from, &to)
+        );
+    }
+
+    #[test]
+    fn diffs_altered_tables() {
+        let from = Schema {
+            tables: [("users".to_string(), users())].into(),
+        };
+        let mut target = users();
+        target.columns = vec![
+            column("id", "bigint", false, None),
+            column("Email", "text", false, Some("''::text")),
+        ];
+        target.indexes = [(
+            "users_email".to_string(),
+            "CREATE INDEX users_email ON users USING btree (\"Email\")".to_string(),
+        )]
+        .into();
+        let to = Schema {
+            tables: [("users".to_string(), target)].into(),
+        };
+
+        assert_eq!(
+            vec![
+                "ALTER TABLE users ALTER COLUMN id TYPE bigint",
+                "ALTER TABLE users DROP COLUMN name",
+                "ALTER TABLE users ADD COLUMN \"Email\" text NOT NULL DEFAULT ''::text",
+                "CREATE INDEX users_email ON users USING btree (\"Email\")",
+            ],
+            diff_schemas(&from, &to)
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Error, IntegrityIssue, Migration};
+
+use async_trait::async_trait;
+use bb8::{Pool, RunError};
+use bb8_postgres::PostgresConnectionManager;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{Client, Error as PgError, Socket};
+
+// a connection is checked out of the pool for each of the calls of the traits, with any of the TLS connectors
+// the pool can be built with
+#[async_trait]
+impl<Tls> AsyncTransaction for Pool<PostgresConnectionManager<Tls>>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    <Tls as MakeTlsConnect<Socket>>::Stream: Send + Sync,
+    <Tls as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <<Tls as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    type Error = RunError<PgError>;
+
+    fn is_serialization_failure(error: &RunError<PgError>) -> bool {
+        matches!(error, RunError::User(error) if <Client as AsyncTransaction>::is_serialization_failure(error))
+    }
+
+    fn is_timeout(error: &RunError<PgError>) -> bool {
+        matches!(error, RunError::User(error) if <Client as AsyncTransaction>::is_timeout(error))
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut conn = self.get().await?;
+        AsyncTransaction::execute(&mut *conn, queries)
+            .await
+            .map_err(RunError::User)
+    }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut conn = self.get().await?;
+        AsyncTransaction::execute_without_transaction(&mut *conn, queries)
+            .await
+            .map_err(RunError::User)
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl<Tls> AsyncQuery<Vec<Migration>> for Pool<PostgresConnectionManager<Tls>>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    <Tls as MakeTlsConnect<Socket>>::Stream: Send + Sync,
+    <Tls as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <<Tls as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let mut conn = self.get().await?;
+        AsyncQuery::query(&mut *conn, query)
+            .await
+            .map_err(RunError::User)
+    }
+}
+
+#[async_trait]
+impl<Tls> AsyncMigrate for Pool<PostgresConnectionManager<Tls>>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    <Tls as MakeTlsConnect<Socket>>::Stream: Send + Sync,
+    <Tls as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <<Tls as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let mut conn = self
+            .get()
+            .await
+            .migration_err("could not get a connection from the pool", None)?;
+        AsyncMigrate::check_integrity(&mut *conn).await
+    }
+}
@@ -0,0 +1,64 @@
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Migration, Splitter};
+
+use async_trait::async_trait;
+use bb8::{Pool, RunError};
+use bb8_tiberius::{ConnectionManager, Error};
+
+// a connection is checked out of the pool for each of the calls of the traits. The pool is Send and Sync,
+// and the futures of the calls are Send as the connections are only held across awaits by them
+#[async_trait]
+impl AsyncTransaction for Pool<ConnectionManager> {
+    type Error = RunError<Error>;
+
+    fn is_timeout(error: &RunError<Error>) -> bool {
+        matches!(error, RunError::User(Error::Tiberius(error)) if super::tiberius::is_timeout(error))
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut conn = self.get().await?;
+        AsyncTransaction::execute(&mut *conn, queries)
+            .await
+            .map_err(|err| RunError::User(err.into()))
+    }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut conn = self.get().await?;
+        AsyncTransaction::execute_without_transaction(&mut *conn, queries)
+            .await
+            .map_err(|err| RunError::User(err.into()))
+    }
+
+    fn splitter(&self) -> Splitter {
+        Splitter::Separator("GO".into())
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for Pool<ConnectionManager> {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let mut conn = self.get().await?;
+        AsyncQuery::query(&mut *conn, query)
+            .await
+            .map_err(|err| RunError::User(err.into()))
+    }
+}
+
+impl AsyncMigrate for Pool<ConnectionManager> {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mssql)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Migration};
+use async_trait::async_trait;
+use clickhouse::error::Error as ChError;
+use clickhouse::{Client, Row};
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+#[derive(Row, Deserialize)]
+struct SchemaHistoryRow {
+    version: i32,
+    name: String,
+    applied_on: String,
+    checksum: String,
+}
+
+// `?` are the placeholders of the values bound to clickhouse queries, the ones of the sql are escaped as `??`,
+// and the format clickhouse returns the rows in is appended after a trailing semicolon
+fn sql(query: &str) -> String {
+    query.trim_end().trim_end_matches(';').replace('?', "??")
+}
+
+// clickhouse has no transactions, each query is executed on its own, a migration failing midway is left
+// partially applied and isn't recorded on the schema history table
+#[async_trait]
+impl AsyncTransaction for Client {
+    type Error = ChError;
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            Client::query(self, &sql(query)).execute().await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for Client {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let rows = Client::query(self, &sql(query))
+            .fetch_all::<SchemaHistoryRow>()
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                // Safe to call unwrap, as we stored it in RFC3339 format on the database
+                let applied_on = OffsetDateTime::parse(&row.applied_on, &Rfc3339).unwrap();
+                Migration::applied_with_checksum(row.version, row.name, applied_on, &row.checksum)
+            })
+            .collect())
+    }
+}
+
+impl AsyncMigrate for Client {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::ClickHouse)
+    }
+}
@@ -5,12 +5,71 @@
     feature = "mysql_async"
 ))]
 use crate::config::build_db_url;
-use crate::config::{Config, ConfigDbType};
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "tokio-postgres",
+    feature = "mysql_async"
+))]
+use crate::config::build_server_url;
+use crate::config::Config;
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "tokio-postgres",
+    feature = "mysql_async",
+    feature = "tiberius-config",
+    feature = "libsql",
+    feature = "duckdb",
+    feature = "oracle"
+))]
+use crate::config::ConfigDbType;
+#[cfg(feature = "mysql_async")]
+use crate::drivers::mysql_tls::mysql_async_opts;
+#[cfg(feature = "mysql")]
+use crate::drivers::mysql_tls::mysql_opts;
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+use crate::drivers::postgres_tls::make_tls_connector;
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "tokio-postgres",
+    feature = "mysql_async",
+    feature = "tiberius-config",
+    feature = "libsql",
+    feature = "duckdb",
+    feature = "oracle"
+))]
 use crate::error::WrapMigrationError;
 use crate::traits::r#async::{AsyncQuery, AsyncTransaction};
 use crate::traits::sync::{Query, Transaction};
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "tokio-postgres",
+    feature = "mysql_async",
+    feature = "tiberius-config",
+    feature = "libsql",
+    feature = "duckdb",
+    feature = "oracle"
+))]
 use crate::traits::{GET_APPLIED_MIGRATIONS_QUERY, GET_LAST_APPLIED_MIGRATION_QUERY};
-use crate::{Error, Migration, Report, Target};
+use crate::Migration;
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "tokio-postgres",
+    feature = "mysql_async",
+    feature = "tiberius-config",
+    feature = "libsql",
+    feature = "duckdb",
+    feature = "oracle"
+))]
+use crate::{Error, Report, Target};
 use async_trait::async_trait;
 use std::convert::Infallible;
 
@@ -54,7 +113,13 @@ impl AsyncQuery<Vec<Migration>> for Config {
     }
 }
 // this is written as macro so that we don't have to deal with type signatures
-#[cfg(any(feature = "mysql", feature = "postgres", feature = "rusqlite"))]
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "duckdb",
+    feature = "oracle"
+))]
 #[allow(clippy::redundant_closure_call)]
 macro_rules! with_connection {
     ($config:ident, $op: expr) => {
@@ -63,8 +128,11 @@ macro_rules! with_connection {
             ConfigDbType::Mysql => {
                 cfg_if::cfg_if! {
                     if #[cfg(feature = "mysql")] {
+                        if $config.create_db_if_missing() {
+                            create_mysql_db(&$config)?;
+                        }
                         let url = build_db_url("mysql", &$config);
-                        let opts = mysql::Opts::from_url(&url).migration_err("could not parse url", None)?;
+                        let opts = mysql_opts(&url, &$config)?;
                         let conn = mysql::Conn::new(opts).migration_err("could not connect to database", None)?;
                         $op(conn)
                     } else {
@@ -77,18 +145,25 @@ macro_rules! with_connection {
                     if #[cfg(feature = "rusqlite")] {
                         //may have been checked earlier on config parsing, even if not let it fail with a Rusqlite db file not found error
                         let path = $config.db_path().map(|p| p.to_path_buf()).unwrap_or_default();
-                        let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE).migration_err("could not open database", None)?;
+                        if $config.create_db_if_missing() {
+                            create_sqlite_db(&path)?;
+                        }
+                        let mut conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE).migration_err("could not open database", None)?;
+                        crate::Migrate::setup_sqlite(&mut conn, $config.sqlite_pragmas(), $config.sqlite_extensions())?;
                         $op(conn)
                     } else {
                         panic!("tried to migrate from config for a sqlite database, but feature rusqlite not enabled!");
                     }
                 }
             }
-            ConfigDbType::Postgres => {
+            ConfigDbType::Postgres | ConfigDbType::CockroachDb => {
                 cfg_if::cfg_if! {
                     if #[cfg(feature = "postgres")] {
+                        if $config.create_db_if_missing() {
+                            create_postgres_db(&$config)?;
+                        }
                         let path = build_db_url("postgresql", &$config);
-                        let conn = postgres::Client::connect(path.as_str(), postgres::NoTls).migration_err("could not connect to database", None)?;
+                        let conn = postgres::Client::connect(path.as_str(), make_tls_connector(&$config)?).migration_err("could not connect to database", None)?;
                         $op(conn)
                     } else {
                         panic!("tried to migrate from config for a postgresql database, but feature postgres not enabled!");
@@ -98,6 +173,33 @@ macro_rules! with_connection {
             ConfigDbType::Mssql => {
                 panic!("tried to synchronously migrate from config for a mssql database, but tiberius is an async driver");
             }
+            ConfigDbType::Turso => {
+                panic!("tried to synchronously migrate from config for a turso database, but libsql is an async driver");
+            }
+            ConfigDbType::DuckDb => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "duckdb")] {
+                        let path = $config.db_path().map(|p| p.to_path_buf()).unwrap_or_default();
+                        let conn = open_duckdb_db(&path, $config.create_db_if_missing())?;
+                        $op(conn)
+                    } else {
+                        panic!("tried to migrate from config for a duckdb database, but feature duckdb not enabled!");
+                    }
+                }
+            }
+            ConfigDbType::Oracle => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "oracle")] {
+                        use crate::config::{build_oracle_connect_string, db_credentials};
+
+                        let (user, pass) = db_credentials(&$config);
+                        let conn = oracle::Connection::connect(user, pass, build_oracle_connect_string(&$config)).migration_err("could not connect to database", None)?;
+                        $op(conn)
+                    } else {
+                        panic!("tried to migrate from config for an oracle database, but feature oracle not enabled!");
+                    }
+                }
+            }
         }
     }
 }
@@ -105,7 +207,8 @@ macro_rules! with_connection {
 #[cfg(any(
     feature = "tokio-postgres",
     feature = "mysql_async",
-    feature = "tiberius-config"
+    feature = "tiberius-config",
+    feature = "libsql"
 ))]
 macro_rules! with_connection_async {
     ($config: ident, $op: expr) => {
@@ -114,8 +217,11 @@ macro_rules! with_connection_async {
             ConfigDbType::Mysql => {
                 cfg_if::cfg_if! {
                     if #[cfg(feature = "mysql_async")] {
+                        if $config.create_db_if_missing() {
+                            create_mysql_db_async($config).await?;
+                        }
                         let url = build_db_url("mysql", $config);
-                        let pool = mysql_async::Pool::from_url(&url).migration_err("could not connect to the database", None)?;
+                        let pool = mysql_async::Pool::new(mysql_async_opts(&url, $config)?);
                         $op(pool).await
                     } else {
                         panic!("tried to migrate async from config for a mysql database, but feature mysql_async not enabled!");
@@ -125,11 +231,20 @@ macro_rules! with_connection_async {
             ConfigDbType::Sqlite => {
                 panic!("tried to migrate async from config for a sqlite database, but this feature is not implemented yet");
             }
-            ConfigDbType::Postgres => {
+            ConfigDbType::DuckDb => {
+                panic!("tried to migrate async from config for a duckdb database, but duckdb is a sync driver");
+            }
+            ConfigDbType::Oracle => {
+                panic!("tried to migrate async from config for an oracle database, but oracle is a sync driver");
+            }
+            ConfigDbType::Postgres | ConfigDbType::CockroachDb => {
                 cfg_if::cfg_if! {
                     if #[cfg(feature = "tokio-postgres")] {
+                        if $config.create_db_if_missing() {
+                            create_postgres_db_async($config).await?;
+                        }
                         let path = build_db_url("postgresql", $config);
-                        let (client, connection ) = tokio_postgres::connect(path.as_str(), tokio_postgres::NoTls).await.migration_err("could not connect to database", None)?;
+                        let (client, connection ) = tokio_postgres::connect(path.as_str(), make_tls_connector($config)?).await.migration_err("could not connect to database", None)?;
                         tokio::spawn(async move {
                             if let Err(e) = connection.await {
                                 eprintln!("connection error: {}", e);
@@ -149,6 +264,9 @@ macro_rules! with_connection_async {
                         use tokio_util::compat::TokioAsyncWriteCompatExt;
                         use std::convert::TryInto;
 
+                        if $config.create_db_if_missing() {
+                            create_mssql_db($config).await?;
+                        }
                         let config: Config = (&*$config).try_into()?;
                         let tcp = TcpStream::connect(config.get_addr())
                             .await
@@ -163,13 +281,324 @@ macro_rules! with_connection_async {
                     }
                 }
             }
+            ConfigDbType::Turso => {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "libsql")] {
+                        use crate::config::{build_libsql_url, db_auth_token};
+
+                        let database = libsql::Builder::new_remote(build_libsql_url($config), db_auth_token($config).to_string())
+                            .build()
+                            .await
+                            .migration_err("could not connect to database", None)?;
+                        let conn = database.connect().migration_err("could not connect to database", None)?;
+                        $op(conn).await
+                    } else {
+                        panic!("tried to migrate async from config for a turso database, but libsql feature was not enabled!");
+                    }
+                }
+            }
+        }
+    }
+}
+
+// an Error creating the database of the config, with its own kind if the user lacks the permission to create it
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "tokio-postgres",
+    feature = "mysql_async",
+    feature = "tiberius-config",
+    feature = "duckdb"
+))]
+fn create_db_error<E>(db_name: &str, err: E, denied: bool) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let kind = if denied {
+        crate::error::Kind::CreateDatabaseDenied(db_name.to_string(), Box::new(err))
+    } else {
+        crate::error::Kind::Connection(
+            format!("could not create database {}", db_name),
+            Box::new(err),
+        )
+    };
+    Error::new(kind, None)
+}
+
+#[cfg(any(feature = "mysql", feature = "mysql_async"))]
+fn create_mysql_db_query(db_name: &str) -> String {
+    format!(
+        "CREATE DATABASE IF NOT EXISTS `{}`",
+        db_name.replace('`', "``")
+    )
+}
+
+// ER_DBACCESS_DENIED_ERROR and ER_SPECIFIC_ACCESS_DENIED_ERROR
+#[cfg(any(feature = "mysql", feature = "mysql_async"))]
+const MYSQL_ACCESS_DENIED_CODES: [u16; 2] = [1044, 1227];
+
+// connects to the server without selecting a database to create the one of the config
+#[cfg(feature = "mysql")]
+fn create_mysql_db(config: &Config) -> Result<(), Error> {
+    use mysql::prelude::Queryable;
+
+    let Some(db_name) = config.db_name() else {
+        return Ok(());
+    };
+    let url = build_server_url("mysql", config, None);
+    let opts = mysql_opts(&url, config)?;
+    let mut conn = mysql::Conn::new(opts).migration_err("could not connect to the server", None)?;
+    conn.query_drop(create_mysql_db_query(db_name))
+        .map_err(|err| {
+            let denied = matches!(&err, mysql::Error::MySqlError(err) if MYSQL_ACCESS_DENIED_CODES.contains(&err.code));
+            create_db_error(db_name, err, denied)
+        })
+}
+
+#[cfg(feature = "mysql_async")]
+async fn create_mysql_db_async(config: &Config) -> Result<(), Error> {
+    use mysql_async::prelude::Queryable;
+
+    let Some(db_name) = config.db_name() else {
+        return Ok(());
+    };
+    let url = build_server_url("mysql", config, None);
+    let pool = mysql_async::Pool::new(mysql_async_opts(&url, config)?);
+    let mut conn = pool
+        .get_conn()
+        .await
+        .migration_err("could not connect to the server", None)?;
+    conn.query_drop(create_mysql_db_query(db_name))
+        .await
+        .map_err(|err| {
+            let denied = matches!(&err, mysql_async::Error::Server(err) if MYSQL_ACCESS_DENIED_CODES.contains(&err.code));
+            create_db_error(db_name, err, denied)
+        })?;
+    drop(conn);
+    pool.disconnect()
+        .await
+        .migration_err("could not disconnect from the server", None)
+}
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+const POSTGRES_DB_EXISTS_QUERY: &str = "SELECT 1 FROM pg_database WHERE datname = $1";
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+fn create_postgres_db_query(db_name: &str) -> String {
+    format!("CREATE DATABASE \"{}\"", db_name.replace('"', "\"\""))
+}
+
+// connects to the postgres database, or template1 if it isn't available, to create the one of the config
+#[cfg(feature = "postgres")]
+fn create_postgres_db(config: &Config) -> Result<(), Error> {
+    use postgres::{error::SqlState, Client};
+
+    let Some(db_name) = config.db_name() else {
+        return Ok(());
+    };
+    let mut client = match Client::connect(
+        &build_server_url("postgresql", config, Some("postgres")),
+        make_tls_connector(config)?,
+    ) {
+        Ok(client) => client,
+        Err(_) => Client::connect(
+            &build_server_url("postgresql", config, Some("template1")),
+            make_tls_connector(config)?,
+        )
+        .migration_err("could not connect to the server", None)?,
+    };
+    let exists = client
+        .query_opt(POSTGRES_DB_EXISTS_QUERY, &[&db_name])
+        .migration_err("could not query the databases", None)?
+        .is_some();
+    if !exists {
+        client
+            .batch_execute(&create_postgres_db_query(db_name))
+            .map_err(|err| {
+                let denied = err.code() == Some(&SqlState::INSUFFICIENT_PRIVILEGE);
+                create_db_error(db_name, err, denied)
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tokio-postgres")]
+async fn create_postgres_db_async(config: &Config) -> Result<(), Error> {
+    use tokio_postgres::error::SqlState;
+
+    let Some(db_name) = config.db_name() else {
+        return Ok(());
+    };
+    let connection = match tokio_postgres::connect(
+        &build_server_url("postgresql", config, Some("postgres")),
+        make_tls_connector(config)?,
+    )
+    .await
+    {
+        Ok(connection) => connection,
+        Err(_) => tokio_postgres::connect(
+            &build_server_url("postgresql", config, Some("template1")),
+            make_tls_connector(config)?,
+        )
+        .await
+        .migration_err("could not connect to the server", None)?,
+    };
+    let (client, connection) = connection;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
         }
+    });
+    let exists = client
+        .query_opt(POSTGRES_DB_EXISTS_QUERY, &[&db_name])
+        .await
+        .migration_err("could not query the databases", None)?
+        .is_some();
+    if !exists {
+        client
+            .batch_execute(&create_postgres_db_query(db_name))
+            .await
+            .map_err(|err| {
+                let denied = err.code() == Some(&SqlState::INSUFFICIENT_PRIVILEGE);
+                create_db_error(db_name, err, denied)
+            })?;
+    }
+    Ok(())
+}
+
+// creates the database file and its parent directories
+#[cfg(feature = "rusqlite")]
+fn create_sqlite_db(path: &std::path::Path) -> Result<(), Error> {
+    if path.exists() {
+        return Ok(());
     }
+    let create = |path: &std::path::Path| -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map(drop)
+    };
+    create(path).map_err(|err| {
+        let denied = err.kind() == std::io::ErrorKind::PermissionDenied;
+        create_db_error(&path.display().to_string(), err, denied)
+    })
+}
+
+// duckdb creates the database file when opening one that doesn't exist, so it's only opened if it exists unless
+// it's created if missing, along with its parent directories
+#[cfg(feature = "duckdb")]
+fn open_duckdb_db(path: &std::path::Path, create: bool) -> Result<duckdb::Connection, Error> {
+    if create {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| {
+                let denied = err.kind() == std::io::ErrorKind::PermissionDenied;
+                create_db_error(&path.display().to_string(), err, denied)
+            })?;
+        }
+    } else if !path.exists() {
+        return Err(Error::new(
+            crate::error::Kind::Connection(
+                "could not open database".into(),
+                Box::new(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} doesn't exist", path.display()),
+                )),
+            ),
+            None,
+        ));
+    }
+    duckdb::Connection::open(path).migration_err("could not open database", None)
+}
+
+// connects to the master database to create the one of the config
+#[cfg(feature = "tiberius-config")]
+async fn create_mssql_db(config: &Config) -> Result<(), Error> {
+    use std::convert::TryInto;
+    use tiberius::Client;
+    use tokio::net::TcpStream;
+    use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+    // CREATE DATABASE permission denied in database 'master'
+    const PERMISSION_DENIED_CODE: u32 = 262;
+
+    let Some(db_name) = config.db_name() else {
+        return Ok(());
+    };
+    let mut tconfig: tiberius::Config = config.try_into()?;
+    tconfig.database("master");
+    let tcp = TcpStream::connect(tconfig.get_addr())
+        .await
+        .migration_err("could not connect to the server", None)?;
+    let mut client = Client::connect(tconfig, tcp.compat_write())
+        .await
+        .migration_err("could not connect to the server", None)?;
+    let query = format!(
+        "IF DB_ID(N'{}') IS NULL CREATE DATABASE [{}]",
+        db_name.replace('\'', "''"),
+        db_name.replace(']', "]]")
+    );
+    let result = match client.simple_query(query).await {
+        Ok(stream) => stream.into_results().await.map(drop),
+        Err(err) => Err(err),
+    };
+    result.map_err(|err| {
+        let denied = matches!(&err, tiberius::error::Error::Server(err) if err.code() == PERMISSION_DENIED_CODE);
+        create_db_error(db_name, err, denied)
+    })
 }
 
 // rewrite all the default methods as we overrode Transaction and Query
-#[cfg(any(feature = "mysql", feature = "postgres", feature = "rusqlite"))]
+#[cfg(any(
+    feature = "mysql",
+    feature = "postgres",
+    feature = "rusqlite",
+    feature = "duckdb",
+    feature = "oracle"
+))]
 impl crate::Migrate for Config {
+    fn dialect(&self) -> Option<crate::Dialect> {
+        Some(self.db_type().into())
+    }
+
+    // the connections are opened for each call, so the settings are kept to set up each of them
+    #[cfg(feature = "rusqlite")]
+    fn setup_sqlite(
+        &mut self,
+        pragmas: &[String],
+        extensions: &[std::path::PathBuf],
+    ) -> Result<(), Error> {
+        self.add_sqlite_setup(pragmas, extensions);
+        Ok(())
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<crate::IntegrityIssue>, Error> {
+        with_connection!(self, |mut conn| crate::Migrate::check_integrity(&mut conn))
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<crate::introspect::Schema>, Error> {
+        with_connection!(self, |mut conn| crate::Migrate::snapshot_schema(&mut conn))
+    }
+
+    fn assert_checks(&mut self, checks: &[String]) -> Result<(), Error> {
+        let dialect = crate::Migrate::dialect(self);
+        with_connection!(self, |mut conn| crate::traits::sync::assert_checks(
+            &mut conn, dialect, checks
+        ))
+    }
+
+    fn assert_migrations_table(&mut self, migration_table_name: &str) -> Result<usize, Error> {
+        with_connection!(self, |mut conn| crate::Migrate::assert_migrations_table(
+            &mut conn,
+            migration_table_name
+        ))
+    }
+
     fn get_last_applied_migration(
         &mut self,
         migration_table_name: &str,
@@ -202,6 +631,74 @@ impl crate::Migrate for Config {
         })
     }
 
+    fn get_skipped_migrations(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::get_skipped_migrations(&mut conn, migration_table_name)
+        })
+    }
+
+    fn get_unapplied_migrations(
+        &mut self,
+        migrations: &[Migration],
+        abort_divergent: bool,
+        abort_missing: bool,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::get_unapplied_migrations(
+                &mut conn,
+                migrations,
+                abort_divergent,
+                abort_missing,
+                migration_table_name,
+            )
+        })
+    }
+
+    fn rollback(
+        &mut self,
+        migrations: &[Migration],
+        grouped: bool,
+        fake: bool,
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::rollback(&mut conn, migrations, grouped, fake, migration_table_name)
+        })
+    }
+
+    fn import_history(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::import_history(&mut conn, migrations, migration_table_name)
+        })
+    }
+
+    fn assert_namespaced_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<(), Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::assert_namespaced_migrations_table(&mut conn, migration_table_name)
+        })
+    }
+
+    fn apply_pending(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        with_connection!(self, |mut conn| {
+            crate::Migrate::apply_pending(&mut conn, migrations, migration_table_name)
+        })
+    }
+
     fn migrate(
         &mut self,
         migrations: &[Migration],
@@ -228,10 +725,43 @@ impl crate::Migrate for Config {
 #[cfg(any(
     feature = "mysql_async",
     feature = "tokio-postgres",
-    feature = "tiberius-config"
+    feature = "tiberius-config",
+    feature = "libsql"
 ))]
 #[async_trait]
 impl crate::AsyncMigrate for Config {
+    fn dialect(&self) -> Option<crate::Dialect> {
+        Some(self.db_type().into())
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<crate::IntegrityIssue>, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::check_integrity(&mut conn).await
+        })
+    }
+
+    async fn snapshot_schema(&mut self) -> Result<Option<crate::introspect::Schema>, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::snapshot_schema(&mut conn).await
+        })
+    }
+
+    async fn assert_checks(&mut self, checks: &[String]) -> Result<(), Error> {
+        let dialect = crate::AsyncMigrate::dialect(self);
+        with_connection_async!(self, move |mut conn| async move {
+            crate::traits::r#async::assert_checks(&mut conn, dialect, checks).await
+        })
+    }
+
+    async fn assert_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::assert_migrations_table(&mut conn, migration_table_name).await
+        })
+    }
+
     async fn get_last_applied_migration(
         &mut self,
         migration_table_name: &str,
@@ -265,6 +795,83 @@ impl crate::AsyncMigrate for Config {
         })
     }
 
+    async fn get_skipped_migrations(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::get_skipped_migrations(&mut conn, migration_table_name).await
+        })
+    }
+
+    async fn get_unapplied_migrations(
+        &mut self,
+        migrations: &[Migration],
+        abort_divergent: bool,
+        abort_missing: bool,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::get_unapplied_migrations(
+                &mut conn,
+                migrations,
+                abort_divergent,
+                abort_missing,
+                migration_table_name,
+            )
+            .await
+        })
+    }
+
+    async fn rollback(
+        &mut self,
+        migrations: &[Migration],
+        grouped: bool,
+        fake: bool,
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::rollback(
+                &mut conn,
+                migrations,
+                grouped,
+                fake,
+                migration_table_name,
+            )
+            .await
+        })
+    }
+
+    async fn import_history(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::import_history(&mut conn, migrations, migration_table_name).await
+        })
+    }
+
+    async fn assert_namespaced_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<(), Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::assert_namespaced_migrations_table(&mut conn, migration_table_name)
+                .await
+        })
+    }
+
+    async fn apply_pending(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        with_connection_async!(self, move |mut conn| async move {
+            crate::AsyncMigrate::apply_pending(&mut conn, migrations, migration_table_name).await
+        })
+    }
+
     async fn migrate(
         &mut self,
         migrations: &[Migration],
@@ -0,0 +1,130 @@
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Error, IntegrityIssue, Migration};
+
+use async_trait::async_trait;
+use deadpool_postgres::{Object, Pool, PoolError};
+use tokio_postgres::{Client, Error as PgError};
+
+// an object checked out of the pool derefs to its client, which runs the migrations
+fn client(object: &mut Object) -> &mut Client {
+    object
+}
+
+#[async_trait]
+impl AsyncTransaction for Object {
+    type Error = PgError;
+
+    fn is_serialization_failure(error: &PgError) -> bool {
+        <Client as AsyncTransaction>::is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        <Client as AsyncTransaction>::is_timeout(error)
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        AsyncTransaction::execute(client(self), queries).await
+    }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        AsyncTransaction::execute_without_transaction(client(self), queries).await
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for Object {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        AsyncQuery::query(client(self), query).await
+    }
+}
+
+#[async_trait]
+impl AsyncMigrate for Object {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        AsyncMigrate::check_integrity(client(self)).await
+    }
+}
+
+// an object is checked out of the pool for each of the calls of the traits
+#[async_trait]
+impl AsyncTransaction for Pool {
+    type Error = PoolError;
+
+    fn is_serialization_failure(error: &PoolError) -> bool {
+        matches!(error, PoolError::Backend(error) if <Client as AsyncTransaction>::is_serialization_failure(error))
+    }
+
+    fn is_timeout(error: &PoolError) -> bool {
+        matches!(error, PoolError::Backend(error) if <Client as AsyncTransaction>::is_timeout(error))
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut object = self.get().await?;
+        AsyncTransaction::execute(&mut object, queries)
+            .await
+            .map_err(PoolError::Backend)
+    }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut object = self.get().await?;
+        AsyncTransaction::execute_without_transaction(&mut object, queries)
+            .await
+            .map_err(PoolError::Backend)
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for Pool {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let mut object = self.get().await?;
+        AsyncQuery::query(&mut object, query)
+            .await
+            .map_err(PoolError::Backend)
+    }
+}
+
+#[async_trait]
+impl AsyncMigrate for Pool {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let mut object = self
+            .get()
+            .await
+            .migration_err("could not get a connection from the pool", None)?;
+        AsyncMigrate::check_integrity(&mut object).await
+    }
+}
@@ -0,0 +1,73 @@
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Migration};
+use async_trait::async_trait;
+use libsql::{Connection, Error as LibsqlError, Transaction as LibsqlTransaction};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+async fn query_applied_migrations(
+    transaction: &LibsqlTransaction,
+    query: &str,
+) -> Result<Vec<Migration>, LibsqlError> {
+    let mut rows = transaction.query(query, ()).await?;
+    let mut applied = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let version = row.get(0)?;
+        let applied_on: String = row.get(2)?;
+        // Safe to call unwrap, as we stored it in RFC3339 format on the database
+        let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
+        let checksum: String = row.get(3)?;
+
+        applied.push(Migration::applied_with_checksum(
+            version,
+            row.get(1)?,
+            applied_on,
+            &checksum,
+        ));
+    }
+    Ok(applied)
+}
+
+// the same for local databases, embedded replicas and remote ones, i.e. on Turso, which run each query
+// over http on the stream of the transaction
+#[async_trait]
+impl AsyncTransaction for Connection {
+    type Error = LibsqlError;
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let transaction = self.transaction().await?;
+        let mut count = 0;
+        for query in queries {
+            transaction.execute_batch(query).await?;
+            count += 1;
+        }
+        transaction.commit().await?;
+        Ok(count)
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for Connection {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let transaction = self.transaction().await?;
+        let applied = query_applied_migrations(&transaction, query).await?;
+        transaction.commit().await?;
+        Ok(applied)
+    }
+}
+
+impl AsyncMigrate for Connection {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Sqlite)
+    }
+}
@@ -7,6 +7,28 @@ pub mod tokio_postgres;
 #[cfg(feature = "mysql_async")]
 pub mod mysql_async;
 
+#[cfg(any(
+    feature = "sqlx-postgres",
+    feature = "sqlx-mysql",
+    feature = "sqlx-sqlite"
+))]
+pub mod sqlx;
+
+#[cfg(feature = "libsql")]
+pub mod libsql;
+
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
+
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+
+#[cfg(feature = "oracle")]
+pub mod oracle;
+
+#[cfg(feature = "odbc")]
+pub mod odbc;
+
 #[cfg(feature = "postgres")]
 pub mod postgres;
 
@@ -16,4 +38,19 @@ pub mod mysql;
 #[cfg(feature = "tiberius")]
 pub mod tiberius;
 
+#[cfg(feature = "bb8-tiberius")]
+pub mod bb8_tiberius;
+
+#[cfg(feature = "bb8-postgres")]
+pub mod bb8_postgres;
+
+#[cfg(feature = "deadpool-postgres")]
+pub mod deadpool_postgres;
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+mod postgres_tls;
+
+#[cfg(any(feature = "mysql", feature = "mysql_async"))]
+mod mysql_tls;
+
 mod config;
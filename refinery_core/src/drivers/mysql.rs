@@ -1,7 +1,9 @@
+use crate::error::WrapMigrationError;
+use crate::introspect::{Column, Introspect, Schema};
 use crate::traits::sync::{Migrate, Query, Transaction};
-use crate::Migration;
+use crate::{Dialect, Error, Migration, Splitter};
 use mysql::{
-    error::Error as MError, prelude::Queryable, Conn, IsolationLevel, PooledConn,
+    error::Error as MError, prelude::Queryable, Conn, IsolationLevel, Pool, PooledConn,
     Transaction as MTransaction, TxOpts,
 };
 use time::format_description::well_known::Rfc3339;
@@ -14,6 +16,11 @@ fn get_tx_opts() -> TxOpts {
         .set_isolation_level(Some(IsolationLevel::RepeatableRead))
 }
 
+// ER_QUERY_TIMEOUT, from queries exceeding their max_execution_time, and ER_LOCK_WAIT_TIMEOUT
+fn is_timeout(error: &MError) -> bool {
+    matches!(error, MError::MySqlError(error) if error.code == 3024 || error.code == 1205)
+}
+
 fn query_applied_migrations(
     transaction: &mut MTransaction,
     query: &str,
@@ -28,21 +35,110 @@ fn query_applied_migrations(
         let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
         let checksum: String = row.get(3).unwrap();
 
-        applied.push(Migration::applied(
+        applied.push(Migration::applied_with_checksum(
             version,
             row.get(1).unwrap(),
             applied_on,
-            checksum
-                .parse::<u64>()
-                .expect("checksum must be a valid u64"),
+            &checksum,
         ))
     }
     Ok(applied)
 }
 
+const COLUMNS_QUERY: &str = "SELECT c.TABLE_NAME, c.COLUMN_NAME, c.COLUMN_TYPE, c.IS_NULLABLE, c.COLUMN_DEFAULT
+    FROM information_schema.COLUMNS c
+    JOIN information_schema.TABLES t ON t.TABLE_SCHEMA = c.TABLE_SCHEMA AND t.TABLE_NAME = c.TABLE_NAME
+    WHERE c.TABLE_SCHEMA = DATABASE() AND t.TABLE_TYPE = 'BASE TABLE'
+    ORDER BY c.TABLE_NAME, c.ORDINAL_POSITION";
+
+const CONSTRAINTS_QUERY: &str = "SELECT tc.TABLE_NAME, tc.CONSTRAINT_NAME, tc.CONSTRAINT_TYPE,
+    GROUP_CONCAT(k.COLUMN_NAME ORDER BY k.ORDINAL_POSITION SEPARATOR ', '),
+    MAX(k.REFERENCED_TABLE_NAME),
+    GROUP_CONCAT(k.REFERENCED_COLUMN_NAME ORDER BY k.ORDINAL_POSITION SEPARATOR ', ')
+    FROM information_schema.TABLE_CONSTRAINTS tc
+    JOIN information_schema.KEY_COLUMN_USAGE k ON k.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA
+    AND k.TABLE_NAME = tc.TABLE_NAME AND k.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+    WHERE tc.TABLE_SCHEMA = DATABASE()
+    GROUP BY tc.TABLE_NAME, tc.CONSTRAINT_NAME, tc.CONSTRAINT_TYPE";
+
+const INDEXES_QUERY: &str = "SELECT TABLE_NAME, INDEX_NAME, NON_UNIQUE,
+    GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX SEPARATOR ', ')
+    FROM information_schema.STATISTICS
+    WHERE TABLE_SCHEMA = DATABASE()
+    GROUP BY TABLE_NAME, INDEX_NAME, NON_UNIQUE";
+
+// table, name, type, columns, referenced table and referenced columns
+type ConstraintRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+// Mysql names the indexes backing constraints after them, those are only listed as constraints
+fn snapshot_schema<Q: Queryable>(conn: &mut Q) -> Result<Schema, MError> {
+    let mut snapshot = Schema::default();
+    let columns: Vec<(String, String, String, String, Option<String>)> =
+        conn.query(COLUMNS_QUERY)?;
+    for (table, name, data_type, nullable, default) in columns {
+        snapshot.add_column(
+            table,
+            Column {
+                name,
+                data_type,
+                nullable: nullable == "YES",
+                default,
+            },
+        );
+    }
+
+    let constraints: Vec<ConstraintRow> = conn.query(CONSTRAINTS_QUERY)?;
+    for (table, name, constraint_type, columns, referenced_table, referenced_columns) in constraints
+    {
+        let definition = match (referenced_table, referenced_columns) {
+            (Some(referenced_table), Some(referenced_columns)) => format!(
+                "FOREIGN KEY ({}) REFERENCES {} ({})",
+                columns, referenced_table, referenced_columns
+            ),
+            _ => format!("{} ({})", constraint_type, columns),
+        };
+        snapshot.add_constraint(&table, name, definition);
+    }
+
+    let indexes: Vec<(String, String, i64, Option<String>)> = conn.query(INDEXES_QUERY)?;
+    for (table, name, non_unique, columns) in indexes {
+        let is_constraint = snapshot
+            .table(&table)
+            .is_some_and(|table| table.constraints.contains_key(&name));
+        if is_constraint {
+            continue;
+        }
+        let unique = if non_unique == 0 { "UNIQUE " } else { "" };
+        let definition = format!(
+            "CREATE {}INDEX {} ON {} ({})",
+            unique,
+            name,
+            table,
+            columns.unwrap_or_default()
+        );
+        snapshot.add_index(&table, name, definition);
+    }
+    Ok(snapshot)
+}
+
 impl Transaction for Conn {
     type Error = MError;
 
+    fn splitter(&self) -> Splitter {
+        Splitter::Mysql
+    }
+
+    fn is_timeout(error: &MError) -> bool {
+        is_timeout(error)
+    }
+
     fn execute<'a, T: Iterator<Item = &'a str>>(
         &mut self,
         queries: T,
@@ -61,6 +157,14 @@ impl Transaction for Conn {
 impl Transaction for PooledConn {
     type Error = MError;
 
+    fn splitter(&self) -> Splitter {
+        Splitter::Mysql
+    }
+
+    fn is_timeout(error: &MError) -> bool {
+        is_timeout(error)
+    }
+
     fn execute<'a, T: Iterator<Item = &'a str>>(
         &mut self,
         queries: T,
@@ -77,6 +181,27 @@ impl Transaction for PooledConn {
     }
 }
 
+// a connection is checked out of the pool for each of the calls of the traits
+impl Transaction for Pool {
+    type Error = MError;
+
+    fn splitter(&self) -> Splitter {
+        Splitter::Mysql
+    }
+
+    fn is_timeout(error: &MError) -> bool {
+        is_timeout(error)
+    }
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut conn = self.get_conn()?;
+        Transaction::execute(&mut conn, queries)
+    }
+}
+
 impl Query<Vec<Migration>> for Conn {
     fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
         let mut transaction = self.start_transaction(get_tx_opts())?;
@@ -95,5 +220,63 @@ impl Query<Vec<Migration>> for PooledConn {
     }
 }
 
-impl Migrate for Conn {}
-impl Migrate for PooledConn {}
+impl Query<Vec<Migration>> for Pool {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        let mut conn = self.get_conn()?;
+        Query::query(&mut conn, query)
+    }
+}
+
+impl Migrate for Conn {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mysql)
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Introspect::snapshot(self).map(Some)
+    }
+}
+
+impl Migrate for PooledConn {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mysql)
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Introspect::snapshot(self).map(Some)
+    }
+}
+
+impl Migrate for Pool {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mysql)
+    }
+}
+
+impl Introspect for Conn {
+    fn snapshot(&mut self) -> Result<Schema, Error> {
+        let mut transaction = self
+            .start_transaction(get_tx_opts())
+            .migration_err("error starting transaction", None)?;
+        let snapshot =
+            snapshot_schema(&mut transaction).migration_err("error introspecting schema", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(snapshot)
+    }
+}
+
+impl Introspect for PooledConn {
+    fn snapshot(&mut self) -> Result<Schema, Error> {
+        let mut transaction = self
+            .start_transaction(get_tx_opts())
+            .migration_err("error starting transaction", None)?;
+        let snapshot =
+            snapshot_schema(&mut transaction).migration_err("error introspecting schema", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(snapshot)
+    }
+}
@@ -1,5 +1,5 @@
-use crate::traits::r#async::{AsyncMigrate, AsyncQuery, AsyncTransaction};
-use crate::Migration;
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Migration, Splitter};
 use async_trait::async_trait;
 use mysql_async::{
     prelude::Queryable, Error as MError, IsolationLevel, Pool, Transaction as MTransaction, TxOpts,
@@ -7,6 +7,11 @@ use mysql_async::{
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+// ER_QUERY_TIMEOUT, from queries exceeding their max_execution_time, and ER_LOCK_WAIT_TIMEOUT
+fn is_timeout(error: &MError) -> bool {
+    matches!(error, MError::Server(error) if error.code == 3024 || error.code == 1205)
+}
+
 async fn query_applied_migrations<'a>(
     mut transaction: MTransaction<'a>,
     query: &str,
@@ -21,14 +26,7 @@ async fn query_applied_migrations<'a>(
 
             // Safe to call unwrap, as we stored it in RFC3339 format on the database
             let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
-            Migration::applied(
-                version,
-                name,
-                applied_on,
-                checksum
-                    .parse::<u64>()
-                    .expect("checksum must be a valid u64"),
-            )
+            Migration::applied_with_checksum(version, name, applied_on, &checksum)
         })
         .collect();
 
@@ -39,6 +37,14 @@ async fn query_applied_migrations<'a>(
 impl AsyncTransaction for Pool {
     type Error = MError;
 
+    fn splitter(&self) -> Splitter {
+        Splitter::Mysql
+    }
+
+    fn is_timeout(error: &MError) -> bool {
+        is_timeout(error)
+    }
+
     async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
         &mut self,
         queries: T,
@@ -56,6 +62,10 @@ impl AsyncTransaction for Pool {
         transaction.commit().await?;
         Ok(count as usize)
     }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
 }
 
 #[async_trait]
@@ -75,4 +85,8 @@ impl AsyncQuery<Vec<Migration>> for Pool {
     }
 }
 
-impl AsyncMigrate for Pool {}
+impl AsyncMigrate for Pool {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mysql)
+    }
+}
@@ -0,0 +1,107 @@
+use crate::config::{Config, SslMode};
+use crate::error::{Kind, WrapMigrationError};
+use crate::Error;
+
+// the mysql drivers can't fall back to an unencrypted connection, so TLS is only used when the ssl mode requires it.
+// Returns whether the domain of the server certificate is skipped and whether the certificate is accepted unverified,
+// as libpq does a root certificate makes `require` verify it too
+fn tls_verification(config: &Config) -> Result<Option<(bool, bool)>, Error> {
+    let verification = match config.ssl_mode() {
+        Some(SslMode::VerifyFull) => (false, false),
+        Some(SslMode::VerifyCa) => (true, false),
+        Some(SslMode::Require) => (true, config.ssl_root_cert().is_none()),
+        _ => return Ok(None),
+    };
+    if cfg!(not(any(
+        feature = "mysql-native-tls",
+        feature = "mysql-rustls"
+    ))) {
+        return Err(Error::new(
+            Kind::ConfigError(
+                "the ssl mode requires TLS, but neither the mysql-native-tls nor the mysql-rustls feature is enabled"
+                    .into(),
+            ),
+            None,
+        ));
+    }
+    Ok(Some(verification))
+}
+
+// the native-tls backend of the mysql drivers only loads client certificates from PKCS#12 archives
+#[cfg(not(feature = "mysql-rustls"))]
+fn client_cert_unsupported() -> Error {
+    Error::new(
+        Kind::ConfigError(
+            "client certificates need the mysql-rustls feature on Mysql, mysql-native-tls doesn't support PEM ones"
+                .into(),
+        ),
+        None,
+    )
+}
+
+// the options of the `mysql` driver from the url of the config with its ssl settings
+#[cfg(feature = "mysql")]
+pub(crate) fn mysql_opts(url: &str, config: &Config) -> Result<mysql::Opts, Error> {
+    let opts = mysql::Opts::from_url(url).migration_err("could not parse url", None)?;
+    let Some((skip_domain_validation, accept_invalid_certs)) = tls_verification(config)? else {
+        return Ok(opts);
+    };
+
+    #[allow(unused_mut)]
+    let mut ssl_opts = mysql::SslOpts::default()
+        .with_root_cert_path(config.ssl_root_cert().map(|path| path.to_path_buf()))
+        .with_danger_skip_domain_validation(skip_domain_validation)
+        .with_danger_accept_invalid_certs(accept_invalid_certs);
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "mysql-rustls")] {
+            if let Some((cert, key)) = config.ssl_client_cert()? {
+                let identity = mysql::ClientIdentity::new(cert.to_path_buf(), key.to_path_buf());
+                ssl_opts = ssl_opts.with_client_identity(Some(identity));
+            }
+        } else {
+            if config.ssl_client_cert()?.is_some() {
+                return Err(client_cert_unsupported());
+            }
+        }
+    }
+    Ok(mysql::OptsBuilder::from_opts(opts)
+        .ssl_opts(ssl_opts)
+        .into())
+}
+
+// the options of the `mysql_async` driver from the url of the config with its ssl settings
+#[cfg(feature = "mysql_async")]
+pub(crate) fn mysql_async_opts(url: &str, config: &Config) -> Result<mysql_async::Opts, Error> {
+    let opts = mysql_async::Opts::from_url(url).migration_err("could not parse url", None)?;
+    let Some((skip_domain_validation, accept_invalid_certs)) = tls_verification(config)? else {
+        return Ok(opts);
+    };
+
+    let root_certs = config
+        .ssl_root_cert()
+        .map(|path| vec![path.to_path_buf().into()])
+        .unwrap_or_default();
+    #[allow(unused_mut)]
+    let mut ssl_opts = mysql_async::SslOpts::default()
+        .with_root_certs(root_certs)
+        .with_danger_skip_domain_validation(skip_domain_validation)
+        .with_danger_accept_invalid_certs(accept_invalid_certs);
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "mysql-rustls")] {
+            if let Some((cert, key)) = config.ssl_client_cert()? {
+                let identity = mysql_async::ClientIdentity::new(
+                    cert.to_path_buf().into(),
+                    key.to_path_buf().into(),
+                );
+                ssl_opts = ssl_opts.with_client_identity(Some(identity));
+            }
+        } else {
+            if config.ssl_client_cert()?.is_some() {
+                return Err(client_cert_unsupported());
+            }
+        }
+    }
+    Ok(mysql_async::OptsBuilder::from_opts(opts)
+        .ssl_opts(ssl_opts)
+        .into())
+}
@@ -0,0 +1,147 @@
+use crate::error::WrapMigrationError;
+use crate::traits::sync::{Migrate, Query, Transaction};
+use crate::traits::{upgrade_migrations_table_queries, SchemaHistoryDdl};
+use crate::{Error, Migration};
+use odbc_api::{Connection as OdbcConnection, Cursor, CursorRow, Error as OdbcError};
+use std::iter;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// the schema history table with the types of the sql standard, as there's no portable `CREATE TABLE IF NOT EXISTS`
+// it's only created when querying it fails
+const ASSERT_MIGRATIONS_TABLE_ANSI_QUERY: &str = "CREATE TABLE %MIGRATION_TABLE_NAME%(
+             version INTEGER NOT NULL,
+             name VARCHAR(255) NOT NULL,
+             applied_on VARCHAR(255),
+             checksum VARCHAR(255),
+             execution_time_ms BIGINT,
+             applied_by VARCHAR(255),
+             skipped SMALLINT,
+             PRIMARY KEY (version, name))";
+
+struct AnsiSchemaHistoryDdl;
+
+impl SchemaHistoryDdl for AnsiSchemaHistoryDdl {
+    fn create_table_statements(
+        &self,
+        migration_table_name: &str,
+        _dialect: Option<crate::Dialect>,
+    ) -> Vec<String> {
+        vec![ASSERT_MIGRATIONS_TABLE_ANSI_QUERY
+            .replace("%MIGRATION_TABLE_NAME%", migration_table_name)]
+    }
+}
+
+// not every driver accepts the trailing `;` of a statement
+fn statement(query: &str) -> &str {
+    let query = query.trim();
+    query.strip_suffix(';').unwrap_or(query)
+}
+
+// columns are read as text, as drivers map the numeric types to different C types
+fn get_text(row: &mut CursorRow<'_>, column: u16, buf: &mut Vec<u8>) -> Result<String, OdbcError> {
+    row.get_text(column, buf)?;
+    Ok(String::from_utf8_lossy(buf).trim().to_string())
+}
+
+fn query_applied_migrations(
+    conn: &OdbcConnection<'_>,
+    query: &str,
+) -> Result<Vec<Migration>, OdbcError> {
+    let mut applied = Vec::new();
+    let Some(mut cursor) = conn.execute(statement(query), (), None)? else {
+        return Ok(applied);
+    };
+    let mut buf = Vec::new();
+    while let Some(mut row) = cursor.next_row()? {
+        // Safe to call unwrap, as we stored it as an integer on the database
+        let version = get_text(&mut row, 1, &mut buf)?.parse().unwrap();
+        let name = get_text(&mut row, 2, &mut buf)?;
+        let applied_on = get_text(&mut row, 3, &mut buf)?;
+        // Safe to call unwrap, as we stored it in RFC3339 format on the database
+        let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
+        let checksum = get_text(&mut row, 4, &mut buf)?;
+
+        applied.push(Migration::applied_with_checksum(
+            version, name, applied_on, &checksum,
+        ));
+    }
+    Ok(applied)
+}
+
+// executes the queries on a transaction, odbc connections are in autocommit mode otherwise
+fn execute_in_transaction<'a>(
+    conn: &OdbcConnection<'_>,
+    queries: impl Iterator<Item = &'a str>,
+) -> Result<usize, OdbcError> {
+    let mut count = 0;
+    for query in queries {
+        conn.execute(statement(query), (), None)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+impl Transaction for OdbcConnection<'_> {
+    type Error = OdbcError;
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        self.set_autocommit(false)?;
+        let result = execute_in_transaction(self, queries);
+        let result = match result {
+            Ok(count) => self.commit().map(|_| count),
+            Err(err) => OdbcConnection::rollback(self).and(Err(err)),
+        };
+        self.set_autocommit(true)?;
+        result
+    }
+
+    fn execute_without_transaction<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        execute_in_transaction(self, queries)
+    }
+}
+
+impl Query<Vec<Migration>> for OdbcConnection<'_> {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        query_applied_migrations(self, query)
+    }
+}
+
+// the dialect of the database behind the connection is unknown, it can be set with `Runner::set_dialect`
+impl Migrate for OdbcConnection<'_> {
+    fn assert_migrations_table(&mut self, migration_table_name: &str) -> Result<usize, Error> {
+        self.assert_migrations_table_with(migration_table_name, &AnsiSchemaHistoryDdl)
+    }
+
+    fn assert_migrations_table_with(
+        &mut self,
+        migration_table_name: &str,
+        ddl: &dyn SchemaHistoryDdl,
+    ) -> Result<usize, Error> {
+        let assert_table = format!("SELECT version FROM {} WHERE 1 = 0", migration_table_name);
+        let mut count = 0;
+        if self.query(&assert_table).is_err() {
+            let statements = ddl.create_table_statements(migration_table_name, self.dialect());
+            count = self
+                .execute(statements.iter().map(String::as_str))
+                .migration_err("error asserting migrations table", None)?;
+        }
+
+        // the dialect is unknown, so the probes fail when the column is missing
+        for (probe, upgrade) in
+            upgrade_migrations_table_queries(self.dialect(), migration_table_name)?
+        {
+            if self.query(&probe).is_err() {
+                self.execute(iter::once(upgrade.as_str()))
+                    .migration_err("error upgrading migrations table", None)?;
+            }
+        }
+        Ok(count)
+    }
+}
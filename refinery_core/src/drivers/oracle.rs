@@ -0,0 +1,89 @@
+use crate::splitter::is_plsql;
+use crate::traits::sync::{Migrate, Query, Transaction};
+use crate::{Dialect, Migration, Splitter};
+use oracle::{Connection as OConnection, Error as OError};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// ORA-00054 and ORA-30006, resource busy, and ORA-04021, from schema changes waiting on a lock longer than the
+// ddl lock timeout
+fn is_timeout(error: &OError) -> bool {
+    matches!(error.oci_code(), Some(54 | 4021 | 30006))
+}
+
+// oracle executes a single sql statement without its trailing `;`, which is part of PL/SQL blocks,
+// and statements executed without being split may still end with the `/` line of a block
+fn statement(query: &str) -> &str {
+    let query = query.trim();
+    let query = query
+        .strip_suffix("\n/")
+        .map_or(query, |query| query.trim_end());
+    if is_plsql(query) {
+        query
+    } else {
+        query.strip_suffix(';').unwrap_or(query)
+    }
+}
+
+fn query_applied_migrations(conn: &OConnection, query: &str) -> Result<Vec<Migration>, OError> {
+    let rows = conn.query(statement(query), &[])?;
+    let mut applied = Vec::new();
+    for row in rows {
+        let row = row?;
+        let version = row.get(0)?;
+        let applied_on: String = row.get(2)?;
+        // Safe to call unwrap, as we stored it in RFC3339 format on the database
+        let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
+
+        let checksum: String = row.get(3)?;
+        applied.push(Migration::applied_with_checksum(
+            version,
+            row.get(1)?,
+            applied_on,
+            &checksum,
+        ));
+    }
+    Ok(applied)
+}
+
+// schema changes commit the transaction implicitly on oracle, so only the statements after the last one of a
+// migration are rolled back when it fails
+impl Transaction for OConnection {
+    type Error = OError;
+
+    fn splitter(&self) -> Splitter {
+        Splitter::PlSql
+    }
+
+    fn is_timeout(error: &OError) -> bool {
+        is_timeout(error)
+    }
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            if let Err(err) = OConnection::execute(self, statement(query), &[]) {
+                OConnection::rollback(self)?;
+                return Err(err);
+            }
+            count += 1;
+        }
+        OConnection::commit(self)?;
+        Ok(count)
+    }
+}
+
+impl Query<Vec<Migration>> for OConnection {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        query_applied_migrations(self, query)
+    }
+}
+
+impl Migrate for OConnection {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Oracle)
+    }
+}
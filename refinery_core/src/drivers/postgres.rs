@@ -1,9 +1,29 @@
+use crate::error::WrapMigrationError;
+use crate::introspect::{
+    postgres_foreign_key_violation_query, unqualified_index, Column, Introspect, Schema,
+    POSTGRES_COLUMNS_QUERY, POSTGRES_CONSTRAINTS_QUERY, POSTGRES_INDEXES_QUERY,
+    POSTGRES_INVALID_INDEXES_QUERY, POSTGRES_UNVALIDATED_CONSTRAINTS_QUERY,
+};
 use crate::traits::sync::{Migrate, Query, Transaction};
-use crate::Migration;
-use postgres::{Client as PgClient, Error as PgError, Transaction as PgTransaction};
+use crate::{Dialect, Error, IntegrityIssue, Migration};
+use postgres::error::SqlState;
+use postgres::{
+    Client as PgClient, Config as PgConfig, Error as PgError, NoTls, Transaction as PgTransaction,
+};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+// CockroachDB reports contention with serialization failures, as Postgres does on serializable transactions
+fn is_serialization_failure(error: &PgError) -> bool {
+    error.code() == Some(&SqlState::T_R_SERIALIZATION_FAILURE)
+}
+
+// statements exceeding the statement_timeout are cancelled, and lock waits exceeding the lock_timeout fail
+fn is_timeout(error: &PgError) -> bool {
+    error.code() == Some(&SqlState::QUERY_CANCELED)
+        || error.code() == Some(&SqlState::LOCK_NOT_AVAILABLE)
+}
+
 fn query_applied_migrations(
     transaction: &mut PgTransaction,
     query: &str,
@@ -18,21 +38,89 @@ fn query_applied_migrations(
 
         let checksum: String = row.get(3);
 
-        applied.push(Migration::applied(
+        applied.push(Migration::applied_with_checksum(
             version,
             row.get(1),
             applied_on,
-            checksum
-                .parse::<u64>()
-                .expect("checksum must be a valid u64"),
+            &checksum,
         ));
     }
     Ok(applied)
 }
 
+/// Introspects the tables of the given Postgres schema
+pub(crate) fn snapshot_schema(
+    transaction: &mut PgTransaction,
+    schema: &str,
+) -> Result<Schema, PgError> {
+    let mut snapshot = Schema::default();
+    for row in transaction.query(POSTGRES_COLUMNS_QUERY, &[&schema])? {
+        snapshot.add_column(
+            row.get(0),
+            Column {
+                name: row.get(1),
+                data_type: row.get(2),
+                nullable: row.get(3),
+                default: row.get(4),
+            },
+        );
+    }
+    for row in transaction.query(POSTGRES_INDEXES_QUERY, &[&schema])? {
+        snapshot.add_index(
+            row.get(0),
+            row.get(1),
+            unqualified_index(row.get(2), schema),
+        );
+    }
+    for row in transaction.query(POSTGRES_CONSTRAINTS_QUERY, &[&schema])? {
+        snapshot.add_constraint(row.get(0), row.get(1), row.get(2));
+    }
+    Ok(snapshot)
+}
+
+fn integrity_issues(transaction: &mut PgTransaction) -> Result<Vec<IntegrityIssue>, PgError> {
+    let mut issues = Vec::new();
+    for row in transaction.query(POSTGRES_UNVALIDATED_CONSTRAINTS_QUERY, &[])? {
+        let table: String = row.get(0);
+        if row.get(2) {
+            let query = postgres_foreign_key_violation_query(
+                &table,
+                row.get(3),
+                &row.get::<_, Vec<String>>(4),
+                &row.get::<_, Vec<String>>(5),
+            );
+            if transaction.query_one(query.as_str(), &[])?.get(0) {
+                issues.push(IntegrityIssue::ForeignKeyViolation {
+                    table: table.clone(),
+                    references: row.get(3),
+                });
+            }
+        }
+        issues.push(IntegrityIssue::UnvalidatedConstraint {
+            table,
+            constraint: row.get(1),
+        });
+    }
+    for row in transaction.query(POSTGRES_INVALID_INDEXES_QUERY, &[])? {
+        issues.push(IntegrityIssue::InvalidIndex {
+            table: row.get(0),
+            index: row.get(1),
+        });
+    }
+    Ok(issues)
+}
+
 impl Transaction for PgClient {
     type Error = PgError;
 
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
     fn execute<'a, T: Iterator<Item = &'a str>>(
         &mut self,
         queries: T,
@@ -46,6 +134,18 @@ impl Transaction for PgClient {
         transaction.commit()?;
         Ok(count as usize)
     }
+
+    fn execute_without_transaction<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            PgClient::batch_execute(self, query)?;
+            count += 1;
+        }
+        Ok(count)
+    }
 }
 
 impl Query<Vec<Migration>> for PgClient {
@@ -57,4 +157,124 @@ impl Query<Vec<Migration>> for PgClient {
     }
 }
 
-impl Migrate for PgClient {}
+impl Migrate for PgClient {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let mut transaction =
+            PgClient::transaction(self).migration_err("error starting transaction", None)?;
+        let issues =
+            integrity_issues(&mut transaction).migration_err("error checking integrity", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(issues)
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Introspect::snapshot(self).map(Some)
+    }
+}
+
+impl Introspect for PgClient {
+    fn snapshot(&mut self) -> Result<Schema, Error> {
+        let mut transaction =
+            PgClient::transaction(self).migration_err("error starting transaction", None)?;
+        let schema: String = transaction
+            .query_one("SELECT current_schema()", &[])
+            .migration_err("error querying current schema", None)?
+            .get(0);
+        let snapshot = snapshot_schema(&mut transaction, &schema)
+            .migration_err("error introspecting schema", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(snapshot)
+    }
+}
+
+// a transaction managed by the application runs the migrations on savepoints of it, committed with it
+impl Transaction for PgTransaction<'_> {
+    type Error = PgError;
+
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut savepoint = PgTransaction::transaction(self)?;
+        let mut count = 0;
+        for query in queries {
+            PgTransaction::batch_execute(&mut savepoint, query)?;
+            count += 1;
+        }
+        savepoint.commit()?;
+        Ok(count as usize)
+    }
+}
+
+impl Query<Vec<Migration>> for PgTransaction<'_> {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        query_applied_migrations(self, query)
+    }
+}
+
+impl Migrate for PgTransaction<'_> {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        integrity_issues(self).migration_err("error checking integrity", None)
+    }
+}
+
+// a `postgres::Config` built elsewhere opens a connection, without TLS, for each of the calls of the traits
+impl Transaction for PgConfig {
+    type Error = PgError;
+
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut client = self.connect(NoTls)?;
+        Transaction::execute(&mut client, queries)
+    }
+}
+
+impl Query<Vec<Migration>> for PgConfig {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        let mut client = self.connect(NoTls)?;
+        Query::query(&mut client, query)
+    }
+}
+
+impl Migrate for PgConfig {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let mut client = self
+            .connect(NoTls)
+            .migration_err("could not connect to database", None)?;
+        Migrate::check_integrity(&mut client)
+    }
+}
@@ -0,0 +1,207 @@
+use crate::config::Config;
+#[cfg(any(feature = "postgres-native-tls", feature = "postgres-rustls"))]
+use crate::config::SslMode;
+use crate::error::Kind;
+use crate::Error;
+
+// how much of the server certificate is verified, as libpq does a root certificate makes `require` verify it too
+#[cfg(any(feature = "postgres-native-tls", feature = "postgres-rustls"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Verification {
+    None,
+    Ca,
+    Full,
+}
+
+#[cfg(any(feature = "postgres-native-tls", feature = "postgres-rustls"))]
+fn verification(config: &Config) -> Verification {
+    match config.ssl_mode() {
+        Some(SslMode::VerifyFull) => Verification::Full,
+        Some(SslMode::VerifyCa) => Verification::Ca,
+        _ if config.ssl_root_cert().is_some() => Verification::Ca,
+        _ => Verification::None,
+    }
+}
+
+#[cfg(any(feature = "postgres-native-tls", feature = "postgres-rustls"))]
+fn tls_error<E: std::fmt::Display>(msg: &str, path: Option<&std::path::Path>, err: E) -> Error {
+    let msg = match path {
+        Some(path) => format!("{} {}, {}", msg, path.display(), err),
+        None => format!("{}, {}", msg, err),
+    };
+    Error::new(Kind::ConfigError(msg), None)
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "postgres-native-tls")] {
+        pub(crate) type MakeTlsConnector = postgres_native_tls::MakeTlsConnector;
+
+        // builds the TLS connector of the postgres drivers from the ssl settings of the config
+        pub(crate) fn make_tls_connector(config: &Config) -> Result<MakeTlsConnector, Error> {
+            let read = |path: &std::path::Path| {
+                std::fs::read(path).map_err(|err| tls_error("could not read", Some(path), err))
+            };
+
+            let mut builder = native_tls::TlsConnector::builder();
+            if let Some(path) = config.ssl_root_cert() {
+                let cert = native_tls::Certificate::from_pem(&read(path)?)
+                    .map_err(|err| tls_error("invalid root certificate", Some(path), err))?;
+                builder.add_root_certificate(cert);
+            }
+            if let Some((cert, key)) = config.ssl_client_cert()? {
+                let identity = native_tls::Identity::from_pkcs8(&read(cert)?, &read(key)?)
+                    .map_err(|err| tls_error("invalid client certificate", Some(cert), err))?;
+                builder.identity(identity);
+            }
+            match verification(config) {
+                Verification::None => {
+                    builder.danger_accept_invalid_certs(true);
+                }
+                Verification::Ca => {
+                    builder.danger_accept_invalid_hostnames(true);
+                }
+                Verification::Full => {}
+            }
+
+            let connector = builder
+                .build()
+                .map_err(|err| tls_error("could not build the TLS connector", None, err))?;
+            Ok(postgres_native_tls::MakeTlsConnector::new(connector))
+        }
+    } else if #[cfg(feature = "postgres-rustls")] {
+        use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+        use rustls::client::WebPkiServerVerifier;
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+        use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+        use std::sync::Arc;
+
+        pub(crate) type MakeTlsConnector = tokio_postgres_rustls::MakeRustlsConnect;
+
+        // verifies the server certificate as much as the ssl mode asks for, the handshake signatures are always verified
+        #[derive(Debug)]
+        struct Verifier {
+            inner: Arc<WebPkiServerVerifier>,
+            verification: Verification,
+        }
+
+        impl ServerCertVerifier for Verifier {
+            fn verify_server_cert(
+                &self,
+                end_entity: &CertificateDer<'_>,
+                intermediates: &[CertificateDer<'_>],
+                server_name: &ServerName<'_>,
+                ocsp_response: &[u8],
+                now: UnixTime,
+            ) -> Result<ServerCertVerified, rustls::Error> {
+                if self.verification == Verification::None {
+                    return Ok(ServerCertVerified::assertion());
+                }
+                let verified = self.inner.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    ocsp_response,
+                    now,
+                );
+                match verified {
+                    Err(rustls::Error::InvalidCertificate(
+                        CertificateError::NotValidForName
+                        | CertificateError::NotValidForNameContext { .. },
+                    )) if self.verification == Verification::Ca => Ok(ServerCertVerified::assertion()),
+                    verified => verified,
+                }
+            }
+
+            fn verify_tls12_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                self.inner.verify_tls12_signature(message, cert, dss)
+            }
+
+            fn verify_tls13_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, rustls::Error> {
+                self.inner.verify_tls13_signature(message, cert, dss)
+            }
+
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                self.inner.supported_verify_schemes()
+            }
+        }
+
+        // builds the TLS connector of the postgres drivers from the ssl settings of the config,
+        // trusting the webpki roots if no root certificate is set
+        pub(crate) fn make_tls_connector(config: &Config) -> Result<MakeTlsConnector, Error> {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+            let mut roots = RootCertStore::empty();
+            match config.ssl_root_cert() {
+                Some(path) => {
+                    let certs = CertificateDer::pem_file_iter(path)
+                        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                        .map_err(|err| tls_error("invalid root certificate", Some(path), err))?;
+                    for cert in certs {
+                        roots
+                            .add(cert)
+                            .map_err(|err| tls_error("invalid root certificate", Some(path), err))?;
+                    }
+                }
+                None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+            }
+            let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|err| tls_error("could not build the TLS connector", None, err))?;
+            let verifier = Verifier {
+                inner,
+                verification: verification(config),
+            };
+
+            let builder = rustls::ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .map_err(|err| tls_error("could not build the TLS connector", None, err))?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier));
+            let tls_config = match config.ssl_client_cert()? {
+                Some((cert, key)) => {
+                    let certs = CertificateDer::pem_file_iter(cert)
+                        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                        .map_err(|err| tls_error("invalid client certificate", Some(cert), err))?;
+                    let key = PrivateKeyDer::from_pem_file(key)
+                        .map_err(|err| tls_error("invalid client key", Some(key), err))?;
+                    builder
+                        .with_client_auth_cert(certs, key)
+                        .map_err(|err| tls_error("invalid client certificate", Some(cert), err))?
+                }
+                None => builder.with_no_client_auth(),
+            };
+            Ok(tokio_postgres_rustls::MakeRustlsConnect::new(tls_config))
+        }
+    } else {
+        #[cfg(feature = "postgres")]
+        use postgres::NoTls;
+        #[cfg(not(feature = "postgres"))]
+        use tokio_postgres::NoTls;
+
+        pub(crate) type MakeTlsConnector = NoTls;
+
+        // without a TLS feature the drivers can only connect without TLS, failing if the ssl mode requires it
+        pub(crate) fn make_tls_connector(config: &Config) -> Result<MakeTlsConnector, Error> {
+            if config.ssl_root_cert().is_some() || config.ssl_client_cert()?.is_some() {
+                return Err(Error::new(
+                    Kind::ConfigError(
+                        "ssl certificates are set, but neither the postgres-native-tls nor the postgres-rustls feature is enabled".into(),
+                    ),
+                    None,
+                ));
+            }
+            Ok(NoTls)
+        }
+    }
+}
@@ -1,9 +1,18 @@
+use crate::error::WrapMigrationError;
+use crate::introspect::{Column, Introspect, Schema};
 use crate::traits::sync::{Migrate, Query, Transaction};
-use crate::Migration;
-use rusqlite::{Connection as RqlConnection, Error as RqlError};
+use crate::{Dialect, Error, IntegrityIssue, Migration};
+use rusqlite::{Connection as RqlConnection, Error as RqlError, Transaction as RqlTransaction};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+// statements waiting on a lock longer than the busy timeout of the connection fail with SQLITE_BUSY
+fn is_timeout(error: &RqlError) -> bool {
+    matches!(error, RqlError::SqliteFailure(error, _) if error.code == rusqlite::ErrorCode::DatabaseBusy)
+}
+
 fn query_applied_migrations(
     transaction: &RqlConnection,
     query: &str,
@@ -18,20 +27,178 @@ fn query_applied_migrations(
         let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
 
         let checksum: String = row.get(3)?;
-        applied.push(Migration::applied(
+        applied.push(Migration::applied_with_checksum(
             version,
             row.get(1)?,
             applied_on,
-            checksum
-                .parse::<u64>()
-                .expect("checksum must be a valid u64"),
+            &checksum,
         ));
     }
     Ok(applied)
 }
 
+// Sqlite doesn't name constraints, they are named after the table and their columns
+// as Postgres does by default, and without the sqlite_autoindex_ indexes backing them
+fn snapshot_schema(transaction: &RqlConnection) -> Result<Schema, RqlError> {
+    let mut snapshot = Schema::default();
+    let tables = transaction
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for table in tables {
+        let mut primary_key = Vec::new();
+        let mut stmt = transaction.prepare(
+            "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid",
+        )?;
+        let mut rows = stmt.query([&table])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let pk: i64 = row.get(4)?;
+            if pk > 0 {
+                primary_key.push((pk, name.clone()));
+            }
+            snapshot.add_column(
+                table.clone(),
+                Column {
+                    name,
+                    data_type: row.get(1)?,
+                    nullable: row.get::<_, i64>(2)? == 0,
+                    default: row.get(3)?,
+                },
+            );
+        }
+        if !primary_key.is_empty() {
+            primary_key.sort();
+            let columns: Vec<String> = primary_key.into_iter().map(|(_, name)| name).collect();
+            snapshot.add_constraint(
+                &table,
+                format!("{}_pkey", table),
+                format!("PRIMARY KEY ({})", columns.join(", ")),
+            );
+        }
+
+        let mut stmt = transaction.prepare(
+            "SELECT l.name, l.origin, m.sql FROM pragma_index_list(?1) l
+            LEFT JOIN sqlite_master m ON m.type = 'index' AND m.name = l.name",
+        )?;
+        let mut rows = stmt.query([&table])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let origin: String = row.get(1)?;
+            match origin.as_str() {
+                "c" => {
+                    if let Some(definition) = row.get::<_, Option<String>>(2)? {
+                        snapshot.add_index(&table, name, definition);
+                    }
+                }
+                "u" => {
+                    let columns = transaction
+                        .prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?
+                        .query_map([&name], |row| row.get::<_, String>(0))?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    snapshot.add_constraint(
+                        &table,
+                        format!("{}_{}_key", table, columns.join("_")),
+                        format!("UNIQUE ({})", columns.join(", ")),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let mut foreign_keys: Vec<(String, Vec<String>, Vec<String>)> = Vec::new();
+        let mut stmt = transaction.prepare(
+            "SELECT id, \"table\", \"from\", \"to\" FROM pragma_foreign_key_list(?1) ORDER BY id, seq",
+        )?;
+        let mut rows = stmt.query([&table])?;
+        let mut last_id = None;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            if last_id != Some(id) {
+                foreign_keys.push((row.get(1)?, Vec::new(), Vec::new()));
+                last_id = Some(id);
+            }
+            let (_, from, to) = foreign_keys.last_mut().unwrap();
+            from.push(row.get(2)?);
+            // the referenced columns are null when referencing the primary key implicitly
+            if let Some(column) = row.get::<_, Option<String>>(3)? {
+                to.push(column);
+            }
+        }
+        for (referenced, from, to) in foreign_keys {
+            let references = if to.is_empty() {
+                referenced
+            } else {
+                format!("{}({})", referenced, to.join(", "))
+            };
+            snapshot.add_constraint(
+                &table,
+                format!("{}_{}_fkey", table, from.join("_")),
+                format!(
+                    "FOREIGN KEY ({}) REFERENCES {}",
+                    from.join(", "),
+                    references
+                ),
+            );
+        }
+    }
+    Ok(snapshot)
+}
+
+// foreign_key_check reports every violating row, they are reported once per table and referenced table
+fn integrity_issues(transaction: &RqlConnection) -> Result<Vec<IntegrityIssue>, RqlError> {
+    let mut stmt = transaction.prepare("PRAGMA foreign_key_check")?;
+    let mut rows = stmt.query([])?;
+    let mut violations = BTreeSet::new();
+    while let Some(row) = rows.next()? {
+        violations.insert((row.get::<_, String>(0)?, row.get::<_, String>(2)?));
+    }
+    Ok(violations
+        .into_iter()
+        .map(|(table, references)| IntegrityIssue::ForeignKeyViolation { table, references })
+        .collect())
+}
+
+// PRAGMAs that set a value return it, and the ones that only query it return rows too
+fn execute_pragma(conn: &RqlConnection, pragma: &str) -> Result<(), RqlError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {}", pragma))?;
+    let mut rows = stmt.query([])?;
+    while rows.next()?.is_some() {}
+    Ok(())
+}
+
+#[cfg(feature = "rusqlite-load-extension")]
+fn load_extension(conn: &RqlConnection, extension: &Path) -> Result<(), Error> {
+    // Safety: extensions are configured by the application, just like the migrations it runs
+    unsafe {
+        let _guard = rusqlite::LoadExtensionGuard::new(conn)
+            .migration_err("error enabling extension loading", None)?;
+        conn.load_extension(extension, None).migration_err(
+            &format!("error loading extension {}", extension.display()),
+            None,
+        )
+    }
+}
+
+#[cfg(not(feature = "rusqlite-load-extension"))]
+fn load_extension(_conn: &RqlConnection, extension: &Path) -> Result<(), Error> {
+    Err(Error::new(
+        crate::error::Kind::ConfigError(format!(
+            "could not load sqlite extension {}, loading extensions needs the rusqlite-load-extension feature",
+            extension.display()
+        )),
+        None,
+    ))
+}
+
 impl Transaction for RqlConnection {
     type Error = RqlError;
+
+    fn is_timeout(error: &RqlError) -> bool {
+        is_timeout(error)
+    }
+
     fn execute<'a, T: Iterator<Item = &'a str>>(
         &mut self,
         queries: T,
@@ -45,6 +212,18 @@ impl Transaction for RqlConnection {
         transaction.commit()?;
         Ok(count)
     }
+
+    fn execute_without_transaction<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            self.execute_batch(query)?;
+            count += 1;
+        }
+        Ok(count)
+    }
 }
 
 impl Query<Vec<Migration>> for RqlConnection {
@@ -56,4 +235,106 @@ impl Query<Vec<Migration>> for RqlConnection {
     }
 }
 
-impl Migrate for RqlConnection {}
+impl Migrate for RqlConnection {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Sqlite)
+    }
+
+    fn setup_sqlite(&mut self, pragmas: &[String], extensions: &[PathBuf]) -> Result<(), Error> {
+        for pragma in pragmas {
+            execute_pragma(self, pragma)
+                .migration_err(&format!("error executing PRAGMA {}", pragma), None)?;
+        }
+        for extension in extensions {
+            load_extension(self, extension)?;
+        }
+        Ok(())
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let transaction = self
+            .transaction()
+            .migration_err("error starting transaction", None)?;
+        let issues =
+            integrity_issues(&transaction).migration_err("error checking integrity", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(issues)
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Introspect::snapshot(self).map(Some)
+    }
+}
+
+// a transaction managed by the application runs the migrations on savepoints of it, committed with it
+impl Transaction for RqlTransaction<'_> {
+    type Error = RqlError;
+
+    fn is_timeout(error: &RqlError) -> bool {
+        is_timeout(error)
+    }
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let savepoint = self.savepoint()?;
+        let mut count = 0;
+        for query in queries {
+            savepoint.execute_batch(query)?;
+            count += 1;
+        }
+        savepoint.commit()?;
+        Ok(count)
+    }
+}
+
+impl Query<Vec<Migration>> for RqlTransaction<'_> {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        query_applied_migrations(self, query)
+    }
+}
+
+impl Migrate for RqlTransaction<'_> {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Sqlite)
+    }
+
+    // PRAGMAs that can't be changed inside a transaction fail here, they have to be set on the connection instead
+    fn setup_sqlite(&mut self, pragmas: &[String], extensions: &[PathBuf]) -> Result<(), Error> {
+        for pragma in pragmas {
+            execute_pragma(self, pragma)
+                .migration_err(&format!("error executing PRAGMA {}", pragma), None)?;
+        }
+        for extension in extensions {
+            load_extension(self, extension)?;
+        }
+        Ok(())
+    }
+
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        integrity_issues(self).migration_err("error checking integrity", None)
+    }
+
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        snapshot_schema(self)
+            .map(Some)
+            .migration_err("error introspecting schema", None)
+    }
+}
+
+impl Introspect for RqlConnection {
+    fn snapshot(&mut self) -> Result<Schema, Error> {
+        let transaction = self
+            .transaction()
+            .migration_err("error starting transaction", None)?;
+        let snapshot =
+            snapshot_schema(&transaction).migration_err("error introspecting schema", None)?;
+        transaction
+            .commit()
+            .migration_err("error committing transaction", None)?;
+        Ok(snapshot)
+    }
+}
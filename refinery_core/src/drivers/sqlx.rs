@@ -0,0 +1,151 @@
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Migration, Splitter};
+use async_trait::async_trait;
+use sqlx::{Connection, Database, Error as SqlxError, Executor, Row, Transaction};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// queries are executed without arguments, so sqlx runs them as simple queries which can have multiple statements
+async fn execute_queries<'a, DB, T>(
+    mut transaction: Transaction<'_, DB>,
+    queries: T,
+) -> Result<usize, SqlxError>
+where
+    DB: Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    T: Iterator<Item = &'a str> + Send,
+{
+    let mut count = 0;
+    for query in queries {
+        (&mut *transaction).execute(query).await?;
+        count += 1;
+    }
+    transaction.commit().await?;
+    Ok(count)
+}
+
+async fn query_applied_migrations<DB>(
+    mut transaction: Transaction<'_, DB>,
+    query: &str,
+) -> Result<Vec<Migration>, SqlxError>
+where
+    DB: Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'r> i32: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+    for<'r> String: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+    usize: sqlx::ColumnIndex<DB::Row>,
+{
+    let rows = (&mut *transaction).fetch_all(query).await?;
+    let mut applied = Vec::new();
+    for row in rows.into_iter() {
+        let version: i32 = row.try_get(0)?;
+        let applied_on: String = row.try_get(2)?;
+        // Safe to call unwrap, as we stored it in RFC3339 format on the database
+        let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
+        let checksum: String = row.try_get(3)?;
+
+        applied.push(Migration::applied_with_checksum(
+            version,
+            row.try_get(1)?,
+            applied_on,
+            &checksum,
+        ));
+    }
+    transaction.commit().await?;
+    Ok(applied)
+}
+
+// Postgres cancels statements exceeding their statement_timeout and fails lock waits exceeding their lock_timeout,
+// Mysql reports ER_QUERY_TIMEOUT and ER_LOCK_WAIT_TIMEOUT and sqlite SQLITE_BUSY once the busy timeout is exceeded
+fn is_timeout(error: &SqlxError, dialect: Dialect) -> bool {
+    let Some(error) = error.as_database_error() else {
+        return false;
+    };
+    match dialect {
+        #[cfg(feature = "sqlx-mysql")]
+        Dialect::Mysql => error
+            .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+            .is_some_and(|error| error.number() == 3024 || error.number() == 1205),
+        // the extended result codes of SQLITE_BUSY
+        Dialect::Sqlite => error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| code & 0xff == 5),
+        _ => error
+            .code()
+            .is_some_and(|code| code == "57014" || code == "55P03"),
+    }
+}
+
+// implements the traits for a sqlx connection and pool of the given database, both begin a transaction with
+// `begin`, the pool checking out a connection for each of the queries refinery runs
+macro_rules! impl_sqlx {
+    ($type:ty, $dialect:expr) => {
+        #[async_trait]
+        impl AsyncTransaction for $type {
+            type Error = SqlxError;
+
+            async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+                &mut self,
+                queries: T,
+            ) -> Result<usize, Self::Error> {
+                let transaction = self.begin().await?;
+                execute_queries(transaction, queries).await
+            }
+
+            fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+                Some(self)
+            }
+
+            fn splitter(&self) -> Splitter {
+                match $dialect {
+                    Dialect::Mysql => Splitter::Mysql,
+                    _ => Splitter::Semicolon,
+                }
+            }
+
+            fn is_serialization_failure(error: &SqlxError) -> bool {
+                error
+                    .as_database_error()
+                    .and_then(|error| error.code())
+                    .is_some_and(|code| code == "40001")
+            }
+
+            fn is_timeout(error: &SqlxError) -> bool {
+                is_timeout(error, $dialect)
+            }
+        }
+
+        #[async_trait]
+        impl AsyncQuery<Vec<Migration>> for $type {
+            async fn query(
+                &mut self,
+                query: &str,
+            ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+                let transaction = self.begin().await?;
+                query_applied_migrations(transaction, query).await
+            }
+        }
+
+        impl AsyncMigrate for $type {
+            fn dialect(&self) -> Option<Dialect> {
+                Some($dialect)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "sqlx-postgres")]
+impl_sqlx!(sqlx::PgConnection, Dialect::Postgres);
+#[cfg(feature = "sqlx-postgres")]
+impl_sqlx!(sqlx::PgPool, Dialect::Postgres);
+
+#[cfg(feature = "sqlx-mysql")]
+impl_sqlx!(sqlx::MySqlConnection, Dialect::Mysql);
+#[cfg(feature = "sqlx-mysql")]
+impl_sqlx!(sqlx::MySqlPool, Dialect::Mysql);
+
+#[cfg(feature = "sqlx-sqlite")]
+impl_sqlx!(sqlx::SqliteConnection, Dialect::Sqlite);
+#[cfg(feature = "sqlx-sqlite")]
+impl_sqlx!(sqlx::SqlitePool, Dialect::Sqlite);
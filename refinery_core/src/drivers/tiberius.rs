@@ -1,5 +1,5 @@
 use crate::traits::r#async::{AsyncMigrate, AsyncQuery, AsyncTransaction};
-use crate::Migration;
+use crate::{Dialect, Migration, Splitter};
 
 use async_trait::async_trait;
 use futures::{
@@ -25,13 +25,11 @@ async fn query_applied_migrations<S: AsyncRead + AsyncWrite + Unpin + Send>(
             let applied_on = OffsetDateTime::parse(applied_on, &Rfc3339).unwrap();
             let checksum: String = row.get::<&str, usize>(3).unwrap().to_string();
 
-            applied.push(Migration::applied(
+            applied.push(Migration::applied_with_checksum(
                 version,
                 row.get::<&str, usize>(1).unwrap().to_string(),
                 applied_on,
-                checksum
-                    .parse::<u64>()
-                    .expect("checksum must be a valid u64"),
+                &checksum,
             ));
         }
     }
@@ -39,6 +37,11 @@ async fn query_applied_migrations<S: AsyncRead + AsyncWrite + Unpin + Send>(
     Ok(applied)
 }
 
+// lock requests exceeding the LOCK_TIMEOUT fail with error 1222
+pub(crate) fn is_timeout(error: &Error) -> bool {
+    matches!(error, Error::Server(error) if error.code() == 1222)
+}
+
 #[async_trait]
 impl<S> AsyncTransaction for Client<S>
 where
@@ -46,6 +49,10 @@ where
 {
     type Error = Error;
 
+    fn is_timeout(error: &Error) -> bool {
+        is_timeout(error)
+    }
+
     async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
         &mut self,
         queries: T,
@@ -66,6 +73,23 @@ where
         self.simple_query("COMMIT TRAN T1").await?;
         Ok(count as usize)
     }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            self.simple_query(query).await.map(drop)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // `GO` isn't sql but the batch separator of the Mssql tools, the batches are executed one at a time
+    fn splitter(&self) -> Splitter {
+        Splitter::Separator("GO".into())
+    }
 }
 
 #[async_trait]
@@ -86,17 +110,7 @@ impl<S> AsyncMigrate for Client<S>
 where
     S: AsyncRead + AsyncWrite + Unpin + Send,
 {
-    fn assert_migrations_table_query(migration_table_name: &str) -> String {
-        format!(
-            "IF NOT EXISTS(SELECT 1 FROM sys.Tables WHERE  Name = N'{table_name}')
-         BEGIN
-           CREATE TABLE {table_name}(
-             version INT PRIMARY KEY,
-             name VARCHAR(255),
-             applied_on VARCHAR(255),
-             checksum VARCHAR(255));
-         END",
-            table_name = migration_table_name
-        )
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Mssql)
     }
 }
@@ -1,10 +1,27 @@
-use crate::traits::r#async::{AsyncMigrate, AsyncQuery, AsyncTransaction};
-use crate::Migration;
+use crate::error::WrapMigrationError;
+use crate::introspect::{
+    postgres_foreign_key_violation_query, unqualified_index, AsyncIntrospect, Column, Schema,
+    POSTGRES_COLUMNS_QUERY, POSTGRES_CONSTRAINTS_QUERY, POSTGRES_INDEXES_QUERY,
+    POSTGRES_INVALID_INDEXES_QUERY, POSTGRES_UNVALIDATED_CONSTRAINTS_QUERY,
+};
+use crate::traits::r#async::{AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction};
+use crate::{Dialect, Error, IntegrityIssue, Migration};
 use async_trait::async_trait;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
-use tokio_postgres::error::Error as PgError;
-use tokio_postgres::{Client, Transaction as PgTransaction};
+use tokio_postgres::error::{Error as PgError, SqlState};
+use tokio_postgres::{Client, Config as PgConfig, NoTls, Transaction as PgTransaction};
+
+// CockroachDB reports contention with serialization failures, as Postgres does on serializable transactions
+fn is_serialization_failure(error: &PgError) -> bool {
+    error.code() == Some(&SqlState::T_R_SERIALIZATION_FAILURE)
+}
+
+// statements exceeding the statement_timeout are cancelled, and lock waits exceeding the lock_timeout fail
+fn is_timeout(error: &PgError) -> bool {
+    error.code() == Some(&SqlState::QUERY_CANCELED)
+        || error.code() == Some(&SqlState::LOCK_NOT_AVAILABLE)
+}
 
 async fn query_applied_migrations(
     transaction: &PgTransaction<'_>,
@@ -19,22 +36,105 @@ async fn query_applied_migrations(
         let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
         let checksum: String = row.get(3);
 
-        applied.push(Migration::applied(
+        applied.push(Migration::applied_with_checksum(
             version,
             row.get(1),
             applied_on,
-            checksum
-                .parse::<u64>()
-                .expect("checksum must be a valid u64"),
+            &checksum,
         ));
     }
     Ok(applied)
 }
 
+async fn snapshot_schema(transaction: &PgTransaction<'_>) -> Result<Schema, PgError> {
+    let schema: String = transaction
+        .query_one("SELECT current_schema()", &[])
+        .await?
+        .get(0);
+    let mut snapshot = Schema::default();
+    for row in transaction
+        .query(POSTGRES_COLUMNS_QUERY, &[&schema])
+        .await?
+    {
+        snapshot.add_column(
+            row.get(0),
+            Column {
+                name: row.get(1),
+                data_type: row.get(2),
+                nullable: row.get(3),
+                default: row.get(4),
+            },
+        );
+    }
+    for row in transaction
+        .query(POSTGRES_INDEXES_QUERY, &[&schema])
+        .await?
+    {
+        snapshot.add_index(
+            row.get(0),
+            row.get(1),
+            unqualified_index(row.get(2), &schema),
+        );
+    }
+    for row in transaction
+        .query(POSTGRES_CONSTRAINTS_QUERY, &[&schema])
+        .await?
+    {
+        snapshot.add_constraint(row.get(0), row.get(1), row.get(2));
+    }
+    Ok(snapshot)
+}
+
+async fn integrity_issues(transaction: &PgTransaction<'_>) -> Result<Vec<IntegrityIssue>, PgError> {
+    let mut issues = Vec::new();
+    for row in transaction
+        .query(POSTGRES_UNVALIDATED_CONSTRAINTS_QUERY, &[])
+        .await?
+    {
+        let table: String = row.get(0);
+        if row.get(2) {
+            let query = postgres_foreign_key_violation_query(
+                &table,
+                row.get(3),
+                &row.get::<_, Vec<String>>(4),
+                &row.get::<_, Vec<String>>(5),
+            );
+            if transaction.query_one(query.as_str(), &[]).await?.get(0) {
+                issues.push(IntegrityIssue::ForeignKeyViolation {
+                    table: table.clone(),
+                    references: row.get(3),
+                });
+            }
+        }
+        issues.push(IntegrityIssue::UnvalidatedConstraint {
+            table,
+            constraint: row.get(1),
+        });
+    }
+    for row in transaction
+        .query(POSTGRES_INVALID_INDEXES_QUERY, &[])
+        .await?
+    {
+        issues.push(IntegrityIssue::InvalidIndex {
+            table: row.get(0),
+            index: row.get(1),
+        });
+    }
+    Ok(issues)
+}
+
 #[async_trait]
 impl AsyncTransaction for Client {
     type Error = PgError;
 
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
     async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
         &mut self,
         queries: T,
@@ -48,6 +148,22 @@ impl AsyncTransaction for Client {
         transaction.commit().await?;
         Ok(count as usize)
     }
+
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            self.batch_execute(query).await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        Some(self)
+    }
 }
 
 #[async_trait]
@@ -63,4 +179,155 @@ impl AsyncQuery<Vec<Migration>> for Client {
     }
 }
 
-impl AsyncMigrate for Client {}
+#[async_trait]
+impl AsyncMigrate for Client {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let transaction = self
+            .transaction()
+            .await
+            .migration_err("error starting transaction", None)?;
+        let issues = integrity_issues(&transaction)
+            .await
+            .migration_err("error checking integrity", None)?;
+        transaction
+            .commit()
+            .await
+            .migration_err("error committing transaction", None)?;
+        Ok(issues)
+    }
+
+    async fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        AsyncIntrospect::snapshot(self).await.map(Some)
+    }
+}
+
+#[async_trait]
+impl AsyncIntrospect for Client {
+    async fn snapshot(&mut self) -> Result<Schema, Error> {
+        let transaction = self
+            .transaction()
+            .await
+            .migration_err("error starting transaction", None)?;
+        let snapshot = snapshot_schema(&transaction)
+            .await
+            .migration_err("error introspecting schema", None)?;
+        transaction
+            .commit()
+            .await
+            .migration_err("error committing transaction", None)?;
+        Ok(snapshot)
+    }
+}
+
+// a transaction managed by the application runs the migrations on savepoints of it, committed with it
+#[async_trait]
+impl AsyncTransaction for PgTransaction<'_> {
+    type Error = PgError;
+
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let savepoint = self.transaction().await?;
+        let mut count = 0;
+        for query in queries {
+            savepoint.batch_execute(query).await?;
+            count += 1;
+        }
+        savepoint.commit().await?;
+        Ok(count as usize)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for PgTransaction<'_> {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        query_applied_migrations(self, query).await
+    }
+}
+
+#[async_trait]
+impl AsyncMigrate for PgTransaction<'_> {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        integrity_issues(self)
+            .await
+            .migration_err("error checking integrity", None)
+    }
+}
+
+// a `tokio_postgres::Config` built elsewhere opens a connection, without TLS, for each of the calls of the traits,
+// which is driven by a task spawned on the tokio runtime
+async fn connect(config: &PgConfig) -> Result<Client, PgError> {
+    let (client, connection) = config.connect(NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::error!("connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+#[async_trait]
+impl AsyncTransaction for PgConfig {
+    type Error = PgError;
+
+    fn is_serialization_failure(error: &PgError) -> bool {
+        is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &PgError) -> bool {
+        is_timeout(error)
+    }
+
+    async fn execute<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut client = connect(self).await?;
+        AsyncTransaction::execute(&mut client, queries).await
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for PgConfig {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<Migration>, <Self as AsyncTransaction>::Error> {
+        let mut client = connect(self).await?;
+        AsyncQuery::query(&mut client, query).await
+    }
+}
+
+#[async_trait]
+impl AsyncMigrate for PgConfig {
+    fn dialect(&self) -> Option<Dialect> {
+        Some(Dialect::Postgres)
+    }
+
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        let mut client = connect(self)
+            .await
+            .migration_err("could not connect to database", None)?;
+        AsyncMigrate::check_integrity(&mut client).await
+    }
+}
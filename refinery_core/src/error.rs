@@ -1,6 +1,7 @@
-use crate::{Migration, Report};
+use crate::{Dialect, Migration, Report, RolloutStage, Target};
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error as TError;
 
 /// An Error occurred during a migration cycle
@@ -8,6 +9,7 @@ use thiserror::Error as TError;
 pub struct Error {
     kind: Box<Kind>,
     report: Option<Report>,
+    statement: Option<Box<FailedStatement>>,
 }
 
 impl Error {
@@ -16,6 +18,7 @@ impl Error {
         Error {
             kind: Box::new(kind),
             report,
+            statement: None,
         }
     }
 
@@ -28,11 +31,54 @@ impl Error {
     pub fn kind(&self) -> &Kind {
         &self.kind
     }
+
+    /// Return the statement of the migration that failed to apply if any
+    pub fn statement(&self) -> Option<&FailedStatement> {
+        self.statement.as_deref()
+    }
+
+    // set the statement of the migration the error happened applying
+    pub(crate) fn with_statement(mut self, statement: Option<FailedStatement>) -> Error {
+        self.statement = statement.map(Box::new);
+        self
+    }
+
+    // prepend the migrations applied by the previous steps of a run to the report of the step that failed
+    pub(crate) fn prepend_applied(mut self, applied: &[Migration]) -> Error {
+        if applied.is_empty() {
+            return self;
+        }
+        let mut applied_migrations = applied.to_vec();
+        if let Some(report) = self.report.take() {
+            applied_migrations.extend(report.applied_migrations().iter().cloned());
+        }
+        self.report = Some(Report::new(applied_migrations));
+        self
+    }
+
+    // add the migrations rolled back before moving forward to the target to the report of the step that failed,
+    // as the rollback is already committed
+    pub(crate) fn with_rolled_back(mut self, rolled_back: &[Migration]) -> Error {
+        if rolled_back.is_empty() {
+            return self;
+        }
+        let mut report = self
+            .report
+            .take()
+            .unwrap_or_else(|| Report::new(Vec::new()));
+        report.rolled_back_migrations = rolled_back.to_vec();
+        self.report = Some(report);
+        self
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.kind)
+        write!(f, "{}", self.kind)?;
+        if let Some(statement) = &self.statement {
+            write!(f, ", on {}", statement)?;
+        }
+        Ok(())
     }
 }
 
@@ -42,6 +88,68 @@ impl std::error::Error for Error {
     }
 }
 
+/// The statement of a migration that failed to apply, see [`Error::statement`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedStatement {
+    migration: String,
+    index: usize,
+    line: usize,
+    sql: String,
+}
+
+impl FailedStatement {
+    // the statement of the given sql of a migration, being the one at the given index of its statements
+    pub(crate) fn new(
+        migration: &Migration,
+        sql: &str,
+        statement: &str,
+        index: usize,
+    ) -> FailedStatement {
+        // the statements are split from the sql of the migration, so their offset is found from where they start
+        let offset = (statement.as_ptr() as usize)
+            .checked_sub(sql.as_ptr() as usize)
+            .filter(|offset| offset + statement.len() <= sql.len())
+            .or_else(|| sql.find(statement))
+            .unwrap_or_default();
+        FailedStatement {
+            migration: migration.to_string(),
+            index: index + 1,
+            line: sql[..offset].matches('\n').count() + 1,
+            sql: statement.to_string(),
+        }
+    }
+
+    /// Return the name of the migration the statement is of, i.e. `V2__add_cars`
+    pub fn migration(&self) -> &str {
+        &self.migration
+    }
+
+    /// Return the position of the statement among the statements of the migration, starting at 1
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Return the line of the migration sql the statement starts at, starting at 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Return the sql of the statement
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+}
+
+impl fmt::Display for FailedStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "statement {} of migration {} at line {}",
+            self.index, self.migration, self.line
+        )
+    }
+}
+
 /// Enum listing possible errors from Refinery.
 #[derive(Debug, TError)]
 pub enum Kind {
@@ -60,18 +168,156 @@ pub enum Kind {
     /// An Error from an divergent version, the applied version is missing on the filesystem
     #[error("migration {0} is missing from the filesystem")]
     MissingVersion(Migration),
+    /// An Error parsing a migration Target
+    #[error("invalid migration target {0}, it must be latest, fake, next, a version, fake:{{version}} or steps:{{number}}")]
+    InvalidTarget(String),
+    /// An Error parsing a Dialect
+    #[error("invalid dialect {0}, it must be postgres, mysql, sqlite, mssql, cockroach, clickhouse or duckdb")]
+    InvalidDialect(String),
+    /// An Error from a migration version that isn't found on the filesystem
+    #[error("migration version {0} not found")]
+    VersionNotFound(u32),
+    /// An Error from running a single migration that isn't pending, it's either applied or missing
+    #[error("migration {0} is not pending")]
+    NotPending(Migration),
+    /// An Error from force re-running a migration that isn't applied
+    #[error("migration {0} is not applied")]
+    NotApplied(Migration),
+    /// An Error from running a single migration before the migrations that precede it are applied
+    #[error("migration {0} must be applied before migration {1}")]
+    UnappliedPredecessor(Migration, Migration),
+    /// An Error from rolling back a migration without a `-- refinery:down` section
+    #[error("migration {0} can't be rolled back, it has no down section")]
+    Irreversible(Migration),
+    /// An Error from rolling back or archiving a migration whose down section was omitted when it was embedded with
+    /// `embed_migrations!(omit_down = true)`
+    #[error("migration {0} was embedded without its down section, with omit_down = true")]
+    DownOmitted(Migration),
+    /// An Error from rolling back to a Target that isn't a version or a number of steps
+    #[error(
+        "invalid rollback target {0:?}, it must be a version, fake:{{version}} or steps:{{number}}"
+    )]
+    InvalidRollbackTarget(Target),
+    /// An Error from a migration that depends on the database dialect, run on a connection that doesn't report it
+    #[error(
+        "migration {0} depends on the database dialect, but the connection dialect is unknown"
+    )]
+    UnknownDialect(Migration),
+    /// An Error from a Rust migration generating its sql for a database dialect it doesn't support, i.e. with
+    /// `sea_query::MigrationBuilder`, which has no backend for some of them
+    #[error("can't generate the migration sql for {0}, the dialect isn't supported")]
+    UnsupportedDialect(Dialect),
+    /// An Error from an async Rust migration run synchronously, grouped with other migrations or on a connection
+    /// that doesn't provide an `AsyncExecutor` to run it on
+    #[error("migration {0} is an async Rust migration, it can only be run on its own with run_async on a connection that provides an executor")]
+    UnsupportedAsyncMigration(Migration),
+    /// An Error from a `${name}` placeholder of a migration without a value, when placeholders are strict
+    #[error("placeholder ${{{0}}} of migration {1} has no value")]
+    UnknownPlaceholder(String, Migration),
+    /// An Error from a migration marked with the `-- refinery:no-transaction` directive run grouped with other
+    /// migrations in a single transaction
+    #[error("migration {0} is marked no-transaction, it can't be grouped with other migrations in a single transaction")]
+    GroupedNoTransaction(Migration),
+    /// An Error from a grouped run on a dialect that can't roll back schema changes, so the migrations can't be
+    /// applied atomically, see `Runner::set_grouped_fallback`
+    #[error("{0} can't roll back schema changes, the migrations can't be grouped in a single transaction")]
+    GroupingNotSupported(Dialect),
+    /// An Error from a `-- refinery:` directive of a migration with an invalid value
+    #[error("invalid directive {1} on migration {0}")]
+    InvalidDirective(String, String),
+    /// An Error from a migration run that exceeded the timeout set with `Runner::set_run_timeout`,
+    /// the migrations applied before it are on the Error report
+    #[error("migration run exceeded its timeout of {0:?}")]
+    RunTimeout(Duration),
+    /// An Error from a migration the database cancelled for exceeding its timeout, set with `Runner::set_timeout`
+    /// or its `-- refinery:timeout` directive, the migrations applied before it are on the Error report
+    #[error("migration {0} exceeded its timeout of {1:?}")]
+    Timeout(Migration, Duration),
+    /// An Error from a migration run cancelled with its `CancelHandle`,
+    /// the migrations applied before it are on the Error report
+    #[error("migration run was cancelled")]
+    Cancelled,
+    /// An Error from verification checks run on a connection that doesn't report its dialect
+    #[error(
+        "verification checks depend on the database dialect, but the connection dialect is unknown"
+    )]
+    UnknownCheckDialect,
+    /// An Error from a `Rollout` aborted on the canary database or on one of the targets after it,
+    /// the Error report has the migrations applied on it
+    #[error("rollout aborted on {0}: {1}")]
+    RolloutAborted(RolloutStage, #[source] Error),
+    /// An Error from a schema history record imported with `Runner::import_history` with an invalid version, name
+    /// or checksum
+    #[error("invalid schema history record {0}, {1}")]
+    InvalidHistoryRecord(String, String),
+    /// An Error from a schema history record imported with `Runner::import_history` with the version of an applied
+    /// migration but a different name or checksum
+    #[error("applied migration {0} is different than imported one {1}")]
+    ConflictingHistoryRecord(Migration, Migration),
+    /// An Error from a `MultiTenantRunner` aborted on one of its tenants, the Error report has the migrations applied
+    /// on it
+    #[error("migrating tenant {0} aborted: {1}")]
+    TenantAborted(String, #[source] Error),
+    /// An Error from a `MultiTenantRunner` run on a connection whose dialect can't switch to the schema of a tenant
+    #[error("migrating tenants needs a Postgres, Cockroach or Mysql connection, the connection dialect is {0:?}")]
+    UnsupportedTenantDialect(Option<Dialect>),
+    /// An Error from an invalid namespace, see `Runner::set_namespace`
+    #[error("invalid migration namespace {0:?}, it can only have alphanumerics, `_` and `-`")]
+    InvalidNamespace(String),
+    /// An Error from a namespace set on a schema history table with the version alone as primary key, as created
+    /// before namespaces, see `Runner::set_namespace`
+    #[error("the schema history table {0} has the version alone as primary key, change it to (version, name) to share it between namespaces")]
+    LegacyPrimaryKey(String),
+    /// An Error from an invalid migration table name, or one qualified with more parts than its dialect supports
+    #[error("invalid migration table name {0}, {1}")]
+    InvalidMigrationTableName(String, String),
     /// An Error from an invalid migrations path location
     #[error("invalid migrations path {0}, {1}")]
     InvalidMigrationPath(PathBuf, std::io::Error),
+    /// An Error reading, writing or parsing a lockfile
+    #[error("invalid lockfile {0}, {1}")]
+    InvalidLockfile(PathBuf, String),
+    /// An Error writing the schema snapshot set with `Runner::set_snapshot_path`, or from a connection that can't
+    /// introspect its schema
+    #[error("could not write schema snapshot {0}, {1}")]
+    InvalidSnapshot(PathBuf, String),
     /// An Error parsing refinery Config
     #[error("Error parsing config: {0}")]
     ConfigError(String),
+    /// An Error creating the database of a Config with `create_db_if_missing`, as the user lacks the permission to
+    /// create it
+    #[error("could not create database {0}, permission denied: {1}")]
+    CreateDatabaseDenied(String, #[source] Box<dyn std::error::Error + Sync + Send>),
     /// An Error from an underlying database connection Error
     #[error("`{0}`, `{1}`")]
     Connection(String, #[source] Box<dyn std::error::Error + Sync + Send>),
+    /// An Error from migration files on the migrations location with the same version for the same database dialect,
+    /// usually on different subdirectories
+    #[error("migrations {0} and {1} conflict, they have the same version and dialect")]
+    ConflictingMigrations(PathBuf, PathBuf),
+    /// An Error from migration files named with different [`MigrationNaming`](crate::MigrationNaming) schemes
+    #[error("migrations {0} and {1} are named with different naming schemes, a project must use one consistently")]
+    MixedNaming(PathBuf, PathBuf),
     /// An Error from an invalid migration file (not UTF-8 etc)
     #[error("invalid migration file at path {0}, {1}")]
     InvalidMigrationFile(PathBuf, std::io::Error),
+    /// An Error reading a migrations archive, or from one that isn't a tar, gzipped tar or zip archive
+    #[error("invalid migrations archive, {0}")]
+    InvalidArchive(std::io::Error),
+    /// An Error from an invalid glob pattern of a `MigrationLoader`
+    #[error("invalid glob pattern {0}, {1}")]
+    InvalidGlobPattern(String, String),
+    /// An Error from the migration files a `MigrationLoader` couldn't load, with the Error of each of them
+    #[error("{} invalid migration files: {}", .0.len(), display_file_errors(.0))]
+    InvalidMigrationFiles(Vec<(PathBuf, Error)>),
+}
+
+fn display_file_errors(errors: &[(PathBuf, Error)]) -> String {
+    errors
+        .iter()
+        .map(|(path, err)| format!("{}: {}", path.display(), err))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 // Helper trait for adding custom messages and applied migrations to Connection error's.
@@ -93,6 +339,7 @@ where
             Err(err) => Err(Error {
                 kind: Box::new(Kind::Connection(msg.into(), Box::new(err))),
                 report: applied_migrations.map(|am| Report::new(am.to_vec())),
+                statement: None,
             }),
         }
     }
@@ -0,0 +1,149 @@
+use crate::error::Kind;
+use crate::util::recorded_name_re;
+use crate::{ChecksumAlgorithm, Error, Migration};
+use time::OffsetDateTime;
+
+/// A row of the schema history table, exported with [`Runner::export_history`] to be imported on a database rebuilt
+/// from a backup or on a schema history table with another name with [`Runner::import_history`].
+/// `name` includes the namespace of migrations applied on one, i.e. `billing/initial`, and `checksum` the prefix of
+/// its [`ChecksumAlgorithm`], as they're recorded on the table
+///
+/// [`Runner::export_history`]: crate::Runner::export_history
+/// [`Runner::import_history`]: crate::Runner::import_history
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AppliedMigrationRecord {
+    pub version: u32,
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
+    pub applied_on: OffsetDateTime,
+    pub checksum: String,
+}
+
+impl AppliedMigrationRecord {
+    // the record of a migration queried from the schema history table
+    pub(crate) fn from_applied(migration: &Migration) -> AppliedMigrationRecord {
+        AppliedMigrationRecord {
+            version: migration.version(),
+            name: migration.recorded_name(),
+            // Safe to call unwrap, as migrations queried from the database have been applied
+            applied_on: *migration.applied_on().unwrap(),
+            checksum: migration.recorded_checksum(),
+        }
+    }
+
+    // the applied migration the record is of, failing on records with an invalid name or checksum
+    fn to_applied(&self) -> Result<Migration, Error> {
+        let invalid = |reason: &str| {
+            Error::new(
+                Kind::InvalidHistoryRecord(
+                    format!("{} {}", self.version, self.name),
+                    reason.into(),
+                ),
+                None,
+            )
+        };
+        let version = i32::try_from(self.version).map_err(|_| invalid("version is too large"))?;
+        if !recorded_name_re().is_match(&self.name) {
+            return Err(invalid("invalid name"));
+        }
+        if ChecksumAlgorithm::parse_checksum(&self.checksum).is_none() {
+            return Err(invalid(&format!("invalid checksum {}", self.checksum)));
+        }
+        Ok(Migration::applied_with_checksum(
+            version,
+            self.name.clone(),
+            self.applied_on,
+            &self.checksum,
+        ))
+    }
+}
+
+// the migrations of the records that aren't on the schema history table yet. Records already on it are skipped,
+// and ones with the version of an applied migration of the same namespace but another name or checksum fail
+pub(crate) fn imported_migrations(
+    records: &[AppliedMigrationRecord],
+    applied: &[Migration],
+) -> Result<Vec<Migration>, Error> {
+    let mut imported: Vec<Migration> = Vec::new();
+    for record in records {
+        let migration = record.to_applied()?;
+        let existing = applied.iter().chain(&imported).find(|existing| {
+            existing.version() == migration.version()
+                && existing.namespace() == migration.namespace()
+        });
+        match existing {
+            Some(existing) if existing == &migration => continue,
+            Some(existing) => {
+                return Err(Error::new(
+                    Kind::ConflictingHistoryRecord(existing.clone(), migration),
+                    None,
+                ))
+            }
+            None => imported.push(migration),
+        }
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{imported_migrations, AppliedMigrationRecord};
+    use crate::error::Kind;
+    use crate::Migration;
+    use time::OffsetDateTime;
+
+    // 2024-03-01T10:00:00Z
+    fn applied_on() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_709_287_200).unwrap()
+    }
+
+    fn record(version: u32, name: &str, checksum: &str) -> AppliedMigrationRecord {
+        AppliedMigrationRecord {
+            version,
+            name: name.into(),
+            applied_on: applied_on(),
+            checksum: checksum.into(),
+        }
+    }
+
+    #[test]
+    fn imports_missing_records() {
+        let applied = vec![Migration::applied(1, "initial".into(), applied_on(), 42)];
+        let records = vec![
+            record(1, "initial", "42"),
+            record(2, "add_cars", "sha256:00000000000000ff"),
+            record(1, "billing/initial", "7"),
+        ];
+
+        let imported = imported_migrations(&records, &applied).unwrap();
+        assert_eq!(2, imported.len());
+        assert_eq!("add_cars", imported[0].name());
+        assert_eq!(Some("billing"), imported[1].namespace());
+        assert_eq!(
+            record(2, "add_cars", "sha256:00000000000000ff"),
+            AppliedMigrationRecord::from_applied(&records[1].to_applied().unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_and_invalid_records() {
+        let applied = vec![Migration::applied(1, "initial".into(), applied_on(), 42)];
+
+        let err = imported_migrations(&[record(1, "initial", "43")], &applied).unwrap_err();
+        assert!(matches!(err.kind(), Kind::ConflictingHistoryRecord(..)));
+
+        let err = imported_migrations(&[record(2, "add_cars", "md5:ff")], &applied).unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidHistoryRecord(..)));
+
+        for name in [
+            "",
+            "billing/",
+            "add_cars'); DROP TABLE cars; --",
+            "a/b/add_cars",
+        ] {
+            let err = imported_migrations(&[record(2, name, "42")], &applied).unwrap_err();
+            assert!(matches!(err.kind(), Kind::InvalidHistoryRecord(..)));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Introspects the schema of a live database into a normalized model of its tables, columns, indexes and constraints.
+//!
+//! [`Introspect`] is implemented for the `postgres`, `rusqlite` and `mysql` connections, and [`AsyncIntrospect`]
+//! for the `tokio-postgres` one. Index and constraint definitions are kept in the syntax of each database,
+//! so snapshots are only comparable between databases of the same dialect.
+use crate::error::Kind;
+use crate::Error;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A normalized model of the tables of a database schema, by name.
+/// It includes the migrations table, remove it to compare schemas that may be at different versions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Schema {
+    pub tables: BTreeMap<String, Table>,
+}
+
+/// A table, with its columns in order, and its indexes and constraints by name with their definition.
+/// Indexes that back a constraint, like primary keys, are only listed as constraints
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+    pub columns: Vec<Column>,
+    pub indexes: BTreeMap<String, String>,
+    pub constraints: BTreeMap<String, String>,
+}
+
+/// A table column, `data_type` and `default` are as reported by the database
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+impl Schema {
+    /// get the table with the given name
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    /// Render the schema as canonical sql, a `CREATE TABLE` statement for each table with its columns in order and its
+    /// constraints, followed by its indexes, with the tables, constraints and indexes sorted by name, so snapshots of
+    /// the same schema are identical and can be tracked to detect drift
+    pub fn to_sql(&self) -> String {
+        let tables: Vec<String> =
+            self.tables
+                .iter()
+                .map(|(name, table)| {
+                    let definitions: Vec<String> = table
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            let mut definition = format!("{} {}", column.name, column.data_type);
+                            if !column.nullable {
+                                definition.push_str(" NOT NULL");
+                            }
+                            if let Some(default) = &column.default {
+                                definition.push_str(&format!(" DEFAULT {}", default));
+                            }
+                            definition.trim_end().to_string()
+                        })
+                        .chain(table.constraints.iter().map(|(name, definition)| {
+                            format!("CONSTRAINT {} {}", name, definition)
+                        }))
+                        .collect();
+                    let mut sql = format!(
+                        "CREATE TABLE {} (\n    {}\n);\n",
+                        name,
+                        definitions.join(",\n    ")
+                    );
+                    for definition in table.indexes.values() {
+                        sql.push_str(&format!("{};\n", definition));
+                    }
+                    sql
+                })
+                .collect();
+        tables.join("\n")
+    }
+
+    /// Serialize the schema to pretty printed JSON, with the tables sorted by name
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("schema must be serializable")
+    }
+
+    // write the snapshot of the schema to the given path, as json if its extension is `json` and as sql otherwise,
+    // see [`crate::Runner::set_snapshot_path`]
+    pub(crate) fn write_snapshot(&self, path: &Path) -> Result<(), Error> {
+        let invalid =
+            |reason: String| Error::new(Kind::InvalidSnapshot(path.to_path_buf(), reason), None);
+        let snapshot = match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "serde")]
+            Some("json") => self.to_json(),
+            #[cfg(not(feature = "serde"))]
+            Some("json") => return Err(invalid("json snapshots need the serde feature".into())),
+            _ => self.to_sql(),
+        };
+        std::fs::write(path, snapshot).map_err(|err| invalid(err.to_string()))
+    }
+}
+
+#[cfg(any(
+    feature = "rusqlite",
+    feature = "postgres",
+    feature = "tokio-postgres",
+    feature = "mysql",
+    test
+))]
+impl Schema {
+    pub(crate) fn add_column(&mut self, table: String, column: Column) {
+        self.tables.entry(table).or_default().columns.push(column);
+    }
+
+    // indexes and constraints are only added to tables which columns were introspected
+    pub(crate) fn add_index(&mut self, table: &str, name: String, definition: String) {
+        if let Some(table) = self.tables.get_mut(table) {
+            table.indexes.insert(name, definition);
+        }
+    }
+
+    pub(crate) fn add_constraint(&mut self, table: &str, name: String, definition: String) {
+        if let Some(table) = self.tables.get_mut(table) {
+            table.constraints.insert(name, definition);
+        }
+    }
+}
+
+impl Table {
+    /// get the column with the given name
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+/// Introspect the schema of a database connection, for Postgres the current schema,
+/// i.e. the first one of the `search_path`, and for Mysql the current database
+pub trait Introspect {
+    fn snapshot(&mut self) -> Result<Schema, Error>;
+}
+
+#[async_trait]
+pub trait AsyncIntrospect {
+    async fn snapshot(&mut self) -> Result<Schema, Error>;
+}
+
+/// Introspect the schema of the given connection, see [`Introspect`]
+pub fn snapshot<C: Introspect>(conn: &mut C) -> Result<Schema, Error> {
+    conn.snapshot()
+}
+
+/// Introspect the schema of the given async connection, see [`AsyncIntrospect`]
+pub async fn snapshot_async<C: AsyncIntrospect + Send>(conn: &mut C) -> Result<Schema, Error> {
+    conn.snapshot().await
+}
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) const POSTGRES_COLUMNS_QUERY: &str = "SELECT c.relname, a.attname, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull, pg_get_expr(d.adbin, d.adrelid)
+    FROM pg_attribute a
+    JOIN pg_class c ON c.oid = a.attrelid
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+    WHERE n.nspname = $1 AND c.relkind = 'r' AND a.attnum > 0 AND NOT a.attisdropped
+    ORDER BY c.relname, a.attnum";
+
+// indexes backing constraints are created with them
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) const POSTGRES_INDEXES_QUERY: &str =
+    "SELECT t.relname, i.relname, pg_get_indexdef(i.oid)
+    FROM pg_index x
+    JOIN pg_class i ON i.oid = x.indexrelid
+    JOIN pg_class t ON t.oid = x.indrelid
+    JOIN pg_namespace n ON n.oid = t.relnamespace
+    WHERE n.nspname = $1 AND t.relkind = 'r'
+    AND NOT EXISTS (SELECT 1 FROM pg_constraint con WHERE con.conindid = i.oid)";
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) const POSTGRES_CONSTRAINTS_QUERY: &str =
+    "SELECT c.relname, con.conname, pg_get_constraintdef(con.oid)
+    FROM pg_constraint con
+    JOIN pg_class c ON c.oid = con.conrelid
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE n.nspname = $1 AND c.relkind = 'r'";
+
+// constraints added as NOT VALID, with the columns of the foreign keys to check them
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) const POSTGRES_UNVALIDATED_CONSTRAINTS_QUERY: &str =
+    "SELECT con.conrelid::regclass::text, con.conname, con.contype = 'f', con.confrelid::regclass::text,
+    ARRAY(SELECT quote_ident(a.attname) FROM unnest(con.conkey) WITH ORDINALITY k(attnum, i)
+        JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum ORDER BY k.i),
+    ARRAY(SELECT quote_ident(a.attname) FROM unnest(con.confkey) WITH ORDINALITY k(attnum, i)
+        JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum ORDER BY k.i)
+    FROM pg_constraint con
+    WHERE NOT con.convalidated AND con.conrelid <> 0
+    ORDER BY 1, 2";
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) const POSTGRES_INVALID_INDEXES_QUERY: &str =
+    "SELECT x.indrelid::regclass::text, x.indexrelid::regclass::text
+    FROM pg_index x
+    WHERE NOT x.indisvalid
+    ORDER BY 1, 2";
+
+// checks if any row of `table` with all the foreign key columns set doesn't match a row of `references`
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) fn postgres_foreign_key_violation_query(
+    table: &str,
+    references: &str,
+    columns: &[String],
+    referenced_columns: &[String],
+) -> String {
+    let not_null: Vec<String> = columns
+        .iter()
+        .map(|column| format!("t.{} IS NOT NULL", column))
+        .collect();
+    let matches: Vec<String> = columns
+        .iter()
+        .zip(referenced_columns)
+        .map(|(column, referenced)| format!("r.{} = t.{}", referenced, column))
+        .collect();
+    format!(
+        "SELECT EXISTS (SELECT 1 FROM {} t WHERE {} AND NOT EXISTS (SELECT 1 FROM {} r WHERE {}))",
+        table,
+        not_null.join(" AND "),
+        references,
+        matches.join(" AND ")
+    )
+}
+
+// index definitions are always schema qualified, strip it so they can be compared across schemas
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub(crate) fn unqualified_index(definition: &str, schema: &str) -> String {
+    definition.replacen(&format!(" ON {}.", schema), " ON ", 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Column, Schema};
+
+    #[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+    #[test]
+    fn builds_postgres_foreign_key_violation_query() {
+        let query = super::postgres_foreign_key_violation_query(
+            "pets",
+            "owners",
+            &["owner_id".into(), "\"Kind\"".into()],
+            &["id".into(), "kind".into()],
+        );
+        assert_eq!(
+            "SELECT EXISTS (SELECT 1 FROM pets t WHERE t.owner_id IS NOT NULL AND t.\"Kind\" IS NOT NULL \
+            AND NOT EXISTS (SELECT 1 FROM owners r WHERE r.id = t.owner_id AND r.kind = t.\"Kind\"))",
+            query
+        );
+    }
+
+    #[test]
+    fn renders_canonical_sql() {
+        let mut schema = Schema::default();
+        for (table, name, data_type, nullable, default) in [
+            ("users", "id", "integer", false, None),
+            ("users", "name", "text", true, Some("'anonymous'")),
+            ("cars", "id", "", true, None),
+        ] {
+            schema.add_column(
+                table.into(),
+                Column {
+                    name: name.into(),
+                    data_type: data_type.into(),
+                    nullable,
+                    default: default.map(Into::into),
+                },
+            );
+        }
+        schema.add_constraint("users", "users_pkey".into(), "PRIMARY KEY (id)".into());
+        schema.add_index(
+            "users",
+            "users_name".into(),
+            "CREATE INDEX users_name ON users (name)".into(),
+        );
+
+        assert_eq!(
+            concat!(
+                "CREATE TABLE cars (\n    id\n);\n",
+                "\n",
+                "CREATE TABLE users (\n",
+                "    id integer NOT NULL,\n",
+                "    name text DEFAULT 'anonymous',\n",
+                "    CONSTRAINT users_pkey PRIMARY KEY (id)\n",
+                ");\n",
+                "CREATE INDEX users_name ON users (name);\n",
+            ),
+            schema.to_sql()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn writes_json_snapshots() {
+        let mut schema = Schema::default();
+        schema.add_column(
+            "users".into(),
+            Column {
+                name: "id".into(),
+                data_type: "integer".into(),
+                nullable: false,
+                default: None,
+            },
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("schema.json");
+        schema.write_snapshot(&path).unwrap();
+        let written: Schema =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(schema, written);
+    }
+
+    #[test]
+    fn only_adds_indexes_and_constraints_to_introspected_tables() {
+        let mut schema = Schema::default();
+        schema.add_column(
+            "users".into(),
+            Column {
+                name: "id".into(),
+                data_type: "integer".into(),
+                nullable: false,
+                default: None,
+            },
+        );
+        schema.add_constraint("users", "users_pkey".into(), "PRIMARY KEY (id)".into());
+        schema.add_index("cars", "cars_brand".into(), "CREATE INDEX ...".into());
+
+        assert_eq!(1, schema.tables.len());
+        let users = schema.table("users").unwrap();
+        assert_eq!("integer", users.column("id").unwrap().data_type);
+        assert_eq!("PRIMARY KEY (id)", users.constraints["users_pkey"]);
+        assert!(users.indexes.is_empty());
+    }
+}
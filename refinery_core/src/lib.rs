@@ -1,16 +1,63 @@
+pub mod analyze;
+#[cfg(feature = "archive")]
+pub mod archive;
+mod checksum;
 pub mod config;
+#[cfg(feature = "diff")]
+pub mod diff;
 mod drivers;
 pub mod error;
+mod history;
+pub mod introspect;
+#[cfg(feature = "fs")]
+pub mod lint;
+#[cfg(feature = "fs")]
+mod loader;
+#[cfg(feature = "fs")]
+pub mod lockfile;
+pub mod manifest;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod registry;
+mod rollout;
 mod runner;
+#[cfg(feature = "fs")]
+pub mod scaffold;
+#[cfg(feature = "sea-query")]
+pub mod sea_query;
+mod shards;
+mod splitter;
+mod tenants;
+#[cfg(feature = "tracing")]
+mod tracing;
 pub mod traits;
 mod util;
 
-pub use crate::error::Error;
-pub use crate::runner::{Migration, Report, Runner, Target};
-pub use crate::traits::r#async::AsyncMigrate;
-pub use crate::traits::sync::Migrate;
+pub use crate::checksum::ChecksumAlgorithm;
+pub use crate::error::{Error, FailedStatement};
+pub use crate::history::AppliedMigrationRecord;
+#[cfg(feature = "fs")]
+pub use crate::loader::MigrationLoader;
+pub use crate::rollout::{Rollout, RolloutStage};
+pub use crate::runner::{
+    CancelHandle, ChecksumMismatch, Dialect, IntegrityIssue, Migration, Report, Runner, Target,
+    Type, ValidationIssue, ValidationReport,
+};
+pub use crate::shards::{ShardFailurePolicy, ShardResults, ShardRunner};
+pub use crate::splitter::Splitter;
+pub use crate::tenants::MultiTenantRunner;
+pub use crate::traits::r#async::{AsyncExecutor, AsyncMigrate};
+pub use crate::traits::sync::{Migrate, RefineryConnection, SchemaHistoryRow};
+pub use crate::traits::{DefaultSchemaHistoryDdl, SchemaHistoryDdl};
+#[cfg(feature = "fs")]
+pub use crate::util::{
+    assert_no_conflicts, find_migration_files, find_migration_files_with_depth,
+    load_sql_migrations, load_sql_migrations_with_depth, migration_file_name,
+    rerun_if_migrations_changed,
+};
 pub use crate::util::{
-    find_migration_files, load_sql_migrations, parse_migration_name, MigrationType,
+    format_timestamp_version, parse_migration_dialect, parse_migration_name,
+    parse_migration_naming, strip_down_section, MigrationNaming, MigrationType,
 };
 
 #[cfg(feature = "rusqlite")]
@@ -28,5 +75,39 @@ pub use tokio_postgres;
 #[cfg(feature = "mysql_async")]
 pub use mysql_async;
 
+#[cfg(any(
+    feature = "sqlx-postgres",
+    feature = "sqlx-mysql",
+    feature = "sqlx-sqlite"
+))]
+pub use sqlx;
+
+#[cfg(feature = "libsql")]
+pub use libsql;
+
+#[cfg(feature = "clickhouse")]
+pub use clickhouse;
+
+#[cfg(feature = "duckdb")]
+pub use duckdb;
+
+#[cfg(feature = "oracle")]
+pub use oracle;
+
+#[cfg(feature = "odbc")]
+pub use odbc_api;
+
 #[cfg(feature = "tiberius")]
 pub use tiberius;
+
+#[cfg(any(feature = "bb8-tiberius", feature = "bb8-postgres"))]
+pub use bb8;
+
+#[cfg(feature = "bb8-tiberius")]
+pub use bb8_tiberius;
+
+#[cfg(feature = "bb8-postgres")]
+pub use bb8_postgres;
+
+#[cfg(feature = "deadpool-postgres")]
+pub use deadpool_postgres;
@@ -0,0 +1,603 @@
+//! Lints a migrations location without a database connection: file naming, encoding, repeated or missing versions,
+//! invalid directives, forbidden statements like `DROP DATABASE`, and statements that behave unexpectedly on a dialect.
+use crate::error::{Error, Kind};
+use crate::runner::Type;
+use crate::util::{
+    migration_file_name, parse_migration_dialect, parse_migration_name, parse_migration_naming,
+    strip_comments, MigrationNaming, MigrationType,
+};
+use crate::{Dialect, Migration};
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The severity of a [`LintIssue`], only errors fail `refinery lint`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(fmt, "warning"),
+            Severity::Error => write!(fmt, "error"),
+        }
+    }
+}
+
+/// The rule a [`LintIssue`] was found by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintRule {
+    /// A `.sql` or `.rs` file not named `[U|V|S]{version}__{name}`, which is ignored when migrating
+    Naming,
+    /// A sql migration that isn't valid UTF-8, or starts with a byte order mark
+    Encoding,
+    /// A migration with an invalid `-- refinery:` directive
+    Directive,
+    /// Two migrations with the same version and dialect
+    RepeatedVersion,
+    /// A gap between the versions of the versioned migrations
+    VersionGap,
+    /// A statement forbidden with [`Linter::set_forbidden_statements`]
+    ForbiddenStatement,
+    /// A statement that behaves unexpectedly on the dialect of the migration
+    Dialect,
+    /// A migration of the base revision that was modified or removed, see [`Linter::lint_changes`]
+    ChangedMigration,
+    /// A new versioned migration with a version not greater than the ones of the base revision
+    OutOfOrderVersion,
+}
+
+/// A migration file added, modified or removed relative to a base revision, i.e. the main branch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigrationChange {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// An issue found on a migration file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintIssue {
+    pub path: PathBuf,
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{}: {}: {}",
+            self.severity,
+            self.path.display(),
+            self.message
+        )
+    }
+}
+
+// statements that behave unexpectedly on a dialect when found at least the given times,
+// matched case insensitively against the sql without comments. Those that only fail inside a transaction
+// don't apply to migrations marked with `-- refinery:no-transaction`
+const DIALECT_RULES: &[(Dialect, &str, usize, bool, &str)] = &[
+    (
+        Dialect::Postgres,
+        r"\b(CREATE|DROP)\s+(UNIQUE\s+)?INDEX\s+CONCURRENTLY\b|\bREINDEX\b[^;]*\bCONCURRENTLY\b",
+        1,
+        true,
+        "concurrent index operations can't run inside the transaction migrations are applied in, mark the migration with -- refinery:no-transaction",
+    ),
+    (
+        Dialect::Postgres,
+        r"\bVACUUM\b",
+        1,
+        true,
+        "VACUUM can't run inside the transaction migrations are applied in, mark the migration with -- refinery:no-transaction",
+    ),
+    (
+        Dialect::Mysql,
+        r"\b(CREATE|ALTER|DROP|RENAME|TRUNCATE)\s+(TABLE|INDEX|VIEW)\b",
+        2,
+        false,
+        "schema changes are committed implicitly on Mysql, a failure can leave the migration partially applied",
+    ),
+    (
+        Dialect::Sqlite,
+        r"\bALTER\s+TABLE\b[^;]*\bADD\s+(COLUMN\s+)?\S+[^;]*\b(PRIMARY\s+KEY|UNIQUE)\b",
+        1,
+        false,
+        "Sqlite can't add PRIMARY KEY or UNIQUE columns with ALTER TABLE",
+    ),
+    (
+        Dialect::Mssql,
+        r"\b(CREATE|ALTER|DROP)\s+DATABASE\b|\bBACKUP\b|\bRESTORE\b",
+        1,
+        true,
+        "database level statements can't run inside the transaction migrations are applied in, mark the migration with -- refinery:no-transaction",
+    ),
+];
+
+// matches the statement case insensitively, with any whitespace between its words
+fn statement_re(statement: &str) -> Regex {
+    let words: Vec<String> = statement.split_whitespace().map(regex::escape).collect();
+    RegexBuilder::new(&format!(r"\b{}\b", words.join(r"\s+")))
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+}
+
+/// Struct that lints the migrations of a location
+pub struct Linter {
+    contiguous_versions: bool,
+    forbidden_statements: Vec<String>,
+    dialect: Option<Dialect>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// instantiate a new Linter
+    pub fn new() -> Linter {
+        Linter {
+            contiguous_versions: true,
+            forbidden_statements: vec!["DROP DATABASE".into()],
+            dialect: None,
+        }
+    }
+
+    /// Set true if gaps between the versions of the versioned migrations are errors, otherwise they are warnings.
+    /// by default this is set to true
+    pub fn set_contiguous_versions(self, contiguous_versions: bool) -> Linter {
+        Linter {
+            contiguous_versions,
+            ..self
+        }
+    }
+
+    /// Set the statements migrations must not contain, matched case insensitively.
+    /// by default this is set to `DROP DATABASE`
+    pub fn set_forbidden_statements<S: AsRef<str>>(self, statements: &[S]) -> Linter {
+        Linter {
+            forbidden_statements: statements
+                .iter()
+                .map(|statement| statement.as_ref().to_string())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Set the [`Dialect`] the migrations are checked for, migration variants are always checked for their own.
+    /// by default only migration variants are checked
+    pub fn set_dialect(self, dialect: Dialect) -> Linter {
+        Linter {
+            dialect: Some(dialect),
+            ..self
+        }
+    }
+
+    /// Lint the migrations found recursively on the given location, returning the issues found ordered by path
+    pub fn lint(&self, location: impl AsRef<Path>) -> Result<Vec<LintIssue>, Error> {
+        let location = location.as_ref();
+        let location = location.canonicalize().map_err(|err| {
+            Error::new(
+                Kind::InvalidMigrationPath(location.to_path_buf(), err),
+                None,
+            )
+        })?;
+
+        let mut issues = Vec::new();
+        let mut seen: HashMap<(bool, i32, Option<Dialect>), PathBuf> = HashMap::new();
+        // unversioned migrations are applied in any order, so only versioned ones need to be contiguous
+        let mut versioned: BTreeMap<i32, PathBuf> = BTreeMap::new();
+        let mut first_naming: Option<(MigrationNaming, PathBuf)> = None;
+        for path in WalkDir::new(&location)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(walkdir::DirEntry::into_path)
+        {
+            let Some(extension) = path.extension().and_then(OsStr::to_str) else {
+                continue;
+            };
+            if extension != "sql" && extension != "rs" {
+                continue;
+            }
+            let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+            if !MigrationType::All.file_match_re().is_match(file_name) {
+                issues.push(issue(
+                    &path,
+                    LintRule::Naming,
+                    Severity::Error,
+                    "migrations must be named [U|V|S]{version}__{name}.sql or [U|V|S]{version}__{name}.rs, or {YYYYMMDD}_{HHMMSS}_{name} with the timestamp naming"
+                        .into(),
+                ));
+                continue;
+            }
+
+            let name = migration_file_name(&location, &path);
+            let (prefix, version, _) = match parse_migration_name(&name) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    issues.push(issue(
+                        &path,
+                        LintRule::Naming,
+                        Severity::Error,
+                        err.to_string(),
+                    ));
+                    continue;
+                }
+            };
+            let naming = parse_migration_naming(&name).unwrap_or_default();
+            match &first_naming {
+                Some((first, first_path)) if *first != naming => issues.push(issue(
+                    &path,
+                    LintRule::Naming,
+                    Severity::Error,
+                    format!(
+                        "migration is named with another naming scheme than {}, a project must use one consistently",
+                        first_path.display()
+                    ),
+                )),
+                Some(_) => {}
+                None => first_naming = Some((naming, path.clone())),
+            }
+            let dialect = parse_migration_dialect(&name);
+            if let Some(repeated) =
+                seen.insert((prefix == Type::Seed, version, dialect), path.clone())
+            {
+                issues.push(issue(
+                    &path,
+                    LintRule::RepeatedVersion,
+                    Severity::Error,
+                    format!("version {} is repeated on {}", version, repeated.display()),
+                ));
+            }
+            // timestamp migrations always have gaps between them
+            if prefix == Type::Versioned && naming == MigrationNaming::Versioned {
+                versioned.entry(version).or_insert_with(|| path.clone());
+            }
+
+            if extension == "sql" {
+                self.lint_sql(&path, &name, dialect, &mut issues)?;
+            }
+        }
+
+        let severity = if self.contiguous_versions {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        let mut previous: Option<i32> = None;
+        for (version, path) in &versioned {
+            if let Some(previous) = previous.filter(|previous| *version > previous + 1) {
+                issues.push(issue(
+                    path,
+                    LintRule::VersionGap,
+                    severity,
+                    format!("versions {} to {} are missing", previous + 1, version - 1),
+                ));
+            }
+            previous = Some(*version);
+        }
+
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(issues)
+    }
+
+    /// Lint only the migrations changed relative to a base revision, where the migrations are already applied:
+    /// migrations of the base revision must not be modified or removed, and new versioned migrations must have
+    /// a version greater than `base_version`, the highest of the base revision.
+    /// Paths of changes that exist are canonicalized to match the ones of the issues
+    pub fn lint_changes(
+        &self,
+        location: impl AsRef<Path>,
+        changes: &[MigrationChange],
+        base_version: Option<i32>,
+    ) -> Result<Vec<LintIssue>, Error> {
+        let canonical = |path: &PathBuf| path.canonicalize().unwrap_or_else(|_| path.clone());
+        let added: Vec<PathBuf> = changes
+            .iter()
+            .filter_map(|change| match change {
+                MigrationChange::Added(path) => Some(canonical(path)),
+                _ => None,
+            })
+            .collect();
+
+        let mut issues: Vec<LintIssue> = self
+            .lint(location)?
+            .into_iter()
+            .filter(|issue| added.contains(&issue.path))
+            .collect();
+
+        for change in changes {
+            let (path, action) = match change {
+                MigrationChange::Added(path) => {
+                    let path = canonical(path);
+                    let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+                    if let Ok((Type::Versioned, version, _)) = parse_migration_name(stem) {
+                        match base_version {
+                            Some(base_version) if version <= base_version => {
+                                issues.push(issue(
+                                    &path,
+                                    LintRule::OutOfOrderVersion,
+                                    Severity::Error,
+                                    format!(
+                                        "version {} must be greater than {}, the highest of the base revision",
+                                        version, base_version
+                                    ),
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                MigrationChange::Modified(path) => (canonical(path), "modified"),
+                MigrationChange::Removed(path) => (path.clone(), "removed"),
+            };
+            let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+            if MigrationType::All.file_match_re().is_match(file_name) {
+                issues.push(issue(
+                    &path,
+                    LintRule::ChangedMigration,
+                    Severity::Error,
+                    format!("migration was {} after being merged", action),
+                ));
+            }
+        }
+
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(issues)
+    }
+
+    fn lint_sql(
+        &self,
+        path: &Path,
+        name: &str,
+        dialect: Option<Dialect>,
+        issues: &mut Vec<LintIssue>,
+    ) -> Result<(), Error> {
+        let bytes = fs::read(path)
+            .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.to_path_buf(), err), None))?;
+        let sql = match String::from_utf8(bytes) {
+            Ok(sql) => sql,
+            Err(_) => {
+                issues.push(issue(
+                    path,
+                    LintRule::Encoding,
+                    Severity::Error,
+                    "migration is not valid UTF-8".into(),
+                ));
+                return Ok(());
+            }
+        };
+        if sql.starts_with('\u{feff}') {
+            issues.push(issue(
+                path,
+                LintRule::Encoding,
+                Severity::Warning,
+                "migration starts with a byte order mark".into(),
+            ));
+        }
+
+        let transactional = match Migration::unapplied(name, &sql) {
+            Ok(migration) => migration.is_transactional(),
+            Err(err) => {
+                if let Kind::InvalidDirective(..) = err.kind() {
+                    issues.push(issue(
+                        path,
+                        LintRule::Directive,
+                        Severity::Error,
+                        err.to_string(),
+                    ));
+                }
+                true
+            }
+        };
+
+        let code = strip_comments(&sql);
+        for statement in &self.forbidden_statements {
+            if statement_re(statement).is_match(&code) {
+                issues.push(issue(
+                    path,
+                    LintRule::ForbiddenStatement,
+                    Severity::Error,
+                    format!("{} is forbidden", statement),
+                ));
+            }
+        }
+
+        if let Some(dialect) = dialect.or(self.dialect) {
+            let rules = DIALECT_RULES.iter().filter(|(d, _, _, in_transaction, _)| {
+                *d == dialect && (transactional || !in_transaction)
+            });
+            for (_, pattern, times, _, message) in rules {
+                let re = RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap();
+                if re.find_iter(&code).count() >= *times {
+                    issues.push(issue(
+                        path,
+                        LintRule::Dialect,
+                        Severity::Warning,
+                        (*message).into(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn issue(path: &Path, rule: LintRule, severity: Severity, message: String) -> LintIssue {
+    LintIssue {
+        path: path.to_path_buf(),
+        rule,
+        severity,
+        message,
+    }
+}
+
+/// Lint the migrations found recursively on the given location with the default rules, see [`Linter`]
+pub fn lint(location: impl AsRef<Path>) -> Result<Vec<LintIssue>, Error> {
+    Linter::new().lint(location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LintRule, Linter, MigrationChange, Severity};
+    use crate::Dialect;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn lints_migrations() {
+        let tmp_dir = TempDir::new().unwrap();
+        let location = tmp_dir.path();
+        fs::create_dir_all(location.join("postgres")).unwrap();
+        fs::write(location.join("V1__initial.sql"), "CREATE TABLE t(id int);").unwrap();
+        fs::write(location.join("V1__repeated.sql"), "SELECT 1;").unwrap();
+        fs::write(
+            location.join("V4__drop.sql"),
+            "-- drop database is ok here\ndrop\n  database x;",
+        )
+        .unwrap();
+        fs::write(location.join("V5__latin1.sql"), b"SELECT '\xe9';").unwrap();
+        fs::write(
+            location.join("V6__retries.sql"),
+            "-- refinery:retries=many\nSELECT 1;",
+        )
+        .unwrap();
+        fs::write(location.join("initial.sql"), "SELECT 1;").unwrap();
+        fs::write(location.join("README.md"), "migrations").unwrap();
+        fs::write(
+            location.join("postgres/V7__index.sql"),
+            "CREATE INDEX CONCURRENTLY i ON t(id);",
+        )
+        .unwrap();
+        fs::write(
+            location.join("postgres/V8__vacuum.sql"),
+            "-- refinery:no-transaction\nVACUUM t;",
+        )
+        .unwrap();
+
+        let issues = Linter::new().lint(location).unwrap();
+        let rules: Vec<(LintRule, Severity)> = issues
+            .iter()
+            .map(|issue| (issue.rule, issue.severity))
+            .collect();
+        assert_eq!(
+            vec![
+                (LintRule::RepeatedVersion, Severity::Error),
+                (LintRule::ForbiddenStatement, Severity::Error),
+                (LintRule::VersionGap, Severity::Error),
+                (LintRule::Encoding, Severity::Error),
+                (LintRule::Directive, Severity::Error),
+                (LintRule::Naming, Severity::Error),
+                (LintRule::Dialect, Severity::Warning),
+            ],
+            rules
+        );
+        assert!(issues[0].path.ends_with("V1__repeated.sql"));
+        assert_eq!("versions 2 to 3 are missing", issues[2].message);
+    }
+
+    #[test]
+    fn lints_timestamp_migrations() {
+        let tmp_dir = TempDir::new().unwrap();
+        let location = tmp_dir.path();
+        fs::write(location.join("20250501_000000_initial.sql"), "SELECT 1;").unwrap();
+        fs::write(location.join("20250614_093000_cars.sql"), "SELECT 1;").unwrap();
+
+        // timestamp migrations have no version gaps
+        assert!(Linter::new().lint(location).unwrap().is_empty());
+
+        fs::write(location.join("V3__motos.sql"), "SELECT 1;").unwrap();
+        let issues = Linter::new().lint(location).unwrap();
+        assert_eq!(1, issues.len());
+        assert_eq!(LintRule::Naming, issues[0].rule);
+        assert!(issues[0].path.ends_with("V3__motos.sql"));
+    }
+
+    #[test]
+    fn lints_changed_migrations() {
+        let tmp_dir = TempDir::new().unwrap();
+        let location = tmp_dir.path();
+        fs::write(location.join("V1__initial.sql"), "DROP DATABASE x;").unwrap();
+        fs::write(location.join("V2__cars.sql"), "CREATE TABLE cars(id int);").unwrap();
+        fs::write(location.join("V3__late.sql"), "DROP DATABASE y;").unwrap();
+        fs::write(location.join("V6__next.sql"), "SELECT 1;").unwrap();
+
+        let changes = [
+            MigrationChange::Modified(location.join("V2__cars.sql")),
+            MigrationChange::Removed(location.join("V4__motos.sql")),
+            MigrationChange::Added(location.join("V3__late.sql")),
+            MigrationChange::Added(location.join("V6__next.sql")),
+        ];
+        let issues = Linter::new()
+            .set_contiguous_versions(false)
+            .lint_changes(location, &changes, Some(5))
+            .unwrap();
+        let rules: Vec<(LintRule, &str)> = issues
+            .iter()
+            .map(|issue| {
+                let file_name = issue.path.file_name().unwrap().to_str().unwrap();
+                (issue.rule, file_name)
+            })
+            .collect();
+        // the forbidden statement of V1 isn't reported as it wasn't changed
+        assert_eq!(
+            vec![
+                (LintRule::ChangedMigration, "V2__cars.sql"),
+                (LintRule::ForbiddenStatement, "V3__late.sql"),
+                (LintRule::OutOfOrderVersion, "V3__late.sql"),
+                (LintRule::ChangedMigration, "V4__motos.sql"),
+                (LintRule::VersionGap, "V6__next.sql"),
+            ],
+            rules
+        );
+    }
+
+    #[test]
+    fn lints_migrations_for_dialect() {
+        let tmp_dir = TempDir::new().unwrap();
+        let location = tmp_dir.path();
+        fs::write(
+            location.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);\nCREATE TABLE u(id int);",
+        )
+        .unwrap();
+        fs::write(location.join("V3__vacuum.sql"), "VACUUM t;").unwrap();
+
+        let issues = Linter::new()
+            .set_contiguous_versions(false)
+            .set_forbidden_statements(&["VACUUM"])
+            .set_dialect(Dialect::Mysql)
+            .lint(location)
+            .unwrap();
+        let rules: Vec<(LintRule, Severity)> = issues
+            .iter()
+            .map(|issue| (issue.rule, issue.severity))
+            .collect();
+        assert_eq!(
+            vec![
+                (LintRule::Dialect, Severity::Warning),
+                (LintRule::ForbiddenStatement, Severity::Error),
+                (LintRule::VersionGap, Severity::Warning),
+            ],
+            rules
+        );
+    }
+}
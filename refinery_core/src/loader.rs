@@ -0,0 +1,423 @@
+//! Loads SQL migrations from the filesystem at runtime like [`load_sql_migrations`](crate::load_sql_migrations),
+//! filtering the migration files with glob patterns and extensions, and overriding the version of some of them.
+use crate::error::{Error, Kind};
+use crate::util::{
+    assert_no_conflicts, file_re_extensions, format_timestamp_version, migration_file_name,
+    parse_migration_dialect, parse_migration_name, parse_migration_naming, MigrationNaming,
+};
+use crate::Migration;
+use glob::{MatchOptions, Pattern};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+// `*` doesn't match `/`, so `*.sql` only matches the migrations directly on the location and `**/*.sql` every one
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Loads the SQL migrations of a location, i.e. for plugins or installers shipping their migrations next to the
+/// binary:
+///
+/// ```rust,no_run
+/// use refinery_core::MigrationLoader;
+///
+/// let migrations = MigrationLoader::new("migrations")
+///     .add_include("core/**")?
+///     .add_include("plugins/billing/**")?
+///     .add_exclude("**/*_draft.sql")?
+///     .set_extensions(&["sql", "psql"])
+///     .set_version("plugins/billing/V1__invoices.sql", 1001)
+///     .load()?;
+/// # Ok::<(), refinery_core::Error>(())
+/// ```
+///
+/// Patterns and paths are relative to the location and separated by `/` on every platform. Migration files matching
+/// an include pattern must follow the migration naming, the other ones that don't are skipped with a warning as
+/// [`load_sql_migrations`](crate::load_sql_migrations) does.
+/// Instead of failing on the first invalid migration file, [`MigrationLoader::load`] fails with
+/// [`Kind::InvalidMigrationFiles`] listing the Error of each of them.
+#[derive(Clone, Debug)]
+pub struct MigrationLoader {
+    location: PathBuf,
+    max_depth: Option<usize>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    extensions: Vec<String>,
+    versions: BTreeMap<String, i32>,
+}
+
+impl MigrationLoader {
+    /// Create a loader of the `.sql` migrations on every subdirectory of `location`
+    pub fn new(location: impl AsRef<Path>) -> MigrationLoader {
+        MigrationLoader {
+            location: location.as_ref().to_path_buf(),
+            max_depth: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            extensions: vec!["sql".into()],
+            versions: BTreeMap::new(),
+        }
+    }
+
+    /// Descend at most `max_depth` levels of subdirectories, see
+    /// [`find_migration_files_with_depth`](crate::find_migration_files_with_depth)
+    pub fn set_max_depth(self, max_depth: Option<usize>) -> MigrationLoader {
+        MigrationLoader { max_depth, ..self }
+    }
+
+    /// Only load the migration files matching one of the include patterns, i.e. `2023/*.sql` or `payments/**`.
+    /// Every migration file is loaded if there are none, files without one of the extensions are never loaded.
+    pub fn add_include(mut self, pattern: &str) -> Result<MigrationLoader, Error> {
+        self.include.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    /// Skip the migration files matching the pattern, even if they match an include pattern
+    pub fn add_exclude(mut self, pattern: &str) -> Result<MigrationLoader, Error> {
+        self.exclude.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    /// Set the extensions of the migration files loaded, `sql` by default
+    pub fn set_extensions(self, extensions: &[&str]) -> MigrationLoader {
+        MigrationLoader {
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Override the version of the migration file at `path`, relative to the location, to order it differently than
+    /// its name does, i.e. to move the migrations of a plugin after the ones of the application. The migration is
+    /// then named after its new version, as if its file was renamed.
+    pub fn set_version(mut self, path: &str, version: i32) -> MigrationLoader {
+        self.versions
+            .insert(path.trim_start_matches("./").to_string(), version);
+        self
+    }
+
+    /// Load the migrations, ordered by version
+    pub fn load(&self) -> Result<Vec<Migration>, Error> {
+        let location = self.location.canonicalize().map_err(|err| {
+            Error::new(Kind::InvalidMigrationPath(self.location.clone(), err), None)
+        })?;
+
+        let re = file_re_extensions(&self.extensions);
+        let mut migrations = Vec::new();
+        let mut filenames = Vec::new();
+        let mut errors = Vec::new();
+        let mut overridden = Vec::new();
+
+        let entries = WalkDir::new(&location)
+            .max_depth(
+                self.max_depth
+                    .map_or(usize::MAX, |depth| depth.saturating_add(1)),
+            )
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(DirEntry::into_path);
+
+        for path in entries {
+            let relative = relative_path(&location, &path);
+            if self
+                .exclude
+                .iter()
+                .any(|pattern| matches(pattern, &relative))
+            {
+                continue;
+            }
+            let included = !self.include.is_empty();
+            if included
+                && !self
+                    .include
+                    .iter()
+                    .any(|pattern| matches(pattern, &relative))
+            {
+                continue;
+            }
+
+            let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+            if !re.is_match(file_name) {
+                if !self.has_extension(&path) {
+                    continue;
+                }
+                if included {
+                    errors.push((path, Error::new(Kind::InvalidName, None)));
+                } else {
+                    log::warn!(
+                        "File \"{}\" does not adhere to the migration naming convention, it's skipped.",
+                        file_name
+                    );
+                }
+                continue;
+            }
+
+            let version = self.versions.get(&relative).copied();
+            if version.is_some() {
+                overridden.push(relative);
+            }
+            match load_migration(&location, &path, version) {
+                Ok((filename, migration)) => {
+                    migrations.push(migration);
+                    filenames.push((filename, path));
+                }
+                Err(err) => errors.push((path, err)),
+            }
+        }
+
+        if let Some(path) = self.versions.keys().find(|path| !overridden.contains(path)) {
+            return Err(Error::new(
+                Kind::InvalidMigrationPath(
+                    location.join(path),
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "the version of a migration file that isn't loaded can't be overridden",
+                    ),
+                ),
+                None,
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(Error::new(Kind::InvalidMigrationFiles(errors), None));
+        }
+
+        assert_no_conflicts(&filenames)?;
+        migrations.sort();
+        Ok(migrations)
+    }
+
+    fn has_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| self.extensions.iter().any(|ext| ext == extension))
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern, Error> {
+    Pattern::new(pattern).map_err(|err| {
+        Error::new(
+            Kind::InvalidGlobPattern(pattern.to_string(), err.msg.to_string()),
+            None,
+        )
+    })
+}
+
+fn matches(pattern: &Pattern, path: &str) -> bool {
+    pattern.matches_with(path, MATCH_OPTIONS)
+}
+
+// the path of a migration file relative to the location, separated by `/`
+fn relative_path(location: &Path, path: &Path) -> String {
+    path.strip_prefix(location)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(component) => component.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// reads the migration file at path, renaming it after the version overriding its own if any
+fn load_migration(
+    location: &Path,
+    path: &Path,
+    version: Option<i32>,
+) -> Result<(String, Migration), Error> {
+    let sql = std::fs::read_to_string(path)
+        .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.to_path_buf(), err), None))?;
+
+    let mut filename = migration_file_name(location, path);
+    if let Some(version) = version {
+        let (prefix, _, name) = parse_migration_name(&filename)?;
+        let mut renamed = match parse_migration_naming(&filename) {
+            Some(MigrationNaming::Timestamp) => {
+                format!("{}_{}", format_timestamp_version(version), name)
+            }
+            _ => format!("{}{}__{}", prefix, version, name),
+        };
+        if let Some(dialect) = parse_migration_dialect(&filename) {
+            renamed = format!("{}.{}", renamed, dialect);
+        }
+        filename = renamed;
+    }
+
+    let migration = Migration::unapplied_from(&filename, sql)?;
+    Ok((filename, migration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MigrationLoader;
+    use crate::error::Kind;
+    use crate::Dialect;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn create(dir: &Path, path: &str, sql: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, sql).unwrap();
+    }
+
+    fn names(loader: &MigrationLoader) -> Vec<String> {
+        loader
+            .load()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn loads_migrations_matching_patterns() {
+        let tmp_dir = TempDir::new().unwrap();
+        create(tmp_dir.path(), "V1__first.sql", "SELECT 1;");
+        create(tmp_dir.path(), "core/V2__second.sql", "SELECT 2;");
+        create(tmp_dir.path(), "core/V3__third_draft.sql", "SELECT 3;");
+        create(
+            tmp_dir.path(),
+            "plugins/billing/V4__fourth.sql",
+            "SELECT 4;",
+        );
+        create(tmp_dir.path(), "plugins/audit/V5__fifth.sql", "SELECT 5;");
+
+        let loader = MigrationLoader::new(tmp_dir.path());
+        assert_eq!(
+            vec![
+                "V1__first",
+                "V2__second",
+                "V3__third_draft",
+                "V4__fourth",
+                "V5__fifth"
+            ],
+            names(&loader)
+        );
+
+        let loader = loader
+            .add_include("*.sql")
+            .unwrap()
+            .add_include("core/**")
+            .unwrap()
+            .add_include("plugins/billing/*.sql")
+            .unwrap()
+            .add_exclude("**/*_draft.sql")
+            .unwrap();
+        assert_eq!(
+            vec!["V1__first", "V2__second", "V4__fourth"],
+            names(&loader)
+        );
+        assert_eq!(
+            vec!["V1__first"],
+            names(&loader.clone().set_max_depth(Some(0)))
+        );
+    }
+
+    #[test]
+    fn loads_migrations_with_extensions() {
+        let tmp_dir = TempDir::new().unwrap();
+        create(tmp_dir.path(), "V1__first.sql", "SELECT 1;");
+        create(tmp_dir.path(), "V2__second.psql", "SELECT 2;");
+        create(tmp_dir.path(), "V3__third.postgres.psql", "SELECT 3;");
+        create(tmp_dir.path(), "V4__fourth.txt", "SELECT 4;");
+
+        let loader = MigrationLoader::new(tmp_dir.path()).set_extensions(&["sql", ".psql"]);
+        let migrations = loader.load().unwrap();
+        assert_eq!(3, migrations.len());
+        assert_eq!("V3__third", migrations[2].to_string());
+        assert_eq!(Some(Dialect::Postgres), migrations[2].dialect());
+        assert_eq!(
+            vec!["V4__fourth"],
+            names(&MigrationLoader::new(tmp_dir.path()).set_extensions(&["txt"]))
+        );
+    }
+
+    #[test]
+    fn overrides_migration_versions() {
+        let tmp_dir = TempDir::new().unwrap();
+        create(tmp_dir.path(), "V1__first.sql", "SELECT 1;");
+        create(tmp_dir.path(), "V2__second.sql", "SELECT 2;");
+        create(tmp_dir.path(), "plugin/V1__plugin.sql", "SELECT 3;");
+        create(tmp_dir.path(), "plugin/postgres/V2__index.sql", "SELECT 4;");
+
+        let loader = MigrationLoader::new(tmp_dir.path());
+        assert!(matches!(
+            loader.load().unwrap_err().kind(),
+            Kind::ConflictingMigrations(..)
+        ));
+
+        let migrations = loader
+            .set_version("plugin/V1__plugin.sql", 1001)
+            .set_version("plugin/postgres/V2__index.sql", 1002)
+            .load()
+            .unwrap();
+        let names: Vec<String> = migrations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec!["V1__first", "V2__second", "V1001__plugin", "V1002__index"],
+            names
+        );
+        assert_eq!(1001, migrations[2].version());
+        assert_eq!("plugin", migrations[2].name());
+        assert_eq!(Some(Dialect::Postgres), migrations[3].dialect());
+
+        let err = MigrationLoader::new(tmp_dir.path())
+            .set_version("V3__missing.sql", 3)
+            .load()
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationPath(..)));
+    }
+
+    #[test]
+    fn fails_with_every_invalid_migration_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        create(tmp_dir.path(), "V1__first.sql", "SELECT 1;");
+        create(
+            tmp_dir.path(),
+            "plugin/V2__second.sql",
+            "-- refinery:timeout=soon\nSELECT 2;",
+        );
+        create(tmp_dir.path(), "plugin/second.sql", "SELECT 2;");
+        create(tmp_dir.path(), "plugin/README.md", "# plugin");
+        fs::write(tmp_dir.path().join("plugin/V3__third.sql"), [0xff, 0xfe]).unwrap();
+
+        // files that don't follow the migration naming are skipped unless they're explicitly included
+        let loader = MigrationLoader::new(tmp_dir.path())
+            .add_exclude("plugin/*")
+            .unwrap();
+        assert_eq!(vec!["V1__first"], names(&loader));
+
+        let err = MigrationLoader::new(tmp_dir.path())
+            .add_include("**/*.sql")
+            .unwrap()
+            .load()
+            .unwrap_err();
+        let Kind::InvalidMigrationFiles(errors) = err.kind() else {
+            panic!("unexpected error {}", err);
+        };
+        assert_eq!(3, errors.len());
+        assert!(errors[0].0.ends_with("plugin/V2__second.sql"));
+        assert!(matches!(errors[0].1.kind(), Kind::InvalidDirective(..)));
+        assert!(errors[1].0.ends_with("plugin/V3__third.sql"));
+        assert!(matches!(errors[1].1.kind(), Kind::InvalidMigrationFile(..)));
+        assert!(errors[2].0.ends_with("plugin/second.sql"));
+        assert!(matches!(errors[2].1.kind(), Kind::InvalidName));
+    }
+
+    #[test]
+    fn fails_on_invalid_glob_patterns() {
+        let err = MigrationLoader::new("migrations")
+            .add_include("[V1__first.sql")
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidGlobPattern(..)));
+    }
+}
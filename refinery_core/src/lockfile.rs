@@ -0,0 +1,202 @@
+//! Records the checksums of the sql migrations of a location on a `refinery.lock` file, so that migrations edited
+//! after being committed can be caught in CI without a database connection.
+//!
+//! Each line of the lockfile is a migration, i.e. `V1__initial 2959965718684201605`, migrations are identified by
+//! their version and [`Dialect`], so renaming a migration also changes its checksum.
+use crate::error::{Error, Kind};
+use crate::util::{load_sql_migrations, parse_migration_dialect, parse_migration_name};
+use crate::{Dialect, Migration};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The default name of the lockfile
+pub const LOCKFILE_NAME: &str = "refinery.lock";
+
+const LOCKFILE_HEADER: &str =
+    "# checksums of the refinery migrations, migrations must not be edited once committed\n";
+
+/// A locked migration that doesn't match the lockfile anymore
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockMismatch {
+    /// The migration was edited or renamed, `migration` is the name it's locked with
+    Changed {
+        migration: String,
+        locked: u64,
+        current: u64,
+    },
+    /// The migration was removed from the migrations location
+    Missing { migration: String },
+}
+
+impl fmt::Display for LockMismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockMismatch::Changed {
+                migration,
+                locked,
+                current,
+            } => write!(
+                fmt,
+                "migration {} changed, its checksum is {} but {} is locked",
+                migration, current, locked
+            ),
+            LockMismatch::Missing { migration } => {
+                write!(fmt, "locked migration {} is missing", migration)
+            }
+        }
+    }
+}
+
+// the name of a migration on the lockfile, with the dialect of variants
+fn locked_name(migration: &Migration) -> String {
+    match migration.dialect() {
+        Some(dialect) => format!("{}.{}", migration, dialect),
+        None => migration.to_string(),
+    }
+}
+
+fn lock_key(name: &str) -> Result<(i32, Option<Dialect>), Error> {
+    let (_, version, _) = parse_migration_name(name)?;
+    Ok((version, parse_migration_dialect(name)))
+}
+
+fn invalid_lockfile(lockfile: &Path, reason: impl ToString) -> Error {
+    Error::new(
+        Kind::InvalidLockfile(lockfile.to_path_buf(), reason.to_string()),
+        None,
+    )
+}
+
+/// Write the lockfile with the checksums of the sql migrations found on the given location,
+/// replacing the existing one
+pub fn write_lockfile(location: impl AsRef<Path>, lockfile: impl AsRef<Path>) -> Result<(), Error> {
+    let lockfile = lockfile.as_ref();
+    let mut contents = String::from(LOCKFILE_HEADER);
+    for migration in load_sql_migrations(location)? {
+        contents.push_str(&format!(
+            "{} {}\n",
+            locked_name(&migration),
+            migration.checksum()
+        ));
+    }
+    fs::write(lockfile, contents).map_err(|err| invalid_lockfile(lockfile, err))
+}
+
+/// Check the sql migrations found on the given location against the lockfile, returning the locked migrations that
+/// were edited, renamed or removed since. Migrations that aren't locked yet are new, and aren't checked
+pub fn check_lockfile(
+    location: impl AsRef<Path>,
+    lockfile: impl AsRef<Path>,
+) -> Result<Vec<LockMismatch>, Error> {
+    let lockfile = lockfile.as_ref();
+    let contents = fs::read_to_string(lockfile).map_err(|err| invalid_lockfile(lockfile, err))?;
+
+    let current: HashMap<_, _> = load_sql_migrations(location)?
+        .into_iter()
+        .map(|migration| {
+            let key = (migration.version() as i32, migration.dialect());
+            (key, migration.checksum())
+        })
+        .collect();
+
+    let mut mismatches = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (migration, locked) = line
+            .split_once(' ')
+            .and_then(|(name, checksum)| Some((name, checksum.trim().parse::<u64>().ok()?)))
+            .ok_or_else(|| invalid_lockfile(lockfile, format!("invalid line {}", line)))?;
+        let key = lock_key(migration)
+            .map_err(|_| invalid_lockfile(lockfile, format!("invalid migration {}", migration)))?;
+
+        match current.get(&key) {
+            Some(current) if *current == locked => {}
+            Some(current) => mismatches.push(LockMismatch::Changed {
+                migration: migration.into(),
+                locked,
+                current: *current,
+            }),
+            None => mismatches.push(LockMismatch::Missing {
+                migration: migration.into(),
+            }),
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_lockfile, write_lockfile, LockMismatch, LOCKFILE_NAME};
+    use crate::error::Kind;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn checks_migrations_against_lockfile() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(migrations_dir.join("postgres")).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE t(id int);",
+        )
+        .unwrap();
+        fs::write(
+            migrations_dir.join("V2__add_name.sql"),
+            "ALTER TABLE t ADD name text;",
+        )
+        .unwrap();
+        fs::write(
+            migrations_dir.join("postgres/V2__add_name.sql"),
+            "ALTER TABLE t ADD name varchar;",
+        )
+        .unwrap();
+        let lockfile = tmp_dir.path().join(LOCKFILE_NAME);
+
+        write_lockfile(&migrations_dir, &lockfile).unwrap();
+        let contents = fs::read_to_string(&lockfile).unwrap();
+        assert!(contents.contains("\nV1__initial "));
+        assert!(contents.contains("\nV2__add_name.postgres "));
+        assert!(check_lockfile(&migrations_dir, &lockfile)
+            .unwrap()
+            .is_empty());
+
+        // new migrations aren't locked yet
+        fs::write(
+            migrations_dir.join("V3__add_index.sql"),
+            "CREATE INDEX i ON t(id);",
+        )
+        .unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE t(id bigint);",
+        )
+        .unwrap();
+        fs::remove_file(migrations_dir.join("postgres/V2__add_name.sql")).unwrap();
+        let mismatches = check_lockfile(&migrations_dir, &lockfile).unwrap();
+        assert_eq!(2, mismatches.len());
+        assert!(
+            matches!(&mismatches[0], LockMismatch::Changed { migration, .. } if migration == "V1__initial")
+        );
+        assert_eq!(
+            LockMismatch::Missing {
+                migration: "V2__add_name.postgres".into()
+            },
+            mismatches[1]
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_lockfile() {
+        let tmp_dir = TempDir::new().unwrap();
+        let lockfile = tmp_dir.path().join(LOCKFILE_NAME);
+        fs::write(&lockfile, "V1__initial not_a_checksum\n").unwrap();
+
+        let err = check_lockfile(tmp_dir.path(), &lockfile).unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidLockfile(..)));
+    }
+}
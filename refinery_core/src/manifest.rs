@@ -0,0 +1,142 @@
+//! The manifest of the migrations embedded with `embed_migrations!`, available via `migrations::manifest()`,
+//! so a running service can report the migration set it was built with and binaries can be compared across environments.
+#[cfg(feature = "fs")]
+use crate::error::{Error, Kind};
+#[cfg(feature = "fs")]
+use crate::util::{find_migration_files, migration_file_name, MigrationType};
+#[cfg(feature = "fs")]
+use siphasher::sip::SipHasher13;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// The migrations embedded on a binary, and the hash of the sources of the migrations directory they were embedded from
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    pub migrations: Vec<ManifestEntry>,
+    pub source_hash: u64,
+}
+
+/// An embedded migration, `name` includes the dialect of migration variants, i.e. `V7__add_fulltext.postgres`.
+/// The `checksum` is the one recorded on the schema history table, `None` for Rust migrations,
+/// as their sql is only generated when they are run
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    pub name: String,
+    pub version: u32,
+    pub checksum: Option<u64>,
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(fmt, "source hash: {:016x}", self.source_hash)?;
+        for migration in &self.migrations {
+            match migration.checksum {
+                Some(checksum) => writeln!(fmt, "{} {}", migration.name, checksum)?,
+                None => writeln!(fmt, "{}", migration.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Manifest {
+    /// Check if the migrations on the given location are still the ones the manifest was embedded from,
+    /// i.e. that no migration was added, edited or removed after the binary was built
+    pub fn matches_location(&self, location: impl AsRef<Path>) -> Result<bool, Error> {
+        Ok(source_hash(location)? == self.source_hash)
+    }
+
+    /// Check if the migrations on the given locations are still the ones the manifest was embedded from, for
+    /// manifests of migrations embedded from several directories, i.e. `embed_migrations!("migrations/core", "migrations/auth")`
+    pub fn matches_locations(&self, locations: &[impl AsRef<Path>]) -> Result<bool, Error> {
+        Ok(locations_source_hash(locations)? == self.source_hash)
+    }
+}
+
+/// Hash the names and contents of the sql and Rust migrations found recursively on the given location,
+/// the same way `embed_migrations!` does for [`Manifest::source_hash`]
+#[cfg(feature = "fs")]
+pub fn source_hash(location: impl AsRef<Path>) -> Result<u64, Error> {
+    locations_source_hash(&[location])
+}
+
+/// Hash the names and contents of the sql and Rust migrations found recursively on the given locations,
+/// the same way `embed_migrations!` does for [`Manifest::source_hash`] when embedding them from several directories
+#[cfg(feature = "fs")]
+pub fn locations_source_hash(locations: &[impl AsRef<Path>]) -> Result<u64, Error> {
+    let mut files = Vec::new();
+    for location in locations {
+        let location = location.as_ref();
+        for path in find_migration_files(location, MigrationType::All)? {
+            let contents = std::fs::read(&path)
+                .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.clone(), err), None))?;
+            let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+            let name = format!("{}.{}", migration_file_name(location, &path), extension);
+            files.push((name, contents));
+        }
+    }
+    files.sort();
+
+    let mut hasher = SipHasher13::new();
+    for (name, contents) in files {
+        name.hash(&mut hasher);
+        contents.hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locations_source_hash, source_hash};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn hashes_migration_sources() {
+        let tmp_dir = TempDir::new().unwrap();
+        let location = tmp_dir.path();
+        fs::create_dir_all(location.join("nested")).unwrap();
+        fs::write(location.join("V1__initial.sql"), "CREATE TABLE t(id int);").unwrap();
+        fs::write(location.join("nested/V2__cars.rs"), "fn migration() {}").unwrap();
+        fs::write(location.join("README.md"), "not a migration").unwrap();
+        let hash = source_hash(location).unwrap();
+
+        // moving migrations around or editing other files doesn't change the hash
+        fs::rename(
+            location.join("nested/V2__cars.rs"),
+            location.join("V2__cars.rs"),
+        )
+        .unwrap();
+        fs::write(location.join("README.md"), "still not a migration").unwrap();
+        assert_eq!(hash, source_hash(location).unwrap());
+
+        fs::write(
+            location.join("V1__initial.sql"),
+            "CREATE TABLE t(id bigint);",
+        )
+        .unwrap();
+        assert_ne!(hash, source_hash(location).unwrap());
+    }
+
+    #[test]
+    fn hashes_migration_sources_of_several_locations() {
+        let tmp_dir = TempDir::new().unwrap();
+        let (core, auth) = (tmp_dir.path().join("core"), tmp_dir.path().join("auth"));
+        fs::create_dir_all(&core).unwrap();
+        fs::create_dir_all(&auth).unwrap();
+        fs::write(core.join("V1__initial.sql"), "CREATE TABLE t(id int);").unwrap();
+        fs::write(auth.join("V2__users.sql"), "CREATE TABLE users(id int);").unwrap();
+        let hash = locations_source_hash(&[&core, &auth]).unwrap();
+
+        // the migrations are hashed the same as if they were on a single location
+        fs::rename(auth.join("V2__users.sql"), core.join("V2__users.sql")).unwrap();
+        assert_eq!(hash, source_hash(&core).unwrap());
+        assert_eq!(hash, locations_source_hash(&[&auth, &core]).unwrap());
+    }
+}
@@ -0,0 +1,71 @@
+//! Instrumentation of the migration runs with the [`metrics`](https://docs.rs/metrics) crate, so services running
+//! their migrations at startup can scrape their health with the recorder they install, i.e. a Prometheus exporter.
+//!
+//! The sync and async executors emit:
+//! - [`MIGRATIONS_APPLIED`], a counter of the migrations applied.
+//! - [`MIGRATION_DURATION`], a histogram of how long each migration took to apply in seconds, labeled with the
+//!   `migration`. Migrations applied grouped in a single transaction have no duration of their own.
+//! - [`MIGRATION_FAILURES`], a counter of the runs that failed applying a migration, labeled with the `migration`
+//!   when it's known.
+//! - [`PENDING_MIGRATIONS`], a gauge of the migrations left to apply by the run.
+
+use std::time::Duration;
+
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit,
+};
+
+use crate::Migration;
+
+/// The counter of the migrations applied
+pub const MIGRATIONS_APPLIED: &str = "migrations_applied_total";
+/// The histogram of how long each migration took to apply
+pub const MIGRATION_DURATION: &str = "migration_duration_seconds";
+/// The counter of the runs that failed applying a migration
+pub const MIGRATION_FAILURES: &str = "migration_failures_total";
+/// The gauge of the migrations left to apply
+pub const PENDING_MIGRATIONS: &str = "pending_migrations";
+
+/// Describes the metrics emitted by refinery to the installed recorder, with their units and help text
+pub fn describe() {
+    describe_counter!(MIGRATIONS_APPLIED, "Migrations applied");
+    describe_histogram!(
+        MIGRATION_DURATION,
+        Unit::Seconds,
+        "How long each migration took to apply"
+    );
+    describe_counter!(
+        MIGRATION_FAILURES,
+        "Migration runs that failed applying a migration"
+    );
+    describe_gauge!(PENDING_MIGRATIONS, "Migrations left to apply");
+}
+
+// the migrations a run starts with
+pub(crate) fn pending(migrations: usize) {
+    gauge!(PENDING_MIGRATIONS).set(migrations as f64);
+}
+
+// a migration applied on its own transaction, with how long it took
+pub(crate) fn applied(migration: &Migration, duration: Duration) {
+    counter!(MIGRATIONS_APPLIED).increment(1);
+    histogram!(MIGRATION_DURATION, "migration" => migration.to_string())
+        .record(duration.as_secs_f64());
+    gauge!(PENDING_MIGRATIONS).decrement(1.0);
+}
+
+// migrations applied together in a single transaction
+pub(crate) fn applied_grouped(migrations: usize) {
+    counter!(MIGRATIONS_APPLIED).increment(migrations as u64);
+    gauge!(PENDING_MIGRATIONS).decrement(migrations as f64);
+}
+
+// a run that failed applying the given migration, or one of a group
+pub(crate) fn failed(migration: Option<&Migration>) {
+    match migration {
+        Some(migration) => {
+            counter!(MIGRATION_FAILURES, "migration" => migration.to_string()).increment(1)
+        }
+        None => counter!(MIGRATION_FAILURES).increment(1),
+    }
+}
@@ -0,0 +1,32 @@
+//! Registry of the Rust migrations declared with the `#[refinery::migration]` attribute on any module of the crates
+//! linked into a binary, gathered with `refinery::collect_migrations!()`.
+use crate::Migration;
+
+#[doc(hidden)]
+pub use inventory;
+
+/// A Rust migration registered with the `#[refinery::migration]` attribute, built when it's collected
+#[doc(hidden)]
+pub struct RegisteredMigration {
+    build: fn() -> Migration,
+}
+
+impl RegisteredMigration {
+    pub const fn new(build: fn() -> Migration) -> RegisteredMigration {
+        RegisteredMigration { build }
+    }
+}
+
+inventory::collect!(RegisteredMigration);
+
+/// Get the migrations registered with the `#[refinery::migration]` attribute, ordered by version.
+/// Repeated versions are caught when they're added to a [`Runner`](crate::Runner) with
+/// [`Runner::add_migrations`](crate::Runner::add_migrations).
+pub fn registered_migrations() -> Vec<Migration> {
+    let mut migrations: Vec<Migration> = inventory::iter::<RegisteredMigration>
+        .into_iter()
+        .map(|registered| (registered.build)())
+        .collect();
+    migrations.sort();
+    migrations
+}
@@ -0,0 +1,121 @@
+use crate::error::Kind;
+use crate::{AsyncMigrate, Error, Migrate, Report, Runner};
+use std::fmt;
+
+/// The stage of a [`Rollout`] it was aborted on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloutStage {
+    /// Applying the migrations to the canary database
+    Canary,
+    /// Asserting the verification checks on the canary database
+    Checks,
+    /// Applying the migrations to the target with the given index, after the canary database
+    Target(usize),
+}
+
+impl fmt::Display for RolloutStage {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RolloutStage::Canary => write!(fmt, "the canary database"),
+            RolloutStage::Checks => write!(fmt, "the canary checks"),
+            RolloutStage::Target(index) => write!(fmt, "target {}", index),
+        }
+    }
+}
+
+fn aborted(stage: RolloutStage, err: Error) -> Error {
+    let report = err.report().cloned();
+    Error::new(Kind::RolloutAborted(stage, err), report)
+}
+
+/// Rolls out the migrations of a [`Runner`] to several databases, applying them to a canary database first
+/// and asserting the verification checks on it, and only then to the remaining targets, one at a time.
+/// The rollout is aborted with [`Kind::RolloutAborted`] on the first database that fails, so when the canary fails
+/// no target is migrated.
+pub struct Rollout {
+    runner: Runner,
+    checks: Vec<String>,
+}
+
+impl Rollout {
+    /// instantiate a new Rollout of the migrations of the given Runner, run with its settings on every database
+    pub fn new(runner: Runner) -> Rollout {
+        Rollout {
+            runner,
+            checks: Vec::new(),
+        }
+    }
+
+    /// Set the verification checks asserted on the canary database after migrating it, each a `SELECT` returning
+    /// a single value that must be true, i.e. not false, 0 or NULL, like the `-- refinery:check` section of migrations.
+    /// by default there are no checks
+    pub fn set_checks(self, checks: Vec<String>) -> Rollout {
+        Rollout { checks, ..self }
+    }
+
+    /// Get the Runner the migrations are rolled out with
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    /// Rolls out the migrations to the canary database and then to the targets,
+    /// returning the Report of the canary followed by the ones of the targets
+    pub fn run<C>(&self, canary: &mut C, targets: &mut [C]) -> Result<Vec<Report>, Error>
+    where
+        C: Migrate,
+    {
+        let mut reports = Vec::with_capacity(targets.len() + 1);
+        log::info!("rolling out migrations to the canary database");
+        let report = self
+            .runner
+            .run(canary)
+            .map_err(|err| aborted(RolloutStage::Canary, err))?;
+        reports.push(report);
+        Migrate::assert_checks(canary, &self.checks)
+            .map_err(|err| aborted(RolloutStage::Checks, err))?;
+
+        for (index, target) in targets.iter_mut().enumerate() {
+            log::info!("rolling out migrations to target {}", index);
+            let report = self
+                .runner
+                .run(target)
+                .map_err(|err| aborted(RolloutStage::Target(index), err))?;
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Rolls out the migrations asynchronously to the canary database and then to the targets,
+    /// returning the Report of the canary followed by the ones of the targets
+    pub async fn run_async<C>(
+        &self,
+        canary: &mut C,
+        targets: &mut [C],
+    ) -> Result<Vec<Report>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let mut reports = Vec::with_capacity(targets.len() + 1);
+        log::info!("rolling out migrations to the canary database");
+        let report = self
+            .runner
+            .run_async(canary)
+            .await
+            .map_err(|err| aborted(RolloutStage::Canary, err))?;
+        reports.push(report);
+        AsyncMigrate::assert_checks(canary, &self.checks)
+            .await
+            .map_err(|err| aborted(RolloutStage::Checks, err))?;
+
+        for (index, target) in targets.iter_mut().enumerate() {
+            log::info!("rolling out migrations to target {}", index);
+            let report = self
+                .runner
+                .run_async(target)
+                .await
+                .map_err(|err| aborted(RolloutStage::Target(index), err))?;
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+}
@@ -1,22 +1,46 @@
-use siphasher::sip::SipHasher13;
 use time::OffsetDateTime;
 
+use futures::stream::{self, Stream};
 use log::error;
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::hash::{Hash, Hasher};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::traits::{sync::migrate as sync_migrate, DEFAULT_MIGRATION_TABLE_NAME};
-use crate::util::parse_migration_name;
-use crate::{AsyncMigrate, Error, Migrate};
+use crate::checksum::Checksums;
+use crate::error::Kind;
+use crate::history::{imported_migrations, AppliedMigrationRecord};
+use crate::introspect::Schema;
+use crate::traits::{
+    r#async::{migrate_with_dialect as async_migrate, rerun as async_rerun, within, AsyncExecutor},
+    sync::{migrate_with_dialect as sync_migrate, rerun as sync_rerun},
+    validate_migrations, SchemaHistoryDdl, DEFAULT_MIGRATION_TABLE_NAME,
+};
+use crate::util::{
+    format_timestamp_version, parse_dialect, parse_duration, parse_header, parse_migration_dialect,
+    parse_migration_name, parse_migration_naming, replace_placeholders, split_checks, split_down,
+    split_header, MigrationNaming,
+};
+use crate::{AsyncMigrate, ChecksumAlgorithm, Error, Migrate, Splitter};
 use std::fmt::Formatter;
 
 /// An enum set that represents the type of the Migration
 #[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Versioned,
     Unversioned,
+    /// Seed migrations, `S{version}__{name}`, insert test or staging data and are only applied with
+    /// [`Runner::set_apply_seeds`]. They have their own versions, recorded on the `seeds` namespace of the schema
+    /// history table, and are applied after the other migrations.
+    Seed,
 }
 
 impl fmt::Display for Type {
@@ -24,6 +48,7 @@ impl fmt::Display for Type {
         let version_type = match self {
             Type::Versioned => "V",
             Type::Unversioned => "U",
+            Type::Seed => "S",
         };
         write!(f, "{}", version_type)
     }
@@ -34,23 +59,455 @@ impl fmt::Debug for Type {
         let version_type = match self {
             Type::Versioned => "Versioned",
             Type::Unversioned => "Unversioned",
+            Type::Seed => "Seed",
         };
         write!(f, "{}", version_type)
     }
 }
 
+/// The namespace seed migrations are recorded on, suffixed to the one of the Runner if it's set,
+/// i.e. `billing_seeds`
+const SEEDS_NAMESPACE: &str = "seeds";
+
+// the namespace of a migration of the given type on a Runner with the given namespace
+fn seeds_namespace(prefix: &Type, namespace: Option<&str>) -> Option<String> {
+    match (prefix, namespace) {
+        (Type::Seed, Some(namespace)) => Some(format!("{}_{}", namespace, SEEDS_NAMESPACE)),
+        (Type::Seed, None) => Some(SEEDS_NAMESPACE.to_string()),
+        (_, namespace) => namespace.map(ToString::to_string),
+    }
+}
+
 /// An enum set that represents the target version up to which refinery should migrate, it is used by [Runner]
+/// It can be parsed from "latest", "fake", "next", a version i.e. "3", "fake:3" and "steps:2"
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Target {
     Latest,
     Version(u32),
     Fake,
     FakeVersion(u32),
+    /// Apply at most the given number of pending migrations
+    StepsForward(u32),
+    /// Apply only the next pending migration, the same as `StepsForward(1)`
+    Next,
+}
+
+impl Target {
+    // the number of migrations a stepping target applies, or rolls back
+    pub(crate) fn steps(self) -> Option<u32> {
+        match self {
+            Target::StepsForward(steps) => Some(steps),
+            Target::Next => Some(1),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::new(Kind::InvalidTarget(target.into()), None);
+        let parse = |number: &str| number.trim().parse::<u32>().map_err(|_| invalid());
+
+        match target.trim().split_once(':') {
+            None if target.trim() == "latest" => Ok(Target::Latest),
+            None if target.trim() == "fake" => Ok(Target::Fake),
+            None if target.trim() == "next" => Ok(Target::Next),
+            None => parse(target).map(Target::Version),
+            Some(("fake", version)) => parse(version).map(Target::FakeVersion),
+            Some(("steps", steps)) => parse(steps).map(Target::StepsForward),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<&str> for Target {
+    type Error = Error;
+
+    fn try_from(target: &str) -> Result<Self, Self::Error> {
+        target.parse()
+    }
+}
+
+/// An enum set that represents the database dialect a migration is run on, it is reported by the connection
+/// passed to [Runner] and used to generate the sql of Rust migrations written for multiple databases.
+/// Variants are added as refinery supports new databases, so matching on it needs a wildcard arm
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[non_exhaustive]
+pub enum Dialect {
+    Postgres,
+    Mysql,
+    Sqlite,
+    Mssql,
+    /// CockroachDB, run on the Postgres drivers. Sql is generated as for Postgres, except for the statements
+    /// refinery generates itself that CockroachDB doesn't support
+    Cockroach,
+    /// ClickHouse, which has no transactions
+    ClickHouse,
+    /// DuckDB, whose sql mostly follows Postgres'
+    DuckDb,
+    /// Oracle, which commits each schema change right away
+    Oracle,
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let dialect = match self {
+            Dialect::Postgres => "postgres",
+            Dialect::Mysql => "mysql",
+            Dialect::Sqlite => "sqlite",
+            Dialect::Mssql => "mssql",
+            Dialect::Cockroach => "cockroach",
+            Dialect::ClickHouse => "clickhouse",
+            Dialect::DuckDb => "duckdb",
+            Dialect::Oracle => "oracle",
+        };
+        write!(f, "{}", dialect)
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = Error;
+
+    fn from_str(dialect: &str) -> Result<Self, Self::Err> {
+        parse_dialect(dialect.trim())
+            .ok_or_else(|| Error::new(Kind::InvalidDialect(dialect.into()), None))
+    }
+}
+
+impl Dialect {
+    // statements that fail, rolling back the migration transaction, unless the `check` query returns a value
+    // that is true, i.e. not false, 0 or NULL, inserting it on a temporary table with a CHECK constraint,
+    // as not every dialect can raise errors outside of procedures
+    pub(crate) fn check_statements(self, number: usize, check: &str) -> String {
+        let condition = "result IS NOT NULL AND result NOT IN ('false', '0')";
+        match self {
+            Dialect::Postgres => format!(
+                "CREATE TEMP TABLE refinery_check (result text CONSTRAINT refinery_check_{0} CHECK ({1}));
+INSERT INTO refinery_check (result) SELECT (\n{2}\n);
+DROP TABLE refinery_check;",
+                number, condition, check
+            ),
+            Dialect::Mysql => format!(
+                "CREATE TEMPORARY TABLE refinery_check (result text, CONSTRAINT refinery_check_{0} CHECK ({1}));
+INSERT INTO refinery_check (result) SELECT (\n{2}\n);
+DROP TEMPORARY TABLE refinery_check;",
+                number, condition, check
+            ),
+            // sqlite booleans are integers, and columns without type don't convert them to text
+            Dialect::Sqlite => format!(
+                "CREATE TEMP TABLE refinery_check (result CONSTRAINT refinery_check_{0} CHECK ({1} AND result <> 0));
+INSERT INTO refinery_check (result) SELECT (\n{2}\n);
+DROP TABLE temp.refinery_check;",
+                number, condition, check
+            ),
+            // constraint names of temporary tables must be unique across sessions on mssql
+            Dialect::Mssql => format!(
+                "CREATE TABLE #refinery_check (result nvarchar(max) CHECK ({0}));
+INSERT INTO #refinery_check (result) SELECT (\n{1}\n);
+DROP TABLE #refinery_check;",
+                condition, check
+            ),
+            // temporary tables are experimental on cockroach, which instead can raise errors with a function
+            Dialect::Cockroach => format!(
+                "SELECT crdb_internal.force_error('23514', 'refinery_check_{0} failed') FROM (SELECT (\n{2}\n)::STRING AS result) AS refinery_check WHERE NOT ({1});",
+                number, condition, check
+            ),
+            // clickhouse compares the result as a string, and raises the error with a function
+            Dialect::ClickHouse => format!(
+                "SELECT throwIf(NOT ({1}), 'refinery_check_{0} failed') FROM (SELECT toString((\n{2}\n)) AS result);",
+                number, condition, check
+            ),
+            Dialect::DuckDb => format!(
+                "SELECT error('refinery_check_{0} failed') FROM (SELECT (\n{2}\n)::VARCHAR AS result) AS refinery_check WHERE NOT ({1});",
+                number, condition, check
+            ),
+            // oracle raises errors from a PL/SQL block, ended with a `/` line for the PL/SQL splitter, and booleans
+            // are converted to uppercase text
+            Dialect::Oracle => format!(
+                "BEGIN
+  FOR refinery_check IN (SELECT result FROM (SELECT LOWER(TO_CHAR((\n{2}\n))) AS result FROM dual) WHERE NOT ({1})) LOOP
+    RAISE_APPLICATION_ERROR(-20000, 'refinery_check_{0} failed');
+  END LOOP;
+END;
+/",
+                number, condition, check
+            ),
+        }
+    }
+}
+
+impl Dialect {
+    /// Returns true when the dialect can roll back schema changes made on a transaction, so a group of migrations
+    /// can be applied atomically. MySQL and Oracle commit each schema change right away and ClickHouse has no
+    /// transactions
+    pub fn supports_transactional_ddl(self) -> bool {
+        !matches!(self, Dialect::Mysql | Dialect::ClickHouse | Dialect::Oracle)
+    }
+
+    // the sql expression of the user of the connection, recorded on the schema history table as the one applying the
+    // migrations unless it's set with `Runner::set_applied_by`, sqlite and duckdb have no users
+    pub(crate) fn current_user(self) -> Option<&'static str> {
+        match self {
+            Dialect::Postgres | Dialect::Cockroach => Some("CURRENT_USER"),
+            Dialect::Mysql => Some("CURRENT_USER()"),
+            Dialect::Mssql => Some("SUSER_SNAME()"),
+            Dialect::ClickHouse => Some("currentUser()"),
+            Dialect::Oracle => Some("USER"),
+            Dialect::Sqlite | Dialect::DuckDb => None,
+        }
+    }
+}
+
+impl Dialect {
+    // statements that set the timeout of a migration and reset it after it, `None` when it's not supported.
+    // Postgres times out both statements and lock waits, Mysql lock waits and queries, Mssql only lock waits and
+    // Cockroach, that only supports lock timeouts on its latest versions, only statements, which include their lock
+    // waits. Oracle only times out the lock waits of schema changes. Sqlite times out waiting on locks with the busy
+    // timeout of the connection, see `Runner::set_timeout`. Postgres and Cockroach set it for the transaction of the
+    // migration, and on the session for the ones run outside of a transaction, where `SET LOCAL` has no effect
+    fn timeout_statements(
+        self,
+        timeout: Duration,
+        transactional: bool,
+    ) -> Option<(String, String)> {
+        let millis = timeout.as_millis();
+        match self {
+            Dialect::Postgres if transactional => Some((
+                format!(
+                    "SET LOCAL statement_timeout = {0}; SET LOCAL lock_timeout = {0};",
+                    millis
+                ),
+                "SET LOCAL statement_timeout TO DEFAULT; SET LOCAL lock_timeout TO DEFAULT;".into(),
+            )),
+            Dialect::Postgres => Some((
+                format!("SET statement_timeout = {0}; SET lock_timeout = {0};", millis),
+                "RESET statement_timeout; RESET lock_timeout;".into(),
+            )),
+            // mysql lock wait timeouts are in seconds, and the max execution time of its queries in milliseconds
+            Dialect::Mysql => Some((
+                format!(
+                    "SET SESSION innodb_lock_wait_timeout = {0}, lock_wait_timeout = {0}, max_execution_time = {1};",
+                    timeout.as_secs().max(1),
+                    millis
+                ),
+                "SET SESSION innodb_lock_wait_timeout = DEFAULT, lock_wait_timeout = DEFAULT, max_execution_time = DEFAULT;"
+                    .into(),
+            )),
+            Dialect::Mssql => Some((
+                format!("SET LOCK_TIMEOUT {};", millis),
+                "SET LOCK_TIMEOUT -1;".into(),
+            )),
+            Dialect::Cockroach if transactional => Some((
+                format!("SET LOCAL statement_timeout = {};", millis),
+                "SET LOCAL statement_timeout TO DEFAULT;".into(),
+            )),
+            Dialect::Cockroach => Some((
+                format!("SET statement_timeout = {};", millis),
+                "RESET statement_timeout;".into(),
+            )),
+            // the ddl lock timeout of oracle is in seconds
+            Dialect::Oracle => Some((
+                format!(
+                    "ALTER SESSION SET DDL_LOCK_TIMEOUT = {};",
+                    timeout.as_secs().max(1)
+                ),
+                "ALTER SESSION SET DDL_LOCK_TIMEOUT = 0;".into(),
+            )),
+            // clickhouse settings of a session don't apply to the queries of a migration, that aren't run on one
+            Dialect::Sqlite | Dialect::ClickHouse | Dialect::DuckDb => None,
+        }
+    }
+}
+
+impl Dialect {
+    // the statement switching the connection to the schema, or database on mysql, of a tenant, so the unqualified
+    // tables of its migrations are created on it, `None` when the dialect can't switch schemas with a statement
+    pub(crate) fn use_tenant_statement(self, tenant: &str) -> Option<String> {
+        let tenant = self.quote_identifier(tenant);
+        match self {
+            Dialect::Postgres | Dialect::Cockroach => {
+                Some(format!("SET search_path TO {};", tenant))
+            }
+            Dialect::Mysql => Some(format!("USE {};", tenant)),
+            Dialect::Oracle => Some(format!("ALTER SESSION SET CURRENT_SCHEMA = {};", tenant)),
+            Dialect::Sqlite | Dialect::Mssql | Dialect::ClickHouse | Dialect::DuckDb => None,
+        }
+    }
+}
+
+impl Dialect {
+    // the opening and closing quotes of identifiers
+    fn quotes(self) -> (char, char) {
+        match self {
+            Dialect::Postgres
+            | Dialect::Sqlite
+            | Dialect::Cockroach
+            | Dialect::DuckDb
+            | Dialect::Oracle => ('"', '"'),
+            Dialect::Mysql | Dialect::ClickHouse => ('`', '`'),
+            Dialect::Mssql => ('[', ']'),
+        }
+    }
+
+    // quotes the parts of a, possibly schema-qualified, table name that aren't plain identifiers, and re-quotes the
+    // quoted ones with the quotes of the dialect. Plain identifiers are kept unquoted so they keep being case
+    // insensitive. Tables can be qualified with a schema, or an attached database on sqlite, and on mssql and
+    // duckdb also with a database
+    fn quote_table_name(self, name: &str) -> Result<String, Error> {
+        let parts = table_name_parts(name)?;
+        let max_parts = match self {
+            Dialect::Mssql | Dialect::DuckDb => 3,
+            _ => 2,
+        };
+        if parts.len() > max_parts {
+            return Err(Error::new(
+                Kind::InvalidMigrationTableName(
+                    name.into(),
+                    format!(
+                        "{} tables can only be qualified with {}",
+                        self,
+                        if max_parts == 3 {
+                            "a database and a schema"
+                        } else {
+                            "a schema"
+                        }
+                    ),
+                ),
+                None,
+            ));
+        }
+
+        let parts: Vec<String> = parts
+            .into_iter()
+            .map(|(part, quoted)| {
+                if quoted {
+                    self.quote(&part)
+                } else {
+                    self.quote_identifier(&part)
+                }
+            })
+            .collect();
+        Ok(parts.join("."))
+    }
+
+    // quotes an identifier that isn't a plain one
+    fn quote_identifier(self, identifier: &str) -> String {
+        if is_plain_identifier(identifier) {
+            identifier.to_string()
+        } else {
+            self.quote(identifier)
+        }
+    }
+
+    fn quote(self, identifier: &str) -> String {
+        let (open, close) = self.quotes();
+        let escaped = identifier.replace(close, &format!("{0}{0}", close));
+        format!("{}{}{}", open, escaped, close)
+    }
+}
+
+fn is_plain_identifier(part: &str) -> bool {
+    let mut chars = part.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+// splits a table name on the dots that separate its database, schema and table parts, unquoting the parts quoted
+// with double quotes, backticks or square brackets, where a doubled closing quote escapes it
+pub(crate) fn table_name_parts(name: &str) -> Result<Vec<(String, bool)>, Error> {
+    let invalid = |reason: &str| {
+        Error::new(
+            Kind::InvalidMigrationTableName(name.into(), reason.into()),
+            None,
+        )
+    };
+
+    let mut parts = Vec::new();
+    let mut chars = name.chars().peekable();
+    loop {
+        let close = match chars.peek() {
+            Some('"') => Some('"'),
+            Some('`') => Some('`'),
+            Some('[') => Some(']'),
+            _ => None,
+        };
+        let part = match close {
+            Some(close) => {
+                chars.next();
+                let mut part = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == close && chars.peek() == Some(&close) => {
+                            chars.next();
+                            part.push(close);
+                        }
+                        Some(c) if c == close => break,
+                        Some(c) => part.push(c),
+                        None => return Err(invalid("it has an unterminated quoted identifier")),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some('.')) {
+                    return Err(invalid("quoted identifiers must be followed by a dot"));
+                }
+                part
+            }
+            None => {
+                let mut part = String::new();
+                while let Some(c) = chars.next_if(|c| *c != '.') {
+                    part.push(c);
+                }
+                part
+            }
+        };
+        if part.is_empty() {
+            return Err(invalid("it has an empty identifier"));
+        }
+        parts.push((part, close.is_some()));
+        if chars.next().is_none() {
+            break;
+        }
+    }
+    if parts.len() > 3 {
+        return Err(invalid(
+            "tables can only be qualified with a database and a schema",
+        ));
+    }
+    Ok(parts)
+}
+
+// the function of an async Rust migration, see `Migration::unapplied_async`
+type AsyncMigrationFn = for<'a> fn(
+    &'a mut dyn AsyncExecutor,
+) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+// generates the sql of a Rust migration for the Dialect of the connection, see `Migration::unapplied_for_dialect`
+// and `Migration::try_unapplied_for_dialect`
+#[derive(Clone, Copy, Debug)]
+enum GenerateSql {
+    Infallible(fn(Dialect) -> String),
+    Fallible(fn(Dialect) -> Result<String, Error>),
+}
+
+impl GenerateSql {
+    fn generate(self, dialect: Dialect) -> Result<String, Error> {
+        match self {
+            GenerateSql::Infallible(generate_sql) => Ok(generate_sql(dialect)),
+            GenerateSql::Fallible(generate_sql) => generate_sql(dialect),
+        }
+    }
 }
 
 // an Enum set that represents the state of the migration: Applied on the database,
 // or Unapplied yet to be applied on the database
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum State {
     Applied,
     Unapplied,
@@ -62,63 +519,500 @@ enum State {
 ///
 /// [`embed_migrations!`]: macro.embed_migrations.html
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Migration {
     state: State,
     name: String,
     checksum: u64,
+    // the algorithm of the checksum, see `Runner::set_checksum_algorithm`
+    #[cfg_attr(feature = "serde", serde(default))]
+    checksum_algorithm: ChecksumAlgorithm,
+    // the checksums of an unapplied migration with every algorithm, None for applied ones
+    #[cfg_attr(feature = "serde", serde(skip))]
+    checksums: Option<Checksums>,
     version: i32,
     prefix: Type,
-    sql: Option<String>,
+    // the naming scheme of the migration file, applied migrations are displayed as versioned ones
+    #[cfg_attr(feature = "serde", serde(default))]
+    naming: MigrationNaming,
+    sql: Option<Cow<'static, str>>,
+    // generates the sql for the Dialect of the connection, see `Migration::unapplied_for_dialect`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    generate_sql: Option<GenerateSql>,
+    // runs an async Rust migration on the connection, see `Migration::unapplied_async`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    migrate_async: Option<AsyncMigrationFn>,
+    // the Dialect of a migration variant, i.e. `V7__add_fulltext.postgres.sql`
+    dialect: Option<Dialect>,
+    // the namespace of the migration set, see `Runner::set_namespace`
+    #[cfg_attr(feature = "serde", serde(default))]
+    namespace: Option<String>,
+    // the `-- refinery:down` section, kept when it's stripped from the sql that is applied
+    #[cfg_attr(feature = "serde", serde(default))]
+    down: Option<Cow<'static, str>>,
+    // the Splitter the sql is split with, see `Runner::set_splitter`, the default one of the connection when None
+    #[cfg_attr(feature = "serde", serde(skip))]
+    splitter: Option<Splitter>,
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339::option"))]
     applied_on: Option<OffsetDateTime>,
+    // the user recorded as applying the migration, see `Runner::set_applied_by`, the user of the connection when None
+    #[cfg_attr(feature = "serde", serde(default))]
+    applied_by: Option<String>,
+    // how long applying the migration took, only known for the migrations applied on their own by the run
+    #[cfg_attr(feature = "serde", serde(default))]
+    execution_time: Option<Duration>,
+    // when the migration was committed, known along its execution time
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "time::serde::rfc3339::option")
+    )]
+    finished_on: Option<OffsetDateTime>,
+    // the timeout of a migration without a `-- refinery:timeout` directive, see `Runner::set_timeout`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    default_timeout: Option<Duration>,
+    // the statement resetting the timeout set on the session of the connection by the migration, executed after it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    reset_timeout: Option<String>,
+    // whether the migration is skipped without a `-- refinery:skip` directive, see `Runner::set_skip_versions`
+    #[cfg_attr(feature = "serde", serde(default))]
+    skip: bool,
+    // whether the down section was stripped from the sql when it was embedded, see `Migration::unapplied_without_down`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    down_omitted: bool,
 }
 
 impl Migration {
     /// Create an unapplied migration, name and version are parsed from the input_name,
     /// which must be named in the format (U|V){1}__{2}.rs where {1} represents the migration version and {2} the name.
+    /// The name can be followed by a [`Dialect`] i.e. `V7__add_fulltext.postgres` for a variant of the migration
+    /// that is only run on that database.
     pub fn unapplied(input_name: &str, sql: &str) -> Result<Migration, Error> {
+        Migration::unapplied_from(input_name, sql.to_owned())
+    }
+
+    /// Create an unapplied migration without copying its sql content, `sql` can either be an embedded `&'static str`
+    /// which is then borrowed for the lifetime of the Migration, or an owned `String` which is moved into it.
+    /// Name and version are parsed from the input_name the same way as [`Migration::unapplied`].
+    pub fn unapplied_from<S>(input_name: &str, sql: S) -> Result<Migration, Error>
+    where
+        S: Into<Cow<'static, str>>,
+    {
         let (prefix, version, name) = parse_migration_name(input_name)?;
+        let namespace = seeds_namespace(&prefix, None);
+        let sql = sql.into();
+        let checksums = Checksums::new(&name, version, &sql);
+
+        let migration = Migration {
+            state: State::Unapplied,
+            name,
+            version,
+            prefix,
+            naming: parse_migration_naming(input_name).unwrap_or_default(),
+            sql: Some(sql),
+            generate_sql: None,
+            migrate_async: None,
+            dialect: parse_migration_dialect(input_name),
+            namespace,
+            down: None,
+            splitter: None,
+            applied_on: None,
+            applied_by: None,
+            execution_time: None,
+            finished_on: None,
+            default_timeout: None,
+            reset_timeout: None,
+            skip: false,
+            down_omitted: false,
+            checksum: checksums.get(ChecksumAlgorithm::default()),
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            checksums: Some(checksums),
+        };
+        migration.validate_directives()?;
+        Ok(migration)
+    }
+
+    /// Create an unapplied migration whose `-- refinery:down` section was stripped from `sql` when it was embedded,
+    /// used by the [`embed_migrations!`] macro with `omit_down = true`. `checksums` are the checksums of the migration
+    /// with its down section with every [`ChecksumAlgorithm`], in the order of its variants, so it keeps the checksum it
+    /// was applied with. [`Migration::down_sql`] returns `None` and rolling it back fails with [`Kind::DownOmitted`].
+    ///
+    /// [`embed_migrations!`]: macro.embed_migrations.html
+    pub fn unapplied_without_down(
+        input_name: &str,
+        sql: &'static str,
+        checksums: [u64; 3],
+    ) -> Result<Migration, Error> {
+        let migration = Migration::unapplied_from(input_name, sql)?;
+        let checksums = Checksums::from(checksums);
+        Ok(Migration {
+            checksum: checksums.get(migration.checksum_algorithm),
+            checksums: Some(checksums),
+            down_omitted: true,
+            ..migration
+        })
+    }
+
+    // fail on directive options with invalid values, instead of ignoring them when the migration is run
+    fn validate_directives(&self) -> Result<(), Error> {
+        let invalid = |key: &str, value: &str| {
+            Error::new(
+                Kind::InvalidDirective(self.to_string(), format!("{}={}", key, value)),
+                None,
+            )
+        };
+        if let Some(retries) = self.directive_option("retries") {
+            retries
+                .parse::<u32>()
+                .map_err(|_| invalid("retries", retries))?;
+        }
+        for key in ["backoff", "timeout"] {
+            if let Some(duration) = self.directive_option(key) {
+                parse_duration(duration).ok_or_else(|| invalid(key, duration))?;
+            }
+        }
+        Ok(())
+    }
 
-        // Previously, `std::collections::hash_map::DefaultHasher` was used
-        // to calculate the checksum and the implementation at that time
-        // was SipHasher13. However, that implementation is not guaranteed:
-        // > The internal algorithm is not specified, and so it and its
-        // > hashes should not be relied upon over releases.
-        // We now explicitly use SipHasher13 to both remain compatible with
-        // existing migrations and prevent breaking from possible future
-        // changes to `DefaultHasher`.
-        let mut hasher = SipHasher13::new();
-        name.hash(&mut hasher);
-        version.hash(&mut hasher);
-        sql.hash(&mut hasher);
-        let checksum = hasher.finish();
+    /// Create an unapplied migration whose sql is only generated when it's run, for the [`Dialect`] of the connection.
+    /// This is used by the [`embed_migrations!`] macro for Rust migrations with a `migration(dialect: Dialect)` function,
+    /// until then [`Migration::sql`] returns `None`.
+    /// The checksum is formed from the sql generated for the Dialect.
+    ///
+    /// [`embed_migrations!`]: macro.embed_migrations.html
+    pub fn unapplied_for_dialect(
+        input_name: &str,
+        generate_sql: fn(Dialect) -> String,
+    ) -> Result<Migration, Error> {
+        Migration::with_generate_sql(input_name, GenerateSql::Infallible(generate_sql))
+    }
+
+    /// Create an unapplied migration whose sql is generated when it's run like [`Migration::unapplied_for_dialect`],
+    /// with a function that can fail, i.e. for a [`Dialect`] it can't generate the sql for. The Error is returned from
+    /// the run, before the migration is applied.
+    /// This is used by the [`embed_migrations!`] macro for Rust migrations with a
+    /// `migration(dialect: Dialect) -> Result<String, Error>` function.
+    ///
+    /// [`embed_migrations!`]: macro.embed_migrations.html
+    pub fn try_unapplied_for_dialect(
+        input_name: &str,
+        generate_sql: fn(Dialect) -> Result<String, Error>,
+    ) -> Result<Migration, Error> {
+        Migration::with_generate_sql(input_name, GenerateSql::Fallible(generate_sql))
+    }
+
+    fn with_generate_sql(input_name: &str, generate_sql: GenerateSql) -> Result<Migration, Error> {
+        let (prefix, version, name) = parse_migration_name(input_name)?;
+        let namespace = seeds_namespace(&prefix, None);
 
         Ok(Migration {
             state: State::Unapplied,
             name,
             version,
             prefix,
-            sql: Some(sql.into()),
+            naming: parse_migration_naming(input_name).unwrap_or_default(),
+            sql: None,
+            generate_sql: Some(generate_sql),
+            migrate_async: None,
+            dialect: None,
+            namespace,
+            down: None,
+            splitter: None,
             applied_on: None,
-            checksum,
+            applied_by: None,
+            execution_time: None,
+            finished_on: None,
+            default_timeout: None,
+            reset_timeout: None,
+            skip: false,
+            down_omitted: false,
+            checksum: 0,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            checksums: None,
+        })
+    }
+
+    /// Create an unapplied async Rust migration, that instead of sql runs the given function on the connection
+    /// so it can query, transform and write data with the driver, see [`AsyncExecutor`].
+    /// This is used by the [`embed_migrations!`] macro for Rust migrations with an
+    /// `async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), Error>` function,
+    /// [`Migration::sql`] returns `None` for them and the checksum is formed from the `source` of the migration.
+    /// They can only be run with [`Runner::run_async`] without grouping, on connections that provide the executor,
+    /// and are applied on their own before their insert on the schema history table.
+    ///
+    /// [`embed_migrations!`]: macro.embed_migrations.html
+    pub fn unapplied_async(
+        input_name: &str,
+        source: &str,
+        migrate_async: AsyncMigrationFn,
+    ) -> Result<Migration, Error> {
+        let (prefix, version, name) = parse_migration_name(input_name)?;
+        let namespace = seeds_namespace(&prefix, None);
+        let checksums = Checksums::new(&name, version, source);
+
+        Ok(Migration {
+            state: State::Unapplied,
+            name,
+            version,
+            prefix,
+            naming: parse_migration_naming(input_name).unwrap_or_default(),
+            sql: None,
+            generate_sql: None,
+            migrate_async: Some(migrate_async),
+            dialect: None,
+            namespace,
+            down: None,
+            splitter: None,
+            applied_on: None,
+            applied_by: None,
+            execution_time: None,
+            finished_on: None,
+            default_timeout: None,
+            reset_timeout: None,
+            skip: false,
+            down_omitted: false,
+            checksum: checksums.get(ChecksumAlgorithm::default()),
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            checksums: Some(checksums),
         })
     }
 
-    // Create a migration from an applied migration on the database
+    // Generate the sql of a migration created with `Migration::unapplied_for_dialect`, and the statements
+    // that assert the checks of its `-- refinery:check` section, other migrations are returned unchanged
+    pub(crate) fn for_dialect(&self, dialect: Option<Dialect>) -> Result<Migration, Error> {
+        let has_checks = self
+            .sql()
+            .and_then(|sql| split_checks(split_down(sql).0))
+            .is_some();
+        if dialect.is_none()
+            && (self.generate_sql.is_some()
+                || self.dialect.is_some()
+                || has_checks
+                || self.timeout().is_some())
+        {
+            return Err(Error::new(Kind::UnknownDialect(self.clone()), None));
+        }
+        let mut migration = match (self.generate_sql, dialect) {
+            (Some(generate_sql), Some(dialect)) => {
+                let sql = generate_sql.generate(dialect)?;
+                let checksums = Checksums::new(&self.name, self.version, &sql);
+                Migration {
+                    checksum: checksums.get(self.checksum_algorithm),
+                    checksums: Some(checksums),
+                    sql: Some(sql.into()),
+                    generate_sql: None,
+                    ..self.clone()
+                }
+            }
+            _ => self.clone(),
+        };
+
+        // the down section is never applied, it's the last section of the sql, after the check one
+        if let Some((sql, Some(down))) = migration.sql().map(split_down) {
+            let (sql, down) = (sql.to_string(), down.to_string());
+            migration.sql = Some(sql.into());
+            migration.down = Some(down.into());
+        }
+
+        // the checksum includes the check section as written
+        if let (Some(dialect), Some((sql, checks))) =
+            (dialect, migration.sql().and_then(split_checks))
+        {
+            let mut sql = sql.to_string();
+            for (i, check) in checks.into_iter().enumerate() {
+                sql.push('\n');
+                sql.push_str(&dialect.check_statements(i + 1, check));
+            }
+            migration.sql = Some(sql.into());
+        }
+
+        // the timeout is set after the header so its directives can still be read
+        if let (Some(dialect), Some(timeout)) = (dialect, migration.timeout()) {
+            let transactional = migration.is_transactional();
+            match dialect.timeout_statements(timeout, transactional) {
+                // `SET LOCAL` is undone with the transaction of the migration, it's only reset at its end for the
+                // next migrations of a grouped run
+                Some((set_timeout, reset_timeout))
+                    if transactional
+                        && matches!(dialect, Dialect::Postgres | Dialect::Cockroach) =>
+                {
+                    let (header, sql) = split_header(migration.sql().expect("sql must be Some!"));
+                    let sql = format!("{}{}\n{}\n{}", header, set_timeout, sql, reset_timeout);
+                    migration.sql = Some(sql.into());
+                }
+                // settings of the session outlive the migration, so they are reset after it whether it's applied or
+                // not, see `Migration::reset_timeout`
+                Some((set_timeout, reset_timeout)) => {
+                    let (header, sql) = split_header(migration.sql().expect("sql must be Some!"));
+                    let sql = format!("{}{}\n{}", header, set_timeout, sql);
+                    migration.sql = Some(sql.into());
+                    migration.reset_timeout = Some(reset_timeout);
+                }
+                // the busy timeout of sqlite connections is set before the run
+                None if dialect == Dialect::Sqlite && migration.default_timeout.is_some() => {}
+                None => log::warn!(
+                    "ignoring the timeout of migration {}, timeouts aren't supported on {}",
+                    migration,
+                    dialect
+                ),
+            }
+        }
+        Ok(migration)
+    }
+
+    // the name and contents of a migration file the migration is loaded back from with the same checksum,
+    // i.e. `V7__add_fulltext.postgres.sql`, the sql of Rust migrations is generated for the given Dialect
+    #[cfg(feature = "archive")]
+    pub(crate) fn to_file(&self, dialect: Option<Dialect>) -> Result<(String, String), Error> {
+        if self.migrate_async.is_some() {
+            return Err(Error::new(
+                Kind::UnsupportedAsyncMigration(self.clone()),
+                None,
+            ));
+        }
+        // the checksum of the migration is formed from the sql with the down section
+        if self.down_omitted {
+            return Err(Error::new(Kind::DownOmitted(self.clone()), None));
+        }
+        let sql = match (self.generate_sql, dialect) {
+            (Some(generate_sql), Some(dialect)) => generate_sql.generate(dialect)?,
+            (Some(_), None) => {
+                return Err(Error::new(Kind::UnknownDialect(self.clone()), None));
+            }
+            (None, _) => self.sql().expect("sql must be Some!").to_string(),
+        };
+        let mut file_name = match self.naming {
+            MigrationNaming::Versioned => format!("{}{}__{}", self.prefix, self.version, self.name),
+            MigrationNaming::Timestamp => {
+                format!("{}_{}", format_timestamp_version(self.version), self.name)
+            }
+        };
+        if let Some(dialect) = self.dialect {
+            file_name.push_str(&format!(".{}", dialect));
+        }
+        file_name.push_str(".sql");
+        Ok((file_name, sql))
+    }
+
+    // replace the `${name}` placeholders of the sql and the down section with their values, the checksum is kept
+    // as it's formed from the sql as written, see `Runner::set_placeholders`
+    fn with_placeholders(
+        mut self,
+        placeholders: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Migration, Error> {
+        let replace = |sql: &str| {
+            replace_placeholders(sql, placeholders, strict)
+                .map(Cow::into_owned)
+                .map_err(|name| Error::new(Kind::UnknownPlaceholder(name, self.clone()), None))
+        };
+        let sql = self.sql.as_deref().map(replace).transpose()?;
+        let down = self.down.as_deref().map(replace).transpose()?;
+        if let Some(sql) = sql {
+            self.sql = Some(sql.into());
+        }
+        if let Some(down) = down {
+            self.down = Some(down.into());
+        }
+        Ok(self)
+    }
+
+    // Create a migration from an applied migration on the database,
+    // the name of migrations applied on a namespace is recorded as `{namespace}/{name}`
     pub fn applied(
         version: i32,
         name: String,
         applied_on: OffsetDateTime,
         checksum: u64,
     ) -> Migration {
+        let (namespace, name) = match name.split_once('/') {
+            Some((namespace, name)) => (Some(namespace.to_string()), name.to_string()),
+            None => (None, name),
+        };
         Migration {
             state: State::Applied,
             name,
             checksum,
+            checksum_algorithm: ChecksumAlgorithm::Sip13Legacy,
+            checksums: None,
             version,
             // applied migrations are always versioned
             prefix: Type::Versioned,
+            naming: MigrationNaming::Versioned,
             sql: None,
+            generate_sql: None,
+            migrate_async: None,
+            dialect: None,
+            namespace,
+            down: None,
+            splitter: None,
             applied_on: Some(applied_on),
+            applied_by: None,
+            execution_time: None,
+            finished_on: None,
+            default_timeout: None,
+            reset_timeout: None,
+            skip: false,
+            down_omitted: false,
+        }
+    }
+
+    /// Create a migration from an applied migration on the database with the checksum recorded on the schema history
+    /// table, prefixed by its [`ChecksumAlgorithm`] or a legacy one, see [`ChecksumAlgorithm::parse_checksum`].
+    ///
+    /// # Panics
+    ///
+    /// If the checksum is invalid
+    pub fn applied_with_checksum(
+        version: i32,
+        name: String,
+        applied_on: OffsetDateTime,
+        checksum: &str,
+    ) -> Migration {
+        let (checksum_algorithm, checksum) = ChecksumAlgorithm::parse_checksum(checksum)
+            .unwrap_or_else(|| panic!("invalid checksum {}", checksum));
+        Migration {
+            checksum_algorithm,
+            ..Migration::applied(version, name, applied_on, checksum)
+        }
+    }
+
+    // form the checksum with the given algorithm, applied migrations keep theirs
+    fn with_checksum_algorithm(mut self, algorithm: ChecksumAlgorithm) -> Migration {
+        if matches!(self.state, State::Unapplied) {
+            self.checksum_algorithm = algorithm;
+            if let Some(checksums) = self.checksums {
+                self.checksum = checksums.get(algorithm);
+            }
+        }
+        self
+    }
+
+    // the checksum as it's recorded on the schema history table
+    pub(crate) fn recorded_checksum(&self) -> String {
+        self.checksum_algorithm.format_checksum(self.checksum)
+    }
+
+    // whether the checksums match, with the algorithm of the applied one when the other is unapplied,
+    // so migrations applied with the legacy algorithm are still verified after switching to a new one
+    fn checksum_matches(&self, other: &Migration) -> bool {
+        if self.checksum_algorithm == other.checksum_algorithm {
+            return self.checksum == other.checksum;
+        }
+        match (self.checksums, other.checksums) {
+            (Some(checksums), _) => checksums.get(other.checksum_algorithm) == other.checksum,
+            (None, Some(checksums)) => checksums.get(self.checksum_algorithm) == self.checksum,
+            (None, None) => false,
+        }
+    }
+
+    // the checksum as it would be recorded with the algorithm of the applied migration, when it was formed
+    // with every algorithm, so mismatches are shown with comparable values
+    fn recorded_checksum_as(&self, applied: &Migration) -> String {
+        match self.checksums {
+            Some(checksums) => applied
+                .checksum_algorithm
+                .format_checksum(checksums.get(applied.checksum_algorithm)),
+            None => self.recorded_checksum(),
         }
     }
 
@@ -128,11 +1022,51 @@ impl Migration {
         self.state = State::Applied;
     }
 
-    // Get migration sql content
+    /// Get the Migration sql content, borrowed from the embedded file content when the Migration was created from it.
+    /// `None` for migrations queried from the database.
     pub fn sql(&self) -> Option<&str> {
         self.sql.as_deref()
     }
 
+    /// Get the [`Dialect`] the Migration is a variant for, i.e. `Some(Dialect::Postgres)` for `V7__add_fulltext.postgres.sql`.
+    /// `None` for migrations that run on any database.
+    pub fn dialect(&self) -> Option<Dialect> {
+        self.dialect
+    }
+
+    /// Get the sql of the `-- refinery:down` section that reverts the Migration, which is never applied with it.
+    /// It's kept on the applied migrations of the [`Report`], so the exact rollback script of what was just applied
+    /// can be archived. `None` for migrations without it, migrations queried from the database and the ones embedded
+    /// without it with `omit_down = true`, see [`Migration::unapplied_without_down`].
+    pub fn down_sql(&self) -> Option<&str> {
+        self.down
+            .as_deref()
+            .or_else(|| self.sql().and_then(|sql| split_down(sql).1))
+    }
+
+    /// Get the namespace of the migration set the Migration belongs to, see [`Runner::set_namespace`]
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    // the function of a migration created with `Migration::unapplied_async`
+    pub(crate) fn migrate_async(&self) -> Option<AsyncMigrationFn> {
+        self.migrate_async
+    }
+
+    // the Splitter set on the Migration with `Runner::set_splitter`
+    pub(crate) fn splitter(&self) -> Option<&Splitter> {
+        self.splitter.as_ref()
+    }
+
+    // the name the Migration is recorded with on the schema history table
+    pub(crate) fn recorded_name(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, self.name),
+            None => self.name.clone(),
+        }
+    }
+
     /// Get the Migration version
     pub fn version(&self) -> u32 {
         self.version as u32
@@ -143,26 +1077,188 @@ impl Migration {
         &self.prefix
     }
 
-    /// Get the Migration Name
+    /// Get the Migration Name, including its metadata segments if any
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Get the Migration metadata, the `__` separated segments following the name in the migration filename,
+    /// i.e. `["TICKET-123", "alice"]` for `V12__add_users__TICKET-123__alice.sql`.
+    /// Also available for applied migrations as it's parsed from the name stored on the schema history table.
+    pub fn metadata(&self) -> Vec<&str> {
+        self.name.split("__").skip(1).collect()
+    }
+
+    /// Get the value of a `-- key: value` line from the leading comment block of the Migration sql,
+    /// keys are case insensitive. `None` for applied migrations, as their sql isn't retrieved.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        parse_header(self.sql()?)
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Get the Migration author from the `-- author:` header comment
+    pub fn author(&self) -> Option<&str> {
+        self.header("author")
+    }
+
+    /// Get the Migration description from the `-- description:` header comment
+    pub fn description(&self) -> Option<&str> {
+        self.header("description")
+    }
+
+    /// Get the Migration ticket from the `-- ticket:` header comment
+    pub fn ticket(&self) -> Option<&str> {
+        self.header("ticket")
+    }
+
+    /// Get the values of the `-- refinery:` directives from the leading comment block of the Migration sql
+    pub(crate) fn directives(&self) -> impl Iterator<Item = &str> {
+        self.sql()
+            .map(parse_header)
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("refinery"))
+            .map(|(_, value)| value)
+    }
+
+    // the value of a `key=value` option of the `-- refinery:` directives, i.e. `-- refinery:retries=3 backoff=5s`
+    fn directive_option(&self, key: &str) -> Option<&str> {
+        self.directives()
+            .flat_map(str::split_whitespace)
+            .filter_map(|option| option.split_once('='))
+            .find(|(option, _)| option.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Get how many times the Migration is retried when it fails to apply, set with the `-- refinery:retries=3` directive
+    /// to retry migrations known to hit transient errors like lock contention. By default migrations aren't retried.
+    /// Migrations run grouped in a single transaction aren't retried.
+    pub fn retries(&self) -> u32 {
+        self.directive_option("retries")
+            .and_then(|retries| retries.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Get how long to wait before retrying the Migration, set with the `backoff` option of the retries directive,
+    /// i.e. `-- refinery:retries=3 backoff=5s`, as `ms`, `s`, `m` or `h`. By default this is set to 1 second
+    pub fn backoff(&self) -> Duration {
+        self.directive_option("backoff")
+            .and_then(parse_duration)
+            .unwrap_or(Duration::from_secs(1))
+    }
+
+    /// Get the timeout of the Migration, set with the `-- refinery:timeout=120s` directive or for every migration
+    /// with [`Runner::set_timeout`], exceeding it cancels the migration and rolls it back, failing with
+    /// [`Kind::Timeout`]. On Postgres it's both a statement and lock timeout, on Mysql a lock wait timeout and the
+    /// `max_execution_time` of its queries, on Mssql a lock timeout, and on Sqlite the busy timeout of the connection
+    /// when it's set with [`Runner::set_timeout`]. Timeouts set on the session of the connection, on Mysql, Mssql,
+    /// Oracle and for migrations run outside of a transaction on Postgres, are reset after the migration whether it's
+    /// applied or not
+    pub fn timeout(&self) -> Option<Duration> {
+        self.directive_option("timeout")
+            .and_then(parse_duration)
+            .or(self.default_timeout)
+    }
+
+    // the statement resetting the timeout the migration sets on the session of the connection, executed after the
+    // migration whether it's applied or not, so it doesn't outlive it on pooled connections
+    pub(crate) fn reset_timeout(&self) -> Option<&str> {
+        self.reset_timeout.as_deref()
+    }
+
+    /// Check if the Migration is marked with the `-- refinery:skip` directive or its version is skipped with
+    /// [`Runner::set_skip_versions`], skipped migrations are recorded on the schema history table to preserve the
+    /// ordering, flagged on its `skipped` column, but their sql is never executed.
+    pub fn is_skipped(&self) -> bool {
+        self.skip || self.directives().any(|directive| directive == "skip")
+    }
+
+    /// Check if the Migration is run in a transaction, unless it's marked with the `-- refinery:no-transaction`
+    /// directive for statements that can't run inside one, i.e. `CREATE INDEX CONCURRENTLY` on Postgres.
+    /// Its statements are then executed one at a time and it's recorded on the schema history table after them,
+    /// so a failure can leave it partially applied and not recorded. It can't be run grouped with other migrations.
+    pub fn is_transactional(&self) -> bool {
+        !self
+            .directives()
+            .flat_map(str::split_whitespace)
+            .any(|directive| directive == "no-transaction")
+    }
+
     /// Get the timestamp from when the Migration was applied. `None` when unapplied.
     /// Migrations returned from Runner::get_migrations() will always have `None`.
     pub fn applied_on(&self) -> Option<&OffsetDateTime> {
         self.applied_on.as_ref()
     }
 
-    /// Get the Migration checksum. Checksum is formed from the name version and sql of the Migration
+    /// Get the user set with [`Runner::set_applied_by`] the Migration is recorded as applied by on the schema history
+    /// table. `None` when it's recorded with the user of the connection, and for migrations queried from the database.
+    pub fn applied_by(&self) -> Option<&str> {
+        self.applied_by.as_deref()
+    }
+
+    /// Get how long applying the Migration took, from the start of its transaction until it was committed, as recorded
+    /// on the `execution_time_ms` column of the schema history table. Only known for the migrations of the [`Report`]
+    /// that were applied on their own, grouped and fake runs don't record it.
+    pub fn execution_time(&self) -> Option<Duration> {
+        self.execution_time
+    }
+
+    pub(crate) fn set_execution_time(
+        &mut self,
+        execution_time: Duration,
+        finished_on: OffsetDateTime,
+    ) {
+        self.execution_time = Some(execution_time);
+        self.finished_on = Some(finished_on);
+    }
+
+    /// Get the timestamp from when the run started applying the Migration, known along its [`Migration::execution_time`]
+    pub fn started_on(&self) -> Option<OffsetDateTime> {
+        Some(self.finished_on? - self.execution_time?)
+    }
+
+    /// Get the timestamp from when the Migration was committed, known along its [`Migration::execution_time`]
+    pub fn finished_on(&self) -> Option<OffsetDateTime> {
+        self.finished_on
+    }
+
+    /// Get the timestamp from when the Migration was applied as a [`chrono::DateTime`]. `None` when unapplied.
+    #[cfg(feature = "chrono")]
+    pub fn applied_on_chrono(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.applied_on.and_then(|applied_on| {
+            chrono::DateTime::from_timestamp(applied_on.unix_timestamp(), applied_on.nanosecond())
+        })
+    }
+
+    /// Get the Migration checksum. Checksum is formed from the name version and sql of the Migration,
+    /// with its [`Migration::checksum_algorithm`]
     pub fn checksum(&self) -> u64 {
         self.checksum
     }
+
+    /// Get the [`ChecksumAlgorithm`] the checksum is formed with, the one it was recorded with for applied migrations
+    pub fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+        self.checksum_algorithm
+    }
 }
 
 impl fmt::Display for Migration {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, "{}{}__{}", self.prefix, self.version, self.name)
+        if let Some(namespace) = &self.namespace {
+            write!(fmt, "{}/", namespace)?;
+        }
+        match self.naming {
+            MigrationNaming::Versioned => {
+                write!(fmt, "{}{}__{}", self.prefix, self.version, self.name)
+            }
+            MigrationNaming::Timestamp => write!(
+                fmt,
+                "{}_{}",
+                format_timestamp_version(self.version),
+                self.name
+            ),
+        }
     }
 }
 
@@ -172,13 +1268,15 @@ impl PartialEq for Migration {
     fn eq(&self, other: &Migration) -> bool {
         self.version == other.version
             && self.name == other.name
-            && self.checksum() == other.checksum()
+            && self.namespace == other.namespace
+            && self.checksum_matches(other)
     }
 }
 
+// seeds are ordered after the other migrations, as they insert data on the tables those create
 impl Ord for Migration {
     fn cmp(&self, other: &Migration) -> Ordering {
-        self.version.cmp(&other.version)
+        (self.prefix == Type::Seed, self.version).cmp(&(other.prefix == Type::Seed, other.version))
     }
 }
 
@@ -198,58 +1296,464 @@ impl PartialOrd for Migration {
 /// [`Result`]: https://doc.rust-lang.org/std/result/enum.Result.html
 /// [`Error.report`]:  struct.Error.html#method.report
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Report {
     applied_migrations: Vec<Migration>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    integrity_issues: Vec<IntegrityIssue>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) rolled_back_migrations: Vec<Migration>,
 }
 
 impl Report {
     /// Instantiate a new Report
     pub(crate) fn new(applied_migrations: Vec<Migration>) -> Report {
-        Report { applied_migrations }
+        Report {
+            applied_migrations,
+            integrity_issues: Vec::new(),
+            rolled_back_migrations: Vec::new(),
+        }
     }
 
     /// Retrieves the list of applied `Migration` of the migration cycle
     pub fn applied_migrations(&self) -> &Vec<Migration> {
         &self.applied_migrations
     }
-}
 
-/// Struct that represents the entrypoint to run the migrations,
-/// an instance of this struct is returned by the [`embed_migrations!`] macro.
-/// `Runner` should not need to be instantiated manually
-///
-/// [`embed_migrations!`]: macro.embed_migrations.html
-pub struct Runner {
-    grouped: bool,
-    abort_divergent: bool,
-    abort_missing: bool,
-    migrations: Vec<Migration>,
-    target: Target,
-    migration_table_name: String,
-}
+    /// Retrieves the issues found by the integrity checks run after the migration cycle,
+    /// always empty unless enabled with [`Runner::set_integrity_checks`]
+    pub fn integrity_issues(&self) -> &Vec<IntegrityIssue> {
+        &self.integrity_issues
+    }
 
-impl Runner {
+    /// Retrieves the list of `Migration` rolled back, latest first, when the database was ahead of the
+    /// `Target::Version` of the migration cycle, see [`Runner::set_target`]
+    pub fn rolled_back_migrations(&self) -> &Vec<Migration> {
+        &self.rolled_back_migrations
+    }
+
+    /// Serializes the Report to JSON for deployment dashboards, with the version, name, checksum and sql byte size
+    /// of the applied migrations and their start and finish timestamps and duration in milliseconds, `null` when
+    /// they weren't applied on their own, see [`Migration::execution_time`]
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        use serde_json::json;
+        use time::format_description::well_known::Rfc3339;
+
+        let timestamp = |timestamp: Option<OffsetDateTime>| {
+            timestamp.map(|timestamp| timestamp.format(&Rfc3339).unwrap())
+        };
+        let applied: Vec<_> = self
+            .applied_migrations
+            .iter()
+            .map(|migration| {
+                json!({
+                    "version": migration.version(),
+                    "name": migration.name(),
+                    "checksum": migration.checksum().to_string(),
+                    "sql_bytes": migration.sql().map_or(0, str::len),
+                    "started_on": timestamp(migration.started_on()),
+                    "finished_on": timestamp(migration.finished_on()),
+                    "duration_ms": migration.execution_time().map(|duration| duration.as_millis() as u64),
+                })
+            })
+            .collect();
+        let rolled_back: Vec<_> = self
+            .rolled_back_migrations
+            .iter()
+            .map(|migration| json!({ "version": migration.version(), "name": migration.name() }))
+            .collect();
+        let integrity_issues: Vec<String> = self
+            .integrity_issues
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        json!({
+            "applied_migrations": applied,
+            "rolled_back_migrations": rolled_back,
+            "integrity_issues": integrity_issues,
+        })
+        .to_string()
+    }
+}
+
+/// An issue found by the integrity checks run after the migration cycle, see [`Runner::set_integrity_checks`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegrityIssue {
+    /// Rows of `table` reference rows of `references` that don't exist
+    ForeignKeyViolation { table: String, references: String },
+    /// A constraint added as `NOT VALID` that was never validated
+    UnvalidatedConstraint { table: String, constraint: String },
+    /// An index left invalid, i.e. by a failed `CREATE INDEX CONCURRENTLY`, which isn't used by queries
+    InvalidIndex { table: String, index: String },
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityIssue::ForeignKeyViolation { table, references } => write!(
+                fmt,
+                "rows of {} reference missing rows of {}",
+                table, references
+            ),
+            IntegrityIssue::UnvalidatedConstraint { table, constraint } => {
+                write!(
+                    fmt,
+                    "constraint {} on {} is not validated",
+                    constraint, table
+                )
+            }
+            IntegrityIssue::InvalidIndex { table, index } => {
+                write!(fmt, "index {} on {} is invalid", index, table)
+            }
+        }
+    }
+}
+
+/// An inconsistency between the migrations of a [`Runner`] and the ones applied on the database, see
+/// [`Runner::validate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::large_enum_variant)]
+pub enum ValidationIssue {
+    /// An applied migration with the same version but a different name or checksum than `migration`
+    Divergent {
+        applied: Migration,
+        migration: Migration,
+    },
+    /// An applied migration missing from the migrations of the Runner
+    Missing(Migration),
+    /// A versioned migration that wasn't applied, older than the last applied one
+    OutOfOrder(Migration),
+    /// A migration with the same version as another one to be applied
+    Repeated(Migration),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Divergent { applied, migration } => write!(
+                fmt,
+                "applied migration {} is different than {}",
+                applied, migration
+            ),
+            ValidationIssue::Missing(migration) => {
+                write!(fmt, "applied migration {} is missing", migration)
+            }
+            ValidationIssue::OutOfOrder(migration) => write!(
+                fmt,
+                "migration {} is older than the last applied one but wasn't applied",
+                migration
+            ),
+            ValidationIssue::Repeated(migration) => {
+                write!(
+                    fmt,
+                    "migration {} repeats the version of another one",
+                    migration
+                )
+            }
+        }
+    }
+}
+
+/// Struct that represents the report of the validation of the migrations of a [`Runner`] against the ones
+/// applied on the database, see [`Runner::validate`]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+    pending_migrations: Vec<Migration>,
+}
+
+impl ValidationReport {
+    /// Retrieves the issues found, in the order they would fail a run
+    pub fn issues(&self) -> &Vec<ValidationIssue> {
+        &self.issues
+    }
+
+    /// Retrieves the migrations that a run would apply, without a target
+    pub fn pending_migrations(&self) -> &Vec<Migration> {
+        &self.pending_migrations
+    }
+
+    /// Returns true when no issues were found, regardless of whether a run would abort on them
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// An applied migration whose checksum differs from the one of the migration with its version, i.e. a migration
+/// edited after it was applied, see [`Runner::verify_checksums`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChecksumMismatch {
+    applied: Migration,
+    migration: Migration,
+}
+
+impl ChecksumMismatch {
+    /// Retrieves the migration applied on the database
+    pub fn applied(&self) -> &Migration {
+        &self.applied
+    }
+
+    /// Retrieves the migration with the version of the applied one
+    pub fn migration(&self) -> &Migration {
+        &self.migration
+    }
+
+    /// Retrieves the checksum recorded on the schema history table for the applied migration
+    pub fn applied_checksum(&self) -> String {
+        self.applied.recorded_checksum()
+    }
+
+    /// Retrieves the checksum of the migration, formed with the algorithm of the applied one when possible
+    pub fn checksum(&self) -> String {
+        self.migration.recorded_checksum_as(&self.applied)
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "applied migration {} has checksum {} but {} has checksum {}",
+            self.applied,
+            self.applied_checksum(),
+            self.migration,
+            self.checksum()
+        )
+    }
+}
+
+/// A handle to cancel a migration run between migrations, see [`Runner::set_cancel_handle`].
+/// Clones share the same cancellation, so a clone can be kept to cancel the run it's set on
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// instantiate a new CancelHandle, not cancelled
+    pub fn new() -> CancelHandle {
+        CancelHandle::default()
+    }
+
+    /// Cancel the runs this handle is set on, the migration being applied is finished before they stop
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::SeqCst);
+    }
+
+    /// Check if the handle was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::SeqCst)
+    }
+}
+
+// the callbacks invoked around each migration, see [`Runner::set_before_migration`] and
+// [`Runner::set_after_migration`]
+type BeforeMigration = Arc<dyn Fn(&Migration) + Send + Sync>;
+type AfterMigration = Arc<dyn Fn(&Migration, Duration) + Send + Sync>;
+
+/// Struct that represents the entrypoint to run the migrations,
+/// an instance of this struct is returned by the [`embed_migrations!`] macro.
+/// `Runner` should not need to be instantiated manually
+///
+/// [`embed_migrations!`]: macro.embed_migrations.html
+pub struct Runner {
+    grouped: bool,
+    grouped_fallback: bool,
+    apply_seeds: bool,
+    abort_divergent: bool,
+    abort_missing: bool,
+    migrations: Vec<Migration>,
+    target: Target,
+    migration_table_name: String,
+    migration_table_schema: Option<String>,
+    dialect: Option<Dialect>,
+    integrity_checks: bool,
+    snapshot_path: Option<PathBuf>,
+    run_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    cancel_handle: Option<CancelHandle>,
+    namespace: Option<String>,
+    sqlite_pragmas: Vec<String>,
+    sqlite_extensions: Vec<PathBuf>,
+    schema_history_ddl: Option<Arc<dyn SchemaHistoryDdl>>,
+    placeholders: HashMap<String, String>,
+    strict_placeholders: bool,
+    skip_versions: HashSet<u32>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+    splitter: Option<Splitter>,
+    applied_by: Option<String>,
+    before_migration: Option<BeforeMigration>,
+    after_migration: Option<AfterMigration>,
+}
+
+impl Runner {
     /// instantiate a new Runner
     pub fn new(migrations: &[Migration]) -> Runner {
         Runner {
             grouped: false,
+            grouped_fallback: false,
+            apply_seeds: false,
             target: Target::Latest,
             abort_divergent: true,
             abort_missing: true,
             migrations: migrations.to_vec(),
             migration_table_name: DEFAULT_MIGRATION_TABLE_NAME.into(),
+            migration_table_schema: None,
+            dialect: None,
+            integrity_checks: false,
+            snapshot_path: None,
+            run_timeout: None,
+            timeout: None,
+            cancel_handle: None,
+            namespace: None,
+            sqlite_pragmas: Vec::new(),
+            sqlite_extensions: Vec::new(),
+            schema_history_ddl: None,
+            placeholders: HashMap::new(),
+            strict_placeholders: false,
+            skip_versions: HashSet::new(),
+            checksum_algorithm: None,
+            splitter: None,
+            applied_by: None,
+            before_migration: None,
+            after_migration: None,
         }
     }
 
-    /// Get the gathered migrations.
+    /// instantiate a new Runner with the SQL migrations of a tar, gzipped tar or zip archive, i.e. one built with
+    /// `refinery package`, so a release artifact can be applied without a checkout of the migrations directory.
+    /// `.sql` files are loaded from every directory of the archive as with
+    /// [`load_sql_migrations`](crate::load_sql_migrations), the files not following the migration naming are skipped.
+    /// Archives with a manifest, as bundles written by [`archive::write_bundle`](crate::archive::write_bundle) have,
+    /// fail with [`Kind::InvalidArchive`] unless every migration file matches its checksum on it.
+    #[cfg(feature = "archive")]
+    pub fn from_archive(reader: impl std::io::Read) -> Result<Runner, Error> {
+        let migrations = crate::archive::load_archive_migrations(reader)?;
+        Ok(Runner::new(&migrations))
+    }
+
+    /// Get the gathered migrations, including the variants of every [`Dialect`].
+    /// Migrations generated for the [`Dialect`] of the connection don't have their sql until they are run.
     pub fn get_migrations(&self) -> &Vec<Migration> {
         &self.migrations
     }
 
+    /// Consume the Runner, returning the gathered migrations, i.e. to build a modified Runner with [`Runner::new`]
+    pub fn into_migrations(self) -> Vec<Migration> {
+        self.migrations
+    }
+
+    /// Keep only the migrations for which the predicate returns true, i.e. to skip the versions above the ones
+    /// enabled by a feature flag of the application, before running them.
+    pub fn filter<F>(self, predicate: F) -> Runner
+    where
+        F: FnMut(&Migration) -> bool,
+    {
+        let mut migrations = self.migrations;
+        migrations.retain(predicate);
+        Runner { migrations, ..self }
+    }
+
+    /// Merge the migrations of another Runner, i.e. the one embedded by another crate of the application, so they are
+    /// all applied in version order on a single run against the same schema history table.
+    /// The settings of this Runner are kept, and merging fails with [`Kind::RepeatedVersion`] if both Runners have a
+    /// migration with the same version for the same [`Dialect`] on the same namespace, see [`Runner::set_namespace`]
+    pub fn merge(self, other: Runner) -> Result<Runner, Error> {
+        let mut migrations = self.migrations;
+        for migration in other.migrations {
+            if migrations.iter().any(|m| {
+                m.version == migration.version
+                    && m.dialect == migration.dialect
+                    && m.namespace == migration.namespace
+            }) {
+                return Err(Error::new(Kind::RepeatedVersion(migration), None));
+            }
+            migrations.push(migration);
+        }
+        migrations.sort();
+        Ok(Runner { migrations, ..self })
+    }
+
+    /// Add migrations to the ones of this Runner, i.e. those gathered by a plugin with [`load_sql_migrations`](crate::load_sql_migrations),
+    /// failing with [`Kind::RepeatedVersion`] on a version collision the same way as [`Runner::merge`]
+    pub fn add_migrations(self, migrations: &[Migration]) -> Result<Runner, Error> {
+        self.merge(Runner::new(migrations))
+    }
+
+    // select the migration variants for the dialect of the connection they are run on, unless overridden with
+    // `Runner::set_dialect`, generate the sql of the migrations that depend on it and replace its placeholders
+    fn migrations_for_dialect(&self, dialect: Option<Dialect>) -> Result<Vec<Migration>, Error> {
+        let dialect = self.dialect.or(dialect);
+        let variant_versions: HashSet<(i32, Option<&str>)> = self
+            .migrations
+            .iter()
+            .filter(|migration| migration.dialect.is_some() && migration.dialect == dialect)
+            .map(|migration| (migration.version, migration.namespace()))
+            .collect();
+
+        self.migrations
+            .iter()
+            .filter(|migration| self.applies(migration))
+            .filter(|migration| match migration.dialect {
+                // variants for an unknown dialect error when generated
+                Some(_) if dialect.is_none() => true,
+                Some(_) => migration.dialect == dialect,
+                None => !variant_versions.contains(&(migration.version, migration.namespace())),
+            })
+            .map(|migration| {
+                // the settings of the migrations also cover the ones added after they were set
+                let mut migration = migration.clone();
+                migration.skip |= self.skip_versions.contains(&migration.version());
+                if let Some(algorithm) = self.checksum_algorithm {
+                    migration = migration.with_checksum_algorithm(algorithm);
+                }
+                if let Some(splitter) = &self.splitter {
+                    migration.splitter = Some(splitter.clone());
+                }
+                if let Some(applied_by) = &self.applied_by {
+                    migration.applied_by = Some(applied_by.clone());
+                }
+                // timeouts are set with statements of the dialect
+                if let (Some(timeout), Some(_)) = (self.timeout, dialect) {
+                    migration.default_timeout = Some(timeout);
+                }
+                let migration = migration.for_dialect(dialect)?;
+                if self.placeholders.is_empty() && !self.strict_placeholders {
+                    return Ok(migration);
+                }
+                migration.with_placeholders(&self.placeholders, self.strict_placeholders)
+            })
+            .collect()
+    }
+
+    // the Dialect set with `Runner::set_dialect`
+    #[cfg(feature = "archive")]
+    pub(crate) fn dialect(&self) -> Option<Dialect> {
+        self.dialect
+    }
+
+    /// Set the [`Dialect`] used to select the migration variants and generate the sql of migrations that depend on it,
+    /// overriding the one reported by the connection.
+    /// by default the dialect of the connection is used
+    pub fn set_dialect(self, dialect: Dialect) -> Runner {
+        Runner {
+            dialect: Some(dialect),
+            ..self
+        }
+    }
+
     /// Set the target version up to which refinery should migrate, Latest migrates to the latest version available
     /// Version migrates to a user provided version, a Version with a higher version than the latest will be ignored,
     /// and Fake doesn't actually run any migration, just creates and updates refinery's schema migration table
-    /// by default this is set to Latest
+    /// by default this is set to Latest.
+    /// When the database is ahead of a Version, [`Runner::run`] and [`Runner::run_async`] first roll back the
+    /// migrations applied after it the same way as [`Runner::rollback`], failing with [`Kind::Irreversible`] if any of
+    /// them has no down section. Up to 0.8.14 a Version below the applied ones was a no-op, runs that relied on it
+    /// now roll back, or fail if the migrations can't be rolled back. The migrations rolled back are in the report
+    /// of the run, also when it fails afterwards
     pub fn set_target(self, target: Target) -> Runner {
         Runner { target, ..self }
     }
@@ -259,144 +1763,1466 @@ impl Runner {
     ///
     /// # Note
     ///
-    /// set_grouped won't probably work on MySQL Databases as MySQL lacks support for transactions around schema alteration operations,
-    /// meaning that if a migration fails to apply you will have to manually unpick the changes in order to try again (it’s impossible to roll back to an earlier point).
+    /// Grouped runs fail with [`Kind::GroupingNotSupported`](crate::error::Kind::GroupingNotSupported) on dialects
+    /// that can't roll back schema changes, see [`Dialect::supports_transactional_ddl`], as MySQL commits each schema
+    /// alteration operation, meaning that if a migration fails to apply the ones before it wouldn't be rolled back.
+    /// See [`Runner::set_grouped_fallback`] to run them on their own transactions instead.
     pub fn set_grouped(self, grouped: bool) -> Runner {
         Runner { grouped, ..self }
     }
 
-    /// Set true if migration process should abort if divergent migrations are found
-    /// i.e. applied migrations with the same version but different name or checksum from the ones on the filesystem.
-    /// by default this is set to true
-    pub fn set_abort_divergent(self, abort_divergent: bool) -> Runner {
-        Runner {
-            abort_divergent,
-            ..self
+    /// Set true if grouped runs on dialects that can't roll back schema changes should log a warning and apply each
+    /// migration on its own transaction, instead of failing, see [`Runner::set_grouped`].
+    /// by default this is set to false
+    pub fn set_grouped_fallback(self, grouped_fallback: bool) -> Runner {
+        Runner {
+            grouped_fallback,
+            ..self
+        }
+    }
+
+    /// Set true to also apply the seed migrations, `S{version}__{name}`, i.e. test or staging data living alongside
+    /// the schema migrations. They are recorded on their own `seeds` namespace of the schema history table and
+    /// applied after the other migrations, and when not applied the seeds recorded on the table are ignored.
+    /// by default this is set to false
+    pub fn set_apply_seeds(self, apply_seeds: bool) -> Runner {
+        Runner {
+            apply_seeds,
+            ..self
+        }
+    }
+
+    /// Set true if migration process should abort if divergent migrations are found
+    /// i.e. applied migrations with the same version but different name or checksum from the ones on the filesystem.
+    /// by default this is set to true
+    pub fn set_abort_divergent(self, abort_divergent: bool) -> Runner {
+        Runner {
+            abort_divergent,
+            ..self
+        }
+    }
+
+    /// Set true if migration process should abort if missing migrations are found
+    /// i.e. applied migrations that are not found on the filesystem,
+    /// or migrations found on filesystem with a version inferior to the last one applied but not applied.
+    /// by default this is set to true
+    pub fn set_abort_missing(self, abort_missing: bool) -> Runner {
+        Runner {
+            abort_missing,
+            ..self
+        }
+    }
+
+    /// Set true to check the integrity of the database after running the migrations, reporting the issues found
+    /// on [`Report::integrity_issues`]: rows violating foreign keys, constraints left unvalidated and invalid indexes.
+    /// Postgres checks all of them while Sqlite only checks foreign keys, other databases aren't checked.
+    /// by default this is set to false
+    pub fn set_integrity_checks(self, integrity_checks: bool) -> Runner {
+        Runner {
+            integrity_checks,
+            ..self
+        }
+    }
+
+    /// Set the path the schema of the database is written to after a successful run, its tables with their columns,
+    /// indexes and constraints but the schema history table, so repositories can track the authoritative schema and
+    /// CI can detect drift. The snapshot is written as canonical sql, see [`Schema::to_sql`], or as json if the path
+    /// has the `json` extension, which needs the `serde` feature. Runs on connections that can't introspect their
+    /// schema fail with [`Kind::InvalidSnapshot`], see [`introspect`](crate::introspect).
+    /// by default no snapshot is written
+    ///
+    /// [`Schema::to_sql`]: crate::introspect::Schema::to_sql
+    pub fn set_snapshot_path(self, path: impl AsRef<Path>) -> Runner {
+        Runner {
+            snapshot_path: Some(path.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Set the maximum duration of the whole migration run, once exceeded no further migration is applied and the run
+    /// fails with [`Kind::RunTimeout`], with the migrations applied until then on the Error report.
+    /// Runs are checked between migrations, and async runs on a tokio driver also cancel the migration being applied,
+    /// rolling it back. Grouped and fake runs apply all the migrations in one step, so sync ones can't be timed out.
+    /// by default runs don't have a timeout
+    pub fn set_run_timeout(self, run_timeout: Duration) -> Runner {
+        Runner {
+            run_timeout: Some(run_timeout),
+            ..self
+        }
+    }
+
+    /// Set the timeout of each migration, overridden by the `-- refinery:timeout` directive of a migration, see
+    /// [`Migration::timeout`]. It's enforced by the database: Postgres and Cockroach cancel statements exceeding
+    /// their `statement_timeout`, Mysql queries exceeding their `max_execution_time` and lock waits, Mssql lock
+    /// requests exceeding their `LOCK_TIMEOUT` and Sqlite connections stop waiting on locks after their busy timeout,
+    /// which is set before running the migrations unless it's set with [`Runner::set_sqlite_pragmas`]. Migrations
+    /// exceeding it are rolled back, failing the run with [`Kind::Timeout`]. Migrations marked with
+    /// `-- refinery:no-transaction` set it on the session on Postgres and Cockroach, reset once they're applied.
+    /// by default migrations don't have a timeout
+    pub fn set_timeout(self, timeout: Duration) -> Runner {
+        Runner {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Set a [`CancelHandle`] to cancel the migration run from elsewhere, i.e. an orchestrator shutting down.
+    /// Once cancelled the migration being applied is finished and recorded, no further migration is applied and the run
+    /// fails with [`Kind::Cancelled`], with the migrations applied until then on the Error report.
+    /// Grouped and fake runs apply all the migrations in one step, so they are only cancelled before it starts.
+    /// by default runs can't be cancelled
+    pub fn set_cancel_handle(self, cancel_handle: CancelHandle) -> Runner {
+        Runner {
+            cancel_handle: Some(cancel_handle),
+            ..self
+        }
+    }
+
+    /// Set the namespace of the migrations of this Runner, so migration sets of different components can share the
+    /// schema history table with overlapping versions, i.e. both having a `V1`. Migrations are recorded on the table as
+    /// `{namespace}/{name}`, and each namespace is verified independently of the others.
+    /// History tables created by previous versions have the version alone as primary key, runs with a namespace fail
+    /// with [`Kind::LegacyPrimaryKey`] before applying anything until it's changed to `(version, name)`.
+    /// by default migrations don't have a namespace
+    ///
+    /// Fails with [`Kind::InvalidNamespace`] if the provided `namespace` is empty or has characters other than
+    /// alphanumerics, `_` and `-`
+    pub fn set_namespace<S: AsRef<str>>(self, namespace: S) -> Result<Runner, Error> {
+        let namespace = namespace.as_ref();
+        if namespace.is_empty()
+            || !namespace
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Error::new(
+                Kind::InvalidNamespace(namespace.to_string()),
+                None,
+            ));
+        }
+
+        let migrations = self
+            .migrations
+            .into_iter()
+            .map(|migration| Migration {
+                namespace: seeds_namespace(&migration.prefix, Some(namespace)),
+                ..migration
+            })
+            .collect();
+        Ok(Runner {
+            migrations,
+            namespace: Some(namespace.to_string()),
+            ..self
+        })
+    }
+
+    /// Set the [`Splitter`] the sql of the migrations is split with into the statements executed one at a time,
+    /// i.e. [`Splitter::None`] to execute each migration as written on a driver supporting multiple statements.
+    /// It also applies to migrations added afterwards with [`Runner::add_migrations`] or [`Runner::merge`].
+    /// by default the sql is split with the default of the driver, on `GO` lines for tiberius and on `;` for the rest
+    pub fn set_splitter(self, splitter: Splitter) -> Runner {
+        let migrations = self
+            .migrations
+            .into_iter()
+            .map(|migration| Migration {
+                splitter: Some(splitter.clone()),
+                ..migration
+            })
+            .collect();
+        Runner {
+            migrations,
+            splitter: Some(splitter),
+            ..self
+        }
+    }
+
+    /// Set the user the migrations are recorded as applied by on the `applied_by` column of the schema history table,
+    /// i.e. the name of the deploy pipeline when it connects with a shared database user. It also applies to
+    /// migrations added afterwards with [`Runner::add_migrations`] or [`Runner::merge`].
+    /// by default it's the user of the connection, and none on sqlite
+    pub fn set_applied_by(self, applied_by: &str) -> Runner {
+        let migrations = self
+            .migrations
+            .into_iter()
+            .map(|migration| Migration {
+                applied_by: Some(applied_by.to_string()),
+                ..migration
+            })
+            .collect();
+        Runner {
+            migrations,
+            applied_by: Some(applied_by.to_string()),
+            ..self
+        }
+    }
+
+    /// Set the versions of the migrations that are skipped, as if they were marked with the `-- refinery:skip`
+    /// directive, i.e. to bypass a known-bad migration in an emergency. They are recorded as applied on the schema
+    /// history table, flagged on its `skipped` column, but their sql isn't executed, and can be listed with
+    /// [`Runner::get_skipped_migrations`] to be repaired later, i.e. by fixing them and re-running them with
+    /// [`Runner::force_rerun`] once they're no longer skipped. Calling it again adds to the skipped versions, and they
+    /// also apply to migrations added afterwards with [`Runner::add_migrations`] or [`Runner::merge`].
+    /// by default no migration is skipped
+    pub fn set_skip_versions(self, versions: &[u32]) -> Runner {
+        let migrations = self
+            .migrations
+            .into_iter()
+            .map(|migration| Migration {
+                skip: migration.skip || versions.contains(&migration.version()),
+                ..migration
+            })
+            .collect();
+        let mut skip_versions = self.skip_versions;
+        skip_versions.extend(versions);
+        Runner {
+            migrations,
+            skip_versions,
+            ..self
+        }
+    }
+
+    /// Set the [`ChecksumAlgorithm`] the checksums of the migrations are formed and recorded with.
+    /// Migrations applied with another algorithm, i.e. the legacy one before switching to [`ChecksumAlgorithm::Sha256`],
+    /// are still verified with theirs, so the algorithm can be switched on existing databases, and they are only
+    /// recorded with the new one when re-run. It also applies to migrations added afterwards with
+    /// [`Runner::add_migrations`] or [`Runner::merge`] when they are run.
+    /// by default the checksums are formed with [`ChecksumAlgorithm::Sip13Legacy`]
+    pub fn set_checksum_algorithm(self, algorithm: ChecksumAlgorithm) -> Runner {
+        let migrations = self
+            .migrations
+            .into_iter()
+            .map(|migration| migration.with_checksum_algorithm(algorithm))
+            .collect();
+        Runner {
+            migrations,
+            checksum_algorithm: Some(algorithm),
+            ..self
+        }
+    }
+
+    /// Set the values of the `${name}` placeholders of the migrations, replaced when they are run so the same
+    /// migrations can i.e. create the schema, tablespace or role of each environment. Checksums are formed from the
+    /// sql as written, so they don't change with the values. Placeholders without a value are run as they are unless
+    /// [`Runner::set_strict_placeholders`] is set.
+    /// by default there are none
+    pub fn set_placeholders(self, placeholders: HashMap<String, String>) -> Runner {
+        Runner {
+            placeholders,
+            ..self
+        }
+    }
+
+    /// Fail with [`Kind::UnknownPlaceholder`] before running migrations with a `${name}` placeholder that has no
+    /// value set with [`Runner::set_placeholders`].
+    /// by default they are run as they are
+    pub fn set_strict_placeholders(self, strict_placeholders: bool) -> Runner {
+        Runner {
+            strict_placeholders,
+            ..self
+        }
+    }
+
+    /// Set the PRAGMAs executed on sqlite connections before the migrations are run on them, i.e.
+    /// `journal_mode=WAL`, `foreign_keys=ON` or `busy_timeout=5000`, as migrations often depend on them.
+    /// They are executed outside of the migration transactions, as some can't be changed inside one.
+    /// Other connections ignore them. by default there are none
+    pub fn set_sqlite_pragmas(self, sqlite_pragmas: Vec<String>) -> Runner {
+        Runner {
+            sqlite_pragmas,
+            ..self
+        }
+    }
+
+    /// Set the extensions loaded on sqlite connections before the migrations are run on them, after the PRAGMAs.
+    /// Loading extensions needs the `rusqlite-load-extension` feature. Other connections ignore them.
+    /// by default there are none
+    pub fn set_sqlite_extensions(self, sqlite_extensions: Vec<PathBuf>) -> Runner {
+        Runner {
+            sqlite_extensions,
+            ..self
+        }
+    }
+
+    /// Set the DDL the schema history table is created with when it doesn't exist, to customize its column types,
+    /// add indexes or use a specific tablespace, see [`SchemaHistoryDdl`].
+    /// by default it's the one of [`DefaultSchemaHistoryDdl`](crate::DefaultSchemaHistoryDdl) for the dialect of
+    /// the connection
+    pub fn set_schema_history_ddl<D>(self, schema_history_ddl: D) -> Runner
+    where
+        D: SchemaHistoryDdl + 'static,
+    {
+        Runner {
+            schema_history_ddl: Some(Arc::new(schema_history_ddl)),
+            ..self
+        }
+    }
+
+    /// Set a callback invoked before each migration is applied, i.e. to emit structured logs or notify a channel
+    /// per migration. Grouped runs apply all the migrations in one transaction, so it's invoked for each of them
+    /// before the transaction starts. It's not invoked on fake runs.
+    /// by default there is none
+    pub fn set_before_migration<F>(self, before_migration: F) -> Runner
+    where
+        F: Fn(&Migration) + Send + Sync + 'static,
+    {
+        Runner {
+            before_migration: Some(Arc::new(before_migration)),
+            ..self
+        }
+    }
+
+    /// Set a callback invoked after each migration is applied with the duration it took, i.e. to emit metrics
+    /// per migration. Grouped runs apply all the migrations in one transaction, so it's invoked for each of them
+    /// after the transaction is committed, with the duration of the whole transaction. It's not invoked on fake runs.
+    /// by default there is none
+    pub fn set_after_migration<F>(self, after_migration: F) -> Runner
+    where
+        F: Fn(&Migration, Duration) + Send + Sync + 'static,
+    {
+        Runner {
+            after_migration: Some(Arc::new(after_migration)),
+            ..self
+        }
+    }
+
+    // whether an applied migration belongs to one of the namespaces of this Runner's migrations
+    fn in_namespaces(&self, applied: &Migration) -> bool {
+        applied.namespace == self.namespace
+            || self
+                .migrations
+                .iter()
+                .filter(|migration| self.applies(migration))
+                .any(|migration| migration.namespace == applied.namespace)
+    }
+
+    // seeds are only applied with `Runner::set_apply_seeds`
+    fn applies(&self, migration: &Migration) -> bool {
+        self.apply_seeds || migration.prefix != Type::Seed
+    }
+
+    fn is_fake(&self) -> bool {
+        matches!(self.target, Target::Fake | Target::FakeVersion(_))
+    }
+
+    // configures the connection before the migrations are run on it, and creates the schema history table with the
+    // DDL set with `Runner::set_schema_history_ddl` before the traits assert it with the default one
+    // the PRAGMAs set with `Runner::set_sqlite_pragmas`, and the busy timeout of `Runner::set_timeout` unless it's
+    // one of them
+    fn sqlite_pragmas(&self) -> Cow<'_, [String]> {
+        let busy_timeout = self.sqlite_pragmas.iter().any(|pragma| {
+            pragma
+                .trim()
+                .to_ascii_lowercase()
+                .starts_with("busy_timeout")
+        });
+        match self.timeout {
+            Some(timeout) if !busy_timeout => {
+                let mut pragmas = self.sqlite_pragmas.clone();
+                pragmas.push(format!("busy_timeout={}", timeout.as_millis()));
+                Cow::Owned(pragmas)
+            }
+            _ => Cow::Borrowed(&self.sqlite_pragmas),
+        }
+    }
+
+    fn setup<C>(&self, connection: &mut C, table_name: &str) -> Result<(), Error>
+    where
+        C: Migrate,
+    {
+        Migrate::setup_sqlite(connection, &self.sqlite_pragmas(), &self.sqlite_extensions)?;
+        match &self.schema_history_ddl {
+            Some(ddl) => {
+                Migrate::assert_migrations_table_with(connection, table_name, ddl.as_ref())?;
+            }
+            // namespaces are checked against the primary key of the table once it exists
+            None if self.namespace.is_some() => {
+                Migrate::assert_migrations_table(connection, table_name)?;
+            }
+            None => {}
+        }
+        if self.namespace.is_some() {
+            Migrate::assert_namespaced_migrations_table(connection, table_name)?;
+        }
+        Ok(())
+    }
+
+    async fn setup_async<C>(&self, connection: &mut C, table_name: &str) -> Result<(), Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        AsyncMigrate::setup_sqlite(connection, &self.sqlite_pragmas(), &self.sqlite_extensions)
+            .await?;
+        match &self.schema_history_ddl {
+            Some(ddl) => {
+                AsyncMigrate::assert_migrations_table_with(connection, table_name, ddl.as_ref())
+                    .await?;
+            }
+            // namespaces are checked against the primary key of the table once it exists
+            None if self.namespace.is_some() => {
+                AsyncMigrate::assert_migrations_table(connection, table_name).await?;
+            }
+            None => {}
+        }
+        if self.namespace.is_some() {
+            AsyncMigrate::assert_namespaced_migrations_table(connection, table_name).await?;
+        }
+        Ok(())
+    }
+
+    // whether the migrations are run grouped on the dialect of the connection, failing on the ones that can't roll
+    // back schema changes, unless falling back to running each migration on its own transaction is enabled.
+    // fake runs only update the schema history table, so they can be grouped on any dialect
+    fn grouped(&self, dialect: Option<Dialect>) -> Result<bool, Error> {
+        match self.dialect.or(dialect) {
+            Some(dialect)
+                if self.grouped && !self.is_fake() && !dialect.supports_transactional_ddl() =>
+            {
+                if !self.grouped_fallback {
+                    return Err(Error::new(Kind::GroupingNotSupported(dialect), None));
+                }
+                log::warn!(
+                    "{} can't roll back schema changes, running each migration on its own transaction instead of grouped",
+                    dialect
+                );
+                Ok(false)
+            }
+            _ => Ok(self.grouped),
+        }
+    }
+
+    // runs that can be timed out or cancelled, or that invoke callbacks around each migration, apply each migration
+    // on its own transaction, unless they are grouped
+    fn run_in_steps(&self, grouped: bool) -> bool {
+        (self.run_timeout.is_some()
+            || self.cancel_handle.is_some()
+            || self.before_migration.is_some()
+            || self.after_migration.is_some())
+            && !grouped
+            && !self.is_fake()
+    }
+
+    // grouped runs query the pending migrations beforehand only to invoke the before callback on them
+    fn needs_pending(&self) -> bool {
+        self.before_migration.is_some() && !self.is_fake()
+    }
+
+    // the pending migrations a run applies, up to the target
+    fn step_migrations<'m>(&self, unapplied: &'m [Migration]) -> Vec<&'m Migration> {
+        let migrations = unapplied.iter();
+        match (self.target, self.target.steps()) {
+            (Target::Version(target) | Target::FakeVersion(target), _) => migrations
+                .filter(|migration| migration.version() <= target)
+                .collect(),
+            (_, Some(steps)) => migrations.take(steps as usize).collect(),
+            _ => migrations.collect(),
+        }
+    }
+
+    fn before_migration(&self, migration: &Migration) {
+        if let Some(before_migration) = self.before_migration.as_ref().filter(|_| !self.is_fake()) {
+            before_migration(migration);
+        }
+    }
+
+    fn after_migration(&self, applied: &[Migration], duration: Duration) {
+        if let Some(after_migration) = self.after_migration.as_ref().filter(|_| !self.is_fake()) {
+            for migration in applied {
+                after_migration(migration, duration);
+            }
+        }
+    }
+
+    fn run_timeout_err(timeout: Duration, applied: Vec<Migration>) -> Error {
+        log::error!("migration run exceeded its timeout of {:?}", timeout);
+        Error::new(Kind::RunTimeout(timeout), Some(Report::new(applied)))
+    }
+
+    // the error that stops a run started at `started` before applying the next migration, if any
+    fn interruption(&self, started: Instant, applied: &[Migration]) -> Option<Error> {
+        if self
+            .cancel_handle
+            .as_ref()
+            .is_some_and(CancelHandle::is_cancelled)
+        {
+            log::warn!("migration run cancelled");
+            return Some(Error::new(
+                Kind::Cancelled,
+                Some(Report::new(applied.to_vec())),
+            ));
+        }
+        match self.run_timeout {
+            Some(timeout) if started.elapsed() >= timeout => {
+                Some(Self::run_timeout_err(timeout, applied.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Queries the database for the last applied migration, returns None if there aren't applied Migrations
+    pub fn get_last_applied_migration<C>(&self, conn: &'_ mut C) -> Result<Option<Migration>, Error>
+    where
+        C: Migrate,
+    {
+        Ok(self.get_applied_migrations(conn)?.pop())
+    }
+
+    /// Queries the database asynchronously for the last applied migration, returns None if there aren't applied Migrations
+    pub async fn get_last_applied_migration_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Option<Migration>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        Ok(self.get_applied_migrations_async(conn).await?.pop())
+    }
+
+    /// Queries the database for all previous applied migrations, of the namespaces of this Runner's migrations
+    pub fn get_applied_migrations<C>(&self, conn: &'_ mut C) -> Result<Vec<Migration>, Error>
+    where
+        C: Migrate,
+    {
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        let mut applied = Migrate::get_applied_migrations(conn, &table_name)?;
+        applied.retain(|migration| self.in_namespaces(migration));
+        Ok(applied)
+    }
+
+    /// Queries the database asynchronously for all previous applied migrations, of the namespaces of this Runner's migrations
+    pub async fn get_applied_migrations_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Vec<Migration>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        let mut applied = AsyncMigrate::get_applied_migrations(conn, &table_name).await?;
+        applied.retain(|migration| self.in_namespaces(migration));
+        Ok(applied)
+    }
+
+    /// Exports the rows of the schema history table of the supplied database connection, of every namespace, to be
+    /// imported with [`Runner::import_history`], i.e. on a database rebuilt from a backup that predates some of them,
+    /// or on a schema history table with another name. The `applied_by` and `execution_time_ms` columns aren't exported.
+    pub fn export_history<C>(&self, conn: &'_ mut C) -> Result<Vec<AppliedMigrationRecord>, Error>
+    where
+        C: Migrate,
+    {
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        let applied = Migrate::get_applied_migrations(conn, &table_name)?;
+        Ok(applied
+            .iter()
+            .map(AppliedMigrationRecord::from_applied)
+            .collect())
+    }
+
+    /// Exports asynchronously the rows of the schema history table of the supplied database connection,
+    /// see [`Runner::export_history`]
+    pub async fn export_history_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Vec<AppliedMigrationRecord>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        let applied = AsyncMigrate::get_applied_migrations(conn, &table_name).await?;
+        Ok(applied
+            .iter()
+            .map(AppliedMigrationRecord::from_applied)
+            .collect())
+    }
+
+    /// Imports the schema history rows exported with [`Runner::export_history`] into the schema history table of the
+    /// supplied database connection, creating it if it doesn't exist, without running any migration.
+    /// Records already on the table are skipped and the others are inserted in a single transaction, returning how
+    /// many were imported. Fails with [`Kind::ConflictingHistoryRecord`], importing none, if a record has the version
+    /// of an applied migration of the same namespace but another name or checksum.
+    pub fn import_history<C>(
+        &self,
+        conn: &'_ mut C,
+        records: &[AppliedMigrationRecord],
+    ) -> Result<usize, Error>
+    where
+        C: Migrate,
+    {
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        self.setup(conn, &table_name)?;
+        Migrate::assert_migrations_table(conn, &table_name)?;
+        let applied = Migrate::get_applied_migrations(conn, &table_name)?;
+        let imported = imported_migrations(records, &applied)?;
+        if !imported.is_empty() {
+            Migrate::import_history(conn, &imported, &table_name)?;
+        }
+        Ok(imported.len())
+    }
+
+    /// Imports asynchronously the schema history rows exported with [`Runner::export_history`] into the schema history
+    /// table of the supplied database connection, see [`Runner::import_history`]
+    pub async fn import_history_async<C>(
+        &self,
+        conn: &mut C,
+        records: &[AppliedMigrationRecord],
+    ) -> Result<usize, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        self.setup_async(conn, &table_name).await?;
+        AsyncMigrate::assert_migrations_table(conn, &table_name).await?;
+        let applied = AsyncMigrate::get_applied_migrations(conn, &table_name).await?;
+        let imported = imported_migrations(records, &applied)?;
+        if !imported.is_empty() {
+            AsyncMigrate::import_history(conn, &imported, &table_name).await?;
+        }
+        Ok(imported.len())
+    }
+
+    /// Queries the database for the migrations recorded as skipped without executing their sql, with the
+    /// `-- refinery:skip` directive or [`Runner::set_skip_versions`], of the namespaces of this Runner's migrations
+    pub fn get_skipped_migrations<C>(&self, conn: &'_ mut C) -> Result<Vec<Migration>, Error>
+    where
+        C: Migrate,
+    {
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        let mut skipped = Migrate::get_skipped_migrations(conn, &table_name)?;
+        skipped.retain(|migration| self.in_namespaces(migration));
+        Ok(skipped)
+    }
+
+    /// Queries the database asynchronously for the migrations recorded as skipped without executing their sql,
+    /// see [`Runner::get_skipped_migrations`]
+    pub async fn get_skipped_migrations_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Vec<Migration>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        let mut skipped = AsyncMigrate::get_skipped_migrations(conn, &table_name).await?;
+        skipped.retain(|migration| self.in_namespaces(migration));
+        Ok(skipped)
+    }
+
+    /// Get the pending migrations a run would apply on the supplied database connection, in order and up to the
+    /// target set with [`Runner::set_target`], without applying them. They are verified against the applied ones the
+    /// same way as on [`Runner::run`], so divergent and missing migrations fail unless that's disabled,
+    /// i.e. for an application to refuse to start or warn while there are pending migrations.
+    pub fn get_pending_migrations<C>(&self, conn: &'_ mut C) -> Result<Vec<Migration>, Error>
+    where
+        C: Migrate,
+    {
+        let migrations = self.migrations_for_dialect(Migrate::dialect(conn))?;
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        self.setup(conn, &table_name)?;
+        let unapplied = Migrate::get_unapplied_migrations(
+            conn,
+            &migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            &table_name,
+        )?;
+        Ok(self
+            .step_migrations(&unapplied)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// Validates the migrations against the ones applied on the supplied database connection without applying
+    /// any, collecting every divergent, missing and repeated migration a run would fail or warn on into a
+    /// [`ValidationReport`] instead of failing on the first one, i.e. for CI to check a staging database.
+    /// The schema history table is created if it doesn't exist.
+    pub fn validate<C>(&self, conn: &'_ mut C) -> Result<ValidationReport, Error>
+    where
+        C: Migrate,
+    {
+        let migrations = self.migrations_for_dialect(Migrate::dialect(conn))?;
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        self.setup(conn, &table_name)?;
+        Migrate::assert_migrations_table(conn, &table_name)?;
+        let applied = Migrate::get_applied_migrations(conn, &table_name)?;
+        Ok(validation_report(applied, migrations))
+    }
+
+    /// Validates asynchronously the migrations against the ones applied on the supplied database connection,
+    /// see [`Runner::validate`]
+    pub async fn validate_async<C>(&self, conn: &mut C) -> Result<ValidationReport, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(conn))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        self.setup_async(conn, &table_name).await?;
+        AsyncMigrate::assert_migrations_table(conn, &table_name).await?;
+        let applied = AsyncMigrate::get_applied_migrations(conn, &table_name).await?;
+        Ok(validation_report(applied, migrations))
+    }
+
+    /// Compares the checksum of every applied migration on the supplied database connection against the one of the
+    /// migration with its version, returning the mismatches with both checksums instead of failing on the first
+    /// one as [`Runner::run`] does, i.e. to find the migrations edited after they were applied.
+    /// Applied migrations missing from this Runner aren't compared. The schema history table is created if it
+    /// doesn't exist.
+    pub fn verify_checksums<C>(&self, conn: &'_ mut C) -> Result<Vec<ChecksumMismatch>, Error>
+    where
+        C: Migrate,
+    {
+        let migrations = self.migrations_for_dialect(Migrate::dialect(conn))?;
+        let table_name = self.table_name(Migrate::dialect(conn))?;
+        self.setup(conn, &table_name)?;
+        Migrate::assert_migrations_table(conn, &table_name)?;
+        let applied = Migrate::get_applied_migrations(conn, &table_name)?;
+        Ok(checksum_mismatches(applied, &migrations))
+    }
+
+    /// Compares asynchronously the checksum of every applied migration on the supplied database connection against
+    /// the one of the migration with its version, see [`Runner::verify_checksums`]
+    pub async fn verify_checksums_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Vec<ChecksumMismatch>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(conn))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        self.setup_async(conn, &table_name).await?;
+        AsyncMigrate::assert_migrations_table(conn, &table_name).await?;
+        let applied = AsyncMigrate::get_applied_migrations(conn, &table_name).await?;
+        Ok(checksum_mismatches(applied, &migrations))
+    }
+
+    /// Get asynchronously the pending migrations a run would apply on the supplied database connection,
+    /// see [`Runner::get_pending_migrations`]
+    pub async fn get_pending_migrations_async<C>(
+        &self,
+        conn: &mut C,
+    ) -> Result<Vec<Migration>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(conn))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(conn))?;
+        self.setup_async(conn, &table_name).await?;
+        let unapplied = AsyncMigrate::get_unapplied_migrations(
+            conn,
+            &migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            &table_name,
+        )
+        .await?;
+        Ok(self
+            .step_migrations(&unapplied)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// Set the table name to use for the migrations table. The default name is `refinery_schema_history`.
+    /// The name can be qualified with a schema, i.e. `audit.refinery_history`, or an attached database on sqlite,
+    /// and on mssql also with a database. Parts that aren't plain identifiers are quoted with the quotes of the
+    /// [`Dialect`] of the connection, and parts quoted with double quotes, backticks or square brackets are
+    /// re-quoted with them.
+    ///
+    /// Fails with [`Kind::InvalidMigrationTableName`] if the name is empty or malformed, or if it's qualified
+    /// with more parts than the [`Dialect`] set with [`Runner::set_dialect`] supports, otherwise that's checked
+    /// for the dialect of the connection when running the migrations.
+    ///
+    /// ### Warning
+    /// Changing this can be disastrous for your database. You should verify that the migrations table has the same
+    /// name as the name you specify here, if this is changed on an existing project.
+    pub fn set_migration_table_name<S: AsRef<str>>(
+        &mut self,
+        migration_table_name: S,
+    ) -> Result<&mut Self, Error> {
+        let migration_table_name = migration_table_name.as_ref();
+        match self.dialect {
+            Some(dialect) => {
+                dialect.quote_table_name(migration_table_name)?;
+            }
+            None => {
+                table_name_parts(migration_table_name)?;
+            }
+        }
+
+        self.migration_table_name = migration_table_name.to_string();
+        Ok(self)
+    }
+
+    /// Set the schema the migrations table is created and queried on, i.e. `dbo` on Mssql or `audit` on Postgres,
+    /// instead of relying on the default schema of the connection. On Mysql the schema is the database.
+    /// The schema is quoted with the quotes of the [`Dialect`] of the connection if it isn't a plain identifier.
+    /// by default the table isn't qualified with a schema, unless it's set with [`Runner::set_migration_table_name`]
+    ///
+    /// ### Warning
+    /// Changing this can be disastrous for your database, as the migrations table is looked up on the given schema.
+    pub fn set_migration_table_schema<S: AsRef<str>>(self, migration_table_schema: S) -> Runner {
+        Runner {
+            migration_table_schema: Some(migration_table_schema.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    // writes the snapshot of the schema after a run without the schema history table, failing with the migrations
+    // applied by the run on the Error report
+    fn write_snapshot(
+        &self,
+        schema: Option<Schema>,
+        path: &Path,
+        report: &Report,
+    ) -> Result<(), Error> {
+        let mut schema = schema.ok_or_else(|| {
+            Error::new(
+                Kind::InvalidSnapshot(
+                    path.to_path_buf(),
+                    "the connection can't introspect its schema".into(),
+                ),
+                None,
+            )
+            .prepend_applied(report.applied_migrations())
+        })?;
+        if let Some((table_name, _)) = table_name_parts(&self.migration_table_name)?.pop() {
+            schema.tables.remove(&table_name);
+        }
+        schema
+            .write_snapshot(path)
+            .map_err(|err| err.prepend_applied(report.applied_migrations()))
+    }
+
+    // the migration table name, qualified with the migration table schema if it's set,
+    // quoted for the dialect of the connection, unless overridden with `Runner::set_dialect`
+    fn table_name(&self, dialect: Option<Dialect>) -> Result<String, Error> {
+        let table_name = match &self.migration_table_schema {
+            Some(schema) => {
+                if table_name_parts(&self.migration_table_name)?.len() > 1 {
+                    return Err(Error::new(
+                        Kind::InvalidMigrationTableName(
+                            self.migration_table_name.clone(),
+                            format!("it's already qualified and the schema {} is set", schema),
+                        ),
+                        None,
+                    ));
+                }
+                let schema = if is_plain_identifier(schema) {
+                    schema.clone()
+                } else {
+                    format!("\"{}\"", schema.replace('"', "\"\""))
+                };
+                format!("{}.{}", schema, self.migration_table_name)
+            }
+            None => self.migration_table_name.clone(),
+        };
+        match self.dialect.or(dialect) {
+            Some(dialect) => dialect.quote_table_name(&table_name),
+            None => Ok(table_name),
+        }
+    }
+
+    /// Creates an iterator over pending migrations, applying each before returning
+    /// the result from `next()`. If a migration fails, the iterator will return that
+    /// result and further calls to `next()` will return `None`. Errors preparing the run, i.e. creating the schema
+    /// history table with the [`SchemaHistoryDdl`] or setting up sqlite, are returned by the first call to `next()`.
+    pub fn run_iter<C>(
+        self,
+        connection: &mut C,
+    ) -> impl Iterator<Item = Result<Migration, Error>> + '_
+    where
+        C: Migrate,
+    {
+        RunIterator::new(self, connection)
+    }
+
+    /// Runs the Migrations in the supplied database connection
+    pub fn run<C>(&self, connection: &mut C) -> Result<Report, Error>
+    where
+        C: Migrate,
+    {
+        let migrations = self.migrations_for_dialect(Migrate::dialect(connection))?;
+        let table_name = self.table_name(Migrate::dialect(connection))?;
+        let grouped = self.grouped(Migrate::dialect(connection))?;
+        self.setup(connection, &table_name)?;
+        let rolled_back = match self.target {
+            Target::Version(version) => {
+                Migrate::assert_migrations_table(connection, &table_name)?;
+                let applied = Migrate::get_applied_migrations(connection, &table_name)?;
+                let rolled_back = self.downgraded_migrations(version, &migrations, applied)?;
+                if rolled_back.is_empty() {
+                    Vec::new()
+                } else {
+                    Migrate::rollback(connection, &rolled_back, grouped, false, &table_name)?
+                        .applied_migrations
+                }
+            }
+            _ => Vec::new(),
+        };
+        // the rollback is committed already, so errors moving forward still report it
+        let mut report = self
+            .run_forward(connection, &migrations, &table_name, grouped)
+            .map_err(|err| err.with_rolled_back(&rolled_back))?;
+        report.rolled_back_migrations = rolled_back;
+        Ok(report)
+    }
+
+    // applies the pending migrations once the ones past the target are rolled back, and runs the checks after them
+    fn run_forward<C>(
+        &self,
+        connection: &mut C,
+        migrations: &[Migration],
+        table_name: &str,
+        grouped: bool,
+    ) -> Result<Report, Error>
+    where
+        C: Migrate,
+    {
+        let mut report = if self.run_in_steps(grouped) {
+            self.run_steps(connection, migrations, table_name)?
+        } else {
+            if let Some(err) = self.interruption(Instant::now(), &[]) {
+                return Err(err);
+            }
+            if self.needs_pending() {
+                let unapplied = Migrate::get_unapplied_migrations(
+                    connection,
+                    migrations,
+                    self.abort_divergent,
+                    self.abort_missing,
+                    table_name,
+                )?;
+                for migration in self.step_migrations(&unapplied) {
+                    self.before_migration(migration);
+                }
+            }
+            let started = Instant::now();
+            let report = Migrate::migrate(
+                connection,
+                migrations,
+                self.abort_divergent,
+                self.abort_missing,
+                grouped,
+                self.target,
+                table_name,
+            )?;
+            self.after_migration(report.applied_migrations(), started.elapsed());
+            report
+        };
+        if self.integrity_checks {
+            report.integrity_issues = Migrate::check_integrity(connection)?;
+        }
+        if let Some(path) = &self.snapshot_path {
+            let schema = Migrate::snapshot_schema(connection)
+                .map_err(|err| err.prepend_applied(report.applied_migrations()))?;
+            self.write_snapshot(schema, path, &report)?;
+        }
+        Ok(report)
+    }
+
+    // applies the pending migrations one at a time, checking if the run is timed out or cancelled before each of them
+    fn run_steps<C>(
+        &self,
+        connection: &mut C,
+        migrations: &[Migration],
+        table_name: &str,
+    ) -> Result<Report, Error>
+    where
+        C: Migrate,
+    {
+        let started = Instant::now();
+        let unapplied = Migrate::get_unapplied_migrations(
+            connection,
+            migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            table_name,
+        )?;
+
+        let mut applied = Vec::new();
+        for migration in self.step_migrations(&unapplied) {
+            if let Some(err) = self.interruption(started, &applied) {
+                return Err(err);
+            }
+            self.before_migration(migration);
+            let step_started = Instant::now();
+            // the pending migrations were verified once, each step only applies its migration
+            let report =
+                Migrate::apply_pending(connection, std::slice::from_ref(migration), table_name)
+                    .map_err(|err| err.prepend_applied(&applied))?;
+            self.after_migration(report.applied_migrations(), step_started.elapsed());
+            applied.extend(report.applied_migrations);
+        }
+        Ok(Report::new(applied))
+    }
+
+    // checks that the migration with `version` is pending and all the migrations before it are applied,
+    // returning the Target that applies only it
+    fn single_target(
+        &self,
+        version: u32,
+        migrations: &[Migration],
+        unapplied: &[Migration],
+    ) -> Result<Target, Error> {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.version() == version)
+            .ok_or_else(|| Error::new(Kind::VersionNotFound(version), None))?;
+
+        if !unapplied.iter().any(|m| m.version() == version) {
+            return Err(Error::new(Kind::NotPending(migration.clone()), None));
+        }
+
+        if let Some(predecessor) = unapplied.iter().filter(|m| m.version() < version).min() {
+            return Err(Error::new(
+                Kind::UnappliedPredecessor(predecessor.clone(), migration.clone()),
+                None,
+            ));
+        }
+
+        match self.target {
+            Target::Fake | Target::FakeVersion(_) => Ok(Target::FakeVersion(version)),
+            Target::Latest | Target::Version(_) | Target::StepsForward(_) | Target::Next => {
+                Ok(Target::Version(version))
+            }
+        }
+    }
+
+    /// Runs only the pending Migration with the given version in the supplied database connection,
+    /// failing if any of the migrations before it isn't applied yet.
+    /// The target set with [`Runner::set_target`] is ignored, unless it's fake.
+    pub fn run_single<C>(&self, version: u32, connection: &mut C) -> Result<Report, Error>
+    where
+        C: Migrate,
+    {
+        let migrations = self.migrations_for_dialect(Migrate::dialect(connection))?;
+        let table_name = self.table_name(Migrate::dialect(connection))?;
+        let grouped = self.grouped(Migrate::dialect(connection))?;
+        self.setup(connection, &table_name)?;
+        let unapplied = Migrate::get_unapplied_migrations(
+            connection,
+            &migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            &table_name,
+        )?;
+        let target = self.single_target(version, &migrations, &unapplied)?;
+
+        for migration in unapplied.iter().filter(|m| m.version() == version) {
+            self.before_migration(migration);
         }
+        let started = Instant::now();
+        let report = Migrate::migrate(
+            connection,
+            &migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            grouped,
+            target,
+            &table_name,
+        )?;
+        self.after_migration(report.applied_migrations(), started.elapsed());
+        Ok(report)
     }
 
-    /// Set true if migration process should abort if missing migrations are found
-    /// i.e. applied migrations that are not found on the filesystem,
-    /// or migrations found on filesystem with a version inferior to the last one applied but not applied.
-    /// by default this is set to true
-    pub fn set_abort_missing(self, abort_missing: bool) -> Runner {
-        Runner {
-            abort_missing,
-            ..self
+    // finds the migration with `version` to be force re-run, checking that it's applied
+    fn applied_migration(
+        &self,
+        version: u32,
+        migrations: &[Migration],
+        applied: &[Migration],
+    ) -> Result<Migration, Error> {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.version() == version)
+            .ok_or_else(|| Error::new(Kind::VersionNotFound(version), None))?;
+
+        if !applied.iter().any(|app| app.version() == version) {
+            return Err(Error::new(Kind::NotApplied(migration.clone()), None));
         }
+
+        Ok(migration.clone())
     }
 
-    /// Queries the database for the last applied migration, returns None if there aren't applied Migrations
-    pub fn get_last_applied_migration<C>(&self, conn: &'_ mut C) -> Result<Option<Migration>, Error>
+    /// Force re-runs the already applied Migration with the given version in the supplied database connection,
+    /// i.e. a view definition that was fixed, updating its name, checksum and applied_on on the schema history table.
+    /// The re-run is logged as a warning.
+    pub fn force_rerun<C>(&self, version: u32, connection: &mut C) -> Result<Report, Error>
     where
         C: Migrate,
     {
-        Migrate::get_last_applied_migration(conn, &self.migration_table_name)
+        let migrations = self.migrations_for_dialect(Migrate::dialect(connection))?;
+        let table_name = self.table_name(Migrate::dialect(connection))?;
+        self.setup(connection, &table_name)?;
+        let applied = Migrate::get_applied_migrations(connection, &table_name)?;
+        let migration = self.applied_migration(version, &migrations, &applied)?;
+        let dialect = Migrate::dialect(connection);
+        sync_rerun(connection, migration, dialect, &table_name)
     }
 
-    /// Queries the database asynchronously for the last applied migration, returns None if there aren't applied Migrations
-    pub async fn get_last_applied_migration_async<C>(
+    /// Force re-runs the already applied Migration with the given version asynchronously in the supplied database connection,
+    /// updating its name, checksum and applied_on on the schema history table.
+    /// The re-run is logged as a warning.
+    pub async fn force_rerun_async<C>(
         &self,
-        conn: &mut C,
-    ) -> Result<Option<Migration>, Error>
+        version: u32,
+        connection: &mut C,
+    ) -> Result<Report, Error>
     where
         C: AsyncMigrate + Send,
     {
-        AsyncMigrate::get_last_applied_migration(conn, &self.migration_table_name).await
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(connection))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(connection))?;
+        self.setup_async(connection, &table_name).await?;
+        let applied = AsyncMigrate::get_applied_migrations(connection, &table_name).await?;
+        let migration = self.applied_migration(version, &migrations, &applied)?;
+        let dialect = AsyncMigrate::dialect(connection);
+        async_rerun(connection, migration, dialect, &table_name).await
     }
 
-    /// Queries the database for all previous applied migrations
-    pub fn get_applied_migrations<C>(&self, conn: &'_ mut C) -> Result<Vec<Migration>, Error>
+    // the applied migrations rolled back when the database is ahead of the target version of a run, latest first
+    fn downgraded_migrations(
+        &self,
+        version: u32,
+        migrations: &[Migration],
+        applied: Vec<Migration>,
+    ) -> Result<Vec<Migration>, Error> {
+        if applied
+            .iter()
+            .any(|app| self.in_namespaces(app) && app.version() > version)
+        {
+            self.rollback_migrations(Target::Version(version), migrations, applied)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    // the applied migrations rolled back to the target, latest first, with their down section
+    fn rollback_migrations(
+        &self,
+        target: Target,
+        migrations: &[Migration],
+        applied: Vec<Migration>,
+    ) -> Result<Vec<Migration>, Error> {
+        let mut applied: Vec<Migration> = applied
+            .into_iter()
+            .filter(|app| self.in_namespaces(app))
+            .collect();
+        applied.sort();
+        let applied = applied.into_iter().rev();
+        let rolled_back: Vec<Migration> = match (target, target.steps()) {
+            (Target::Version(version) | Target::FakeVersion(version), _) => {
+                applied.take_while(|app| app.version() > version).collect()
+            }
+            (_, Some(steps)) => applied.take(steps as usize).collect(),
+            _ => return Err(Error::new(Kind::InvalidRollbackTarget(target), None)),
+        };
+        let fake = matches!(target, Target::FakeVersion(_));
+
+        rolled_back
+            .into_iter()
+            .map(|app| {
+                let migration = migrations
+                    .iter()
+                    .find(|migration| {
+                        migration.version() == app.version()
+                            && migration.namespace() == app.namespace()
+                    })
+                    .ok_or_else(|| Error::new(Kind::MissingVersion(app.clone()), None))?;
+                if self.abort_divergent && migration != &app {
+                    return Err(Error::new(
+                        Kind::DivergentVersion(app, migration.clone()),
+                        None,
+                    ));
+                }
+                if !fake && migration.down_omitted {
+                    return Err(Error::new(Kind::DownOmitted(migration.clone()), None));
+                }
+                if !fake && migration.down_sql().is_none() {
+                    return Err(Error::new(Kind::Irreversible(migration.clone()), None));
+                }
+                Ok(migration.clone())
+            })
+            .collect()
+    }
+
+    /// Rolls back the applied Migrations after the given target in the supplied database connection, latest first,
+    /// executing their `-- refinery:down` section and deleting them from the schema history table.
+    /// `Target::Version(n)` rolls back the migrations applied after version n, `Target::StepsForward(n)` the last n
+    /// applied ones, `Target::Next` the last one, and `Target::FakeVersion(n)` only deletes them from the schema
+    /// history table.
+    /// Nothing is rolled back if any of them has no down section or is missing, the Report has the rolled back migrations.
+    pub fn rollback<C>(&self, connection: &mut C, target: Target) -> Result<Report, Error>
     where
         C: Migrate,
     {
-        Migrate::get_applied_migrations(conn, &self.migration_table_name)
+        let migrations = self.migrations_for_dialect(Migrate::dialect(connection))?;
+        let table_name = self.table_name(Migrate::dialect(connection))?;
+        let grouped = self.grouped(Migrate::dialect(connection))?;
+        self.setup(connection, &table_name)?;
+        let applied = Migrate::get_applied_migrations(connection, &table_name)?;
+        let rolled_back = self.rollback_migrations(target, &migrations, applied)?;
+        Migrate::rollback(
+            connection,
+            &rolled_back,
+            grouped,
+            matches!(target, Target::FakeVersion(_)),
+            &table_name,
+        )
     }
 
-    /// Queries the database asynchronously for all previous applied migrations
-    pub async fn get_applied_migrations_async<C>(
+    /// Rolls back the applied Migrations after the given target asynchronously in the supplied database connection,
+    /// latest first, executing their `-- refinery:down` section and deleting them from the schema history table,
+    /// see [`Runner::rollback`].
+    pub async fn rollback_async<C>(
         &self,
-        conn: &mut C,
-    ) -> Result<Vec<Migration>, Error>
+        connection: &mut C,
+        target: Target,
+    ) -> Result<Report, Error>
     where
         C: AsyncMigrate + Send,
     {
-        AsyncMigrate::get_applied_migrations(conn, &self.migration_table_name).await
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(connection))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(connection))?;
+        let grouped = self.grouped(AsyncMigrate::dialect(connection))?;
+        self.setup_async(connection, &table_name).await?;
+        let applied = AsyncMigrate::get_applied_migrations(connection, &table_name).await?;
+        let rolled_back = self.rollback_migrations(target, &migrations, applied)?;
+        AsyncMigrate::rollback(
+            connection,
+            &rolled_back,
+            grouped,
+            matches!(target, Target::FakeVersion(_)),
+            &table_name,
+        )
+        .await
     }
 
-    /// Set the table name to use for the migrations table. The default name is `refinery_schema_history`
-    ///
-    /// ### Warning
-    /// Changing this can be disastrous for your database. You should verify that the migrations table has the same
-    /// name as the name you specify here, if this is changed on an existing project.
-    ///
-    /// # Panics
-    ///
-    /// If the provided `migration_table_name` is empty
-    pub fn set_migration_table_name<S: AsRef<str>>(
-        &mut self,
-        migration_table_name: S,
-    ) -> &mut Self {
-        if migration_table_name.as_ref().is_empty() {
-            panic!("Migration table name must not be empty");
-        }
+    /// Creates a stream over pending migrations, applying each asynchronously before yielding it.
+    /// If a migration fails, the stream yields that error and ends, as it does if getting the pending
+    /// migrations fails.
+    pub fn run_iter_async<C>(
+        self,
+        connection: &mut C,
+    ) -> impl Stream<Item = Result<Migration, Error>> + Send + '_
+    where
+        C: AsyncMigrate + Send,
+    {
+        let stream = RunStream {
+            runner: self,
+            connection,
+            migration_table_name: String::new(),
+            items: None,
+        };
+        stream::unfold(Some(stream), |stream| async move {
+            let mut stream = stream?;
+            match stream.next().await? {
+                Ok(migration) => Some((Ok(migration), Some(stream))),
+                Err(err) => {
+                    error!("migration failed: {err:?}");
+                    Some((Err(err), None))
+                }
+            }
+        })
+    }
 
-        self.migration_table_name = migration_table_name.as_ref().to_string();
-        self
+    /// Runs the Migrations asynchronously in the supplied database connection
+    pub async fn run_async<C>(&self, connection: &mut C) -> Result<Report, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(connection))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(connection))?;
+        let grouped = self.grouped(AsyncMigrate::dialect(connection))?;
+        self.setup_async(connection, &table_name).await?;
+        let rolled_back = match self.target {
+            Target::Version(version) => {
+                AsyncMigrate::assert_migrations_table(connection, &table_name).await?;
+                let applied = AsyncMigrate::get_applied_migrations(connection, &table_name).await?;
+                let rolled_back = self.downgraded_migrations(version, &migrations, applied)?;
+                if rolled_back.is_empty() {
+                    Vec::new()
+                } else {
+                    AsyncMigrate::rollback(connection, &rolled_back, grouped, false, &table_name)
+                        .await?
+                        .applied_migrations
+                }
+            }
+            _ => Vec::new(),
+        };
+        // the rollback is committed already, so errors moving forward still report it
+        let mut report = self
+            .run_forward_async(connection, &migrations, &table_name, grouped)
+            .await
+            .map_err(|err| err.with_rolled_back(&rolled_back))?;
+        report.rolled_back_migrations = rolled_back;
+        Ok(report)
     }
 
-    /// Creates an iterator over pending migrations, applying each before returning
-    /// the result from `next()`. If a migration fails, the iterator will return that
-    /// result and further calls to `next()` will return `None`.
-    pub fn run_iter<C>(
-        self,
+    // applies the pending migrations once the ones past the target are rolled back, and runs the checks after them
+    async fn run_forward_async<C>(
+        &self,
         connection: &mut C,
-    ) -> impl Iterator<Item = Result<Migration, Error>> + '_
+        migrations: &[Migration],
+        table_name: &str,
+        grouped: bool,
+    ) -> Result<Report, Error>
     where
-        C: Migrate,
+        C: AsyncMigrate + Send,
     {
-        RunIterator::new(self, connection)
+        let mut report = if self.run_in_steps(grouped) {
+            self.run_async_steps(connection, migrations, table_name)
+                .await?
+        } else {
+            if let Some(err) = self.interruption(Instant::now(), &[]) {
+                return Err(err);
+            }
+            if self.needs_pending() {
+                let unapplied = AsyncMigrate::get_unapplied_migrations(
+                    connection,
+                    migrations,
+                    self.abort_divergent,
+                    self.abort_missing,
+                    table_name,
+                )
+                .await?;
+                for migration in self.step_migrations(&unapplied) {
+                    self.before_migration(migration);
+                }
+            }
+            let started = Instant::now();
+            let run = AsyncMigrate::migrate(
+                connection,
+                migrations,
+                self.abort_divergent,
+                self.abort_missing,
+                grouped,
+                self.target,
+                table_name,
+            );
+            let report = match self.run_timeout {
+                // grouped runs are rolled back as a whole
+                Some(timeout) => within(timeout, run)
+                    .await
+                    .ok_or_else(|| Self::run_timeout_err(timeout, Vec::new()))??,
+                None => run.await?,
+            };
+            self.after_migration(report.applied_migrations(), started.elapsed());
+            report
+        };
+        if self.integrity_checks {
+            report.integrity_issues = AsyncMigrate::check_integrity(connection).await?;
+        }
+        if let Some(path) = &self.snapshot_path {
+            let schema = AsyncMigrate::snapshot_schema(connection)
+                .await
+                .map_err(|err| err.prepend_applied(report.applied_migrations()))?;
+            self.write_snapshot(schema, path, &report)?;
+        }
+        Ok(report)
     }
 
-    /// Runs the Migrations in the supplied database connection
-    pub fn run<C>(&self, connection: &mut C) -> Result<Report, Error>
+    // applies the pending migrations one at a time, checking if the run is timed out or cancelled before each of them,
+    // and applying each within the time left of the timeout
+    async fn run_async_steps<C>(
+        &self,
+        connection: &mut C,
+        migrations: &[Migration],
+        table_name: &str,
+    ) -> Result<Report, Error>
     where
-        C: Migrate,
+        C: AsyncMigrate + Send,
     {
-        Migrate::migrate(
+        let started = Instant::now();
+        let unapplied = AsyncMigrate::get_unapplied_migrations(
             connection,
-            &self.migrations,
+            migrations,
             self.abort_divergent,
             self.abort_missing,
-            self.grouped,
-            self.target,
-            &self.migration_table_name,
+            table_name,
         )
+        .await?;
+
+        let mut applied = Vec::new();
+        for migration in self.step_migrations(&unapplied) {
+            if let Some(err) = self.interruption(started, &applied) {
+                return Err(err);
+            }
+            self.before_migration(migration);
+            let step_started = Instant::now();
+            // the pending migrations were verified once, each step only applies its migration
+            let step = AsyncMigrate::apply_pending(
+                connection,
+                std::slice::from_ref(migration),
+                table_name,
+            );
+            let report = match self.run_timeout {
+                Some(timeout) => {
+                    match within(timeout.saturating_sub(started.elapsed()), step).await {
+                        Some(report) => report,
+                        None => return Err(Self::run_timeout_err(timeout, applied)),
+                    }
+                }
+                None => step.await,
+            }
+            .map_err(|err| err.prepend_applied(&applied))?;
+            self.after_migration(report.applied_migrations(), step_started.elapsed());
+            applied.extend(report.applied_migrations);
+        }
+        Ok(Report::new(applied))
     }
 
-    /// Runs the Migrations asynchronously in the supplied database connection
-    pub async fn run_async<C>(&self, connection: &mut C) -> Result<Report, Error>
+    /// Runs only the pending Migration with the given version asynchronously in the supplied database connection,
+    /// failing if any of the migrations before it isn't applied yet.
+    /// The target set with [`Runner::set_target`] is ignored, unless it's fake.
+    pub async fn run_single_async<C>(
+        &self,
+        version: u32,
+        connection: &mut C,
+    ) -> Result<Report, Error>
     where
         C: AsyncMigrate + Send,
     {
-        AsyncMigrate::migrate(
+        let migrations = self.migrations_for_dialect(AsyncMigrate::dialect(connection))?;
+        let table_name = self.table_name(AsyncMigrate::dialect(connection))?;
+        let grouped = self.grouped(AsyncMigrate::dialect(connection))?;
+        self.setup_async(connection, &table_name).await?;
+        let unapplied = AsyncMigrate::get_unapplied_migrations(
             connection,
-            &self.migrations,
+            &migrations,
             self.abort_divergent,
             self.abort_missing,
-            self.grouped,
-            self.target,
-            &self.migration_table_name,
+            &table_name,
         )
-        .await
+        .await?;
+        let target = self.single_target(version, &migrations, &unapplied)?;
+
+        for migration in unapplied.iter().filter(|m| m.version() == version) {
+            self.before_migration(migration);
+        }
+        let started = Instant::now();
+        let report = AsyncMigrate::migrate(
+            connection,
+            &migrations,
+            self.abort_divergent,
+            self.abort_missing,
+            grouped,
+            target,
+            &table_name,
+        )
+        .await?;
+        self.after_migration(report.applied_migrations(), started.elapsed());
+        Ok(report)
     }
 }
 
 pub struct RunIterator<'a, C> {
     connection: &'a mut C,
     target: Target,
+    before_migration: Option<BeforeMigration>,
+    after_migration: Option<AfterMigration>,
     migration_table_name: String,
     items: VecDeque<Migration>,
+    error: Option<Error>,
     failed: bool,
 }
 impl<'a, C> RunIterator<'a, C>
@@ -404,20 +3230,39 @@ where
     C: Migrate,
 {
     pub(crate) fn new(runner: Runner, connection: &'a mut C) -> RunIterator<'a, C> {
+        let dialect = Migrate::dialect(connection);
+        let pending = runner.table_name(dialect).and_then(|migration_table_name| {
+            runner.setup(connection, &migration_table_name)?;
+            let migrations = runner.migrations_for_dialect(dialect)?;
+            let items = Migrate::get_unapplied_migrations(
+                connection,
+                &migrations,
+                runner.abort_divergent,
+                runner.abort_missing,
+                &migration_table_name,
+            )?;
+            Ok((migration_table_name, items))
+        });
+        // errors preparing the run are yielded by the first call to `next()`
+        let (migration_table_name, mut items, error) = match pending {
+            Ok((migration_table_name, items)) => (migration_table_name, items, None),
+            Err(err) => (String::new(), Vec::new(), Some(err)),
+        };
+        // each migration is applied on its own, so the steps are limited here
+        if let Some(steps) = runner.target.steps() {
+            items.truncate(steps as usize);
+        }
+
+        // fake runs don't invoke the callbacks
+        let fake = runner.is_fake();
         RunIterator {
-            items: VecDeque::from(
-                Migrate::get_unapplied_migrations(
-                    connection,
-                    &runner.migrations,
-                    runner.abort_divergent,
-                    runner.abort_missing,
-                    &runner.migration_table_name,
-                )
-                .unwrap(),
-            ),
+            items: VecDeque::from(items),
             connection,
             target: runner.target,
-            migration_table_name: runner.migration_table_name.clone(),
+            before_migration: runner.before_migration.filter(|_| !fake),
+            after_migration: runner.after_migration.filter(|_| !fake),
+            migration_table_name,
+            error,
             failed: false,
         }
     }
@@ -429,17 +3274,35 @@ where
     type Item = Result<Migration, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            error!("migration failed: {err:?}");
+            self.failed = true;
+            return Some(Err(err));
+        }
         match self.failed {
             true => None,
             false => self.items.pop_front().and_then(|migration| {
+                if let Some(before_migration) = &self.before_migration {
+                    before_migration(&migration);
+                }
+                let started = Instant::now();
+                let dialect = Migrate::dialect(self.connection);
                 sync_migrate(
                     self.connection,
                     vec![migration],
                     self.target,
                     &self.migration_table_name,
                     false,
+                    dialect,
                 )
-                .map(|r| r.applied_migrations.first().cloned())
+                .map(|r| {
+                    if let Some(after_migration) = &self.after_migration {
+                        for applied in &r.applied_migrations {
+                            after_migration(applied, started.elapsed());
+                        }
+                    }
+                    r.applied_migrations.first().cloned()
+                })
                 .map_err(|e| {
                     error!("migration failed: {e:?}");
                     self.failed = true;
@@ -450,3 +3313,727 @@ where
         }
     }
 }
+
+fn validation_report(applied: Vec<Migration>, migrations: Vec<Migration>) -> ValidationReport {
+    let mut issues = Vec::new();
+    let pending_migrations = validate_migrations(applied, migrations, &mut issues);
+    ValidationReport {
+        issues,
+        pending_migrations,
+    }
+}
+
+// the applied migrations with a different checksum than the migration with their version and namespace
+fn checksum_mismatches(applied: Vec<Migration>, migrations: &[Migration]) -> Vec<ChecksumMismatch> {
+    applied
+        .into_iter()
+        .filter_map(|app| {
+            let migration = migrations.iter().find(|migration| {
+                migration.version == app.version && migration.namespace == app.namespace
+            })?;
+            (!migration.checksum_matches(&app)).then(|| ChecksumMismatch {
+                applied: app,
+                migration: migration.clone(),
+            })
+        })
+        .collect()
+}
+
+// state of the stream returned by Runner::run_iter_async, the pending migrations are only
+// fetched once it's first polled
+struct RunStream<'a, C> {
+    runner: Runner,
+    connection: &'a mut C,
+    migration_table_name: String,
+    items: Option<VecDeque<Migration>>,
+}
+
+impl<C> RunStream<'_, C>
+where
+    C: AsyncMigrate + Send,
+{
+    async fn pending(&mut self) -> Result<VecDeque<Migration>, Error> {
+        let dialect = AsyncMigrate::dialect(self.connection);
+        let migrations = self.runner.migrations_for_dialect(dialect)?;
+        self.migration_table_name = self.runner.table_name(dialect)?;
+        self.runner
+            .setup_async(self.connection, &self.migration_table_name)
+            .await?;
+        let unapplied = AsyncMigrate::get_unapplied_migrations(
+            self.connection,
+            &migrations,
+            self.runner.abort_divergent,
+            self.runner.abort_missing,
+            &self.migration_table_name,
+        )
+        .await?;
+        Ok(self
+            .runner
+            .step_migrations(&unapplied)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    async fn next(&mut self) -> Option<Result<Migration, Error>> {
+        if self.items.is_none() {
+            match self.pending().await {
+                Ok(items) => self.items = Some(items),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        let migration = self.items.as_mut()?.pop_front()?;
+        self.runner.before_migration(&migration);
+        let started = Instant::now();
+        let dialect = AsyncMigrate::dialect(self.connection);
+        let report = async_migrate(
+            self.connection,
+            vec![migration],
+            self.runner.target,
+            &self.migration_table_name,
+            false,
+            dialect,
+        )
+        .await;
+        match report {
+            Ok(report) => {
+                self.runner
+                    .after_migration(report.applied_migrations(), started.elapsed());
+                report.applied_migrations.into_iter().next().map(Ok)
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, Migration, Report, Runner, Target};
+    use crate::error::Kind;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+
+    fn generate_sql(dialect: Dialect) -> String {
+        match dialect {
+            Dialect::Mssql => "CREATE TABLE persons (id INT IDENTITY);".into(),
+            _ => "CREATE TABLE persons (id int);".into(),
+        }
+    }
+
+    #[test]
+    fn unapplied_from_borrows_static_sql() {
+        const SQL: &str = "CREATE TABLE persons (id int, name varchar(255), city varchar(255));";
+        let migration = Migration::unapplied_from("V1__initial", SQL).unwrap();
+        assert_eq!(SQL.as_ptr(), migration.sql().unwrap().as_ptr());
+    }
+
+    #[test]
+    fn unapplied_from_keeps_checksum() {
+        let sql = "CREATE TABLE persons (id int, name varchar(255), city varchar(255));";
+        let borrowed = Migration::unapplied("V1__initial", sql).unwrap();
+        let owned = Migration::unapplied_from("V1__initial", sql.to_string()).unwrap();
+        assert_eq!(borrowed.checksum(), owned.checksum());
+    }
+
+    #[test]
+    fn fails_grouping_on_dialects_without_transactional_ddl() {
+        let runner = Runner::new(&[]).set_grouped(true);
+        assert!(runner.grouped(Some(Dialect::Postgres)).unwrap());
+        assert!(runner.grouped(None).unwrap());
+        let err = runner.grouped(Some(Dialect::Mysql)).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            Kind::GroupingNotSupported(Dialect::Mysql)
+        ));
+        // the dialect set on the Runner overrides the one of the connection
+        let runner = runner.set_dialect(Dialect::ClickHouse);
+        assert!(runner.grouped(Some(Dialect::Postgres)).is_err());
+
+        let runner = runner.set_grouped_fallback(true);
+        assert!(!runner.grouped(Some(Dialect::Mysql)).unwrap());
+        let runner = Runner::new(&[]).set_grouped(true).set_target(Target::Fake);
+        assert!(runner.grouped(Some(Dialect::Mysql)).unwrap());
+        assert!(!Runner::new(&[]).grouped(Some(Dialect::Mysql)).unwrap());
+    }
+
+    #[test]
+    fn merges_runners() {
+        let users = Runner::new(&[
+            Migration::unapplied("V1__add_users", "CREATE TABLE users(id int);").unwrap(),
+            Migration::unapplied("V3__add_emails", "ALTER TABLE users ADD email text;").unwrap(),
+        ]);
+        let orders =
+            Runner::new(&[
+                Migration::unapplied("V2__add_orders", "CREATE TABLE orders(id int);").unwrap(),
+            ]);
+
+        let runner = users.set_grouped(true).merge(orders).unwrap();
+        assert!(runner.grouped);
+        let versions: Vec<u32> = runner
+            .get_migrations()
+            .iter()
+            .map(|m| m.version())
+            .collect();
+        assert_eq!(vec![1, 2, 3], versions);
+
+        let repeated = Runner::new(&[Migration::unapplied(
+            "V2__add_products",
+            "CREATE TABLE products(id int);",
+        )
+        .unwrap()]);
+        let err = Runner::new(runner.get_migrations())
+            .merge(repeated)
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind(), Kind::RepeatedVersion(m) if m.name() == "add_products"));
+
+        let runner = runner
+            .add_migrations(&[
+                Migration::unapplied("V5__add_payments", "CREATE TABLE payments(id int);").unwrap(),
+                Migration::unapplied("V4__add_products", "CREATE TABLE products(id int);").unwrap(),
+            ])
+            .unwrap();
+        let versions: Vec<u32> = runner
+            .get_migrations()
+            .iter()
+            .map(|m| m.version())
+            .collect();
+        assert_eq!(vec![1, 2, 3, 4, 5], versions);
+        let err = runner
+            .add_migrations(&[
+                Migration::unapplied("V6__add_refunds", "CREATE TABLE refunds(id int);").unwrap(),
+                Migration::unapplied("V6__add_invoices", "CREATE TABLE invoices(id int);").unwrap(),
+            ])
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind(), Kind::RepeatedVersion(m) if m.name() == "add_invoices"));
+    }
+
+    #[test]
+    fn switches_to_tenant_schemas() {
+        assert_eq!(
+            Some("SET search_path TO tenant_a;".to_string()),
+            Dialect::Postgres.use_tenant_statement("tenant_a")
+        );
+        assert_eq!(
+            Some("USE `tenant-b`;".to_string()),
+            Dialect::Mysql.use_tenant_statement("tenant-b")
+        );
+        assert_eq!(
+            Some("SET search_path TO \"Tenant\"\"C\";".to_string()),
+            Dialect::Cockroach.use_tenant_statement("Tenant\"C")
+        );
+        assert_eq!(None, Dialect::Sqlite.use_tenant_statement("tenant_a"));
+    }
+
+    #[test]
+    fn quotes_schema_qualified_table_names() {
+        assert_eq!(
+            "audit.refinery_history",
+            Dialect::Postgres
+                .quote_table_name("audit.refinery_history")
+                .unwrap()
+        );
+        assert_eq!(
+            "`audit-log`.refinery_history",
+            Dialect::Mysql
+                .quote_table_name("audit-log.refinery_history")
+                .unwrap()
+        );
+        assert_eq!(
+            "[Audit].[refinery.history]",
+            Dialect::Mssql
+                .quote_table_name("\"Audit\".[refinery.history]")
+                .unwrap()
+        );
+        assert_eq!(
+            "\"a\"\"b\".history",
+            Dialect::Sqlite.quote_table_name("`a\"b`.history").unwrap()
+        );
+        assert!(Dialect::Mssql.quote_table_name("db.audit.history").is_ok());
+
+        let err = Dialect::Postgres
+            .quote_table_name("db.audit.history")
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationTableName(..)));
+        for name in ["", "audit.", "\"audit.history", "\"audit\"x.history"] {
+            assert!(
+                Dialect::Postgres.quote_table_name(name).is_err(),
+                "{} is valid",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn validates_migration_table_name() {
+        let mut runner = Runner::new(&[]);
+        assert!(runner.set_migration_table_name("db.audit.history").is_ok());
+        assert!(runner.set_migration_table_name("a.b.c.d").is_err());
+
+        let mut runner = Runner::new(&[]).set_dialect(Dialect::Sqlite);
+        assert!(runner.set_migration_table_name("audit.history").is_ok());
+        let err = runner
+            .set_migration_table_name("db.audit.history")
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationTableName(..)));
+    }
+
+    #[test]
+    fn qualifies_table_name_with_schema() {
+        let runner = Runner::new(&[]).set_migration_table_schema("dbo");
+        assert_eq!(
+            "dbo.refinery_schema_history",
+            runner.table_name(None).unwrap()
+        );
+        assert_eq!(
+            "dbo.[refinery history]",
+            Runner::new(&[])
+                .set_migration_table_schema("dbo")
+                .set_dialect(Dialect::Mssql)
+                .set_migration_table_name("[refinery history]")
+                .unwrap()
+                .table_name(None)
+                .unwrap()
+        );
+        let runner = Runner::new(&[]).set_migration_table_schema("audit log");
+        assert_eq!(
+            "`audit log`.refinery_schema_history",
+            runner.table_name(Some(Dialect::Mysql)).unwrap()
+        );
+
+        let mut runner = Runner::new(&[]).set_migration_table_schema("dbo");
+        runner.set_migration_table_name("audit.history").unwrap();
+        let err = runner.table_name(Some(Dialect::Postgres)).unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationTableName(..)));
+    }
+
+    #[test]
+    fn filters_migrations() {
+        let runner = Runner::new(&[
+            Migration::unapplied("V1__add_users", "CREATE TABLE users(id int);").unwrap(),
+            Migration::unapplied("V2__add_orders", "CREATE TABLE orders(id int);").unwrap(),
+            Migration::unapplied("V3__add_emails", "ALTER TABLE users ADD email text;").unwrap(),
+        ])
+        .set_grouped(true);
+
+        let runner = runner.filter(|m| m.version() < 3);
+        assert!(runner.grouped);
+        let migrations = runner.into_migrations();
+        let names: Vec<&str> = migrations.iter().map(|m| m.name()).collect();
+        assert_eq!(vec!["add_users", "add_orders"], names);
+    }
+
+    #[test]
+    fn parses_metadata_from_name() {
+        let migration =
+            Migration::unapplied("V12__add_users__TICKET-123__alice.sql", "SELECT 1").unwrap();
+        assert_eq!(12, migration.version());
+        assert_eq!("add_users__TICKET-123__alice", migration.name());
+        assert_eq!(vec!["TICKET-123", "alice"], migration.metadata());
+
+        let migration = Migration::unapplied("V1__initial", "SELECT 1").unwrap();
+        assert!(migration.metadata().is_empty());
+    }
+
+    #[test]
+    fn exposes_header_comment_metadata() {
+        let sql =
+            "-- Author: alice\n-- description: add the users table\nCREATE TABLE users(id int);";
+        let migration = Migration::unapplied("V1__add_users", sql).unwrap();
+        assert_eq!(Some("alice"), migration.author());
+        assert_eq!(Some("add the users table"), migration.description());
+        assert_eq!(None, migration.ticket());
+    }
+
+    #[test]
+    fn parses_skip_directive() {
+        let sql = "-- author: alice\n-- refinery:skip\nCREATE TABLE users(id int);";
+        let migration = Migration::unapplied("V1__add_users", sql).unwrap();
+        assert!(migration.is_skipped());
+
+        let sql = "CREATE TABLE users(id int);\n-- refinery:skip";
+        let migration = Migration::unapplied("V1__add_users", sql).unwrap();
+        assert!(!migration.is_skipped());
+    }
+
+    #[test]
+    fn parses_no_transaction_directive() {
+        let sql = "-- refinery:no-transaction retries=2\nCREATE INDEX CONCURRENTLY i ON users(id);";
+        let migration = Migration::unapplied("V2__index_users", sql).unwrap();
+        assert!(!migration.is_transactional());
+        assert_eq!(2, migration.retries());
+
+        let sql = "CREATE INDEX i ON users(id);\n-- refinery:no-transaction";
+        let migration = Migration::unapplied("V2__index_users", sql).unwrap();
+        assert!(migration.is_transactional());
+    }
+
+    #[test]
+    fn selects_migration_variants_for_dialect() {
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);").unwrap(),
+            Migration::unapplied("V2__add_fulltext", "SELECT 1;").unwrap(),
+            Migration::unapplied("V2__add_fulltext.postgres", "SELECT 2;").unwrap(),
+            Migration::unapplied("V2__add_fulltext.sqlite", "SELECT 3;").unwrap(),
+            Migration::unapplied("V3__add_index.mysql", "SELECT 4;").unwrap(),
+        ];
+        let sql = |migrations: Vec<Migration>| -> Vec<String> {
+            migrations
+                .iter()
+                .map(|migration| migration.sql().unwrap().to_string())
+                .collect()
+        };
+
+        let runner = Runner::new(&migrations);
+        let postgres = runner
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap();
+        assert_eq!(
+            vec!["CREATE TABLE persons (id int);", "SELECT 2;"],
+            sql(postgres)
+        );
+        let mysql = runner.migrations_for_dialect(Some(Dialect::Mysql)).unwrap();
+        assert_eq!(
+            vec!["CREATE TABLE persons (id int);", "SELECT 1;", "SELECT 4;"],
+            sql(mysql)
+        );
+
+        let runner = Runner::new(&migrations).set_dialect(Dialect::Sqlite);
+        let sqlite = runner
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap();
+        assert_eq!(
+            vec!["CREATE TABLE persons (id int);", "SELECT 3;"],
+            sql(sqlite)
+        );
+
+        let err = Runner::new(&migrations)
+            .migrations_for_dialect(None)
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownDialect(_)));
+    }
+
+    #[test]
+    fn parses_target() {
+        assert!(matches!("latest".parse(), Ok(Target::Latest)));
+        assert!(matches!("fake".parse(), Ok(Target::Fake)));
+        assert!(matches!("3".parse(), Ok(Target::Version(3))));
+        assert!(matches!("fake:3".parse(), Ok(Target::FakeVersion(3))));
+        assert!(matches!(
+            Target::try_from("steps:2"),
+            Ok(Target::StepsForward(2))
+        ));
+        assert!(matches!("next".parse(), Ok(Target::Next)));
+
+        for invalid in ["", "nex", "-1", "fake:", "steps:two", "version:3"] {
+            let err = invalid.parse::<Target>().unwrap_err();
+            assert!(matches!(err.kind(), Kind::InvalidTarget(_)));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn converts_applied_on_to_chrono() {
+        let applied_on = OffsetDateTime::parse(
+            "2024-03-05T10:20:30.123456789Z",
+            &time::format_description::well_known::Rfc3339,
+        )
+        .unwrap();
+        let migration = Migration::applied(1, "initial".into(), applied_on, 0);
+        let applied_on_chrono = migration.applied_on_chrono().unwrap();
+        assert_eq!(applied_on.unix_timestamp(), applied_on_chrono.timestamp());
+        assert_eq!(123456789, applied_on_chrono.timestamp_subsec_nanos());
+
+        let migration = Migration::unapplied("V1__initial", "SELECT 1").unwrap();
+        assert!(migration.applied_on_chrono().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serializes_migration() {
+        let mut migration =
+            Migration::unapplied("V7__add_fulltext.postgres", "CREATE INDEX;").unwrap();
+        migration.set_applied();
+
+        let json = serde_json::to_value(&migration).unwrap();
+        assert_eq!("add_fulltext", json["name"]);
+        assert_eq!(7, json["version"]);
+        assert_eq!("postgres", json["dialect"]);
+        assert_eq!("CREATE INDEX;", json["sql"]);
+
+        let deserialized: Migration = serde_json::from_value(json).unwrap();
+        assert_eq!(migration.checksum(), deserialized.checksum());
+        assert_eq!(migration.applied_on(), deserialized.applied_on());
+        assert_eq!(migration.sql(), deserialized.sql());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serializes_report_to_json() {
+        let mut applied = Migration::unapplied("V1__initial", "CREATE TABLE t(id int);").unwrap();
+        applied.set_applied();
+        let finished_on = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        applied.set_execution_time(Duration::from_millis(1500), finished_on);
+        let mut grouped =
+            Migration::unapplied("V2__add_name", "ALTER TABLE t ADD name text;").unwrap();
+        grouped.set_applied();
+        let mut report = Report::new(vec![applied.clone(), grouped]);
+        report.rolled_back_migrations =
+            vec![Migration::applied(3, "drop_t".into(), finished_on, 0)];
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        let migrations = json["applied_migrations"].as_array().unwrap();
+        assert_eq!(1, migrations[0]["version"]);
+        assert_eq!(applied.checksum().to_string(), migrations[0]["checksum"]);
+        assert_eq!(23, migrations[0]["sql_bytes"]);
+        assert_eq!("2023-11-14T22:13:18.5Z", migrations[0]["started_on"]);
+        assert_eq!("2023-11-14T22:13:20Z", migrations[0]["finished_on"]);
+        assert_eq!(1500, migrations[0]["duration_ms"]);
+        assert!(migrations[1]["duration_ms"].is_null());
+        assert!(migrations[1]["started_on"].is_null());
+        assert_eq!("drop_t", json["rolled_back_migrations"][0]["name"]);
+        assert!(json["integrity_issues"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn generates_sql_for_dialect() {
+        let migration = Migration::unapplied_for_dialect("V1__initial", generate_sql).unwrap();
+        assert!(migration.sql().is_none());
+
+        let sqlite = migration.for_dialect(Some(Dialect::Sqlite)).unwrap();
+        let mssql = migration.for_dialect(Some(Dialect::Mssql)).unwrap();
+        assert_eq!(Some("CREATE TABLE persons (id int);"), sqlite.sql());
+        assert_eq!(Some("CREATE TABLE persons (id INT IDENTITY);"), mssql.sql());
+        assert_eq!(
+            Migration::unapplied("V1__initial", "CREATE TABLE persons (id int);")
+                .unwrap()
+                .checksum(),
+            sqlite.checksum()
+        );
+        assert_ne!(sqlite.checksum(), mssql.checksum());
+    }
+
+    #[test]
+    fn fails_generating_sql_the_dialect_is_unsupported_by() {
+        fn try_generate_sql(dialect: Dialect) -> Result<String, crate::Error> {
+            match dialect {
+                Dialect::Mssql => Err(crate::Error::new(Kind::UnsupportedDialect(dialect), None)),
+                _ => Ok(generate_sql(dialect)),
+            }
+        }
+
+        let migration =
+            Migration::try_unapplied_for_dialect("V1__initial", try_generate_sql).unwrap();
+        let sqlite = migration.for_dialect(Some(Dialect::Sqlite)).unwrap();
+        assert_eq!(Some("CREATE TABLE persons (id int);"), sqlite.sql());
+        let err = migration.for_dialect(Some(Dialect::Mssql)).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            Kind::UnsupportedDialect(Dialect::Mssql)
+        ));
+    }
+
+    #[test]
+    fn fails_generating_sql_for_unknown_dialect() {
+        let migration = Migration::unapplied_for_dialect("V1__initial", generate_sql).unwrap();
+        let err = migration.for_dialect(None).unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownDialect(m) if m.version() == 1));
+    }
+
+    #[test]
+    fn generates_check_statements_for_dialect() {
+        let sql = "UPDATE users SET active = 1;\n-- refinery:check\nSELECT count(*) > 0 FROM users;\nSELECT 1;\n";
+        let migration = Migration::unapplied("V2__activate_users", sql).unwrap();
+
+        let sqlite = migration.for_dialect(Some(Dialect::Sqlite)).unwrap();
+        let sql = sqlite.sql().unwrap();
+        assert!(sql.starts_with("UPDATE users SET active = 1;\n\nCREATE TEMP TABLE refinery_check"));
+        assert!(sql.contains("CONSTRAINT refinery_check_1 CHECK"));
+        assert!(sql.contains("SELECT (\nSELECT count(*) > 0 FROM users\n);"));
+        assert!(sql.contains("CONSTRAINT refinery_check_2 CHECK"));
+        assert!(!sql.contains("refinery:check"));
+        assert_eq!(migration.checksum(), sqlite.checksum());
+
+        let cockroach = migration.for_dialect(Some(Dialect::Cockroach)).unwrap();
+        let sql = cockroach.sql().unwrap();
+        assert!(!sql.contains("TEMP"));
+        assert!(sql.contains("crdb_internal.force_error('23514', 'refinery_check_1 failed')"));
+        assert!(sql.contains("FROM (SELECT (\nSELECT 1\n)::STRING AS result)"));
+
+        let clickhouse = migration.for_dialect(Some(Dialect::ClickHouse)).unwrap();
+        let sql = clickhouse.sql().unwrap();
+        assert!(sql.contains("SELECT throwIf(NOT (result IS NOT NULL AND result NOT IN ('false', '0')), 'refinery_check_2 failed')"));
+
+        let err = migration.for_dialect(None).unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownDialect(m) if m.version() == 2));
+    }
+
+    #[test]
+    fn strips_down_section() {
+        let sql = "CREATE TABLE users(id int);\n-- refinery:check\nSELECT 1;\n-- refinery:down\nDROP TABLE users;\n";
+        let migration = Migration::unapplied("V1__add_users", sql).unwrap();
+        assert_eq!(Some("DROP TABLE users;\n"), migration.down_sql());
+
+        let sqlite = migration.for_dialect(Some(Dialect::Sqlite)).unwrap();
+        let sql = sqlite.sql().unwrap();
+        assert!(sql.contains("CONSTRAINT refinery_check_1 CHECK"));
+        assert!(sql.contains("SELECT (\nSELECT 1\n);"));
+        assert!(!sql.contains("DROP TABLE users"));
+        assert_eq!(migration.checksum(), sqlite.checksum());
+        assert_eq!(Some("DROP TABLE users;\n"), sqlite.down_sql());
+
+        let migration = Migration::unapplied(
+            "V1__add_users",
+            "CREATE TABLE users(id int);\n-- refinery:down\n",
+        )
+        .unwrap();
+        let sql = migration.for_dialect(None).unwrap();
+        assert_eq!(Some("CREATE TABLE users(id int);\n"), sql.sql());
+    }
+
+    #[test]
+    fn sets_timeout_for_dialect() {
+        let sql = "-- refinery:timeout=2m retries=1\n-- description: backfill\nUPDATE users SET active = 1;";
+        let migration = Migration::unapplied("V2__backfill", sql).unwrap();
+        assert_eq!(Some(Duration::from_secs(120)), migration.timeout());
+
+        let postgres = migration.for_dialect(Some(Dialect::Postgres)).unwrap();
+        assert_eq!(
+            Some("-- refinery:timeout=2m retries=1\n-- description: backfill\nSET LOCAL statement_timeout = 120000; SET LOCAL lock_timeout = 120000;\nUPDATE users SET active = 1;\nSET LOCAL statement_timeout TO DEFAULT; SET LOCAL lock_timeout TO DEFAULT;"),
+            postgres.sql()
+        );
+        assert_eq!(Some("backfill"), postgres.description());
+        assert_eq!(1, postgres.retries());
+        assert_eq!(migration.checksum(), postgres.checksum());
+
+        let cockroach = migration.for_dialect(Some(Dialect::Cockroach)).unwrap();
+        assert_eq!(
+            Some("-- refinery:timeout=2m retries=1\n-- description: backfill\nSET LOCAL statement_timeout = 120000;\nUPDATE users SET active = 1;\nSET LOCAL statement_timeout TO DEFAULT;"),
+            cockroach.sql()
+        );
+
+        assert_eq!(None, postgres.reset_timeout());
+
+        let sqlite = migration.for_dialect(Some(Dialect::Sqlite)).unwrap();
+        assert_eq!(Some(sql), sqlite.sql());
+
+        let err = migration.for_dialect(None).unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownDialect(_)));
+    }
+
+    #[test]
+    fn sets_session_timeout_for_migrations_without_transaction() {
+        let migrations = [Migration::unapplied(
+            "V3__index_users",
+            "-- refinery:no-transaction\nCREATE INDEX CONCURRENTLY users_name ON users(name);",
+        )
+        .unwrap()];
+        let runner = Runner::new(&migrations).set_timeout(Duration::from_secs(30));
+
+        // `SET LOCAL` has no effect outside of a transaction
+        let postgres = runner
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap();
+        assert_eq!(
+            Some("-- refinery:no-transaction\nSET statement_timeout = 30000; SET lock_timeout = 30000;\nCREATE INDEX CONCURRENTLY users_name ON users(name);"),
+            postgres[0].sql()
+        );
+        assert_eq!(
+            Some("RESET statement_timeout; RESET lock_timeout;"),
+            postgres[0].reset_timeout()
+        );
+
+        let cockroach = runner
+            .migrations_for_dialect(Some(Dialect::Cockroach))
+            .unwrap();
+        assert_eq!(
+            Some("-- refinery:no-transaction\nSET statement_timeout = 30000;\nCREATE INDEX CONCURRENTLY users_name ON users(name);"),
+            cockroach[0].sql()
+        );
+        assert_eq!(
+            Some("RESET statement_timeout;"),
+            cockroach[0].reset_timeout()
+        );
+    }
+
+    #[test]
+    fn sets_runner_timeout_for_dialect() {
+        let migrations = [
+            Migration::unapplied("V1__initial", "CREATE TABLE users(id int);").unwrap(),
+            Migration::unapplied(
+                "V2__backfill",
+                "-- refinery:timeout=2m\nUPDATE users SET active = 1;",
+            )
+            .unwrap(),
+        ];
+        let runner = Runner::new(&migrations).set_timeout(Duration::from_millis(1500));
+        let mysql = runner.migrations_for_dialect(Some(Dialect::Mysql)).unwrap();
+        assert_eq!(Some(Duration::from_millis(1500)), mysql[0].timeout());
+        assert_eq!(
+            Some("SET SESSION innodb_lock_wait_timeout = 1, lock_wait_timeout = 1, max_execution_time = 1500;\nCREATE TABLE users(id int);"),
+            mysql[0].sql()
+        );
+        // the session settings are reset by the executor after the migration, also when it fails
+        assert_eq!(
+            Some("SET SESSION innodb_lock_wait_timeout = DEFAULT, lock_wait_timeout = DEFAULT, max_execution_time = DEFAULT;"),
+            mysql[0].reset_timeout()
+        );
+        assert_eq!(migrations[0].checksum(), mysql[0].checksum());
+        // the directive overrides it
+        assert_eq!(Some(Duration::from_secs(120)), mysql[1].timeout());
+
+        // sqlite sets it as the busy timeout of the connection instead
+        let sqlite = runner
+            .migrations_for_dialect(Some(Dialect::Sqlite))
+            .unwrap();
+        assert_eq!(migrations[0].sql(), sqlite[0].sql());
+        assert_eq!(vec!["busy_timeout=1500"], runner.sqlite_pragmas().to_vec());
+        let runner = runner.set_sqlite_pragmas(vec!["busy_timeout = 5000".into()]);
+        assert_eq!(
+            vec!["busy_timeout = 5000"],
+            runner.sqlite_pragmas().to_vec()
+        );
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        let migrations = [Migration::unapplied(
+            "V1__initial",
+            "CREATE SCHEMA ${schema};\nGRANT USAGE ON SCHEMA ${schema} TO ${role};\n-- refinery:down\nDROP SCHEMA ${schema};",
+        )
+        .unwrap()];
+        let placeholders = HashMap::from([
+            ("schema".to_string(), "billing".to_string()),
+            ("role".to_string(), "app".to_string()),
+        ]);
+        let runner = Runner::new(&migrations).set_placeholders(placeholders);
+        let for_postgres = runner
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap();
+        assert_eq!(
+            Some("CREATE SCHEMA billing;\nGRANT USAGE ON SCHEMA billing TO app;\n"),
+            for_postgres[0].sql()
+        );
+        assert_eq!(Some("DROP SCHEMA billing;"), for_postgres[0].down_sql());
+        assert_eq!(migrations[0].checksum(), for_postgres[0].checksum());
+
+        // placeholders without a value are kept unless they are strict
+        let runner = Runner::new(&migrations).set_placeholders(HashMap::from([(
+            "schema".to_string(),
+            "billing".to_string(),
+        )]));
+        let for_postgres = runner
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap();
+        assert!(for_postgres[0].sql().unwrap().contains("TO ${role};"));
+
+        let err = runner
+            .set_strict_placeholders(true)
+            .migrations_for_dialect(Some(Dialect::Postgres))
+            .unwrap_err();
+        assert!(matches!(err.kind(), Kind::UnknownPlaceholder(name, _) if name == "role"));
+    }
+}
@@ -0,0 +1,390 @@
+use crate::error::{Error, Kind};
+use crate::runner::Type;
+use crate::util::{
+    find_migration_files, format_timestamp_version, parse_migration_name, parse_migration_naming,
+    MigrationNaming, MigrationType,
+};
+use std::convert::Infallible;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// enum containing the formats a new migration can be created in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MigrationFormat {
+    Sql,
+    Rust,
+}
+
+impl MigrationFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            MigrationFormat::Sql => "sql",
+            MigrationFormat::Rust => "rs",
+        }
+    }
+
+    fn template(&self) -> &'static str {
+        match self {
+            MigrationFormat::Sql => "",
+            MigrationFormat::Rust => "pub fn migration() -> String {\n    String::new()\n}\n",
+        }
+    }
+}
+
+/// enum containing the templates a new migration can be created from, see [`new_migration_from_template`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MigrationTemplate {
+    /// An empty SQL migration
+    Plain,
+    /// A SQL migration with an empty `-- refinery:down` section to revert it
+    UpDown,
+    /// A Rust migration with a `migration` function returning its sql
+    Rust,
+    /// A template file with a team's standard header comments or `BEGIN`/`COMMIT` wrappers, creating a Rust migration
+    /// if its extension is `rs` and a SQL one otherwise. `{{version}}`, `{{name}}` and `{{date}}` are replaced with the
+    /// version, name and creation date of the migration
+    File(PathBuf),
+}
+
+impl MigrationTemplate {
+    fn format(&self) -> MigrationFormat {
+        match self {
+            MigrationTemplate::Rust => MigrationFormat::Rust,
+            MigrationTemplate::File(path) if path.extension().is_some_and(|ext| ext == "rs") => {
+                MigrationFormat::Rust
+            }
+            _ => MigrationFormat::Sql,
+        }
+    }
+
+    fn render(&self, version: u32, name: &str) -> Result<String, Error> {
+        let template = match self {
+            MigrationTemplate::Plain => return Ok(MigrationFormat::Sql.template().into()),
+            MigrationTemplate::UpDown => return Ok("\n-- refinery:down\n".into()),
+            MigrationTemplate::Rust => return Ok(MigrationFormat::Rust.template().into()),
+            MigrationTemplate::File(path) => fs::read_to_string(path)
+                .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.clone(), err), None))?,
+        };
+        Ok(template
+            .replace("{{version}}", &version.to_string())
+            .replace("{{name}}", name)
+            .replace("{{date}}", &OffsetDateTime::now_utc().date().to_string()))
+    }
+}
+
+impl From<MigrationFormat> for MigrationTemplate {
+    fn from(format: MigrationFormat) -> MigrationTemplate {
+        match format {
+            MigrationFormat::Sql => MigrationTemplate::Plain,
+            MigrationFormat::Rust => MigrationTemplate::Rust,
+        }
+    }
+}
+
+/// Parses `plain`, `up-down` and `rust`, any other value is the path of a template file
+impl FromStr for MigrationTemplate {
+    type Err = Infallible;
+
+    fn from_str(template: &str) -> Result<MigrationTemplate, Infallible> {
+        Ok(match template {
+            "plain" => MigrationTemplate::Plain,
+            "up-down" => MigrationTemplate::UpDown,
+            "rust" => MigrationTemplate::Rust,
+            path => MigrationTemplate::File(PathBuf::from(path)),
+        })
+    }
+}
+
+/// Get the version the next migration on the given location should have,
+/// which is one above the highest version of the migrations already there, or 1 if there are none
+pub fn next_version(location: impl AsRef<Path>) -> Result<u32, Error> {
+    let location = location.as_ref();
+    if !location.exists() {
+        return Ok(1);
+    }
+
+    let mut current = 0;
+    for path in find_migration_files(location, MigrationType::All)? {
+        // safe to call unwrap as find_migration_files returns canonical paths
+        let file_stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap();
+        let (_, version, _) = parse_migration_name(file_stem)?;
+        current = current.max(version as u32);
+    }
+
+    Ok(current + 1)
+}
+
+/// Create a new, empty, migration file named after the next version available on the given location,
+/// creating the location directory if it doesn't exist yet. Returns the paths of the created files.
+///
+/// The name must only contain Unicode word characters plus "-", as the ones of migrations found by [`find_migration_files`].
+///
+/// [`find_migration_files`]: ../fn.find_migration_files.html
+pub fn new_migration(
+    location: impl AsRef<Path>,
+    name: &str,
+    kind: Type,
+    format: MigrationFormat,
+) -> Result<Vec<PathBuf>, Error> {
+    new_migration_from_template(location, name, kind, &format.into(), None)
+}
+
+// the naming scheme of the migrations already on the location and the path of one of them, None if there are none
+fn location_naming(location: &Path) -> Result<Option<(MigrationNaming, PathBuf)>, Error> {
+    if !location.exists() {
+        return Ok(None);
+    }
+    for path in find_migration_files(location, MigrationType::All)? {
+        // safe to call unwrap as find_migration_files returns canonical paths
+        let file_stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap();
+        if let Some(naming) = parse_migration_naming(file_stem) {
+            return Ok(Some((naming, path)));
+        }
+    }
+    Ok(None)
+}
+
+/// Create a new migration file from the given [`MigrationTemplate`], named after the next version available on the
+/// given location the same way as [`new_migration`], or after the current time with [`MigrationNaming::Timestamp`].
+/// When the naming is `None` the migration is named with the scheme of the migrations already on the location,
+/// or versioned if there are none. Returns the paths of the created files.
+pub fn new_migration_from_template(
+    location: impl AsRef<Path>,
+    name: &str,
+    kind: Type,
+    template: &MigrationTemplate,
+    naming: Option<MigrationNaming>,
+) -> Result<Vec<PathBuf>, Error> {
+    let location = location.as_ref();
+    let existing = location_naming(location)?;
+    let naming = naming
+        .or(existing.as_ref().map(|(naming, _)| *naming))
+        .unwrap_or_default();
+    let version = next_version(location)?;
+
+    let (version, file_stem) = match naming {
+        MigrationNaming::Versioned => (version, format!("{}{}__{}", kind, version, name)),
+        // timestamp migrations are all versioned, and named after the current time unless it's before the latest
+        MigrationNaming::Timestamp if kind == Type::Versioned => {
+            let now = i32::try_from(OffsetDateTime::now_utc().unix_timestamp())
+                .map_err(|_| Error::new(Kind::InvalidVersion, None))?;
+            let version = now.max(version as i32);
+            (
+                version as u32,
+                format!("{}_{}", format_timestamp_version(version), name),
+            )
+        }
+        MigrationNaming::Timestamp => return Err(Error::new(Kind::InvalidName, None)),
+    };
+    // validate the name the same way migrations found on the file system are
+    match parse_migration_name(&file_stem)? {
+        (_, _, parsed) if parsed == name => {}
+        _ => return Err(Error::new(Kind::InvalidName, None)),
+    }
+
+    let contents = template.render(version, name)?;
+    let format = template.format();
+    let path = location.join(format!("{}.{}", file_stem, format.extension()));
+    if let Some((_, other)) = existing.filter(|(existing, _)| *existing != naming) {
+        return Err(Error::new(Kind::MixedNaming(other, path), None));
+    }
+    fs::create_dir_all(location).map_err(|err| {
+        Error::new(
+            Kind::InvalidMigrationPath(location.to_path_buf(), err),
+            None,
+        )
+    })?;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|err| Error::new(Kind::InvalidMigrationFile(path.clone(), err), None))?;
+
+    Ok(vec![path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        new_migration, new_migration_from_template, next_version, MigrationFormat,
+        MigrationTemplate,
+    };
+    use crate::error::Kind;
+    use crate::runner::Type;
+    use crate::util::{load_sql_migrations, parse_migration_naming, MigrationNaming};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn creates_first_migration_on_missing_location() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+
+        let paths = new_migration(
+            &migrations_dir,
+            "initial",
+            Type::Versioned,
+            MigrationFormat::Sql,
+        )
+        .unwrap();
+        assert_eq!(vec![migrations_dir.join("V1__initial.sql")], paths);
+        assert!(paths[0].is_file());
+    }
+
+    #[test]
+    fn creates_migration_after_highest_version() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(migrations_dir.join("nested")).unwrap();
+        fs::File::create(migrations_dir.join("V1__first.sql")).unwrap();
+        fs::File::create(migrations_dir.join("nested/U7__second.rs")).unwrap();
+        assert_eq!(8, next_version(&migrations_dir).unwrap());
+
+        let paths = new_migration(
+            &migrations_dir,
+            "add_cars_table",
+            Type::Unversioned,
+            MigrationFormat::Rust,
+        )
+        .unwrap();
+        assert_eq!(vec![migrations_dir.join("U8__add_cars_table.rs")], paths);
+        assert!(fs::read_to_string(&paths[0])
+            .unwrap()
+            .contains("pub fn migration() -> String"));
+    }
+
+    #[test]
+    fn created_migration_is_loaded() {
+        let tmp_dir = TempDir::new().unwrap();
+        new_migration(
+            tmp_dir.path(),
+            "first",
+            Type::Versioned,
+            MigrationFormat::Sql,
+        )
+        .unwrap();
+        new_migration(
+            tmp_dir.path(),
+            "second",
+            Type::Versioned,
+            MigrationFormat::Sql,
+        )
+        .unwrap();
+
+        let migrations = load_sql_migrations(tmp_dir.path()).unwrap();
+        assert_eq!("V1__first", migrations[0].to_string());
+        assert_eq!("V2__second", migrations[1].to_string());
+    }
+
+    #[test]
+    fn fails_on_invalid_name() {
+        let tmp_dir = TempDir::new().unwrap();
+        let err = new_migration(
+            tmp_dir.path(),
+            "add cars",
+            Type::Versioned,
+            MigrationFormat::Sql,
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidName));
+        assert!(fs::read_dir(tmp_dir.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn creates_migration_from_builtin_templates() {
+        let tmp_dir = TempDir::new().unwrap();
+        let paths = new_migration_from_template(
+            tmp_dir.path(),
+            "add_cars_table",
+            Type::Versioned,
+            &"up-down".parse().unwrap(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(vec![tmp_dir.path().join("V1__add_cars_table.sql")], paths);
+
+        let migrations = load_sql_migrations(tmp_dir.path()).unwrap();
+        assert_eq!(Some(""), migrations[0].down_sql());
+    }
+
+    #[test]
+    fn creates_migration_from_template_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        let template = tmp_dir.path().join("template.rs");
+        fs::write(
+            &template,
+            "// V{{version}} {{name}}, created on {{date}}\npub fn migration() -> String {\n    String::new()\n}\n",
+        )
+        .unwrap();
+
+        let paths = new_migration_from_template(
+            &migrations_dir,
+            "add_cars_table",
+            Type::Versioned,
+            &MigrationTemplate::File(template),
+            None,
+        )
+        .unwrap();
+        assert_eq!(vec![migrations_dir.join("V1__add_cars_table.rs")], paths);
+        let contents = fs::read_to_string(&paths[0]).unwrap();
+        assert!(contents.starts_with("// V1 add_cars_table, created on 20"));
+
+        let err = new_migration_from_template(
+            &migrations_dir,
+            "add_motos_table",
+            Type::Versioned,
+            &MigrationTemplate::File(tmp_dir.path().join("missing.sql")),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationFile(..)));
+    }
+
+    #[test]
+    fn creates_timestamp_migrations() {
+        let tmp_dir = TempDir::new().unwrap();
+        let paths = new_migration_from_template(
+            tmp_dir.path(),
+            "initial",
+            Type::Versioned,
+            &MigrationTemplate::Plain,
+            Some(MigrationNaming::Timestamp),
+        )
+        .unwrap();
+        let file_name = paths[0].file_name().unwrap().to_str().unwrap();
+        assert_eq!(
+            Some(MigrationNaming::Timestamp),
+            parse_migration_naming(file_name)
+        );
+
+        // the scheme of the migrations on the location is kept, and a later one can't be mixed in
+        let paths = new_migration(
+            tmp_dir.path(),
+            "add_cars_table",
+            Type::Versioned,
+            MigrationFormat::Sql,
+        )
+        .unwrap();
+        let file_name = paths[0].file_name().unwrap().to_str().unwrap();
+        assert!(file_name.ends_with("_add_cars_table.sql"));
+        let migrations = load_sql_migrations(tmp_dir.path()).unwrap();
+        assert_eq!(2, migrations.len());
+        assert!(migrations[0].version() < migrations[1].version());
+
+        let err = new_migration_from_template(
+            tmp_dir.path(),
+            "add_motos_table",
+            Type::Versioned,
+            &MigrationTemplate::Plain,
+            Some(MigrationNaming::Versioned),
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind(), Kind::MixedNaming(..)));
+    }
+}
@@ -0,0 +1,174 @@
+//! Helpers to write Rust migrations with [`sea-query`](https://crates.io/crates/sea-query) statements.
+//!
+//! Rust migrations whose `migration` function takes a [`Dialect`] are generated when run,
+//! for the database dialect of the connection, so the same statements can be run on every supported driver:
+//!
+//! ```rust,ignore
+//! use refinery::sea_query::{ColumnDef, Iden, MigrationBuilder, Table};
+//! use refinery::{Dialect, Error};
+//!
+//! #[derive(Iden)]
+//! enum Persons {
+//!     Table,
+//!     Id,
+//!     Name,
+//! }
+//!
+//! pub fn migration(dialect: Dialect) -> Result<String, Error> {
+//!     MigrationBuilder::new(dialect)
+//!         .schema(
+//!             Table::create()
+//!                 .table(Persons::Table)
+//!                 .col(ColumnDef::new(Persons::Id).integer().primary_key())
+//!                 .col(ColumnDef::new(Persons::Name).string_len(255)),
+//!         )
+//!         .build()
+//! }
+//! ```
+pub use ::sea_query::*;
+
+use crate::error::Kind;
+use crate::{Dialect, Error};
+
+/// Collects the sea-query statements of a Rust migration and builds them for the [`Dialect`] it is run on.
+///
+/// Statements for [`Dialect::Cockroach`] and [`Dialect::DuckDb`] are built with the Postgres backend.
+/// sea-query doesn't have Mssql, ClickHouse and Oracle backends, [`MigrationBuilder::build`] fails with
+/// [`Kind::UnsupportedDialect`] if sea-query statements were added for [`Dialect::Mssql`], [`Dialect::ClickHouse`] or
+/// [`Dialect::Oracle`], failing the run before the migration is applied.
+#[derive(Debug)]
+pub struct MigrationBuilder {
+    dialect: Dialect,
+    statements: Vec<String>,
+    unsupported: bool,
+}
+
+impl MigrationBuilder {
+    /// Create a new builder for the Dialect passed to the migration function
+    pub fn new(dialect: Dialect) -> MigrationBuilder {
+        MigrationBuilder {
+            dialect,
+            statements: Vec::new(),
+            unsupported: false,
+        }
+    }
+
+    /// Get the Dialect the statements are built for
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Add a schema statement i.e. `Table::create()`, `Index::create()` or `Table::alter()`
+    pub fn schema<S: SchemaStatementBuilder>(&mut self, statement: &S) -> &mut MigrationBuilder {
+        let sql = match self.dialect {
+            Dialect::Postgres | Dialect::Cockroach | Dialect::DuckDb => {
+                statement.to_string(PostgresQueryBuilder)
+            }
+            Dialect::Mysql => statement.to_string(MysqlQueryBuilder),
+            Dialect::Sqlite => statement.to_string(SqliteQueryBuilder),
+            Dialect::Mssql | Dialect::ClickHouse | Dialect::Oracle => {
+                self.unsupported = true;
+                return self;
+            }
+        };
+        self.statements.push(sql);
+        self
+    }
+
+    /// Add a query statement i.e. `Query::insert()` or `Query::update()`, for data migrations
+    pub fn query<Q: QueryStatementWriter>(&mut self, statement: &Q) -> &mut MigrationBuilder {
+        let sql = match self.dialect {
+            Dialect::Postgres | Dialect::Cockroach | Dialect::DuckDb => {
+                statement.to_string(PostgresQueryBuilder)
+            }
+            Dialect::Mysql => statement.to_string(MysqlQueryBuilder),
+            Dialect::Sqlite => statement.to_string(SqliteQueryBuilder),
+            Dialect::Mssql | Dialect::ClickHouse | Dialect::Oracle => {
+                self.unsupported = true;
+                return self;
+            }
+        };
+        self.statements.push(sql);
+        self
+    }
+
+    /// Add a raw sql statement, for what sea-query can't express
+    pub fn raw(&mut self, sql: &str) -> &mut MigrationBuilder {
+        self.statements.push(sql.to_string());
+        self
+    }
+
+    /// Build the migration sql, with each statement terminated by `;`
+    ///
+    /// Fails with [`Kind::UnsupportedDialect`] if sea-query statements were added for a Dialect it has no backend for
+    pub fn build(&self) -> Result<String, Error> {
+        if self.unsupported {
+            return Err(Error::new(Kind::UnsupportedDialect(self.dialect), None));
+        }
+        Ok(self
+            .statements
+            .iter()
+            .map(|statement| format!("{};\n", statement.trim_end_matches(';')))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnDef, Iden, MigrationBuilder, Query, Table};
+    use crate::error::Kind;
+    use crate::{Dialect, Error};
+
+    #[derive(Iden)]
+    enum Persons {
+        Table,
+        Id,
+        Name,
+    }
+
+    fn migration(dialect: Dialect) -> Result<String, Error> {
+        MigrationBuilder::new(dialect)
+            .schema(
+                Table::create()
+                    .table(Persons::Table)
+                    .col(ColumnDef::new(Persons::Id).integer().primary_key())
+                    .col(ColumnDef::new(Persons::Name).string_len(255)),
+            )
+            .query(
+                Query::insert()
+                    .into_table(Persons::Table)
+                    .columns([Persons::Id, Persons::Name])
+                    .values_panic([1.into(), "refinery".into()]),
+            )
+            .build()
+    }
+
+    #[test]
+    fn builds_statements_for_dialect() {
+        assert_eq!(
+            "CREATE TABLE \"persons\" ( \"id\" integer PRIMARY KEY, \"name\" varchar(255) );\n\
+             INSERT INTO \"persons\" (\"id\", \"name\") VALUES (1, 'refinery');\n",
+            migration(Dialect::Postgres).unwrap()
+        );
+        assert_eq!(
+            "CREATE TABLE `persons` ( `id` int PRIMARY KEY, `name` varchar(255) );\n\
+             INSERT INTO `persons` (`id`, `name`) VALUES (1, 'refinery');\n",
+            migration(Dialect::Mysql).unwrap()
+        );
+    }
+
+    #[test]
+    fn fails_building_for_unsupported_dialects() {
+        for dialect in [Dialect::Mssql, Dialect::ClickHouse, Dialect::Oracle] {
+            let err = migration(dialect).unwrap_err();
+            assert!(matches!(err.kind(), Kind::UnsupportedDialect(d) if *d == dialect));
+        }
+        assert_eq!(
+            "SELECT 1;\n",
+            MigrationBuilder::new(Dialect::Mssql)
+                .raw("SELECT 1")
+                .build()
+                .unwrap()
+        );
+    }
+}
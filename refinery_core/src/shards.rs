@@ -0,0 +1,151 @@
+use crate::{AsyncMigrate, Error, Migrate, Report, Runner};
+use futures::stream::{self, StreamExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// What a [`ShardRunner`] does when migrating one of the shards fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardFailurePolicy {
+    /// Don't start migrating the remaining shards, the ones already being migrated are still finished
+    #[default]
+    Stop,
+    /// Keep migrating the remaining shards
+    Continue,
+}
+
+/// The outcome of each shard a [`ShardRunner`] started migrating, as the index of the shard and its Report or Error,
+/// in the order of the shards
+pub type ShardResults = Vec<(usize, Result<Report, Error>)>;
+
+/// Applies the migrations of a [`Runner`] to each of a list of databases holding the same schema, i.e. the shards or
+/// partitions of a database, each keeping its own schema history.
+/// The shards are migrated one at a time by default, or up to the given concurrency at once, and the outcome of each
+/// of them is collected. When a shard fails the remaining ones are left unmigrated or not according to the
+/// [`ShardFailurePolicy`].
+pub struct ShardRunner {
+    runner: Runner,
+    concurrency: usize,
+    failure_policy: ShardFailurePolicy,
+}
+
+impl ShardRunner {
+    /// instantiate a new ShardRunner of the migrations of the given Runner, run with its settings on every shard
+    pub fn new(runner: Runner) -> ShardRunner {
+        ShardRunner {
+            runner,
+            concurrency: 1,
+            failure_policy: ShardFailurePolicy::Stop,
+        }
+    }
+
+    /// Set how many shards are migrated at once, on a thread each with [`ShardRunner::run`].
+    /// by default shards are migrated one at a time, a concurrency of 0 is taken as 1
+    pub fn set_concurrency(self, concurrency: usize) -> ShardRunner {
+        ShardRunner {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// Set what happens to the remaining shards when migrating one of them fails.
+    /// by default migrating them is stopped
+    pub fn set_failure_policy(self, failure_policy: ShardFailurePolicy) -> ShardRunner {
+        ShardRunner {
+            failure_policy,
+            ..self
+        }
+    }
+
+    /// Get the Runner the migrations are applied with
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    /// Get how many shards are migrated at once
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Get what happens to the remaining shards when migrating one of them fails
+    pub fn failure_policy(&self) -> ShardFailurePolicy {
+        self.failure_policy
+    }
+
+    /// Applies the migrations to each shard, returning the outcome of each one that was migrated.
+    /// shards not started because of a previous failure have no outcome
+    pub fn run<C>(&self, shards: &mut [C]) -> ShardResults
+    where
+        C: Migrate + Send,
+    {
+        let pending = Mutex::new(shards.iter_mut().enumerate());
+        let results = Mutex::new(Vec::new());
+        let stopped = AtomicBool::new(false);
+        let workers = self.concurrency.min(pending.lock().unwrap().len());
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Some((index, shard)) = pending.lock().unwrap().next() else {
+                        break;
+                    };
+                    log::info!("migrating shard {}", index);
+                    let result = self.runner.run(shard);
+                    self.record(index, result, &results, &stopped);
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results
+    }
+
+    /// Applies the migrations asynchronously to each shard, returning the outcome of each one that was migrated.
+    /// shards not started because of a previous failure have no outcome
+    pub async fn run_async<C>(&self, shards: &mut [C]) -> ShardResults
+    where
+        C: AsyncMigrate + Send,
+    {
+        let results = Mutex::new(Vec::new());
+        let stopped = AtomicBool::new(false);
+        let pending = shards
+            .iter_mut()
+            .enumerate()
+            .take_while(|_| !stopped.load(Ordering::SeqCst));
+
+        let mut migrated = stream::iter(pending)
+            .map(|(index, shard)| async move {
+                log::info!("migrating shard {}", index);
+                (index, self.runner.run_async(shard).await)
+            })
+            .buffer_unordered(self.concurrency);
+        while let Some((index, result)) = migrated.next().await {
+            self.record(index, result, &results, &stopped);
+        }
+        drop(migrated);
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results
+    }
+
+    fn record(
+        &self,
+        index: usize,
+        result: Result<Report, Error>,
+        results: &Mutex<ShardResults>,
+        stopped: &AtomicBool,
+    ) {
+        if let Err(err) = &result {
+            log::error!("migrating shard {} failed: {}", index, err);
+            if self.failure_policy == ShardFailurePolicy::Stop {
+                stopped.store(true, Ordering::SeqCst);
+            }
+        }
+        results.lock().unwrap().push((index, result));
+    }
+}
@@ -0,0 +1,410 @@
+/// How the sql of a migration is split into the statements executed one at a time, on the same transaction,
+/// for drivers that can't execute several statements at once, see [`Runner::set_splitter`].
+///
+/// [`Runner::set_splitter`]: crate::Runner::set_splitter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Splitter {
+    /// Executes the sql as it's written, in a single batch
+    None,
+    /// Splits the sql on `;`, ignoring the ones in strings, quoted identifiers, comments, Postgres dollar quoted
+    /// strings, parentheses and the `BEGIN ... END` bodies of triggers, functions and procedures.
+    /// Backslashes only escape quotes in Postgres `E'...'` strings.
+    /// The default of every driver but the mysql, tiberius and oracle ones
+    Semicolon,
+    /// Splits the sql on `;` as [`Splitter::Semicolon`], with backslashes escaping quotes in every string as they do
+    /// on MySQL. The default of the mysql drivers
+    Mysql,
+    /// Splits the sql on the lines consisting only of the given separator, case insensitively, i.e. `GO`,
+    /// the default of tiberius
+    Separator(String),
+    /// Splits the sql on `;` as [`Splitter::Semicolon`], except the PL/SQL blocks, anonymous ones and the bodies of
+    /// functions, procedures, packages, triggers and types, that end on a line consisting only of `/`.
+    /// The default of the oracle driver
+    PlSql,
+}
+
+impl Splitter {
+    /// Splits the given sql into statements, leaving out the ones with only whitespace and comments
+    pub fn split<'a>(&self, sql: &'a str) -> Vec<&'a str> {
+        let statements = match self {
+            Splitter::None => return vec![sql],
+            Splitter::Semicolon => split_semicolons(sql, false),
+            Splitter::Mysql => split_semicolons(sql, true),
+            Splitter::Separator(separator) => split_separator(sql, separator),
+            Splitter::PlSql => split_plsql(sql),
+        };
+        statements
+            .into_iter()
+            .map(str::trim)
+            .filter(|statement| !is_blank(statement))
+            .collect()
+    }
+}
+
+fn is_ident(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// the end of the word starting at `start`
+fn word_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|byte| !is_ident(*byte))
+        .map_or(bytes.len(), |len| start + len)
+}
+
+// the index after the closing `quote` of a quoted string or identifier starting at `start`,
+// quotes are escaped doubling them, and with a backslash if `backslash_escapes`
+fn skip_quoted(bytes: &[u8], start: usize, backslash_escapes: bool) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if backslash_escapes => i += 2,
+            byte if byte == quote => {
+                if bytes.get(i + 1) == Some(&quote) {
+                    i += 2;
+                } else {
+                    return i + 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// the index after the closing tag of a Postgres dollar quoted string starting at `start`, i.e. `$body$ ... $body$`,
+// None if it's not one, i.e. a `$1` parameter
+fn skip_dollar_quoted(sql: &str, start: usize) -> Option<usize> {
+    let bytes = sql.as_bytes();
+    let tag_end = word_end(bytes, start + 1);
+    if bytes.get(tag_end) != Some(&b'$') || bytes.get(start + 1).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    let tag = &sql[start..=tag_end];
+    Some(
+        sql[tag_end + 1..]
+            .find(tag)
+            .map_or(sql.len(), |end| tag_end + 1 + end + tag.len()),
+    )
+}
+
+// whether the quote at `start` opens a Postgres escape string, i.e. `E'it\'s'`
+fn is_escape_string(bytes: &[u8], start: usize) -> bool {
+    bytes[start] == b'\''
+        && start > 0
+        && bytes[start - 1].eq_ignore_ascii_case(&b'e')
+        && (start == 1 || !is_ident(bytes[start - 2]))
+}
+
+fn split_semicolons(sql: &str, backslash_escapes: bool) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    while start < sql.len() {
+        let end = semicolon_end(sql, start, backslash_escapes);
+        statements.push(&sql[start..end]);
+        start = end;
+    }
+    statements
+}
+
+// the index after the `;` ending the statement starting at `start`, or the end of the sql,
+// backslashes escape quotes in every string if `backslash_escapes`, otherwise only in Postgres escape strings
+fn semicolon_end(sql: &str, start: usize, backslash_escapes: bool) -> usize {
+    let bytes = sql.as_bytes();
+    let mut parentheses = 0usize;
+    // the depth of the `BEGIN ... END` blocks of the body of a trigger, function or procedure
+    let mut blocks = 0usize;
+    let mut routine = false;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' | b'"' => {
+                i = skip_quoted(bytes, i, backslash_escapes || is_escape_string(bytes, i))
+            }
+            b'`' => i = skip_quoted(bytes, i, false),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(sql.len(), |end| i + end + 1)
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..]
+                    .find("*/")
+                    .map_or(sql.len(), |end| i + 2 + end + 2)
+            }
+            b'$' if i == 0 || !is_ident(bytes[i - 1]) => {
+                i = skip_dollar_quoted(sql, i).unwrap_or(i + 1)
+            }
+            b'(' => {
+                parentheses += 1;
+                i += 1;
+            }
+            b')' => {
+                parentheses = parentheses.saturating_sub(1);
+                i += 1;
+            }
+            b';' if parentheses == 0 && blocks == 0 => return i + 1,
+            byte if is_ident(byte) && (i == 0 || !is_ident(bytes[i - 1])) => {
+                let end = word_end(bytes, i);
+                let word = &sql[i..end];
+                if ["TRIGGER", "FUNCTION", "PROCEDURE"]
+                    .iter()
+                    .any(|kind| word.eq_ignore_ascii_case(kind))
+                {
+                    let statement = sql[start..i].trim_start();
+                    routine |= statement
+                        .get(..6)
+                        .is_some_and(|create| create.eq_ignore_ascii_case("CREATE"));
+                } else if (routine && word.eq_ignore_ascii_case("BEGIN"))
+                    || (blocks > 0 && word.eq_ignore_ascii_case("CASE"))
+                {
+                    blocks += 1;
+                } else if blocks > 0 && word.eq_ignore_ascii_case("END") {
+                    // `END IF`, `END LOOP`, `END WHILE` and `END REPEAT` close statements that weren't counted,
+                    // `END CASE` closes a counted one, the word after `END` is skipped so it doesn't open another
+                    let next_start = sql.len() - sql[end..].trim_start().len();
+                    let next_end = word_end(bytes, next_start);
+                    let next = &sql[next_start..next_end];
+                    if ["IF", "LOOP", "WHILE", "REPEAT"]
+                        .iter()
+                        .any(|kind| next.eq_ignore_ascii_case(kind))
+                    {
+                        i = next_end;
+                        continue;
+                    }
+                    blocks -= 1;
+                    if next.eq_ignore_ascii_case("CASE") {
+                        i = next_end;
+                        continue;
+                    }
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    sql.len()
+}
+
+fn split_plsql(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    while start < sql.len() {
+        if !is_plsql(&sql[start..]) {
+            let end = semicolon_end(sql, start, false);
+            statements.push(&sql[start..end]);
+            start = end;
+            continue;
+        }
+        // the block ends on the next `/` line, which isn't part of it
+        let mut offset = start;
+        let mut end = None;
+        for line in sql[start..].split_inclusive('\n') {
+            if line.trim() == "/" {
+                end = Some((offset, offset + line.len()));
+                break;
+            }
+            offset += line.len();
+        }
+        let (end, next) = end.unwrap_or((sql.len(), sql.len()));
+        statements.push(&sql[start..end]);
+        start = next;
+    }
+    statements
+}
+
+// the statement without its leading whitespace and comments
+fn skip_comments(statement: &str) -> &str {
+    let mut rest = statement.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return rest;
+        }
+        rest = rest.trim_start();
+    }
+}
+
+// whether the statement is a PL/SQL block, an anonymous one or the body of a routine, package, trigger or type,
+// which is executed with its trailing `;` unlike sql statements
+pub(crate) fn is_plsql(statement: &str) -> bool {
+    let mut words = skip_comments(statement)
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty());
+    match words.next() {
+        Some(word)
+            if word.eq_ignore_ascii_case("DECLARE") || word.eq_ignore_ascii_case("BEGIN") =>
+        {
+            true
+        }
+        Some(word) if word.eq_ignore_ascii_case("CREATE") => words
+            .find(|word| {
+                !["OR", "REPLACE", "EDITIONABLE", "NONEDITIONABLE"]
+                    .iter()
+                    .any(|modifier| word.eq_ignore_ascii_case(modifier))
+            })
+            .is_some_and(|kind| {
+                ["FUNCTION", "PROCEDURE", "PACKAGE", "TRIGGER", "TYPE"]
+                    .iter()
+                    .any(|plsql| kind.eq_ignore_ascii_case(plsql))
+            }),
+        _ => false,
+    }
+}
+
+fn split_separator<'a>(sql: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in sql.split_inclusive('\n') {
+        if line.trim().eq_ignore_ascii_case(separator) {
+            statements.push(&sql[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    statements.push(&sql[start..]);
+    statements
+}
+
+// whether the statement only has comments, which some databases fail to execute
+fn is_blank(statement: &str) -> bool {
+    skip_comments(statement)
+        .trim_start_matches(';')
+        .trim()
+        .is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Splitter;
+
+    #[test]
+    fn splits_on_semicolons() {
+        let sql = "-- author: alice
+CREATE TABLE users(id int, name varchar(255) DEFAULT 'a;b');
+INSERT INTO users VALUES (1, 'it''s; fine'), (2, E'it\\'s; fine');
+/* a; comment */ CREATE INDEX \"users;name\" ON users(name); -- the end;
+";
+        assert_eq!(
+            vec![
+                "-- author: alice\nCREATE TABLE users(id int, name varchar(255) DEFAULT 'a;b');",
+                "INSERT INTO users VALUES (1, 'it''s; fine'), (2, E'it\\'s; fine');",
+                "/* a; comment */ CREATE INDEX \"users;name\" ON users(name);",
+            ],
+            Splitter::Semicolon.split(sql)
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_with_backslashes_only_on_mysql() {
+        // a backslash is a plain character in standard strings
+        let sql = "INSERT INTO paths VALUES ('C:\\');\nINSERT INTO paths VALUES ('D:\\');";
+        assert_eq!(
+            vec![
+                "INSERT INTO paths VALUES ('C:\\');",
+                "INSERT INTO paths VALUES ('D:\\');",
+            ],
+            Splitter::Semicolon.split(sql)
+        );
+
+        let sql = "INSERT INTO users VALUES ('it\\'s; fine', \"a \\\" b;\");\nSELECT 1;";
+        assert_eq!(
+            vec![
+                "INSERT INTO users VALUES ('it\\'s; fine', \"a \\\" b;\");",
+                "SELECT 1;",
+            ],
+            Splitter::Mysql.split(sql)
+        );
+    }
+
+    #[test]
+    fn doesnt_split_routine_bodies() {
+        let sql = "CREATE FUNCTION touch() RETURNS trigger AS $body$
+BEGIN NEW.updated_at = now(); RETURN NEW; END;
+$body$ LANGUAGE plpgsql;
+CREATE TRIGGER users_touch BEFORE UPDATE ON users FOR EACH ROW EXECUTE PROCEDURE touch();
+CREATE TRIGGER cars_touch AFTER UPDATE ON cars BEGIN
+  UPDATE cars SET kind = CASE WHEN NEW.year > 2000 THEN 'new' ELSE 'old' END WHERE id = NEW.id;
+  SELECT 1;
+END;
+CREATE PROCEDURE cleanup() BEGIN
+  IF (SELECT COUNT(*) FROM users) > 0 THEN DELETE FROM users; END IF;
+END;
+CREATE PROCEDURE classify(IN year INT) BEGIN
+  CASE WHEN year > 2000 THEN SELECT 'new'; ELSE SELECT 'old'; END CASE;
+END;
+SELECT $1, x$y FROM t;";
+        let statements = Splitter::Semicolon.split(sql);
+        assert_eq!(6, statements.len(), "{:#?}", statements);
+        assert!(statements[0].ends_with("LANGUAGE plpgsql;"));
+        assert!(statements[1].starts_with("CREATE TRIGGER users_touch"));
+        assert!(statements[2].ends_with("SELECT 1;\nEND;"));
+        assert!(statements[3].ends_with("END IF;\nEND;"));
+        assert!(statements[4].ends_with("END CASE;\nEND;"));
+        assert_eq!("SELECT $1, x$y FROM t;", statements[5]);
+    }
+
+    #[test]
+    fn splits_on_separator_lines() {
+        let sql = "CREATE TABLE users(id int);
+go
+CREATE VIEW active_users AS SELECT id FROM users;
+  GO
+-- nothing after the last separator
+GO
+";
+        assert_eq!(
+            vec![
+                "CREATE TABLE users(id int);",
+                "CREATE VIEW active_users AS SELECT id FROM users;"
+            ],
+            Splitter::Separator("GO".into()).split(sql)
+        );
+    }
+
+    #[test]
+    fn splits_plsql_blocks_on_slash_lines() {
+        let sql = "CREATE TABLE users(id NUMBER, name VARCHAR2(255) DEFAULT 'a;b');
+-- touches the users
+CREATE OR REPLACE PROCEDURE touch_users AS
+  touched NUMBER;
+BEGIN
+  UPDATE users SET name = name;
+END;
+/
+INSERT INTO users VALUES (1, 'it''s; fine');
+BEGIN
+  touch_users;
+END;
+  /
+DECLARE
+  total NUMBER;
+BEGIN
+  SELECT COUNT(*) INTO total FROM users;
+END;";
+        let statements = Splitter::PlSql.split(sql);
+        assert_eq!(
+            vec![
+                "CREATE TABLE users(id NUMBER, name VARCHAR2(255) DEFAULT 'a;b');",
+                "-- touches the users\nCREATE OR REPLACE PROCEDURE touch_users AS\n  touched NUMBER;\nBEGIN\n  UPDATE users SET name = name;\nEND;",
+                "INSERT INTO users VALUES (1, 'it''s; fine');",
+                "BEGIN\n  touch_users;\nEND;",
+                "DECLARE\n  total NUMBER;\nBEGIN\n  SELECT COUNT(*) INTO total FROM users;\nEND;",
+            ],
+            statements
+        );
+        assert!(super::is_plsql(statements[1]));
+        assert!(!super::is_plsql(statements[2]));
+        assert!(!super::is_plsql("CREATE TABLE types(id NUMBER);"));
+    }
+
+    #[test]
+    fn doesnt_split_without_splitter() {
+        let sql = "CREATE TABLE users(id int);\nGO\n";
+        assert_eq!(vec![sql], Splitter::None.split(sql));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::{Kind, WrapMigrationError};
+use crate::traits::r#async::AsyncTransaction;
+use crate::traits::sync::Transaction;
+use crate::{AsyncMigrate, Dialect, Error, Migrate, Report, Runner};
+use std::iter;
+
+fn aborted(tenant: &str, err: Error) -> Error {
+    let report = err.report().cloned();
+    Error::new(Kind::TenantAborted(tenant.to_string(), err), report)
+}
+
+fn use_tenant_statement(dialect: Option<Dialect>, tenant: &str) -> Result<String, Error> {
+    dialect
+        .and_then(|dialect| dialect.use_tenant_statement(tenant))
+        .ok_or_else(|| Error::new(Kind::UnsupportedTenantDialect(dialect), None))
+}
+
+/// Applies the migrations of a [`Runner`] to each of a list of tenants sharing a connection, Postgres and Cockroach
+/// schemas or Mysql databases, one at a time.
+/// The connection is switched to each tenant with `SET search_path` or `USE` before running the migrations, so their
+/// unqualified tables and the schema history table are created on it, and each tenant keeps its own history.
+/// The migration table schema of the Runner must not be set, or the tenants would share it.
+///
+/// The connection must be a single one, not a pool that checks out a connection per statement, and is left switched
+/// to the last tenant. Migrating the tenants is aborted with [`Kind::TenantAborted`] on the first one that fails, the
+/// tenants before it are migrated.
+pub struct MultiTenantRunner {
+    runner: Runner,
+    tenants: Vec<String>,
+}
+
+impl MultiTenantRunner {
+    /// instantiate a new MultiTenantRunner of the migrations of the given Runner, run with its settings on every tenant
+    pub fn new(runner: Runner, tenants: &[&str]) -> MultiTenantRunner {
+        MultiTenantRunner {
+            runner,
+            tenants: tenants.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Get the Runner the migrations are applied with
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    /// Get the tenants the migrations are applied to, in order
+    pub fn tenants(&self) -> &[String] {
+        &self.tenants
+    }
+
+    /// Applies the migrations to each tenant, returning the Report of each of them
+    pub fn run<C>(&self, connection: &mut C) -> Result<Vec<(String, Report)>, Error>
+    where
+        C: Migrate,
+    {
+        let mut reports = Vec::with_capacity(self.tenants.len());
+        for tenant in &self.tenants {
+            log::info!("migrating tenant {}", tenant);
+            let statement = use_tenant_statement(Migrate::dialect(connection), tenant)?;
+            Transaction::execute(connection, iter::once(statement.as_str()))
+                .migration_err(&format!("could not switch to tenant {}", tenant), None)
+                .map_err(|err| aborted(tenant, err))?;
+            let report = self
+                .runner
+                .run(connection)
+                .map_err(|err| aborted(tenant, err))?;
+            reports.push((tenant.clone(), report));
+        }
+        Ok(reports)
+    }
+
+    /// Applies the migrations asynchronously to each tenant, returning the Report of each of them
+    pub async fn run_async<C>(&self, connection: &mut C) -> Result<Vec<(String, Report)>, Error>
+    where
+        C: AsyncMigrate + Send,
+    {
+        let mut reports = Vec::with_capacity(self.tenants.len());
+        for tenant in &self.tenants {
+            log::info!("migrating tenant {}", tenant);
+            let statement = use_tenant_statement(AsyncMigrate::dialect(connection), tenant)?;
+            AsyncTransaction::execute(connection, iter::once(statement.as_str()))
+                .await
+                .migration_err(&format!("could not switch to tenant {}", tenant), None)
+                .map_err(|err| aborted(tenant, err))?;
+            let report = self
+                .runner
+                .run_async(connection)
+                .await
+                .map_err(|err| aborted(tenant, err))?;
+            reports.push((tenant.clone(), report));
+        }
+        Ok(reports)
+    }
+}
@@ -0,0 +1,50 @@
+// spans and events of the migration runs, emitted with the `tracing` feature for applications using `tracing`
+// subscribers instead of a `log` logger
+
+use std::time::Duration;
+
+use tracing::{error, field, info_span, Span};
+
+use crate::{Migration, ValidationIssue};
+
+// the span a migration is applied in, its duration is recorded once it's applied
+pub(crate) fn migration_span(migration: &Migration) -> Span {
+    info_span!(
+        "migration",
+        version = migration.version(),
+        name = %migration,
+        checksum = migration.checksum(),
+        duration_ms = field::Empty,
+    )
+}
+
+// the span migrations applied grouped in a single transaction are applied in
+pub(crate) fn grouped_span(migrations: &[Migration]) -> Span {
+    info_span!(
+        "migrations",
+        count = migrations.len(),
+        duration_ms = field::Empty
+    )
+}
+
+// records how long applying the migrations of the span took
+pub(crate) fn record_duration(span: &Span, duration: Duration) {
+    span.record("duration_ms", duration.as_millis() as u64);
+}
+
+// an issue found verifying the migrations against the applied ones
+pub(crate) fn verification_issue(issue: &ValidationIssue) {
+    let (kind, migration) = match issue {
+        ValidationIssue::Divergent { migration, .. } => ("divergent", migration),
+        ValidationIssue::Missing(migration) => ("missing", migration),
+        ValidationIssue::OutOfOrder(migration) => ("out_of_order", migration),
+        ValidationIssue::Repeated(migration) => ("repeated", migration),
+    };
+    error!(
+        issue = kind,
+        version = migration.version(),
+        name = %migration,
+        "{}",
+        issue
+    );
+}
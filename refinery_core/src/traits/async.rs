@@ -1,13 +1,24 @@
-use crate::error::WrapMigrationError;
+use crate::error::{Kind, WrapMigrationError};
+use crate::introspect::Schema;
 use crate::traits::{
-    insert_migration_query, verify_migrations, ASSERT_MIGRATIONS_TABLE_QUERY,
-    GET_APPLIED_MIGRATIONS_QUERY, GET_LAST_APPLIED_MIGRATION_QUERY,
+    applied_statements, assert_no_async_migrations, assert_transactional, delete_migration_query,
+    execution_time_query, failed_statement, insert_migration_query, legacy_primary_key_query,
+    migration_statements, probes_missing_columns, serialization_failure_backoff, timeout_error,
+    update_migration_query, upgrade_migrations_table_queries, verify_migrations,
+    DefaultSchemaHistoryDdl, SchemaHistoryDdl, GET_APPLIED_MIGRATIONS_QUERY,
+    GET_LAST_APPLIED_MIGRATION_QUERY, GET_SKIPPED_MIGRATIONS_QUERY, SERIALIZATION_FAILURE_RETRIES,
 };
-use crate::{Error, Migration, Report, Target};
+use crate::{Dialect, Error, IntegrityIssue, Migration, Report, Splitter, Target};
 
 use async_trait::async_trait;
-use std::ops::Deref;
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::iter;
+use std::path::PathBuf;
 use std::string::ToString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
 
 #[async_trait]
 pub trait AsyncTransaction {
@@ -17,6 +28,147 @@ pub trait AsyncTransaction {
         &mut self,
         queries: T,
     ) -> Result<usize, Self::Error>;
+
+    /// The [`Splitter`] the sql of the migrations is split with when none is set with [`Runner::set_splitter`]
+    ///
+    /// [`Runner::set_splitter`]: crate::Runner::set_splitter
+    fn splitter(&self) -> Splitter {
+        Splitter::Semicolon
+    }
+
+    /// The [`AsyncExecutor`] async Rust migrations are run on, see [`Migration::unapplied_async`].
+    /// Connections that don't provide it can't run them.
+    fn executor(&mut self) -> Option<&mut dyn AsyncExecutor> {
+        None
+    }
+
+    /// Executes the queries one at a time outside of a transaction, for the migrations marked with the
+    /// `-- refinery:no-transaction` directive, see [`Migration::is_transactional`].
+    /// Connections that can't run queries outside of a transaction execute each on its own one.
+    async fn execute_without_transaction<'a, T: Iterator<Item = &'a str> + Send>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            count += self.execute(iter::once(query)).await?;
+        }
+        Ok(count)
+    }
+
+    /// Whether the error is a serialization failure, SQLSTATE `40001`, that rolled back the transaction and is
+    /// resolved by retrying it, as CockroachDB reports under contention. Migrations failing with it are retried.
+    fn is_serialization_failure(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+
+    /// Whether the error is the database cancelling a statement for exceeding the timeout set by the migration,
+    /// see [`Runner::set_timeout`]. Migrations failing with it fail with [`Kind::Timeout`].
+    ///
+    /// [`Runner::set_timeout`]: crate::Runner::set_timeout
+    fn is_timeout(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+}
+
+// executes the statements of a migration and the query recording it on the schema history table last, on a single
+// transaction unless the migration is marked with `-- refinery:no-transaction`, counting the executed ones
+async fn execute_updates<T: AsyncTransaction + Send>(
+    transaction: &mut T,
+    migration: &Migration,
+    statements: &[&str],
+    record: &str,
+    executed: &AtomicUsize,
+) -> Result<usize, T::Error> {
+    executed.store(0, Ordering::Relaxed);
+    let count = |_: &&str| {
+        executed.fetch_add(1, Ordering::Relaxed);
+    };
+    let updates = statements.iter().copied().chain(iter::once(record));
+    let result = if migration.is_transactional() {
+        transaction.execute(updates.inspect(count)).await
+    } else {
+        match transaction
+            .execute_without_transaction(statements.iter().copied().inspect(count))
+            .await
+        {
+            Ok(executed) => transaction
+                .execute(iter::once(record).inspect(count))
+                .await
+                .map(|recorded| executed + recorded),
+            Err(err) => Err(err),
+        }
+    };
+    reset_timeouts(transaction, std::slice::from_ref(migration)).await;
+    result
+}
+
+// resets the timeouts the migrations set on the session of the connection whether they were applied or not, as they
+// outlive them on pooled connections, see `Migration::reset_timeout`
+async fn reset_timeouts<T: AsyncTransaction>(transaction: &mut T, migrations: &[Migration]) {
+    let resets: BTreeSet<&str> = migrations
+        .iter()
+        .filter_map(Migration::reset_timeout)
+        .collect();
+    for reset in resets {
+        if let Err(err) = transaction.execute(iter::once(reset)).await {
+            log::warn!("error resetting the timeout of the migrations: {}", err);
+        }
+    }
+}
+
+/// The connection an async Rust migration is run on, see [`Migration::unapplied_async`].
+/// Besides executing sql it can be downcast to the connection of the driver, i.e. a `tokio_postgres::Client`,
+/// to query and transform data with it:
+///
+/// ```rust,ignore
+/// pub async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), Error> {
+///     let client = executor.downcast_mut::<tokio_postgres::Client>().unwrap();
+///     for row in client.query("SELECT id, name FROM users", &[]).await.unwrap() {
+///         // ...
+///     }
+///     executor.execute("ALTER TABLE users DROP COLUMN name").await
+/// }
+/// ```
+#[async_trait]
+pub trait AsyncExecutor: Send {
+    /// Executes the given sql on its own transaction
+    async fn execute(&mut self, sql: &str) -> Result<(), Error>;
+
+    /// The connection as [`Any`], see [`downcast_mut`](#method.downcast_mut)
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn AsyncExecutor + '_ {
+    /// Get the connection of the driver the migration is run on, `None` if it's of another type
+    pub fn downcast_mut<C: 'static>(&mut self) -> Option<&mut C> {
+        self.as_any_mut().downcast_mut()
+    }
+}
+
+#[async_trait]
+impl<T> AsyncExecutor for T
+where
+    T: AsyncTransaction + Send + 'static,
+{
+    async fn execute(&mut self, sql: &str) -> Result<(), Error> {
+        AsyncTransaction::execute(self, iter::once(sql))
+            .await
+            .migration_err(&format!("error executing {}", sql), None)?;
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 #[async_trait]
@@ -24,15 +176,65 @@ pub trait AsyncQuery<T>: AsyncTransaction {
     async fn query(&mut self, query: &str) -> Result<T, Self::Error>;
 }
 
-async fn migrate<T: AsyncTransaction>(
+// wait before retrying a migration, without the tokio runtime of a driver to wait on it's retried right away
+async fn backoff(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(not(feature = "tokio"))]
+    let _ = duration;
+}
+
+// await a future up to the given duration, returning None if it's exceeded and the future is dropped.
+// without the tokio runtime of a driver to time it the future always runs to completion
+pub(crate) async fn within<F: std::future::Future>(
+    duration: Duration,
+    future: F,
+) -> Option<F::Output> {
+    #[cfg(feature = "tokio")]
+    return tokio::time::timeout(duration, future).await.ok();
+    #[cfg(not(feature = "tokio"))]
+    {
+        let _ = duration;
+        Some(future.await)
+    }
+}
+
+// runs the function of an async Rust migration on the executor of the connection, before its insert
+// on the schema history table, other migrations are applied with it
+async fn migrate_async<T: AsyncTransaction>(
+    transaction: &mut T,
+    migration: &Migration,
+) -> Result<(), Error> {
+    let migrate_async = match migration.migrate_async() {
+        Some(migrate_async) if !migration.is_skipped() => migrate_async,
+        _ => return Ok(()),
+    };
+    let executor = transaction
+        .executor()
+        .ok_or_else(|| Error::new(Kind::UnsupportedAsyncMigration(migration.clone()), None))?;
+    migrate_async(executor).await
+}
+
+async fn migrate<T: AsyncTransaction + Send>(
     transaction: &mut T,
     migrations: Vec<Migration>,
     target: Target,
     migration_table_name: &str,
+    dialect: Option<Dialect>,
 ) -> Result<Report, Error> {
     let mut applied_migrations = vec![];
+    let splitter = transaction.splitter();
+    #[cfg(feature = "metrics")]
+    crate::metrics::pending(migrations.len());
 
     for mut migration in migrations.into_iter() {
+        if let Some(steps) = target.steps() {
+            if applied_migrations.len() >= steps as usize {
+                log::info!("stopping after {} migrations, due to user option", steps);
+                break;
+            }
+        }
+
         if let Target::Version(input_target) = target {
             if input_target < migration.version() {
                 log::info!(
@@ -43,37 +245,186 @@ async fn migrate<T: AsyncTransaction>(
             }
         }
 
-        log::info!("applying migration: {}", migration);
         migration.set_applied();
-        let update_query = insert_migration_query(&migration, migration_table_name);
-        transaction
-            .execute(
-                [
-                    migration.sql().as_ref().expect("sql must be Some!"),
-                    update_query.as_str(),
-                ]
-                .into_iter(),
-            )
+        let update_query = insert_migration_query(&migration, dialect, migration_table_name);
+        if migration.is_skipped() {
+            log::info!("skipping migration: {}", migration);
+        } else {
+            match migration.description() {
+                Some(description) => {
+                    log::info!("applying migration: {} - {}", migration, description)
+                }
+                None => log::info!("applying migration: {}", migration),
+            }
+        }
+        let statements = applied_statements(&migration, false, &splitter);
+        #[cfg(feature = "tracing")]
+        let span = crate::tracing::migration_span(&migration);
+        let mut started = Instant::now();
+        let run_async = migrate_async(transaction, &migration);
+        #[cfg(feature = "tracing")]
+        let run_async = tracing::Instrument::instrument(run_async, span.clone());
+        run_async
             .await
-            .migration_err(
-                &format!("error applying migration {}", migration),
-                Some(&applied_migrations),
-            )?;
+            .map_err(|err| err.prepend_applied(&applied_migrations))?;
+        let mut attempt = 0;
+        let mut serialization_failures = 0;
+        // the connections execute the updates one at a time, the statement that failed is the last one executed
+        let executed = AtomicUsize::new(0);
+        while let Err(err) = {
+            let execute = execute_updates(
+                transaction,
+                &migration,
+                &statements,
+                &update_query,
+                &executed,
+            );
+            #[cfg(feature = "tracing")]
+            let execute = tracing::Instrument::instrument(execute, span.clone());
+            execute.await
+        } {
+            if let Some(wait) = serialization_failure_backoff(
+                T::is_serialization_failure(&err),
+                &mut serialization_failures,
+            ) {
+                log::warn!(
+                    "serialization failure applying migration {}, retrying in {:?} ({}/{}): {}",
+                    migration,
+                    wait,
+                    serialization_failures,
+                    SERIALIZATION_FAILURE_RETRIES,
+                    err
+                );
+                backoff(wait).await;
+                started = Instant::now();
+                continue;
+            }
+            if attempt >= migration.retries() {
+                #[cfg(feature = "metrics")]
+                crate::metrics::failed(Some(&migration));
+                let statement = failed_statement(
+                    std::slice::from_ref(&migration),
+                    false,
+                    &splitter,
+                    executed.load(Ordering::Relaxed),
+                );
+                if let Some(timeout) = timeout_error(
+                    T::is_timeout(&err),
+                    std::slice::from_ref(&migration),
+                    statement.as_ref(),
+                    Some(applied_migrations.as_slice()),
+                ) {
+                    return Err(timeout.with_statement(statement));
+                }
+                return Err(err)
+                    .migration_err(
+                        &format!("error applying migration {}", migration),
+                        Some(&applied_migrations),
+                    )
+                    .map_err(|err| err.with_statement(statement));
+            }
+            attempt += 1;
+            log::warn!(
+                "error applying migration {}, retrying in {:?} ({}/{}): {}",
+                migration,
+                migration.backoff(),
+                attempt,
+                migration.retries(),
+                err
+            );
+            backoff(migration.backoff()).await;
+            started = Instant::now();
+        }
+        let execution_time = started.elapsed();
+        #[cfg(feature = "metrics")]
+        crate::metrics::applied(&migration, execution_time);
+        #[cfg(feature = "tracing")]
+        crate::tracing::record_duration(&span, execution_time);
+        migration.set_execution_time(execution_time, OffsetDateTime::now_utc());
+        record_execution_time(
+            transaction,
+            &migration,
+            execution_time,
+            dialect,
+            migration_table_name,
+        )
+        .await;
         applied_migrations.push(migration);
     }
     Ok(Report::new(applied_migrations))
 }
 
+// applies the migrations recording the user of the connection with the given Dialect as the one applying them
+pub(crate) async fn migrate_with_dialect<T: AsyncTransaction + Send>(
+    transaction: &mut T,
+    migrations: Vec<Migration>,
+    target: Target,
+    migration_table_name: &str,
+    grouped: bool,
+    dialect: Option<Dialect>,
+) -> Result<Report, Error> {
+    if grouped || matches!(target, Target::Fake | Target::FakeVersion(_)) {
+        migrate_grouped(
+            transaction,
+            migrations,
+            target,
+            migration_table_name,
+            dialect,
+        )
+        .await
+    } else {
+        migrate(
+            transaction,
+            migrations,
+            target,
+            migration_table_name,
+            dialect,
+        )
+        .await
+    }
+}
+
+// records how long applying the migration took on its own transaction, once the migration is committed, a failure
+// to record it doesn't fail the run as the migration is already applied
+async fn record_execution_time<T: AsyncTransaction>(
+    transaction: &mut T,
+    migration: &Migration,
+    execution_time: Duration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) {
+    let query = execution_time_query(migration, execution_time, dialect, migration_table_name);
+    if let Err(err) = transaction.execute(iter::once(query.as_str())).await {
+        log::warn!(
+            "error recording the execution time of migration {}: {}",
+            migration,
+            err
+        );
+    }
+}
+
 async fn migrate_grouped<T: AsyncTransaction>(
     transaction: &mut T,
     migrations: Vec<Migration>,
     target: Target,
     migration_table_name: &str,
+    dialect: Option<Dialect>,
 ) -> Result<Report, Error> {
-    let mut grouped_migrations = Vec::new();
-    let mut applied_migrations = Vec::new();
+    let fake = matches!(target, Target::Fake | Target::FakeVersion(_));
+    #[cfg(feature = "metrics")]
+    if !fake {
+        crate::metrics::pending(migrations.len());
+    }
+    let mut migrations_to_apply = Vec::new();
+    let mut insert_migrations = Vec::new();
 
     for mut migration in migrations.into_iter() {
+        if let Some(steps) = target.steps() {
+            if migrations_to_apply.len() >= steps as usize {
+                break;
+            }
+        }
+
         if let Target::Version(input_target) | Target::FakeVersion(input_target) = target {
             if input_target < migration.version() {
                 break;
@@ -81,26 +432,22 @@ async fn migrate_grouped<T: AsyncTransaction>(
         }
 
         migration.set_applied();
-        let query = insert_migration_query(&migration, migration_table_name);
-
-        let sql = migration.sql().expect("sql must be Some!").to_string();
-
-        // If Target is Fake, we only update schema migrations table
-        if !matches!(target, Target::Fake | Target::FakeVersion(_)) {
-            applied_migrations.push(migration);
-            grouped_migrations.push(sql);
-        }
-        grouped_migrations.push(query);
+        insert_migrations.push(insert_migration_query(
+            &migration,
+            dialect,
+            migration_table_name,
+        ));
+        migrations_to_apply.push(migration);
     }
 
     match target {
         Target::Fake | Target::FakeVersion(_) => {
             log::info!("not going to apply any migration as fake flag is enabled");
         }
-        Target::Latest | Target::Version(_) => {
+        Target::Latest | Target::Version(_) | Target::StepsForward(_) | Target::Next => {
             log::info!(
                 "going to apply batch migrations in single transaction: {:#?}",
-                applied_migrations.iter().map(ToString::to_string)
+                migrations_to_apply.iter().map(ToString::to_string)
             );
         }
     };
@@ -112,14 +459,235 @@ async fn migrate_grouped<T: AsyncTransaction>(
         );
     }
 
-    let refs = grouped_migrations.iter().map(AsRef::as_ref);
+    if !fake {
+        assert_no_async_migrations(&migrations_to_apply)?;
+        assert_transactional(&migrations_to_apply)?;
+    }
+
+    // If Target is Fake, we only update schema migrations table
+    let splitter = transaction.splitter();
+    // the connections execute the updates one at a time, the statement that failed is the last one executed
+    let executed = AtomicUsize::new(0);
+    let refs = || {
+        migrations_to_apply
+            .iter()
+            .zip(insert_migrations.iter())
+            .flat_map(|(migration, query)| {
+                // the timeout set on the session is reset before the next migrations of the group
+                applied_statements(migration, fake, &splitter)
+                    .into_iter()
+                    .chain(iter::once(query.as_str()))
+                    .chain(migration.reset_timeout().filter(|_| !fake))
+            })
+            .inspect(|_| {
+                executed.fetch_add(1, Ordering::Relaxed);
+            })
+    };
+
+    #[cfg(feature = "tracing")]
+    let (span, started) = (
+        crate::tracing::grouped_span(&migrations_to_apply),
+        Instant::now(),
+    );
+    let mut serialization_failures = 0;
+    while let Err(err) = {
+        let execute = transaction.execute(refs());
+        #[cfg(feature = "tracing")]
+        let execute = tracing::Instrument::instrument(execute, span.clone());
+        execute.await
+    } {
+        let is_timeout = T::is_timeout(&err);
+        let wait = serialization_failure_backoff(
+            T::is_serialization_failure(&err),
+            &mut serialization_failures,
+        );
+        if wait.is_none() && !fake {
+            reset_timeouts(transaction, &migrations_to_apply).await;
+        }
+        let wait = wait
+            .ok_or(err)
+            .migration_err("error applying migrations", None)
+            .map_err(|err| {
+                #[cfg(feature = "metrics")]
+                if !fake {
+                    crate::metrics::failed(None);
+                }
+                let statement = failed_statement(
+                    &migrations_to_apply,
+                    fake,
+                    &splitter,
+                    executed.load(Ordering::Relaxed),
+                );
+                timeout_error(is_timeout, &migrations_to_apply, statement.as_ref(), None)
+                    .unwrap_or(err)
+                    .with_statement(statement)
+            })?;
+        executed.store(0, Ordering::Relaxed);
+        log::warn!(
+            "serialization failure applying migrations, retrying in {:?} ({}/{})",
+            wait,
+            serialization_failures,
+            SERIALIZATION_FAILURE_RETRIES
+        );
+        backoff(wait).await;
+    }
+
+    if fake {
+        migrations_to_apply.clear();
+    }
+    #[cfg(feature = "tracing")]
+    crate::tracing::record_duration(&span, started.elapsed());
+    #[cfg(feature = "metrics")]
+    crate::metrics::applied_grouped(migrations_to_apply.len());
+
+    Ok(Report::new(migrations_to_apply))
+}
+
+// re-executes an applied migration and updates its name, checksum, applied_on, applied_by and execution time
+// on the schema history table
+pub(crate) async fn rerun<T: AsyncTransaction + Send>(
+    transaction: &mut T,
+    mut migration: Migration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Report, Error> {
+    log::warn!("force re-running applied migration: {}", migration);
+    migration.set_applied();
+    let update_migration = update_migration_query(&migration, dialect, migration_table_name);
+    let splitter = transaction.splitter();
+    let statements = applied_statements(&migration, false, &splitter);
+    let started = Instant::now();
+    migrate_async(transaction, &migration).await?;
+    let executed = AtomicUsize::new(0);
+    execute_updates(
+        transaction,
+        &migration,
+        &statements,
+        &update_migration,
+        &executed,
+    )
+    .await
+    .migration_err(&format!("error re-running migration {}", migration), None)
+    .map_err(|err| {
+        err.with_statement(failed_statement(
+            std::slice::from_ref(&migration),
+            false,
+            &splitter,
+            executed.load(Ordering::Relaxed),
+        ))
+    })?;
+    let execution_time = started.elapsed();
+    migration.set_execution_time(execution_time, OffsetDateTime::now_utc());
+    record_execution_time(
+        transaction,
+        &migration,
+        execution_time,
+        dialect,
+        migration_table_name,
+    )
+    .await;
+
+    Ok(Report::new(vec![migration]))
+}
 
-    transaction
-        .execute(refs)
+// executes the down section of the migrations in the given order and deletes them from the schema history table,
+// each migration on its own transaction unless they are grouped
+async fn rollback<T: AsyncTransaction + Send>(
+    transaction: &mut T,
+    migrations: &[Migration],
+    grouped: bool,
+    fake: bool,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Report, Error> {
+    let delete_migrations: Vec<String> = migrations
+        .iter()
+        .map(|migration| delete_migration_query(migration, dialect, migration_table_name))
+        .collect();
+    let splitter = transaction.splitter();
+    if grouped || fake {
+        if fake {
+            log::info!("not going to roll back any migration as fake flag is enabled");
+        } else {
+            log::info!(
+                "going to roll back batch migrations in single transaction: {:#?}",
+                migrations.iter().map(ToString::to_string)
+            );
+            assert_transactional(migrations)?;
+        }
+        // fake rollbacks only update the schema migrations table
+        let updates = migrations.iter().zip(delete_migrations.iter()).flat_map(
+            |(migration, delete_migration)| {
+                let statements = if fake {
+                    Vec::new()
+                } else {
+                    migration_statements(
+                        migration,
+                        migration.down_sql().expect("down sql must be Some!"),
+                        &splitter,
+                    )
+                };
+                statements
+                    .into_iter()
+                    .chain(iter::once(delete_migration.as_str()))
+            },
+        );
+        transaction
+            .execute(updates)
+            .await
+            .migration_err("error rolling back migrations", None)?;
+        let rolled_back = if fake {
+            Vec::new()
+        } else {
+            migrations.to_vec()
+        };
+        return Ok(Report::new(rolled_back));
+    }
+
+    for (i, (migration, delete_migration)) in
+        migrations.iter().zip(delete_migrations.iter()).enumerate()
+    {
+        log::info!("rolling back migration: {}", migration);
+        let statements = migration_statements(
+            migration,
+            migration.down_sql().expect("down sql must be Some!"),
+            &splitter,
+        );
+        execute_updates(
+            transaction,
+            migration,
+            &statements,
+            delete_migration,
+            &AtomicUsize::new(0),
+        )
         .await
-        .migration_err("error applying migrations", None)?;
+        .migration_err(
+            &format!("error rolling back migration {}", migration),
+            Some(&migrations[..i]),
+        )?;
+    }
+    Ok(Report::new(migrations.to_vec()))
+}
 
-    Ok(Report::new(applied_migrations))
+// asserts the checks with the statements of the given Dialect, that of the config instead of its connection when
+// migrating from a Config
+pub(crate) async fn assert_checks<T: AsyncTransaction + Send>(
+    transaction: &mut T,
+    dialect: Option<Dialect>,
+    checks: &[String],
+) -> Result<(), Error> {
+    if checks.is_empty() {
+        return Ok(());
+    }
+    let dialect = dialect.ok_or_else(|| Error::new(Kind::UnknownCheckDialect, None))?;
+    for (i, check) in checks.iter().enumerate() {
+        let statements = dialect.check_statements(i + 1, check);
+        transaction
+            .execute(iter::once(statements.as_str()))
+            .await
+            .migration_err(&format!("error asserting check {}", check), None)?;
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -127,9 +695,119 @@ pub trait AsyncMigrate: AsyncQuery<Vec<Migration>>
 where
     Self: Sized,
 {
-    // Needed cause some database vendors like Mssql have a non sql standard way of checking the migrations table
-    fn assert_migrations_table_query(migration_table_name: &str) -> String {
-        ASSERT_MIGRATIONS_TABLE_QUERY.replace("%MIGRATION_TABLE_NAME%", migration_table_name)
+    /// The database dialect of the connection, used to generate the sql of migrations written for multiple databases.
+    /// Connections that don't report it can only run migrations with static sql.
+    fn dialect(&self) -> Option<Dialect> {
+        None
+    }
+
+    /// Checks the integrity of the database after migrating, see [`Runner::set_integrity_checks`].
+    /// Connections that don't support it report no issues.
+    ///
+    /// [`Runner::set_integrity_checks`]: crate::Runner::set_integrity_checks
+    async fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        Ok(Vec::new())
+    }
+
+    /// Introspects the schema of the database after migrating, to write its snapshot, see
+    /// [`Runner::set_snapshot_path`]. Connections that can't introspect their schema return None.
+    ///
+    /// [`Runner::set_snapshot_path`]: crate::Runner::set_snapshot_path
+    async fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Ok(None)
+    }
+
+    /// Configures the connection before the migrations are run on it, executing the given PRAGMAs and loading the
+    /// given extensions on sqlite connections, see [`Runner::set_sqlite_pragmas`]. Other connections ignore them.
+    ///
+    /// [`Runner::set_sqlite_pragmas`]: crate::Runner::set_sqlite_pragmas
+    async fn setup_sqlite(
+        &mut self,
+        pragmas: &[String],
+        extensions: &[PathBuf],
+    ) -> Result<(), Error> {
+        let _ = (pragmas, extensions);
+        Ok(())
+    }
+
+    /// Asserts verification checks, each a `SELECT` returning a single value that must be true, i.e. not false,
+    /// 0 or NULL, the same way as the `-- refinery:check` section of migrations, see [`Rollout::set_checks`].
+    /// Checks need the [`Dialect`] of the connection.
+    ///
+    /// [`Rollout::set_checks`]: crate::Rollout::set_checks
+    async fn assert_checks(&mut self, checks: &[String]) -> Result<(), Error> {
+        let dialect = self.dialect();
+        assert_checks(self, dialect, checks).await
+    }
+
+    /// Creates the schema history table if it doesn't exist, and adds the `execution_time_ms`, `applied_by` and
+    /// `skipped` columns to the ones created by previous versions
+    async fn assert_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        self.assert_migrations_table_with(migration_table_name, &DefaultSchemaHistoryDdl)
+            .await
+    }
+
+    /// Fails with [`Kind::LegacyPrimaryKey`] if the schema history table has the version alone as primary key, as
+    /// created before namespaces, so the migrations of different namespaces can't share a version, see
+    /// [`Runner::set_namespace`]
+    ///
+    /// [`Runner::set_namespace`]: crate::Runner::set_namespace
+    async fn assert_namespaced_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<(), Error> {
+        let Some(query) = legacy_primary_key_query(self.dialect(), migration_table_name)? else {
+            return Ok(());
+        };
+        let legacy = self
+            .query(&query)
+            .await
+            .migration_err("error checking the migrations table primary key", None)?;
+        if legacy.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                Kind::LegacyPrimaryKey(migration_table_name.to_string()),
+                None,
+            ))
+        }
+    }
+
+    /// Creates the schema history table with the given DDL if it doesn't exist, see [`assert_migrations_table`]
+    ///
+    /// [`assert_migrations_table`]: AsyncMigrate::assert_migrations_table
+    async fn assert_migrations_table_with(
+        &mut self,
+        migration_table_name: &str,
+        ddl: &dyn SchemaHistoryDdl,
+    ) -> Result<usize, Error> {
+        let statements = ddl.create_table_statements(migration_table_name, self.dialect());
+        let count = self
+            .execute(statements.iter().map(String::as_str))
+            .await
+            .migration_err("error asserting migrations table", None)?;
+
+        let dialect = self.dialect();
+        for (probe, upgrade) in upgrade_migrations_table_queries(dialect, migration_table_name)? {
+            let missing = if probes_missing_columns(dialect) {
+                !self
+                    .query(&probe)
+                    .await
+                    .migration_err("error upgrading migrations table", None)?
+                    .is_empty()
+            } else {
+                self.query(&probe).await.is_err()
+            };
+            if missing {
+                self.execute(iter::once(upgrade.as_str()))
+                    .await
+                    .migration_err("error upgrading migrations table", None)?;
+            }
+        }
+        Ok(count)
     }
 
     async fn get_last_applied_migration(
@@ -162,20 +840,29 @@ where
         Ok(migrations)
     }
 
-    async fn migrate(
+    /// Queries the migrations recorded as skipped on the schema history table,
+    /// see [`Runner::get_skipped_migrations_async`]
+    ///
+    /// [`Runner::get_skipped_migrations_async`]: crate::Runner::get_skipped_migrations_async
+    async fn get_skipped_migrations(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        self.query(
+            &GET_SKIPPED_MIGRATIONS_QUERY.replace("%MIGRATION_TABLE_NAME%", migration_table_name),
+        )
+        .await
+        .migration_err("error getting skipped migrations", None)
+    }
+
+    async fn get_unapplied_migrations(
         &mut self,
         migrations: &[Migration],
         abort_divergent: bool,
         abort_missing: bool,
-        grouped: bool,
-        target: Target,
         migration_table_name: &str,
-    ) -> Result<Report, Error> {
-        self.execute(
-            [Self::assert_migrations_table_query(migration_table_name).as_str()].into_iter(),
-        )
-        .await
-        .migration_err("error asserting migrations table", None)?;
+    ) -> Result<Vec<Migration>, Error> {
+        self.assert_migrations_table(migration_table_name).await?;
 
         let applied_migrations = self
             .query(
@@ -196,10 +883,98 @@ where
             log::info!("no migrations to apply");
         }
 
-        if grouped || matches!(target, Target::Fake | Target::FakeVersion(_)) {
-            migrate_grouped(self, migrations, target, migration_table_name).await
-        } else {
-            migrate(self, migrations, target, migration_table_name).await
-        }
+        Ok(migrations)
+    }
+
+    /// Rolls back the given applied migrations in order, executing their `-- refinery:down` section and deleting
+    /// them from the schema history table, see [`Runner::rollback_async`]. Fake rollbacks only update the schema
+    /// history table.
+    ///
+    /// [`Runner::rollback_async`]: crate::Runner::rollback_async
+    async fn rollback(
+        &mut self,
+        migrations: &[Migration],
+        grouped: bool,
+        fake: bool,
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        let dialect = self.dialect();
+        rollback(
+            self,
+            migrations,
+            grouped,
+            fake,
+            dialect,
+            migration_table_name,
+        )
+        .await
+    }
+
+    /// Inserts the given applied migrations on the schema history table in a single transaction without running
+    /// them, see [`Runner::import_history_async`]
+    ///
+    /// [`Runner::import_history_async`]: crate::Runner::import_history_async
+    async fn import_history(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        let dialect = self.dialect();
+        let inserts: Vec<String> = migrations
+            .iter()
+            .map(|migration| insert_migration_query(migration, dialect, migration_table_name))
+            .collect();
+        self.execute(inserts.iter().map(String::as_str))
+            .await
+            .migration_err("error importing schema history", None)
+    }
+
+    /// Applies the given pending migrations in order on their own transactions, without verifying them against the
+    /// schema history table, as [`AsyncMigrate::get_unapplied_migrations`] already did
+    async fn apply_pending(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        let dialect = self.dialect();
+        migrate_with_dialect(
+            self,
+            migrations.to_vec(),
+            Target::Latest,
+            migration_table_name,
+            false,
+            dialect,
+        )
+        .await
+    }
+
+    async fn migrate(
+        &mut self,
+        migrations: &[Migration],
+        abort_divergent: bool,
+        abort_missing: bool,
+        grouped: bool,
+        target: Target,
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        let migrations = self
+            .get_unapplied_migrations(
+                migrations,
+                abort_divergent,
+                abort_missing,
+                migration_table_name,
+            )
+            .await?;
+
+        let dialect = self.dialect();
+        migrate_with_dialect(
+            self,
+            migrations,
+            target,
+            migration_table_name,
+            grouped,
+            dialect,
+        )
+        .await
     }
 }
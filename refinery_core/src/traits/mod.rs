@@ -3,47 +3,238 @@ use time::format_description::well_known::Rfc3339;
 pub mod r#async;
 pub mod sync;
 
-use crate::runner::Type;
-use crate::{error::Kind, Error, Migration};
+use crate::error::{FailedStatement, Kind};
+use crate::runner::{table_name_parts, Type};
+use crate::{Dialect, Error, Migration, Report, Splitter, ValidationIssue};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+// the statements the given sql of a migration is executed as, split with the Splitter set on the migration
+// or the default one of the connection
+pub(crate) fn migration_statements<'a>(
+    migration: &Migration,
+    sql: &'a str,
+    default: &Splitter,
+) -> Vec<&'a str> {
+    migration.splitter().unwrap_or(default).split(sql)
+}
+
+// the statements applying the migration, skipped migrations and faked ones only update the schema history table
+pub(crate) fn applied_statements<'a>(
+    migration: &'a Migration,
+    fake: bool,
+    splitter: &Splitter,
+) -> Vec<&'a str> {
+    match migration.sql() {
+        Some(sql) if !fake && !migration.is_skipped() => {
+            migration_statements(migration, sql, splitter)
+        }
+        _ => Vec::new(),
+    }
+}
+
+// the statement of the migrations that failed given how many of their updates were executed, each migration's
+// statements followed by the query recording it, as the connections execute them one at a time stopping at the one
+// that fails. `None` when none was executed or it was the query recording a migration
+pub(crate) fn failed_statement(
+    migrations: &[Migration],
+    fake: bool,
+    splitter: &Splitter,
+    executed: usize,
+) -> Option<FailedStatement> {
+    let mut update = executed.checked_sub(1)?;
+    for migration in migrations {
+        let statements = applied_statements(migration, fake, splitter);
+        // grouped runs reset the timeout each migration sets on the session after recording it
+        let resets = usize::from(!fake && migration.reset_timeout().is_some());
+        match statements.get(update) {
+            Some(statement) => {
+                let sql = migration.sql().expect("sql must be Some!");
+                return Some(FailedStatement::new(migration, sql, statement, update));
+            }
+            None if update <= statements.len() + resets => return None,
+            None => update -= statements.len() + 1 + resets,
+        }
+    }
+    None
+}
+
+// the `Kind::Timeout` error of the migration the database cancelled for exceeding its timeout, found by the statement
+// that failed, `None` when the error isn't a timeout or the migration has none
+pub(crate) fn timeout_error(
+    is_timeout: bool,
+    migrations: &[Migration],
+    statement: Option<&FailedStatement>,
+    applied_migrations: Option<&[Migration]>,
+) -> Option<Error> {
+    if !is_timeout {
+        return None;
+    }
+    let migration = match migrations {
+        [migration] => migration,
+        migrations => {
+            let statement = statement?;
+            migrations
+                .iter()
+                .find(|migration| migration.to_string() == statement.migration())?
+        }
+    };
+    Some(Error::new(
+        Kind::Timeout(migration.clone(), migration.timeout()?),
+        applied_migrations.map(|applied| Report::new(applied.to_vec())),
+    ))
+}
+
+// how many times the migrations failing with a serialization failure are retried, besides the retries set with their
+// directive, as the failure rolls back the whole transaction they are safe to retry
+pub(crate) const SERIALIZATION_FAILURE_RETRIES: u32 = 5;
+
+// how long to wait before retrying a transaction after the given serialization failures, doubling from 100ms,
+// `None` when the error isn't one or the retries are exhausted
+pub(crate) fn serialization_failure_backoff(
+    is_serialization_failure: bool,
+    serialization_failures: &mut u32,
+) -> Option<Duration> {
+    if !is_serialization_failure || *serialization_failures >= SERIALIZATION_FAILURE_RETRIES {
+        return None;
+    }
+    let backoff = Duration::from_millis(100 << *serialization_failures);
+    *serialization_failures += 1;
+    Some(backoff)
+}
+
+// async Rust migrations are run on their own by the async executors, they can't be batched with others
+// or run synchronously, skipped ones are only recorded
+pub(crate) fn assert_no_async_migrations(migrations: &[Migration]) -> Result<(), Error> {
+    match migrations
+        .iter()
+        .find(|migration| migration.migrate_async().is_some() && !migration.is_skipped())
+    {
+        Some(migration) => Err(Error::new(
+            Kind::UnsupportedAsyncMigration(migration.clone()),
+            None,
+        )),
+        None => Ok(()),
+    }
+}
+
+// migrations marked with `-- refinery:no-transaction` can't be batched with others in a single transaction,
+// skipped ones are only recorded
+pub(crate) fn assert_transactional(migrations: &[Migration]) -> Result<(), Error> {
+    match migrations
+        .iter()
+        .find(|migration| !migration.is_transactional() && !migration.is_skipped())
+    {
+        Some(migration) => Err(Error::new(
+            Kind::GroupedNoTransaction(migration.clone()),
+            None,
+        )),
+        None => Ok(()),
+    }
+}
 
 // Verifies applied and to be applied migrations returning Error if:
 // - `abort_divergent` is true and there are applied migrations with a different name and checksum but same version as a migration to be applied.
 // - `abort_missing` is true and there are applied migrations that are missing on the file system
 // - there are repeated migrations with the same version to be applied
+// the issues are checked in the order they're found by `validate_migrations`, the ones that don't abort are logged
 pub(crate) fn verify_migrations(
     applied: Vec<Migration>,
-    mut migrations: Vec<Migration>,
+    migrations: Vec<Migration>,
     abort_divergent: bool,
     abort_missing: bool,
 ) -> Result<Vec<Migration>, Error> {
+    let mut issues = Vec::new();
+    let to_be_applied = validate_migrations(applied, migrations, &mut issues);
+    for issue in issues {
+        #[cfg(feature = "tracing")]
+        crate::tracing::verification_issue(&issue);
+        match issue {
+            ValidationIssue::Divergent { applied, migration } => {
+                if abort_divergent {
+                    return Err(Error::new(Kind::DivergentVersion(applied, migration), None));
+                }
+                log::error!(
+                    target: "refinery_core::traits::divergent",
+                    "applied migration {} is different than filesystem one {}",
+                    applied,
+                    migration
+                );
+            }
+            ValidationIssue::Missing(app) => {
+                if abort_missing {
+                    return Err(Error::new(Kind::MissingVersion(app), None));
+                }
+                log::error!(target: "refinery_core::traits::missing", "migration {} is missing from the filesystem", app);
+            }
+            ValidationIssue::OutOfOrder(migration) => {
+                if abort_missing {
+                    return Err(Error::new(Kind::MissingVersion(migration), None));
+                }
+                log::error!(target: "refinery_core::traits::missing", "found migration on file system {} not applied", migration);
+            }
+            ValidationIssue::Repeated(migration) => {
+                return Err(Error::new(Kind::RepeatedVersion(migration), None));
+            }
+        }
+    }
+    Ok(to_be_applied)
+}
+
+// Validates applied and to be applied migrations returning the ones to be applied, pushing to `issues`:
+// - the applied migrations with a different name and checksum but same version as a migration to be applied
+// - the applied migrations that are missing on the file system
+// - the versioned and seed migrations older than the current version that weren't applied
+// - the repeated migrations with the same version to be applied
+// the migrations of each namespace are validated independently, against the ones applied on it
+pub(crate) fn validate_migrations(
+    mut applied: Vec<Migration>,
+    mut migrations: Vec<Migration>,
+    issues: &mut Vec<ValidationIssue>,
+) -> Vec<Migration> {
+    let namespaces: BTreeSet<Option<String>> = migrations
+        .iter()
+        .map(|migration| migration.namespace().map(ToString::to_string))
+        .collect();
+    if namespaces.len() > 1 {
+        let mut to_be_applied = Vec::new();
+        for namespace in namespaces {
+            let (namespace_applied, namespace_migrations) = (
+                applied
+                    .iter()
+                    .filter(|app| app.namespace() == namespace.as_deref())
+                    .cloned()
+                    .collect(),
+                migrations
+                    .iter()
+                    .filter(|migration| migration.namespace() == namespace.as_deref())
+                    .cloned()
+                    .collect(),
+            );
+            to_be_applied.extend(validate_migrations(
+                namespace_applied,
+                namespace_migrations,
+                issues,
+            ));
+        }
+        to_be_applied.sort();
+        return to_be_applied;
+    }
+    let namespace = namespaces.into_iter().next().flatten();
+    applied.retain(|app| app.namespace() == namespace.as_deref());
     migrations.sort();
 
     for app in applied.iter() {
         // iterate applied migrations on database and assert all migrations
         // applied on database exist on the file system and have the same checksum
         match migrations.iter().find(|m| m.version() == app.version()) {
-            None => {
-                if abort_missing {
-                    return Err(Error::new(Kind::MissingVersion(app.clone()), None));
-                } else {
-                    log::error!(target: "refinery_core::traits::missing", "migration {} is missing from the filesystem", app);
-                }
-            }
+            None => issues.push(ValidationIssue::Missing(app.clone())),
             Some(migration) => {
                 if migration != app {
-                    if abort_divergent {
-                        return Err(Error::new(
-                            Kind::DivergentVersion(app.clone(), migration.clone()),
-                            None,
-                        ));
-                    } else {
-                        log::error!(
-                            target: "refinery_core::traits::divergent",
-                            "applied migration {} is different than filesystem one {}",
-                            app,
-                            migration
-                        );
-                    }
+                    issues.push(ValidationIssue::Divergent {
+                        applied: app.clone(),
+                        migration: migration.clone(),
+                    });
                 }
             }
         }
@@ -71,15 +262,11 @@ pub(crate) fn verify_migrations(
             .any(|app| app.version() == migration.version())
         {
             if to_be_applied.contains(&migration) {
-                return Err(Error::new(Kind::RepeatedVersion(migration), None));
-            } else if migration.prefix() == &Type::Versioned
+                issues.push(ValidationIssue::Repeated(migration));
+            } else if migration.prefix() != &Type::Unversioned
                 && current >= migration.version() as i32
             {
-                if abort_missing {
-                    return Err(Error::new(Kind::MissingVersion(migration), None));
-                } else {
-                    log::error!(target: "refinery_core::traits::missing", "found migration on file system {} not applied", migration);
-                }
+                issues.push(ValidationIssue::OutOfOrder(migration));
             } else {
                 to_be_applied.push(migration);
             }
@@ -88,31 +275,367 @@ pub(crate) fn verify_migrations(
     // with these two iterations we both assert that all migrations found on the database
     // exist on the file system and have the same checksum, and all migrations found
     // on the file system are either on the database, or greater than the current, and therefore going to be applied
-    Ok(to_be_applied)
+    to_be_applied
+}
+
+// the user recorded as applying the migration, the one set with `Runner::set_applied_by` or else the user of the
+// connection, NULL when the dialect of the connection is unknown or has no users
+fn applied_by(migration: &Migration, dialect: Option<Dialect>) -> String {
+    match (
+        migration.applied_by(),
+        dialect.and_then(Dialect::current_user),
+    ) {
+        (Some(applied_by), _) => format!("'{}'", applied_by.replace('\'', "''")),
+        (None, Some(current_user)) => current_user.to_string(),
+        (None, None) => "NULL".to_string(),
+    }
 }
 
-pub(crate) fn insert_migration_query(migration: &Migration, migration_table_name: &str) -> String {
+// the value of the `skipped` column of the migration, recorded without executing its sql, NULL otherwise
+fn skipped(migration: &Migration) -> &'static str {
+    if migration.is_skipped() {
+        "1"
+    } else {
+        "NULL"
+    }
+}
+
+pub(crate) fn insert_migration_query(
+    migration: &Migration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> String {
     format!(
-        "INSERT INTO {} (version, name, applied_on, checksum) VALUES ({}, '{}', '{}', '{}')",
+        "INSERT INTO {} (version, name, applied_on, checksum, applied_by, skipped) VALUES ({}, '{}', '{}', '{}', {}, {})",
         // safe to call unwrap as we just converted it to applied, and we are sure it can be formatted according to RFC 33339
         migration_table_name,
         migration.version(),
-        migration.name(),
+        migration.recorded_name().replace('\'', "''"),
         migration.applied_on().unwrap().format(&Rfc3339).unwrap(),
-        migration.checksum()
+        migration.recorded_checksum(),
+        applied_by(migration, dialect),
+        skipped(migration),
     )
 }
 
-pub(crate) const ASSERT_MIGRATIONS_TABLE_QUERY: &str =
-    "CREATE TABLE IF NOT EXISTS %MIGRATION_TABLE_NAME%(
-             version INT4 PRIMARY KEY,
-             name VARCHAR(255),
+// matches the rows of the namespace of the migration on the schema history table,
+// the version can be repeated on other namespaces. The prefix is compared with SUBSTR rather than LIKE, as namespaces
+// can have `_`, a LIKE wildcard. Mssql only has SUBSTRING
+fn namespace_condition(migration: &Migration, dialect: Option<Dialect>) -> String {
+    match migration.namespace() {
+        Some(namespace) => {
+            let substr = match dialect {
+                Some(Dialect::Mssql) => "SUBSTRING",
+                _ => "SUBSTR",
+            };
+            let prefix = format!("{}/", namespace);
+            format!("{}(name, 1, {}) = '{}'", substr, prefix.len(), prefix)
+        }
+        None => "name NOT LIKE '%/%'".to_string(),
+    }
+}
+
+pub(crate) fn update_migration_query(
+    migration: &Migration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> String {
+    format!(
+        "UPDATE {} SET name = '{}', applied_on = '{}', checksum = '{}', applied_by = {}, execution_time_ms = NULL, \
+        skipped = {} WHERE version = {} AND {}",
+        // safe to call unwrap as we just converted it to applied, and we are sure it can be formatted according to RFC 33339
+        migration_table_name,
+        migration.recorded_name().replace('\'', "''"),
+        migration.applied_on().unwrap().format(&Rfc3339).unwrap(),
+        migration.recorded_checksum(),
+        applied_by(migration, dialect),
+        skipped(migration),
+        migration.version(),
+        namespace_condition(migration, dialect),
+    )
+}
+
+// records how long applying the migration took, once its transaction is committed
+pub(crate) fn execution_time_query(
+    migration: &Migration,
+    execution_time: Duration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> String {
+    format!(
+        "UPDATE {} SET execution_time_ms = {} WHERE version = {} AND {}",
+        migration_table_name,
+        execution_time.as_millis(),
+        migration.version(),
+        namespace_condition(migration, dialect),
+    )
+}
+
+pub(crate) fn delete_migration_query(
+    migration: &Migration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> String {
+    format!(
+        "DELETE FROM {} WHERE version = {} AND {}",
+        migration_table_name,
+        migration.version(),
+        namespace_condition(migration, dialect),
+    )
+}
+
+const ASSERT_MIGRATIONS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS %MIGRATION_TABLE_NAME%(
+             version INT4 NOT NULL,
+             name VARCHAR(255) NOT NULL,
+             applied_on VARCHAR(255),
+             checksum VARCHAR(255),
+             execution_time_ms BIGINT,
+             applied_by VARCHAR(255),
+             skipped SMALLINT,
+             PRIMARY KEY (version, name));";
+
+// Mssql has no `CREATE TABLE IF NOT EXISTS`
+const ASSERT_MIGRATIONS_TABLE_MSSQL_QUERY: &str =
+    "IF OBJECT_ID(N'%MIGRATION_OBJECT_NAME%', N'U') IS NULL
+         BEGIN
+           CREATE TABLE %MIGRATION_TABLE_NAME%(
+             version INT NOT NULL,
+             name VARCHAR(255) NOT NULL,
              applied_on VARCHAR(255),
-             checksum VARCHAR(255));";
+             checksum VARCHAR(255),
+             execution_time_ms BIGINT,
+             applied_by VARCHAR(255),
+             skipped SMALLINT,
+             PRIMARY KEY (version, name));
+         END";
+
+// the schema history table is deduplicated on merges by version and name, as clickhouse doesn't enforce primary keys
+const ASSERT_MIGRATIONS_TABLE_CLICKHOUSE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS %MIGRATION_TABLE_NAME%(
+             version Int32,
+             name String,
+             applied_on String,
+             checksum String,
+             execution_time_ms Nullable(Int64),
+             applied_by Nullable(String),
+             skipped Nullable(UInt8))
+         ENGINE = ReplacingMergeTree
+         ORDER BY (version, name)";
+
+// Oracle has no `CREATE TABLE IF NOT EXISTS` before 23ai, the table is created by a PL/SQL block ignoring ORA-00955,
+// the name being used, and VARCHAR is a deprecated synonym of VARCHAR2
+const ASSERT_MIGRATIONS_TABLE_ORACLE_QUERY: &str = "BEGIN
+  EXECUTE IMMEDIATE '
+    CREATE TABLE %MIGRATION_TABLE_NAME%(
+      version NUMBER(10) NOT NULL,
+      name VARCHAR2(255) NOT NULL,
+      applied_on VARCHAR2(255),
+      checksum VARCHAR2(255),
+      execution_time_ms NUMBER(19),
+      applied_by VARCHAR2(255),
+      skipped NUMBER(1),
+      PRIMARY KEY (version, name))';
+EXCEPTION
+  WHEN OTHERS THEN
+    IF SQLCODE != -955 THEN
+      RAISE;
+    END IF;
+END;";
+
+/// Generates the DDL of the schema history table, so it can be customized, i.e. its column types, indexes or
+/// tablespace, or to target databases refinery doesn't know, see [`Runner::set_schema_history_ddl`].
+/// The default method generates the DDL refinery uses for each dialect, see [`DefaultSchemaHistoryDdl`].
+///
+/// [`Runner::set_schema_history_ddl`]: crate::Runner::set_schema_history_ddl
+pub trait SchemaHistoryDdl: Send + Sync {
+    /// The statements creating the schema history table with the given name, already qualified and quoted, for the
+    /// dialect of the connection. They are executed in order each time the table is asserted, so they must not fail
+    /// when it already exists, i.e. with `CREATE TABLE IF NOT EXISTS`. The table must have the `version`, `name`,
+    /// `applied_on`, `checksum`, `execution_time_ms`, `applied_by` and `skipped` columns, with `version` and `name`
+    /// unique.
+    fn create_table_statements(
+        &self,
+        migration_table_name: &str,
+        dialect: Option<Dialect>,
+    ) -> Vec<String> {
+        let query = match dialect {
+            Some(Dialect::Mssql) => ASSERT_MIGRATIONS_TABLE_MSSQL_QUERY.replace(
+                "%MIGRATION_OBJECT_NAME%",
+                &migration_table_name.replace('\'', "''"),
+            ),
+            Some(Dialect::ClickHouse) => ASSERT_MIGRATIONS_TABLE_CLICKHOUSE_QUERY.to_string(),
+            Some(Dialect::Oracle) => {
+                return vec![ASSERT_MIGRATIONS_TABLE_ORACLE_QUERY.replace(
+                    "%MIGRATION_TABLE_NAME%",
+                    &migration_table_name.replace('\'', "''"),
+                )]
+            }
+            _ => ASSERT_MIGRATIONS_TABLE_QUERY.to_string(),
+        };
+        vec![query.replace("%MIGRATION_TABLE_NAME%", migration_table_name)]
+    }
+}
+
+/// The DDL of the schema history table refinery creates by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultSchemaHistoryDdl;
+
+impl SchemaHistoryDdl for DefaultSchemaHistoryDdl {}
+
+// the columns added to the schema history table since it was first released, the tables created by previous
+// versions are upgraded by adding the ones they don't have
+const UPGRADED_COLUMNS: [&str; 3] = ["execution_time_ms", "applied_by", "skipped"];
+
+// the queries upgrading the schema history tables created by previous versions, each adding a missing column,
+// paired with the query probing whether the column is missing. On most databases that query fails when the column
+// doesn't exist, but a failing query aborts the Postgres transaction refinery may be running on, so on Postgres and
+// CockroachDB it looks the column up on `information_schema.columns` and returns a row shaped as an applied migration
+// when it's missing. Each query is executed on its own transaction, as CockroachDB can leave a transaction with
+// several schema changes partially applied
+pub(crate) fn upgrade_migrations_table_queries(
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Vec<(String, String)>, Error> {
+    let if_not_exists = match dialect {
+        Some(Dialect::Postgres | Dialect::Cockroach | Dialect::ClickHouse) => {
+            "COLUMN IF NOT EXISTS "
+        }
+        _ => "",
+    };
+    let types = match dialect {
+        Some(Dialect::Oracle) => ["NUMBER(19)", "VARCHAR2(255)", "NUMBER(1)"],
+        Some(Dialect::ClickHouse) => ["Nullable(Int64)", "Nullable(String)", "Nullable(UInt8)"],
+        _ => ["BIGINT", "VARCHAR(255)", "SMALLINT"],
+    };
+    let columns_condition = if probes_missing_columns(dialect) {
+        // unquoted identifiers are folded to lowercase, the table is looked up on the current schema if it isn't
+        // qualified with one
+        let mut parts = table_name_parts(migration_table_name)?
+            .into_iter()
+            .rev()
+            .map(|(part, quoted)| {
+                let part = if quoted { part } else { part.to_lowercase() };
+                format!("'{}'", part.replace('\'', "''"))
+            });
+        let table = parts.next().unwrap_or_default();
+        let schema = parts.next().unwrap_or_else(|| "current_schema()".into());
+        Some(format!(
+            "table_schema = {} AND table_name = {}",
+            schema, table
+        ))
+    } else {
+        None
+    };
+
+    Ok(UPGRADED_COLUMNS
+        .iter()
+        .zip(types)
+        .map(|(column, column_type)| {
+            let probe = match &columns_condition {
+                Some(condition) => format!(
+                    "SELECT CAST(0 AS INT4), CAST('{column}' AS TEXT), CAST('1970-01-01T00:00:00Z' AS TEXT), \
+                    CAST('0' AS TEXT) WHERE NOT EXISTS (SELECT 1 FROM information_schema.columns WHERE {condition} \
+                    AND column_name = '{column}')",
+                ),
+                None => format!("SELECT {} FROM {} WHERE 1 = 0", column, migration_table_name),
+            };
+            (
+                probe,
+                format!(
+                    "ALTER TABLE {} ADD {}{} {}",
+                    migration_table_name, if_not_exists, column, column_type
+                ),
+            )
+        })
+        .collect())
+}
+
+// whether the probes of the upgrade queries return a row when the column is missing, instead of failing
+pub(crate) fn probes_missing_columns(dialect: Option<Dialect>) -> bool {
+    matches!(dialect, Some(Dialect::Postgres | Dialect::Cockroach))
+}
+
+// the query returning a row when the schema history table has the version alone as primary key, as created before
+// namespaces, so migrations of different namespaces can't share a version. Dialects that don't enforce primary keys,
+// and unknown ones, aren't checked
+pub(crate) fn legacy_primary_key_query(
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Option<String>, Error> {
+    let mut parts = table_name_parts(migration_table_name)?
+        .into_iter()
+        .rev()
+        .map(|(part, quoted)| {
+            // unquoted identifiers are folded to lowercase, and to uppercase on oracle
+            let part = match dialect {
+                _ if quoted => part,
+                Some(Dialect::Postgres | Dialect::Cockroach | Dialect::DuckDb) => {
+                    part.to_lowercase()
+                }
+                Some(Dialect::Oracle) => part.to_uppercase(),
+                _ => part,
+            };
+            format!("'{}'", part.replace('\'', "''"))
+        });
+    let table = parts.next().unwrap_or_default();
+    let schema = parts.next();
+
+    let (row, table_schema) = match dialect {
+        Some(Dialect::Sqlite) => {
+            let table_info = match schema {
+                Some(schema) => format!("pragma_table_info({}, {})", table, schema),
+                None => format!("pragma_table_info({})", table),
+            };
+            return Ok(Some(format!(
+                "SELECT 0, 'version', '1970-01-01T00:00:00Z', '0' \
+                WHERE EXISTS (SELECT 1 FROM {table_info} WHERE pk > 0) \
+                AND NOT EXISTS (SELECT 1 FROM {table_info} WHERE pk > 0 AND name = 'name')",
+            )));
+        }
+        Some(Dialect::Oracle) => {
+            let owner = schema.unwrap_or_else(|| "SYS_CONTEXT('USERENV', 'CURRENT_SCHEMA')".into());
+            return Ok(Some(format!(
+                "SELECT 0, 'version', '1970-01-01T00:00:00Z', '0' FROM dual \
+                WHERE EXISTS (SELECT 1 FROM all_constraints WHERE owner = {owner} AND table_name = {table} \
+                AND constraint_type = 'P') \
+                AND NOT EXISTS (SELECT 1 FROM all_constraints c JOIN all_cons_columns cc \
+                ON c.owner = cc.owner AND c.constraint_name = cc.constraint_name \
+                WHERE c.owner = {owner} AND c.table_name = {table} AND c.constraint_type = 'P' \
+                AND cc.column_name = 'NAME')",
+            )));
+        }
+        Some(Dialect::Postgres | Dialect::Cockroach | Dialect::DuckDb) => (
+            "SELECT CAST(0 AS INT4), CAST('version' AS TEXT), CAST('1970-01-01T00:00:00Z' AS TEXT), \
+            CAST('0' AS TEXT)",
+            schema.unwrap_or_else(|| "current_schema()".into()),
+        ),
+        Some(Dialect::Mysql) => (
+            "SELECT 0, 'version', '1970-01-01T00:00:00Z', '0' FROM DUAL",
+            schema.unwrap_or_else(|| "DATABASE()".into()),
+        ),
+        Some(Dialect::Mssql) => (
+            "SELECT CAST(0 AS INT), 'version', '1970-01-01T00:00:00Z', '0'",
+            schema.unwrap_or_else(|| "SCHEMA_NAME()".into()),
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(format!(
+        "{row} WHERE EXISTS (SELECT 1 FROM information_schema.table_constraints \
+        WHERE table_schema = {table_schema} AND table_name = {table} AND constraint_type = 'PRIMARY KEY') \
+        AND NOT EXISTS (SELECT 1 FROM information_schema.table_constraints tc \
+        JOIN information_schema.key_column_usage kcu ON tc.constraint_schema = kcu.constraint_schema \
+        AND tc.constraint_name = kcu.constraint_name AND tc.table_name = kcu.table_name \
+        WHERE tc.table_schema = {table_schema} AND tc.table_name = {table} \
+        AND tc.constraint_type = 'PRIMARY KEY' AND kcu.column_name = 'name')",
+    )))
+}
 
 pub(crate) const GET_APPLIED_MIGRATIONS_QUERY: &str = "SELECT version, name, applied_on, checksum \
     FROM %MIGRATION_TABLE_NAME% ORDER BY version ASC;";
 
+pub(crate) const GET_SKIPPED_MIGRATIONS_QUERY: &str = "SELECT version, name, applied_on, checksum \
+    FROM %MIGRATION_TABLE_NAME% WHERE skipped = 1 ORDER BY version ASC;";
+
 pub(crate) const GET_LAST_APPLIED_MIGRATION_QUERY: &str =
     "SELECT version, name, applied_on, checksum
     FROM %MIGRATION_TABLE_NAME% WHERE version=(SELECT MAX(version) from %MIGRATION_TABLE_NAME%)";
@@ -121,7 +644,16 @@ pub(crate) const DEFAULT_MIGRATION_TABLE_NAME: &str = "refinery_schema_history";
 
 #[cfg(test)]
 mod tests {
-    use super::{verify_migrations, Kind, Migration};
+    use super::{
+        legacy_primary_key_query, serialization_failure_backoff, upgrade_migrations_table_queries,
+        validate_migrations, verify_migrations, DefaultSchemaHistoryDdl, Dialect, Kind, Migration,
+        SchemaHistoryDdl, ValidationIssue, SERIALIZATION_FAILURE_RETRIES,
+    };
+    use crate::traits::sync::{migrate_with_dialect, Transaction};
+    use crate::Target;
+    use std::io;
+    use std::time::Duration;
+    use time::OffsetDateTime;
 
     fn get_migrations() -> Vec<Migration> {
         let migration1 = Migration::unapplied(
@@ -298,6 +830,51 @@ mod tests {
         assert_eq!(remaining, result);
     }
 
+    #[test]
+    fn verify_migrations_checks_namespaces_independently() {
+        let migrations = get_migrations();
+        let namespaced = |migration: &Migration, namespace: &str| {
+            let name = format!("{}/{}", namespace, migration.name());
+            Migration::applied(
+                migration.version() as i32,
+                name,
+                OffsetDateTime::now_utc(),
+                migration.checksum(),
+            )
+        };
+        let mut billing = crate::Runner::new(&migrations[..2])
+            .set_namespace("billing")
+            .unwrap()
+            .get_migrations()
+            .clone();
+        billing.extend_from_slice(&migrations[..3]);
+
+        // the V1 and V2 of the billing namespace don't count as applied on the default one
+        let applied = vec![
+            namespaced(&migrations[0], "billing"),
+            namespaced(&migrations[1], "billing"),
+            namespaced(&migrations[0], "orders"),
+        ];
+        let result = verify_migrations(applied, billing, true, true).unwrap();
+        assert_eq!(migrations[..3].to_vec(), result);
+
+        // a missing migration is only reported on its namespace
+        let applied = vec![namespaced(&migrations[1], "billing")];
+        let billing = crate::Runner::new(&migrations[..2])
+            .set_namespace("billing")
+            .unwrap()
+            .get_migrations()
+            .clone();
+        let err = verify_migrations(applied, billing, true, true).unwrap_err();
+        match err.kind() {
+            Kind::MissingVersion(missing) => {
+                assert_eq!(Some("billing"), missing.namespace());
+                assert_eq!(1, missing.version());
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
     #[test]
     fn verify_migrations_fails_on_repeated_migration() {
         let mut migrations = get_migrations();
@@ -312,4 +889,188 @@ mod tests {
             _ => panic!("failed test"),
         }
     }
+
+    #[test]
+    fn validate_migrations_collects_all_issues() {
+        let migrations = get_migrations();
+        let divergent = Migration::unapplied(
+            "V2__add_cars_table",
+            "CREATE TABLE cars (id int, name varchar(255));",
+        )
+        .unwrap();
+        let missing =
+            Migration::unapplied("V5__add_trucks_table", "CREATE TABLE trucks (id int);").unwrap();
+        let applied = vec![
+            migrations[0].clone(),
+            divergent.clone(),
+            migrations[3].clone(),
+            missing.clone(),
+        ];
+        let mut local = migrations.clone();
+        local.push(
+            Migration::unapplied("V6__add_owners_table", "CREATE TABLE owners (id int);").unwrap(),
+        );
+        local.push(local[4].clone());
+
+        let mut issues = Vec::new();
+        let pending = validate_migrations(applied, local.clone(), &mut issues);
+        assert_eq!(vec![local[4].clone()], pending);
+        assert_eq!(
+            vec![
+                ValidationIssue::Divergent {
+                    applied: divergent,
+                    migration: migrations[1].clone(),
+                },
+                ValidationIssue::Missing(missing),
+                ValidationIssue::OutOfOrder(migrations[2].clone()),
+                ValidationIssue::Repeated(local[4].clone()),
+            ],
+            issues
+        );
+    }
+
+    #[test]
+    fn generates_schema_history_ddl_for_dialect() {
+        let statements = DefaultSchemaHistoryDdl
+            .create_table_statements("refinery_schema_history", Some(Dialect::Postgres));
+        assert_eq!(1, statements.len());
+        assert!(statements[0].starts_with("CREATE TABLE IF NOT EXISTS refinery_schema_history("));
+
+        let statements = DefaultSchemaHistoryDdl
+            .create_table_statements("[o'brien].[history]", Some(Dialect::Mssql));
+        assert!(statements[0].starts_with("IF OBJECT_ID(N'[o''brien].[history]', N'U') IS NULL"));
+        assert!(statements[0].contains("CREATE TABLE [o'brien].[history]("));
+
+        let statements = DefaultSchemaHistoryDdl
+            .create_table_statements("refinery_schema_history", Some(Dialect::ClickHouse));
+        assert!(statements[0].contains("ENGINE = ReplacingMergeTree"));
+
+        let statements = DefaultSchemaHistoryDdl
+            .create_table_statements("\"O'Brien\".history", Some(Dialect::Oracle));
+        assert!(statements[0].starts_with("BEGIN\n  EXECUTE IMMEDIATE '"));
+        assert!(statements[0].contains("CREATE TABLE \"O''Brien\".history("));
+        assert!(statements[0].contains("name VARCHAR2(255) NOT NULL"));
+        assert!(statements[0].contains("IF SQLCODE != -955 THEN"));
+    }
+
+    #[test]
+    fn probes_missing_columns_on_information_schema_for_postgres() {
+        let queries =
+            upgrade_migrations_table_queries(Some(Dialect::Postgres), "audit.\"History\"").unwrap();
+        assert_eq!(3, queries.len());
+        let (probe, upgrade) = &queries[2];
+        assert!(probe.contains(
+            "FROM information_schema.columns WHERE table_schema = 'audit' AND table_name = 'History' \
+            AND column_name = 'skipped'"
+        ));
+        assert_eq!(
+            "ALTER TABLE audit.\"History\" ADD COLUMN IF NOT EXISTS skipped SMALLINT",
+            upgrade
+        );
+
+        let queries =
+            upgrade_migrations_table_queries(Some(Dialect::Cockroach), "Refinery_History").unwrap();
+        assert!(queries[0]
+            .0
+            .contains("table_schema = current_schema() AND table_name = 'refinery_history'"));
+
+        let queries =
+            upgrade_migrations_table_queries(Some(Dialect::Sqlite), "refinery_schema_history")
+                .unwrap();
+        assert_eq!(
+            "SELECT execution_time_ms FROM refinery_schema_history WHERE 1 = 0",
+            queries[0].0
+        );
+    }
+
+    #[test]
+    fn checks_legacy_primary_key_on_the_catalog_of_the_dialect() {
+        let query = legacy_primary_key_query(Some(Dialect::Postgres), "audit.\"History\"")
+            .unwrap()
+            .unwrap();
+        assert!(query.contains(
+            "WHERE tc.table_schema = 'audit' AND tc.table_name = 'History' \
+            AND tc.constraint_type = 'PRIMARY KEY' AND kcu.column_name = 'name'"
+        ));
+
+        let query = legacy_primary_key_query(Some(Dialect::Mysql), "Refinery_History")
+            .unwrap()
+            .unwrap();
+        assert!(query.contains("table_schema = DATABASE() AND table_name = 'Refinery_History'"));
+
+        let query = legacy_primary_key_query(Some(Dialect::Oracle), "refinery_schema_history")
+            .unwrap()
+            .unwrap();
+        assert!(query.contains("table_name = 'REFINERY_SCHEMA_HISTORY'"));
+
+        assert!(
+            legacy_primary_key_query(Some(Dialect::ClickHouse), "refinery_schema_history")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    // a connection recording the statements it executes, failing on the invalid ones
+    #[derive(Default)]
+    struct RecordingConnection {
+        executed: Vec<String>,
+    }
+
+    impl Transaction for RecordingConnection {
+        type Error = io::Error;
+
+        fn execute<'a, T: Iterator<Item = &'a str>>(
+            &mut self,
+            queries: T,
+        ) -> Result<usize, Self::Error> {
+            let mut count = 0;
+            for query in queries {
+                self.executed.push(query.trim().to_string());
+                if query.contains("NOT VALID") {
+                    return Err(io::Error::other("syntax error"));
+                }
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn resets_session_timeout_after_failed_migrations() {
+        let migration = Migration::unapplied(
+            "V1__backfill",
+            "-- refinery:timeout=5s\nUPDATE users SET active = 1;\nNOT VALID SQL;",
+        )
+        .unwrap()
+        .for_dialect(Some(Dialect::Mysql))
+        .unwrap();
+        let reset = migration.reset_timeout().unwrap().to_string();
+
+        let mut conn = RecordingConnection::default();
+        let result = migrate_with_dialect(
+            &mut conn,
+            vec![migration],
+            Target::Latest,
+            "refinery_schema_history",
+            false,
+            Some(Dialect::Mysql),
+        );
+        assert!(result.is_err());
+        assert!(conn.executed[0].starts_with("-- refinery:timeout=5s\nSET SESSION"));
+        assert_eq!(Some(&reset), conn.executed.last());
+    }
+
+    #[test]
+    fn backs_off_serialization_failures_until_retries_are_exhausted() {
+        let mut failures = 0;
+        assert_eq!(None, serialization_failure_backoff(false, &mut failures));
+        assert_eq!(0, failures);
+
+        let backoffs: Vec<Duration> =
+            std::iter::from_fn(|| serialization_failure_backoff(true, &mut failures)).collect();
+        assert_eq!(SERIALIZATION_FAILURE_RETRIES as usize, backoffs.len());
+        assert_eq!(Duration::from_millis(100), backoffs[0]);
+        assert_eq!(Duration::from_millis(200), backoffs[1]);
+        assert_eq!(SERIALIZATION_FAILURE_RETRIES, failures);
+    }
 }
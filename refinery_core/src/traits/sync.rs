@@ -1,11 +1,23 @@
-use std::ops::Deref;
+use std::collections::BTreeSet;
+use std::iter;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
-use crate::error::WrapMigrationError;
+use crate::error::{Kind, WrapMigrationError};
+use crate::introspect::Schema;
 use crate::traits::{
-    insert_migration_query, verify_migrations, ASSERT_MIGRATIONS_TABLE_QUERY,
-    GET_APPLIED_MIGRATIONS_QUERY, GET_LAST_APPLIED_MIGRATION_QUERY,
+    applied_statements, assert_no_async_migrations, assert_transactional, delete_migration_query,
+    execution_time_query, failed_statement, insert_migration_query, legacy_primary_key_query,
+    migration_statements, probes_missing_columns, serialization_failure_backoff, timeout_error,
+    update_migration_query, upgrade_migrations_table_queries, verify_migrations,
+    DefaultSchemaHistoryDdl, SchemaHistoryDdl, GET_APPLIED_MIGRATIONS_QUERY,
+    GET_LAST_APPLIED_MIGRATION_QUERY, GET_SKIPPED_MIGRATIONS_QUERY, SERIALIZATION_FAILURE_RETRIES,
 };
-use crate::{Error, Migration, Report, Target};
+use crate::{Dialect, Error, IntegrityIssue, Migration, Report, Splitter, Target};
 
 pub trait Transaction {
     type Error: std::error::Error + Send + Sync + 'static;
@@ -14,12 +26,112 @@ pub trait Transaction {
         &mut self,
         queries: T,
     ) -> Result<usize, Self::Error>;
+
+    /// The [`Splitter`] the sql of the migrations is split with when none is set with [`Runner::set_splitter`]
+    ///
+    /// [`Runner::set_splitter`]: crate::Runner::set_splitter
+    fn splitter(&self) -> Splitter {
+        Splitter::Semicolon
+    }
+
+    /// Executes the queries one at a time outside of a transaction, for the migrations marked with the
+    /// `-- refinery:no-transaction` directive, see [`Migration::is_transactional`].
+    /// Connections that can't run queries outside of a transaction execute each on its own one.
+    fn execute_without_transaction<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        for query in queries {
+            count += self.execute(iter::once(query))?;
+        }
+        Ok(count)
+    }
+
+    /// Whether the error is a serialization failure, SQLSTATE `40001`, that rolled back the transaction and is
+    /// resolved by retrying it, as CockroachDB reports under contention. Migrations failing with it are retried.
+    fn is_serialization_failure(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+
+    /// Whether the error is the database cancelling a statement for exceeding the timeout set by the migration,
+    /// see [`Runner::set_timeout`]. Migrations failing with it fail with [`Kind::Timeout`].
+    ///
+    /// [`Runner::set_timeout`]: crate::Runner::set_timeout
+    fn is_timeout(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+}
+
+// executes the updates of a migration, its statements and the query recording it on the schema history table last, on
+// a single transaction unless the migration is marked with `-- refinery:no-transaction`, counting the executed ones
+fn execute_updates<'a, T: Transaction>(
+    transaction: &mut T,
+    migration: &Migration,
+    updates: impl Iterator<Item = &'a str>,
+    executed: &AtomicUsize,
+) -> Result<usize, T::Error> {
+    executed.store(0, Ordering::Relaxed);
+    let count = |_: &&str| {
+        executed.fetch_add(1, Ordering::Relaxed);
+    };
+    let result = if migration.is_transactional() {
+        transaction.execute(updates.inspect(count))
+    } else {
+        let updates: Vec<&str> = updates.collect();
+        let (record, statements) = updates
+            .split_last()
+            .expect("updates must end recording the migration!");
+        match transaction.execute_without_transaction(statements.iter().copied().inspect(count)) {
+            Ok(executed) => transaction
+                .execute(iter::once(*record).inspect(count))
+                .map(|recorded| executed + recorded),
+            Err(err) => Err(err),
+        }
+    };
+    reset_timeouts(transaction, std::slice::from_ref(migration));
+    result
+}
+
+// resets the timeouts the migrations set on the session of the connection whether they were applied or not, as they
+// outlive them on pooled connections, see `Migration::reset_timeout`
+fn reset_timeouts<T: Transaction>(transaction: &mut T, migrations: &[Migration]) {
+    let resets: BTreeSet<&str> = migrations
+        .iter()
+        .filter_map(Migration::reset_timeout)
+        .collect();
+    for reset in resets {
+        if let Err(err) = transaction.execute(iter::once(reset)) {
+            log::warn!("error resetting the timeout of the migrations: {}", err);
+        }
+    }
 }
 
 pub trait Query<T>: Transaction {
     fn query(&mut self, query: &str) -> Result<T, Self::Error>;
 }
 
+// borrow the statements of the migration sql instead of copying them into the batch,
+// skipped migrations only update the schema migrations table
+fn migration_updates<'a>(
+    migration: &'a Migration,
+    insert_migration: &'a str,
+    fake: bool,
+    splitter: &Splitter,
+) -> impl Iterator<Item = &'a str> {
+    applied_statements(migration, fake, splitter)
+        .into_iter()
+        .chain(iter::once(insert_migration))
+}
+
 pub fn migrate<T: Transaction>(
     transaction: &mut T,
     migrations: Vec<Migration>,
@@ -27,10 +139,41 @@ pub fn migrate<T: Transaction>(
     migration_table_name: &str,
     grouped: bool,
 ) -> Result<Report, Error> {
-    let mut migration_batch = Vec::new();
-    let mut applied_migrations = Vec::new();
+    migrate_with_dialect(
+        transaction,
+        migrations,
+        target,
+        migration_table_name,
+        grouped,
+        None,
+    )
+}
+
+// applies the migrations recording the user of the connection with the given Dialect as the one applying them
+pub(crate) fn migrate_with_dialect<T: Transaction>(
+    transaction: &mut T,
+    migrations: Vec<Migration>,
+    target: Target,
+    migration_table_name: &str,
+    grouped: bool,
+    dialect: Option<Dialect>,
+) -> Result<Report, Error> {
+    let fake = matches!(target, Target::Fake | Target::FakeVersion(_));
+    #[cfg(feature = "metrics")]
+    if !fake {
+        crate::metrics::pending(migrations.len());
+    }
+    let mut migrations_to_apply = Vec::new();
+    let mut insert_migrations = Vec::new();
 
     for mut migration in migrations.into_iter() {
+        if let Some(steps) = target.steps() {
+            if migrations_to_apply.len() >= steps as usize {
+                log::info!("stopping after {} migrations, due to user option", steps);
+                break;
+            }
+        }
+
         if let Target::Version(input_target) | Target::FakeVersion(input_target) = target {
             if input_target < migration.version() {
                 log::info!(
@@ -41,30 +184,43 @@ pub fn migrate<T: Transaction>(
             }
         }
 
-        log::info!("applying migration: {}", migration);
+        if migration.is_skipped() {
+            log::info!("skipping migration: {}", migration);
+        } else {
+            match migration.description() {
+                Some(description) => {
+                    log::info!("applying migration: {} - {}", migration, description)
+                }
+                None => log::info!("applying migration: {}", migration),
+            }
+        }
         migration.set_applied();
-        let insert_migration = insert_migration_query(&migration, migration_table_name);
-        let migration_sql = migration.sql().expect("sql must be Some!").to_string();
+        insert_migrations.push(insert_migration_query(
+            &migration,
+            dialect,
+            migration_table_name,
+        ));
+        migrations_to_apply.push(migration);
+    }
 
-        // If Target is Fake, we only update schema migrations table
-        if !matches!(target, Target::Fake | Target::FakeVersion(_)) {
-            applied_migrations.push(migration);
-            migration_batch.push(migration_sql);
-        }
-        migration_batch.push(insert_migration);
+    // If Target is Fake, we only update schema migrations table
+    let applied_migrations: &[Migration] = if fake { &[] } else { &migrations_to_apply };
+    assert_no_async_migrations(applied_migrations)?;
+    if grouped {
+        assert_transactional(applied_migrations)?;
     }
 
     match (target, grouped) {
         (Target::Fake | Target::FakeVersion(_), _) => {
             log::info!("not going to apply any migration as fake flag is enabled");
         }
-        (Target::Latest | Target::Version(_), true) => {
+        (Target::Latest | Target::Version(_) | Target::StepsForward(_) | Target::Next, true) => {
             log::info!(
                 "going to apply batch migrations in single transaction: {:#?}",
                 applied_migrations.iter().map(ToString::to_string)
             );
         }
-        (Target::Latest | Target::Version(_), false) => {
+        (Target::Latest | Target::Version(_) | Target::StepsForward(_) | Target::Next, false) => {
             log::info!(
                 "preparing to apply {} migrations: {:#?}",
                 applied_migrations.len(),
@@ -73,35 +229,429 @@ pub fn migrate<T: Transaction>(
         }
     };
 
+    let splitter = transaction.splitter();
+    let migration_batch = || migrations_to_apply.iter().zip(insert_migrations.iter());
+    // the connections execute the updates one at a time, the statement that failed is the last one executed
+    let executed = AtomicUsize::new(0);
     if grouped {
-        transaction
-            .execute(migration_batch.iter().map(Deref::deref))
-            .migration_err("error applying migrations", None)?;
+        #[cfg(feature = "tracing")]
+        let (span, started) = (
+            crate::tracing::grouped_span(applied_migrations),
+            Instant::now(),
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let mut serialization_failures = 0;
+        while let Err(err) = transaction.execute(
+            migration_batch()
+                .flat_map(|(migration, insert_migration)| {
+                    // the timeout set on the session is reset before the next migrations of the group
+                    migration_updates(migration, insert_migration, fake, &splitter)
+                        .chain(migration.reset_timeout().filter(|_| !fake))
+                })
+                .inspect(|_| {
+                    executed.fetch_add(1, Ordering::Relaxed);
+                }),
+        ) {
+            let is_timeout = T::is_timeout(&err);
+            let backoff = serialization_failure_backoff(
+                T::is_serialization_failure(&err),
+                &mut serialization_failures,
+            )
+            .ok_or(err)
+            .migration_err("error applying migrations", None)
+            .map_err(|err| {
+                reset_timeouts(transaction, applied_migrations);
+                #[cfg(feature = "metrics")]
+                if !fake {
+                    crate::metrics::failed(None);
+                }
+                let statement = failed_statement(
+                    &migrations_to_apply,
+                    fake,
+                    &splitter,
+                    executed.load(Ordering::Relaxed),
+                );
+                timeout_error(is_timeout, &migrations_to_apply, statement.as_ref(), None)
+                    .unwrap_or(err)
+                    .with_statement(statement)
+            })?;
+            executed.store(0, Ordering::Relaxed);
+            log::warn!(
+                "serialization failure applying migrations, retrying in {:?} ({}/{})",
+                backoff,
+                serialization_failures,
+                SERIALIZATION_FAILURE_RETRIES
+            );
+            thread::sleep(backoff);
+        }
+        #[cfg(feature = "metrics")]
+        if !fake {
+            crate::metrics::applied_grouped(migrations_to_apply.len());
+        }
+        #[cfg(feature = "tracing")]
+        crate::tracing::record_duration(&span, started.elapsed());
+    } else {
+        // the statements of each migration are applied on the same transaction as its insert on the schema
+        // history table
+        let mut execution_times = Vec::new();
+        for (i, (migration, insert_migration)) in migration_batch().enumerate() {
+            #[cfg(feature = "tracing")]
+            let span = crate::tracing::migration_span(migration);
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            let mut attempt = 0;
+            let mut serialization_failures = 0;
+            let mut started = Instant::now();
+            while let Err(err) = execute_updates(
+                transaction,
+                migration,
+                migration_updates(migration, insert_migration, fake, &splitter),
+                &executed,
+            ) {
+                if let Some(backoff) = serialization_failure_backoff(
+                    T::is_serialization_failure(&err),
+                    &mut serialization_failures,
+                ) {
+                    log::warn!(
+                        "serialization failure applying migration {}, retrying in {:?} ({}/{}): {}",
+                        migration,
+                        backoff,
+                        serialization_failures,
+                        SERIALIZATION_FAILURE_RETRIES,
+                        err
+                    );
+                    thread::sleep(backoff);
+                    started = Instant::now();
+                    continue;
+                }
+                if attempt >= migration.retries() {
+                    #[cfg(feature = "metrics")]
+                    if !fake {
+                        crate::metrics::failed(Some(migration));
+                    }
+                    let statement = failed_statement(
+                        std::slice::from_ref(migration),
+                        fake,
+                        &splitter,
+                        executed.load(Ordering::Relaxed),
+                    );
+                    let applied = applied_migrations.get(0..i).unwrap_or_default();
+                    if let Some(timeout) = timeout_error(
+                        T::is_timeout(&err),
+                        std::slice::from_ref(migration),
+                        statement.as_ref(),
+                        Some(applied),
+                    ) {
+                        return Err(timeout.with_statement(statement));
+                    }
+                    return Err(err)
+                        .migration_err("error applying update", Some(applied))
+                        .map_err(|err| err.with_statement(statement));
+                }
+                attempt += 1;
+                log::warn!(
+                    "error applying migration {}, retrying in {:?} ({}/{}): {}",
+                    migration,
+                    migration.backoff(),
+                    attempt,
+                    migration.retries(),
+                    err
+                );
+                thread::sleep(migration.backoff());
+                started = Instant::now();
+            }
+            let execution_time = started.elapsed();
+            #[cfg(feature = "metrics")]
+            if !fake {
+                crate::metrics::applied(migration, execution_time);
+            }
+            #[cfg(feature = "tracing")]
+            crate::tracing::record_duration(&span, execution_time);
+            execution_times.push((execution_time, OffsetDateTime::now_utc()));
+            record_execution_time(
+                transaction,
+                migration,
+                execution_time,
+                dialect,
+                migration_table_name,
+            );
+        }
+        for (migration, (execution_time, finished_on)) in
+            migrations_to_apply.iter_mut().zip(execution_times)
+        {
+            migration.set_execution_time(execution_time, finished_on);
+        }
+    }
+
+    if fake {
+        migrations_to_apply.clear();
+    }
+
+    Ok(Report::new(migrations_to_apply))
+}
+
+// records how long applying the migration took on its own transaction, once the migration is committed, a failure
+// to record it doesn't fail the run as the migration is already applied
+fn record_execution_time<T: Transaction>(
+    transaction: &mut T,
+    migration: &Migration,
+    execution_time: Duration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) {
+    let query = execution_time_query(migration, execution_time, dialect, migration_table_name);
+    if let Err(err) = transaction.execute(iter::once(query.as_str())) {
+        log::warn!(
+            "error recording the execution time of migration {}: {}",
+            migration,
+            err
+        );
+    }
+}
+
+// re-executes an applied migration and updates its name, checksum, applied_on, applied_by and execution time
+// on the schema history table
+pub(crate) fn rerun<T: Transaction>(
+    transaction: &mut T,
+    mut migration: Migration,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Report, Error> {
+    log::warn!("force re-running applied migration: {}", migration);
+    assert_no_async_migrations(std::slice::from_ref(&migration))?;
+    migration.set_applied();
+    let update_migration = update_migration_query(&migration, dialect, migration_table_name);
+    let splitter = transaction.splitter();
+    let started = Instant::now();
+    let executed = AtomicUsize::new(0);
+    execute_updates(
+        transaction,
+        &migration,
+        migration_updates(&migration, &update_migration, false, &splitter),
+        &executed,
+    )
+    .migration_err(&format!("error re-running migration {}", migration), None)
+    .map_err(|err| {
+        err.with_statement(failed_statement(
+            std::slice::from_ref(&migration),
+            false,
+            &splitter,
+            executed.load(Ordering::Relaxed),
+        ))
+    })?;
+    let execution_time = started.elapsed();
+    migration.set_execution_time(execution_time, OffsetDateTime::now_utc());
+    record_execution_time(
+        transaction,
+        &migration,
+        execution_time,
+        dialect,
+        migration_table_name,
+    );
+
+    Ok(Report::new(vec![migration]))
+}
+
+// borrow the statements of the down section of the migration, fake rollbacks only update the schema migrations table
+fn rollback_updates<'a>(
+    migration: &'a Migration,
+    delete_migration: &'a str,
+    fake: bool,
+    splitter: &Splitter,
+) -> impl Iterator<Item = &'a str> {
+    let statements = if fake {
+        Vec::new()
     } else {
-        for (i, update) in migration_batch.into_iter().enumerate() {
-            transaction
-                .execute([update.as_str()].into_iter())
-                .migration_err("error applying update", Some(&applied_migrations[0..i / 2]))?;
+        migration_statements(
+            migration,
+            migration.down_sql().expect("down sql must be Some!"),
+            splitter,
+        )
+    };
+    statements.into_iter().chain(iter::once(delete_migration))
+}
+
+// executes the down section of the migrations in the given order and deletes them from the schema history table,
+// each migration on its own transaction unless they are grouped
+fn rollback<T: Transaction>(
+    transaction: &mut T,
+    migrations: &[Migration],
+    grouped: bool,
+    fake: bool,
+    dialect: Option<Dialect>,
+    migration_table_name: &str,
+) -> Result<Report, Error> {
+    let delete_migrations: Vec<String> = migrations
+        .iter()
+        .map(|migration| delete_migration_query(migration, dialect, migration_table_name))
+        .collect();
+    let splitter = transaction.splitter();
+    let rollback_batch = migrations.iter().zip(delete_migrations.iter());
+
+    if grouped || fake {
+        if fake {
+            log::info!("not going to roll back any migration as fake flag is enabled");
+        } else {
+            log::info!(
+                "going to roll back batch migrations in single transaction: {:#?}",
+                migrations.iter().map(ToString::to_string)
+            );
+            assert_transactional(migrations)?;
         }
+        transaction
+            .execute(rollback_batch.flat_map(|(migration, delete_migration)| {
+                rollback_updates(migration, delete_migration, fake, &splitter)
+            }))
+            .migration_err("error rolling back migrations", None)?;
+        let rolled_back = if fake {
+            Vec::new()
+        } else {
+            migrations.to_vec()
+        };
+        return Ok(Report::new(rolled_back));
     }
 
-    Ok(Report::new(applied_migrations))
+    for (i, (migration, delete_migration)) in rollback_batch.enumerate() {
+        log::info!("rolling back migration: {}", migration);
+        execute_updates(
+            transaction,
+            migration,
+            rollback_updates(migration, delete_migration, false, &splitter),
+            &AtomicUsize::new(0),
+        )
+        .migration_err(
+            &format!("error rolling back migration {}", migration),
+            Some(&migrations[..i]),
+        )?;
+    }
+    Ok(Report::new(migrations.to_vec()))
+}
+
+// asserts the checks with the statements of the given Dialect, that of the config instead of its connection when
+// migrating from a Config
+pub(crate) fn assert_checks<T: Transaction>(
+    transaction: &mut T,
+    dialect: Option<Dialect>,
+    checks: &[String],
+) -> Result<(), Error> {
+    if checks.is_empty() {
+        return Ok(());
+    }
+    let dialect = dialect.ok_or_else(|| Error::new(Kind::UnknownCheckDialect, None))?;
+    for (i, check) in checks.iter().enumerate() {
+        transaction
+            .execute(iter::once(dialect.check_statements(i + 1, check).as_str()))
+            .migration_err(&format!("error asserting check {}", check), None)?;
+    }
+    Ok(())
 }
 
 pub trait Migrate: Query<Vec<Migration>>
 where
     Self: Sized,
 {
+    /// The database dialect of the connection, used to generate the sql of migrations written for multiple databases.
+    /// Connections that don't report it can only run migrations with static sql.
+    fn dialect(&self) -> Option<Dialect> {
+        None
+    }
+
+    /// Checks the integrity of the database after migrating, see [`Runner::set_integrity_checks`].
+    /// Connections that don't support it report no issues.
+    ///
+    /// [`Runner::set_integrity_checks`]: crate::Runner::set_integrity_checks
+    fn check_integrity(&mut self) -> Result<Vec<IntegrityIssue>, Error> {
+        Ok(Vec::new())
+    }
+
+    /// Introspects the schema of the database after migrating, to write its snapshot, see
+    /// [`Runner::set_snapshot_path`]. Connections that can't introspect their schema return None.
+    ///
+    /// [`Runner::set_snapshot_path`]: crate::Runner::set_snapshot_path
+    fn snapshot_schema(&mut self) -> Result<Option<Schema>, Error> {
+        Ok(None)
+    }
+
+    /// Configures the connection before the migrations are run on it, executing the given PRAGMAs and loading the
+    /// given extensions on sqlite connections, see [`Runner::set_sqlite_pragmas`]. Other connections ignore them.
+    ///
+    /// [`Runner::set_sqlite_pragmas`]: crate::Runner::set_sqlite_pragmas
+    fn setup_sqlite(&mut self, pragmas: &[String], extensions: &[PathBuf]) -> Result<(), Error> {
+        let _ = (pragmas, extensions);
+        Ok(())
+    }
+
+    /// Asserts verification checks, each a `SELECT` returning a single value that must be true, i.e. not false,
+    /// 0 or NULL, the same way as the `-- refinery:check` section of migrations, see [`Rollout::set_checks`].
+    /// Checks need the [`Dialect`] of the connection.
+    ///
+    /// [`Rollout::set_checks`]: crate::Rollout::set_checks
+    fn assert_checks(&mut self, checks: &[String]) -> Result<(), Error> {
+        let dialect = self.dialect();
+        assert_checks(self, dialect, checks)
+    }
+
+    /// Creates the schema history table if it doesn't exist, and adds the `execution_time_ms`, `applied_by` and
+    /// `skipped` columns to the ones created by previous versions
     fn assert_migrations_table(&mut self, migration_table_name: &str) -> Result<usize, Error> {
-        // Needed cause some database vendors like Mssql have a non sql standard way of checking the migrations table,
-        // thou on this case it's just to be consistent with the async trait `AsyncMigrate`
-        self.execute(
-            [ASSERT_MIGRATIONS_TABLE_QUERY
-                .replace("%MIGRATION_TABLE_NAME%", migration_table_name)
-                .as_str()]
-            .into_iter(),
-        )
-        .migration_err("error asserting migrations table", None)
+        self.assert_migrations_table_with(migration_table_name, &DefaultSchemaHistoryDdl)
+    }
+
+    /// Fails with [`Kind::LegacyPrimaryKey`] if the schema history table has the version alone as primary key, as
+    /// created before namespaces, so the migrations of different namespaces can't share a version, see
+    /// [`Runner::set_namespace`]
+    ///
+    /// [`Runner::set_namespace`]: crate::Runner::set_namespace
+    fn assert_namespaced_migrations_table(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<(), Error> {
+        let Some(query) = legacy_primary_key_query(self.dialect(), migration_table_name)? else {
+            return Ok(());
+        };
+        let legacy = self
+            .query(&query)
+            .migration_err("error checking the migrations table primary key", None)?;
+        if legacy.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                Kind::LegacyPrimaryKey(migration_table_name.to_string()),
+                None,
+            ))
+        }
+    }
+
+    /// Creates the schema history table with the given DDL if it doesn't exist, see [`assert_migrations_table`]
+    ///
+    /// [`assert_migrations_table`]: Migrate::assert_migrations_table
+    fn assert_migrations_table_with(
+        &mut self,
+        migration_table_name: &str,
+        ddl: &dyn SchemaHistoryDdl,
+    ) -> Result<usize, Error> {
+        let statements = ddl.create_table_statements(migration_table_name, self.dialect());
+        let count = self
+            .execute(statements.iter().map(String::as_str))
+            .migration_err("error asserting migrations table", None)?;
+
+        let dialect = self.dialect();
+        for (probe, upgrade) in upgrade_migrations_table_queries(dialect, migration_table_name)? {
+            let missing = if probes_missing_columns(dialect) {
+                !self
+                    .query(&probe)
+                    .migration_err("error upgrading migrations table", None)?
+                    .is_empty()
+            } else {
+                self.query(&probe).is_err()
+            };
+            if missing {
+                self.execute(iter::once(upgrade.as_str()))
+                    .migration_err("error upgrading migrations table", None)?;
+            }
+        }
+        Ok(count)
     }
 
     fn get_last_applied_migration(
@@ -132,6 +682,19 @@ where
         Ok(migrations)
     }
 
+    /// Queries the migrations recorded as skipped on the schema history table, see [`Runner::get_skipped_migrations`]
+    ///
+    /// [`Runner::get_skipped_migrations`]: crate::Runner::get_skipped_migrations
+    fn get_skipped_migrations(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<Migration>, Error> {
+        self.query(
+            &GET_SKIPPED_MIGRATIONS_QUERY.replace("%MIGRATION_TABLE_NAME%", migration_table_name),
+        )
+        .migration_err("error getting skipped migrations", None)
+    }
+
     fn get_unapplied_migrations(
         &mut self,
         migrations: &[Migration],
@@ -157,6 +720,64 @@ where
         Ok(migrations)
     }
 
+    /// Rolls back the given applied migrations in order, executing their `-- refinery:down` section and deleting
+    /// them from the schema history table, see [`Runner::rollback`]. Fake rollbacks only update the schema history table.
+    ///
+    /// [`Runner::rollback`]: crate::Runner::rollback
+    fn rollback(
+        &mut self,
+        migrations: &[Migration],
+        grouped: bool,
+        fake: bool,
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        let dialect = self.dialect();
+        rollback(
+            self,
+            migrations,
+            grouped,
+            fake,
+            dialect,
+            migration_table_name,
+        )
+    }
+
+    /// Inserts the given applied migrations on the schema history table in a single transaction without running
+    /// them, see [`Runner::import_history`]
+    ///
+    /// [`Runner::import_history`]: crate::Runner::import_history
+    fn import_history(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<usize, Error> {
+        let dialect = self.dialect();
+        let inserts: Vec<String> = migrations
+            .iter()
+            .map(|migration| insert_migration_query(migration, dialect, migration_table_name))
+            .collect();
+        self.execute(inserts.iter().map(String::as_str))
+            .migration_err("error importing schema history", None)
+    }
+
+    /// Applies the given pending migrations in order on their own transactions, without verifying them against the
+    /// schema history table, as [`Migrate::get_unapplied_migrations`] already did
+    fn apply_pending(
+        &mut self,
+        migrations: &[Migration],
+        migration_table_name: &str,
+    ) -> Result<Report, Error> {
+        let dialect = self.dialect();
+        migrate_with_dialect(
+            self,
+            migrations.to_vec(),
+            Target::Latest,
+            migration_table_name,
+            false,
+            dialect,
+        )
+    }
+
     fn migrate(
         &mut self,
         migrations: &[Migration],
@@ -173,10 +794,132 @@ where
             migration_table_name,
         )?;
 
-        if grouped || matches!(target, Target::Fake | Target::FakeVersion(_)) {
-            migrate(self, migrations, target, migration_table_name, true)
-        } else {
-            migrate(self, migrations, target, migration_table_name, false)
-        }
+        let grouped = grouped || matches!(target, Target::Fake | Target::FakeVersion(_));
+        let dialect = self.dialect();
+        migrate_with_dialect(
+            self,
+            migrations,
+            target,
+            migration_table_name,
+            grouped,
+            dialect,
+        )
+    }
+}
+
+/// The version, name, applied_on and checksum columns of a row of the schema history table
+pub type SchemaHistoryRow = (i32, String, String, String);
+
+/// A minimal connection refinery can migrate, for the ones it has no driver for, i.e. the connections of another
+/// version of a driver crate than the one refinery re-exports, which don't implement [`Migrate`].
+/// Every type implementing it implements [`Migrate`], as the foreign connections can't implement refinery traits
+/// they are wrapped in a newtype:
+///
+/// ```rust,ignore
+/// struct MysqlConnection(mysql::PooledConn);
+///
+/// impl RefineryConnection for MysqlConnection {
+///     type Error = mysql::Error;
+///
+///     fn execute_in_transaction(&mut self, queries: &[&str]) -> Result<(), Self::Error> {
+///         let mut transaction = self.0.start_transaction(mysql::TxOpts::default())?;
+///         queries.iter().try_for_each(|query| transaction.query_drop(query))?;
+///         transaction.commit()
+///     }
+///
+///     fn query_rows(&mut self, query: &str) -> Result<Vec<SchemaHistoryRow>, Self::Error> {
+///         self.0.query(query)
+///     }
+/// }
+/// ```
+pub trait RefineryConnection {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Executes the queries in a single transaction, rolling it back if any of them fails
+    fn execute_in_transaction(&mut self, queries: &[&str]) -> Result<(), Self::Error>;
+
+    /// Runs a query on the schema history table, returning its rows
+    fn query_rows(&mut self, query: &str) -> Result<Vec<SchemaHistoryRow>, Self::Error>;
+
+    /// Executes the queries one at a time outside of a transaction, see [`Transaction::execute_without_transaction`].
+    /// By default each is executed on its own transaction
+    fn execute_without_transaction(&mut self, queries: &[&str]) -> Result<(), Self::Error> {
+        queries
+            .iter()
+            .try_for_each(|query| self.execute_in_transaction(&[query]))
+    }
+
+    /// The database dialect of the connection, see [`Migrate::dialect`]
+    fn dialect(&self) -> Option<Dialect> {
+        None
+    }
+
+    /// Whether the error is a serialization failure the transaction is retried on,
+    /// see [`Transaction::is_serialization_failure`]
+    fn is_serialization_failure(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+
+    /// Whether the error is a statement cancelled for exceeding its timeout, see [`Transaction::is_timeout`]
+    fn is_timeout(error: &Self::Error) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = error;
+        false
+    }
+}
+
+impl<C: RefineryConnection> Transaction for C {
+    type Error = C::Error;
+
+    fn execute<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let queries: Vec<&str> = queries.collect();
+        self.execute_in_transaction(&queries)?;
+        Ok(queries.len())
+    }
+
+    fn execute_without_transaction<'a, T: Iterator<Item = &'a str>>(
+        &mut self,
+        queries: T,
+    ) -> Result<usize, Self::Error> {
+        let queries: Vec<&str> = queries.collect();
+        RefineryConnection::execute_without_transaction(self, &queries)?;
+        Ok(queries.len())
+    }
+
+    fn is_serialization_failure(error: &C::Error) -> bool {
+        C::is_serialization_failure(error)
+    }
+
+    fn is_timeout(error: &C::Error) -> bool {
+        C::is_timeout(error)
+    }
+}
+
+impl<C: RefineryConnection> Query<Vec<Migration>> for C {
+    fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        let rows = self.query_rows(query)?;
+        Ok(rows
+            .into_iter()
+            .map(|(version, name, applied_on, checksum)| {
+                // Safe to call unwrap, as we stored it in RFC3339 format on the database
+                let applied_on = OffsetDateTime::parse(&applied_on, &Rfc3339).unwrap();
+                Migration::applied_with_checksum(version, name, applied_on, &checksum)
+            })
+            .collect())
+    }
+}
+
+impl<C: RefineryConnection> Migrate for C {
+    fn dialect(&self) -> Option<Dialect> {
+        RefineryConnection::dialect(self)
     }
 }
@@ -1,30 +1,212 @@
 use crate::error::{Error, Kind};
-use crate::runner::Type;
+use crate::runner::{Dialect, Type};
+#[cfg(feature = "fs")]
 use crate::Migration;
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::ffi::OsStr;
+#[cfg(feature = "fs")]
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::OnceLock;
+use std::time::Duration;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+#[cfg(feature = "fs")]
 use walkdir::{DirEntry, WalkDir};
 
-const STEM_RE: &'static str = r"^([U|V])(\d+(?:\.\d+)?)__(\w+)";
+/// The name can contain Unicode word characters and dashes, i.e. `V3__añadir-usuarios`, and may be followed by `__`
+/// separated metadata segments, i.e. `V12__add_users__TICKET-123__alice`.
+const STEM_RE: &str = r"^([U|V|S])(\d+(?:\.\d+)?)__(\w[\w-]*(?:__[\w-]+)*)";
+
+/// Timestamp migrations are named after the UTC time they were created at, i.e. `20250501_000000_initial`,
+/// see [`MigrationNaming::Timestamp`].
+const TIMESTAMP_STEM_RE: &str = r"^(\d{8})_(\d{6})_(\w[\w-]*(?:__[\w-]+)*)";
+
+/// Matches the optional database dialect of SQL migration variants, i.e. `V7__add_fulltext.postgres.sql`
+const DIALECT_RE: &str = r"(?:\.(postgres|mysql|sqlite|mssql|cockroach|clickhouse|duckdb|oracle))?";
+
+/// Matches the `${name}` placeholders of migrations, see [`Runner::set_placeholders`](crate::Runner::set_placeholders).
+fn placeholder_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\$\{([A-Za-z_][\w.-]*)\}").unwrap())
+}
 
 /// Matches the stem of a migration file.
 fn file_stem_re() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
-    RE.get_or_init(|| Regex::new(STEM_RE).unwrap())
+    RE.get_or_init(|| Regex::new([STEM_RE, DIALECT_RE, r"(?:\.|$)"].concat().as_str()).unwrap())
+}
+
+/// Matches the stem of a timestamp migration file.
+fn timestamp_stem_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            [TIMESTAMP_STEM_RE, DIALECT_RE, r"(?:\.|$)"]
+                .concat()
+                .as_str(),
+        )
+        .unwrap()
+    })
+}
+
+/// Matches the name a migration is recorded with on the schema history table, prefixed with its namespace if it has
+/// one, i.e. `billing/add-users`.
+pub(crate) fn recorded_name_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:[\w-]+/)?\w[\w-]*(?:__[\w-]+)*$").unwrap())
 }
 
 /// Matches the stem + extension of a SQL migration file.
+#[cfg(feature = "fs")]
 fn file_re_sql() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
-    RE.get_or_init(|| Regex::new([STEM_RE, r"\.sql$"].concat().as_str()).unwrap())
+    RE.get_or_init(|| {
+        Regex::new(
+            [
+                r"(?:",
+                STEM_RE,
+                r"|",
+                TIMESTAMP_STEM_RE,
+                r")",
+                DIALECT_RE,
+                r"\.sql$",
+            ]
+            .concat()
+            .as_str(),
+        )
+        .unwrap()
+    })
+}
+
+/// Matches the stem + one of the given extensions of a SQL migration file, see [`MigrationLoader`](crate::MigrationLoader).
+#[cfg(feature = "fs")]
+pub(crate) fn file_re_extensions(extensions: &[String]) -> Regex {
+    let extensions: Vec<String> = extensions
+        .iter()
+        .map(|extension| regex::escape(extension.trim_start_matches('.')))
+        .collect();
+    Regex::new(
+        [
+            r"(?:",
+            STEM_RE,
+            r"|",
+            TIMESTAMP_STEM_RE,
+            r")",
+            DIALECT_RE,
+            r"\.(?:",
+            &extensions.join("|"),
+            r")$",
+        ]
+        .concat()
+        .as_str(),
+    )
+    .unwrap()
 }
 
 /// Matches the stem + extension of any migration file.
+#[cfg(feature = "fs")]
 fn file_re_all() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
-    RE.get_or_init(|| Regex::new([STEM_RE, r"\.(rs|sql)$"].concat().as_str()).unwrap())
+    RE.get_or_init(|| {
+        Regex::new(
+            [
+                r"(?:",
+                STEM_RE,
+                r"|",
+                TIMESTAMP_STEM_RE,
+                r")(?:",
+                DIALECT_RE,
+                r"\.sql|\.rs)$",
+            ]
+            .concat()
+            .as_str(),
+        )
+        .unwrap()
+    })
+}
+
+/// The naming scheme of migration files, a project must name all its migrations with the same one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MigrationNaming {
+    /// `V{version}__{name}`, or `U{version}__{name}` for unversioned migrations, i.e. `V1__initial`
+    #[default]
+    Versioned,
+    /// `{YYYYMMDD}_{HHMMSS}_{name}` after the UTC time the migration was created at, i.e. `20250501_000000_initial`,
+    /// so migrations created on different branches don't collide. Their version is the Unix timestamp of that time,
+    /// which fits the version column of the schema history table until 2038-01-19
+    Timestamp,
+}
+
+impl FromStr for MigrationNaming {
+    type Err = Error;
+
+    fn from_str(naming: &str) -> Result<MigrationNaming, Error> {
+        match naming {
+            "versioned" => Ok(MigrationNaming::Versioned),
+            "timestamp" => Ok(MigrationNaming::Timestamp),
+            _ => Err(Error::new(
+                Kind::ConfigError(format!(
+                    "invalid migration naming {}, it must be versioned or timestamp",
+                    naming
+                )),
+                None,
+            )),
+        }
+    }
+}
+
+/// Get the [`MigrationNaming`] scheme of a migration filename stem, `None` if it doesn't follow any
+pub fn parse_migration_naming(name: &str) -> Option<MigrationNaming> {
+    if file_stem_re().is_match(name) {
+        Some(MigrationNaming::Versioned)
+    } else if timestamp_stem_re().is_match(name) {
+        Some(MigrationNaming::Timestamp)
+    } else {
+        None
+    }
+}
+
+// the version of a timestamp migration, the Unix timestamp of its `YYYYMMDD` date and `HHMMSS` time
+fn timestamp_version(date: &str, time: &str) -> Option<i32> {
+    let number = |digits: &str| digits.parse::<u8>().ok();
+    let date = Date::from_calendar_date(
+        date[..4].parse().ok()?,
+        Month::try_from(number(&date[4..6])?).ok()?,
+        number(&date[6..])?,
+    )
+    .ok()?;
+    let time = Time::from_hms(
+        number(&time[..2])?,
+        number(&time[2..4])?,
+        number(&time[4..])?,
+    )
+    .ok()?;
+    let timestamp = PrimitiveDateTime::new(date, time)
+        .assume_utc()
+        .unix_timestamp();
+    i32::try_from(timestamp)
+        .ok()
+        .filter(|version| *version >= 0)
+}
+
+/// Format the version of a timestamp migration as the `YYYYMMDD_HHMMSS` prefix of its name,
+/// see [`MigrationNaming::Timestamp`]
+pub fn format_timestamp_version(version: i32) -> String {
+    // versions of timestamp migrations are always valid Unix timestamps
+    let time = OffsetDateTime::from_unix_timestamp(version.into()).unwrap();
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
 }
 
 /// enum containing the migration types used to search for migrations
@@ -34,8 +216,9 @@ pub enum MigrationType {
     Sql,
 }
 
+#[cfg(feature = "fs")]
 impl MigrationType {
-    fn file_match_re(&self) -> &'static Regex {
+    pub(crate) fn file_match_re(&self) -> &'static Regex {
         match self {
             MigrationType::All => file_re_all(),
             MigrationType::Sql => file_re_sql(),
@@ -44,10 +227,17 @@ impl MigrationType {
 }
 
 /// Parse a migration filename stem into a prefix, version, and name.
+/// Timestamp migrations, see [`MigrationNaming::Timestamp`], are versioned ones with the timestamp as version.
 pub fn parse_migration_name(name: &str) -> Result<(Type, i32, String), Error> {
+    if let Some(captures) = timestamp_stem_re().captures(name) {
+        let version = timestamp_version(&captures[1], &captures[2])
+            .ok_or_else(|| Error::new(Kind::InvalidVersion, None))?;
+        return Ok((Type::Versioned, version, captures[3].into()));
+    }
+
     let captures = file_stem_re()
         .captures(name)
-        .filter(|caps| caps.len() == 4)
+        .filter(|caps| caps.len() == 5)
         .ok_or_else(|| Error::new(Kind::InvalidName, None))?;
     let version: i32 = captures[2]
         .parse()
@@ -57,16 +247,225 @@ pub fn parse_migration_name(name: &str) -> Result<(Type, i32, String), Error> {
     let prefix = match &captures[1] {
         "V" => Type::Versioned,
         "U" => Type::Unversioned,
+        "S" => Type::Seed,
         _ => unreachable!(),
     };
 
     Ok((prefix, version, name))
 }
 
+pub(crate) fn parse_dialect(name: &str) -> Option<Dialect> {
+    match name {
+        "postgres" => Some(Dialect::Postgres),
+        "mysql" => Some(Dialect::Mysql),
+        "sqlite" => Some(Dialect::Sqlite),
+        "mssql" => Some(Dialect::Mssql),
+        "cockroach" => Some(Dialect::Cockroach),
+        "clickhouse" => Some(Dialect::ClickHouse),
+        "duckdb" => Some(Dialect::DuckDb),
+        "oracle" => Some(Dialect::Oracle),
+        _ => None,
+    }
+}
+
+/// Parse the database dialect of a migration variant from its name, i.e. [`Dialect::Postgres`] for `V7__add_fulltext.postgres`.
+/// `None` for migrations that aren't specific to a dialect.
+pub fn parse_migration_dialect(name: &str) -> Option<Dialect> {
+    let captures = file_stem_re()
+        .captures(name)
+        .or_else(|| timestamp_stem_re().captures(name))?;
+    parse_dialect(captures.get(4)?.as_str())
+}
+
+/// Get the name of a migration file found on `location`, migrations on a directory named after a [`Dialect`] are variants for it,
+/// i.e. `migrations/postgres/V7__add_fulltext.sql` is named `V7__add_fulltext.postgres` like `migrations/V7__add_fulltext.postgres.sql`.
+/// This allows sharing the migrations common to every database on `migrations/common` while adding or overriding versions on
+/// `migrations/postgres`.
+#[cfg(feature = "fs")]
+pub fn migration_file_name(location: impl AsRef<Path>, path: &Path) -> String {
+    //safe to call unwrap as find_migration_filenames returns canonical paths
+    let file_stem = path
+        .file_stem()
+        .and_then(|file| file.to_os_string().into_string().ok())
+        .unwrap();
+
+    let directory = location
+        .as_ref()
+        .canonicalize()
+        .ok()
+        .and_then(|location| path.parent()?.strip_prefix(location).ok()?.file_name())
+        .and_then(OsStr::to_str);
+
+    dialect_file_name(file_stem, directory)
+}
+
+/// Get the name of a migration file with the given stem on the given directory, suffixed with the dialect the
+/// directory is named after, see [`migration_file_name`]
+#[cfg(feature = "fs")]
+pub(crate) fn dialect_file_name(file_stem: String, directory: Option<&str>) -> String {
+    match directory.and_then(parse_dialect) {
+        Some(dialect) if parse_migration_dialect(&file_stem).is_none() => {
+            format!("{}.{}", file_stem, dialect)
+        }
+        _ => file_stem,
+    }
+}
+
+// removes the `--` comments of the sql
+pub(crate) fn strip_comments(sql: &str) -> String {
+    sql.lines()
+        .map(|line| line.split_once("--").map_or(line, |(code, _)| code))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse the `-- key: value` lines of the leading comment block of a migration sql, i.e.
+/// ```sql
+/// -- author: alice
+/// -- ticket: TICKET-123
+/// CREATE TABLE users(id int);
+/// ```
+/// The block ends on the first line that isn't a comment, comment lines without a key are ignored.
+pub(crate) fn parse_header(sql: &str) -> impl Iterator<Item = (&str, &str)> {
+    sql.lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| line.starts_with("--"))
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches('-').split_once(':')?;
+            let key = key.trim();
+            let is_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+            is_key.then(|| (key, value.trim()))
+        })
+}
+
+// replaces the `${name}` placeholders of the sql with their values, placeholders without one are kept unless strict,
+// which fails with the name of the first of them
+pub(crate) fn replace_placeholders<'a>(
+    sql: &'a str,
+    placeholders: &HashMap<String, String>,
+    strict: bool,
+) -> Result<Cow<'a, str>, String> {
+    let mut replaced = String::new();
+    let mut last = 0;
+    for captures in placeholder_re().captures_iter(sql) {
+        let (placeholder, name) = (captures.get(0).unwrap(), &captures[1]);
+        let value = match placeholders.get(name) {
+            Some(value) => value,
+            None if strict => return Err(name.to_string()),
+            None => continue,
+        };
+        replaced.push_str(&sql[last..placeholder.start()]);
+        replaced.push_str(value);
+        last = placeholder.end();
+    }
+    if last == 0 {
+        return Ok(Cow::Borrowed(sql));
+    }
+    replaced.push_str(&sql[last..]);
+    Ok(Cow::Owned(replaced))
+}
+
+// splits the sql of a migration after its header, the leading block of comment lines
+pub(crate) fn split_header(sql: &str) -> (&str, &str) {
+    let mut offset = 0;
+    for line in sql.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with("--") {
+            break;
+        }
+        offset += line.len();
+    }
+    sql.split_at(offset)
+}
+
+// parses durations of directives, i.e. `500ms`, `5s`, `2m` or `1h`
+pub(crate) fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit_start = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = duration.split_at(unit_start);
+    let value: u64 = value.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        "m" => Some(Duration::from_secs(value * 60)),
+        "h" => Some(Duration::from_secs(value * 60 * 60)),
+        _ => None,
+    }
+}
+
+// matches the `-- refinery:{section}` line that starts a section of a migration
+fn is_section_marker(line: &str, section: &str) -> bool {
+    line.trim()
+        .strip_prefix("--")
+        .and_then(|line| line.trim_start_matches('-').split_once(':'))
+        .is_some_and(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("refinery")
+                && value.trim().eq_ignore_ascii_case(section)
+        })
+}
+
+// splits the sql of a migration on its `-- refinery:down` line, returning the sql before it,
+// which is the one applied, and the sql after it that reverts the migration
+pub(crate) fn split_down(sql: &str) -> (&str, Option<&str>) {
+    let mut offset = 0;
+    for line in sql.split_inclusive('\n') {
+        if is_section_marker(line, "down") {
+            return (&sql[..offset], Some(&sql[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    (sql, None)
+}
+
+/// The sql of a migration without its `-- refinery:down` section, as it's embedded with
+/// `embed_migrations!(omit_down = true)`, see [`Migration::down_sql`](crate::Migration::down_sql).
+pub fn strip_down_section(sql: &str) -> &str {
+    split_down(sql).0
+}
+
+// splits the sql of a migration on its `-- refinery:check` line, returning the sql before it and the check
+// statements after it, without the ones that are only comments
+pub(crate) fn split_checks(sql: &str) -> Option<(&str, Vec<&str>)> {
+    let mut offset = 0;
+    for line in sql.split_inclusive('\n') {
+        if is_section_marker(line, "check") {
+            let checks = sql[offset + line.len()..]
+                .split(';')
+                .map(str::trim)
+                .filter(|statement| {
+                    statement.lines().any(|line| {
+                        let line = line.trim();
+                        !line.is_empty() && !line.starts_with("--")
+                    })
+                })
+                .collect();
+            return Some((&sql[..offset], checks));
+        }
+        offset += line.len();
+    }
+    None
+}
+
 /// find migrations on file system recursively across directories given a location and [MigrationType]
+#[cfg(feature = "fs")]
 pub fn find_migration_files(
     location: impl AsRef<Path>,
     migration_type: MigrationType,
+) -> Result<impl Iterator<Item = PathBuf>, Error> {
+    find_migration_files_with_depth(location, migration_type, None)
+}
+
+/// find migrations on file system given a location and [MigrationType], descending at most `max_depth` levels of
+/// subdirectories, i.e. `Some(0)` only finds the migrations directly on the location and `Some(1)` also the ones on
+/// `migrations/2023` or `migrations/payments`, `None` descends every level
+#[cfg(feature = "fs")]
+pub fn find_migration_files_with_depth(
+    location: impl AsRef<Path>,
+    migration_type: MigrationType,
+    max_depth: Option<usize>,
 ) -> Result<impl Iterator<Item = PathBuf>, Error> {
     let location: &Path = location.as_ref();
     let location = location.canonicalize().map_err(|err| {
@@ -78,6 +477,7 @@ pub fn find_migration_files(
 
     let re = migration_type.file_match_re();
     let file_paths = WalkDir::new(location)
+        .max_depth(max_depth.map_or(usize::MAX, |depth| depth.saturating_add(1)))
         .into_iter()
         .filter_map(Result::ok)
         .map(DirEntry::into_path)
@@ -87,7 +487,7 @@ pub fn find_migration_files(
                 Some(file_name) if re.is_match(file_name) => true,
                 Some(file_name) => {
                     log::warn!(
-                        "File \"{}\" does not adhere to the migration naming convention. Migrations must be named in the format [U|V]{{1}}__{{2}}.sql or [U|V]{{1}}__{{2}}.rs, where {{1}} represents the migration version and {{2}} the name.",
+                        "File \"{}\" does not adhere to the migration naming convention. Migrations must be named in the format [U|V|S]{{1}}__{{2}}.sql or [U|V|S]{{1}}__{{2}}.rs, where {{1}} represents the migration version and {{2}} the name, or {{YYYYMMDD}}_{{HHMMSS}}_{{2}} for timestamp migrations.",
                         file_name
                     );
                     false
@@ -99,12 +499,51 @@ pub fn find_migration_files(
     Ok(file_paths)
 }
 
+/// Tells cargo to rebuild the crate when a migration file is added to, removed from or changed on the location,
+/// on any of its subdirectories. To be called from the build script of the crates calling `embed_migrations!`,
+/// which only tracks the migration files that existed when it was expanded:
+///
+/// ```rust,ignore
+/// // build.rs
+/// fn main() {
+///     refinery::rerun_if_migrations_changed("migrations").unwrap();
+/// }
+/// ```
+///
+/// As with any `cargo:rerun-if-changed` line, the build script is then no longer re-run on every change of the package.
+#[cfg(feature = "fs")]
+pub fn rerun_if_migrations_changed(location: impl AsRef<Path>) -> Result<(), Error> {
+    let location: &Path = location.as_ref();
+    let location = location.canonicalize().map_err(|err| {
+        Error::new(
+            Kind::InvalidMigrationPath(location.to_path_buf(), err),
+            None,
+        )
+    })?;
+    println!("cargo:rerun-if-changed={}", location.display());
+    Ok(())
+}
+
 /// Loads SQL migrations from a path. This enables dynamic migration discovery, as opposed to
 /// embedding. The resulting collection is ordered by version.
+/// See [`MigrationLoader`](crate::MigrationLoader) to filter the migration files loaded.
+#[cfg(feature = "fs")]
 pub fn load_sql_migrations(location: impl AsRef<Path>) -> Result<Vec<Migration>, Error> {
-    let migration_files = find_migration_files(location, MigrationType::Sql)?;
+    load_sql_migrations_with_depth(location, None)
+}
+
+/// Loads SQL migrations from a path descending at most `max_depth` levels of subdirectories,
+/// see [`find_migration_files_with_depth`]. The resulting collection is ordered by version.
+#[cfg(feature = "fs")]
+pub fn load_sql_migrations_with_depth(
+    location: impl AsRef<Path>,
+    max_depth: Option<usize>,
+) -> Result<Vec<Migration>, Error> {
+    let location = location.as_ref();
+    let migration_files = find_migration_files_with_depth(location, MigrationType::Sql, max_depth)?;
 
     let mut migrations = vec![];
+    let mut filenames = vec![];
 
     for path in migration_files {
         let sql = std::fs::read_to_string(path.as_path()).map_err(|e| {
@@ -117,25 +556,68 @@ pub fn load_sql_migrations(location: impl AsRef<Path>) -> Result<Vec<Migration>,
             Error::new(kind, None)
         })?;
 
-        //safe to call unwrap as find_migration_filenames returns canonical paths
-        let filename = path
-            .file_stem()
-            .and_then(|file| file.to_os_string().into_string().ok())
-            .unwrap();
-
-        let migration = Migration::unapplied(&filename, &sql)?;
+        let filename = migration_file_name(location, &path);
+        let migration = Migration::unapplied_from(&filename, sql)?;
         migrations.push(migration);
+        filenames.push((filename, path));
     }
 
+    assert_no_conflicts(&filenames)?;
     migrations.sort();
     Ok(migrations)
 }
 
+/// Checks that there's at most one of the given migration files, with their names as returned by
+/// [`migration_file_name`], for each version and database dialect, i.e. `migrations/2023/V12__foo.sql` conflicts
+/// with `migrations/payments/V12__bar.sql`, and `V7__add_fulltext.postgres.sql` with `postgres/V7__add_fulltext.sql`,
+/// seed migrations having their own versions, and that they're all named with the same [`MigrationNaming`] scheme
+#[cfg(feature = "fs")]
+pub fn assert_no_conflicts(migrations: &[(String, PathBuf)]) -> Result<(), Error> {
+    let mut versions = std::collections::HashMap::new();
+    let mut naming: Option<(MigrationNaming, &PathBuf)> = None;
+    for (name, path) in migrations {
+        match (naming, parse_migration_naming(name)) {
+            (Some((naming, other)), Some(current)) if naming != current => {
+                return Err(Error::new(
+                    Kind::MixedNaming(other.clone(), path.clone()),
+                    None,
+                ));
+            }
+            (None, Some(current)) => naming = Some((current, path)),
+            _ => {}
+        }
+
+        let (prefix, version, _) = parse_migration_name(name)?;
+        let dialect = parse_migration_dialect(name);
+        if let Some(other) = versions.insert((prefix == Type::Seed, version, dialect), path) {
+            let (first, second) = if other < path {
+                (other, path)
+            } else {
+                (path, other)
+            };
+            return Err(Error::new(
+                Kind::ConflictingMigrations(first.clone(), second.clone()),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_migration_files, load_sql_migrations, MigrationType};
+    use super::{
+        assert_no_conflicts, find_migration_files, format_timestamp_version, load_sql_migrations,
+        load_sql_migrations_with_depth, migration_file_name, parse_duration, parse_header,
+        parse_migration_dialect, parse_migration_name, parse_migration_naming,
+        rerun_if_migrations_changed, split_checks, split_header, MigrationNaming, MigrationType,
+    };
+    use crate::error::Kind;
+    use crate::runner::Type;
+    use crate::Dialect;
     use std::fs;
     use std::path::PathBuf;
+    use std::time::Duration;
     use tempfile::TempDir;
 
     #[test]
@@ -156,6 +638,15 @@ mod tests {
         assert_eq!(sql2.canonicalize().unwrap(), mods[1]);
     }
 
+    #[test]
+    fn fails_tracking_missing_migrations_location() {
+        let tmp_dir = TempDir::new().unwrap();
+        rerun_if_migrations_changed(tmp_dir.path()).unwrap();
+
+        let err = rerun_if_migrations_changed(tmp_dir.path().join("migrations")).unwrap_err();
+        assert!(matches!(err.kind(), Kind::InvalidMigrationPath(..)));
+    }
+
     #[test]
     fn ignores_mod_files_without_migration_regex_match() {
         let tmp_dir = TempDir::new().unwrap();
@@ -188,6 +679,133 @@ mod tests {
         assert_eq!(sql2.canonicalize().unwrap(), mods[1]);
     }
 
+    #[test]
+    fn finds_sql_migrations_with_metadata() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir(&migrations_dir).unwrap();
+        let sql1 = migrations_dir.join("V1__first__TICKET-123__alice.sql");
+        fs::File::create(&sql1).unwrap();
+        let sql2 = migrations_dir.join("V2__second-dashed.sql");
+        fs::File::create(&sql2).unwrap();
+        let sql3 = migrations_dir.join("V3__-third.sql");
+        fs::File::create(sql3).unwrap();
+
+        let mut mods: Vec<PathBuf> = find_migration_files(migrations_dir, MigrationType::All)
+            .unwrap()
+            .collect();
+        mods.sort();
+        assert_eq!(
+            vec![sql1.canonicalize().unwrap(), sql2.canonicalize().unwrap()],
+            mods
+        );
+    }
+
+    #[test]
+    fn finds_sql_migration_dialect_variants() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir(&migrations_dir).unwrap();
+        let sql1 = migrations_dir.join("V1__first.postgres.sql");
+        fs::File::create(&sql1).unwrap();
+        let sql2 = migrations_dir.join("V1__first.sqlite.sql");
+        fs::File::create(&sql2).unwrap();
+        let sql3 = migrations_dir.join("V2__second.db2.sql");
+        fs::File::create(sql3).unwrap();
+
+        let mut mods: Vec<PathBuf> = find_migration_files(migrations_dir, MigrationType::All)
+            .unwrap()
+            .collect();
+        mods.sort();
+        assert_eq!(
+            vec![sql1.canonicalize().unwrap(), sql2.canonicalize().unwrap()],
+            mods
+        );
+    }
+
+    #[test]
+    fn names_migrations_on_dialect_directories_as_variants() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("postgres");
+        fs::create_dir_all(migrations_dir.join("common")).unwrap();
+        fs::create_dir_all(migrations_dir.join("sqlite")).unwrap();
+        let sql1 = migrations_dir.join("common").join("V1__first.sql");
+        fs::File::create(&sql1).unwrap();
+        let sql2 = migrations_dir.join("sqlite").join("V1__first.sql");
+        fs::File::create(&sql2).unwrap();
+        let sql3 = migrations_dir.join("sqlite").join("V2__second.mysql.sql");
+        fs::File::create(&sql3).unwrap();
+        let sql4 = migrations_dir.join("V3__third.sql");
+        fs::File::create(&sql4).unwrap();
+
+        let name =
+            |path: PathBuf| migration_file_name(&migrations_dir, &path.canonicalize().unwrap());
+        assert_eq!("V1__first", name(sql1));
+        assert_eq!("V1__first.sqlite", name(sql2));
+        assert_eq!("V2__second.mysql", name(sql3));
+        // the dialect of the migrations location itself doesn't make them variants
+        assert_eq!("V3__third", name(sql4));
+
+        let migrations = load_sql_migrations(&migrations_dir).unwrap();
+        let dialects: Vec<_> = migrations.iter().map(|m| m.dialect()).collect();
+        assert_eq!(4, dialects.len());
+        assert!(dialects.contains(&Some(Dialect::Sqlite)));
+    }
+
+    #[test]
+    fn parses_unicode_and_dashed_migration_names() {
+        let (_, version, name) = parse_migration_name("V3__añadir-usuarios.postgres").unwrap();
+        assert_eq!((3, "añadir-usuarios"), (version, name.as_str()));
+        assert_eq!(
+            Some(Dialect::Postgres),
+            parse_migration_dialect("V3__añadir-usuarios.postgres")
+        );
+        let (_, _, name) = parse_migration_name("V4__添加用户__TICKET-12").unwrap();
+        assert_eq!("添加用户__TICKET-12", name);
+        let (_, _, name) = parse_migration_name("20250501_000000_add-users").unwrap();
+        assert_eq!("add-users", name);
+        assert!(matches!(
+            parse_migration_name("V5__-add_users").unwrap_err().kind(),
+            Kind::InvalidName
+        ));
+        assert!(matches!(
+            parse_migration_name("V5__add users").unwrap_err().kind(),
+            Kind::InvalidName
+        ));
+    }
+
+    #[test]
+    fn parses_migration_dialect_variants() {
+        let (_, version, name) = parse_migration_name("V7__add_fulltext.postgres").unwrap();
+        assert_eq!((7, "add_fulltext"), (version, name.as_str()));
+        assert_eq!(
+            Some(Dialect::Postgres),
+            parse_migration_dialect("V7__add_fulltext.postgres")
+        );
+        assert_eq!(
+            Some(Dialect::Sqlite),
+            parse_migration_dialect("V7__add_fulltext.sqlite.sql")
+        );
+        assert_eq!(
+            Some(Dialect::Cockroach),
+            parse_migration_dialect("V7__add_fulltext.cockroach.sql")
+        );
+        assert_eq!(
+            Some(Dialect::ClickHouse),
+            parse_migration_dialect("20250501_000000_initial.clickhouse")
+        );
+        assert_eq!(
+            Some(Dialect::DuckDb),
+            parse_migration_dialect("V7__add_fulltext.duckdb.sql")
+        );
+        assert_eq!(
+            Some(Dialect::Oracle),
+            parse_migration_dialect("V7__add_fulltext.oracle.sql")
+        );
+        assert_eq!(None, parse_migration_dialect("V7__add_fulltext.sql"));
+        assert_eq!(None, parse_migration_dialect("V7__add_fulltext"));
+    }
+
     #[test]
     fn finds_unversioned_migrations() {
         let tmp_dir = TempDir::new().unwrap();
@@ -237,4 +855,201 @@ mod tests {
         assert_eq!(&migrations[0].to_string(), "V1__first");
         assert_eq!(&migrations[1].to_string(), "V2__second");
     }
+
+    #[test]
+    fn parses_timestamp_migration_names() {
+        let (prefix, version, name) = parse_migration_name("20250501_000000_initial").unwrap();
+        assert_eq!(Type::Versioned, prefix);
+        assert_eq!(1746057600, version);
+        assert_eq!("initial", name);
+        assert_eq!("20250501_000000", format_timestamp_version(version));
+        assert_eq!(
+            Some(MigrationNaming::Timestamp),
+            parse_migration_naming("20250501_000000_initial.postgres")
+        );
+        assert_eq!(
+            Some(Dialect::Postgres),
+            parse_migration_dialect("20250501_000000_initial.postgres")
+        );
+        assert_eq!(
+            Some(MigrationNaming::Versioned),
+            parse_migration_naming("V1__initial")
+        );
+        assert_eq!(None, parse_migration_naming("initial"));
+
+        // invalid dates and times after the last one fitting the version
+        assert!(matches!(
+            parse_migration_name("20251301_000000_initial")
+                .unwrap_err()
+                .kind(),
+            Kind::InvalidVersion
+        ));
+        assert!(matches!(
+            parse_migration_name("20380119_031408_initial")
+                .unwrap_err()
+                .kind(),
+            Kind::InvalidVersion
+        ));
+    }
+
+    #[test]
+    fn fails_loading_mixed_naming_schemes() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::File::create(tmp_dir.path().join("20250501_000000_initial.sql")).unwrap();
+        let migrations = load_sql_migrations(tmp_dir.path()).unwrap();
+        assert_eq!("20250501_000000_initial", migrations[0].to_string());
+
+        fs::File::create(tmp_dir.path().join("V2__cars.sql")).unwrap();
+        let err = load_sql_migrations(tmp_dir.path()).unwrap_err();
+        assert!(matches!(err.kind(), Kind::MixedNaming(..)));
+    }
+
+    #[test]
+    fn loads_migrations_from_subdirectories_up_to_max_depth() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(migrations_dir.join("2023")).unwrap();
+        fs::create_dir_all(migrations_dir.join("payments/archive")).unwrap();
+        fs::File::create(migrations_dir.join("V1__first.sql")).unwrap();
+        fs::File::create(migrations_dir.join("2023/V12__foo.sql")).unwrap();
+        fs::File::create(migrations_dir.join("payments/V13__bar.sql")).unwrap();
+        fs::File::create(migrations_dir.join("payments/archive/V14__baz.sql")).unwrap();
+
+        let names = |max_depth| -> Vec<String> {
+            load_sql_migrations_with_depth(&migrations_dir, max_depth)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(vec!["V1__first"], names(Some(0)));
+        assert_eq!(vec!["V1__first", "V12__foo", "V13__bar"], names(Some(1)));
+        assert_eq!(
+            vec!["V1__first", "V12__foo", "V13__bar", "V14__baz"],
+            names(None)
+        );
+    }
+
+    #[test]
+    fn fails_loading_conflicting_migrations_from_subdirectories() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(migrations_dir.join("2023")).unwrap();
+        fs::create_dir_all(migrations_dir.join("payments")).unwrap();
+        fs::File::create(migrations_dir.join("2023/V12__foo.sql")).unwrap();
+        fs::File::create(migrations_dir.join("payments/V12__bar.sql")).unwrap();
+
+        let err = load_sql_migrations(&migrations_dir).unwrap_err();
+        match err.kind() {
+            Kind::ConflictingMigrations(first, second) => {
+                assert!(first.ends_with("2023/V12__foo.sql"));
+                assert!(second.ends_with("payments/V12__bar.sql"));
+            }
+            kind => panic!("unexpected error {}", kind),
+        }
+    }
+
+    #[test]
+    fn allows_dialect_variants_of_a_version() {
+        let migrations: Vec<(String, PathBuf)> = [
+            "V1__first",
+            "V2__second",
+            "V2__second.postgres",
+            "V2__second.sqlite",
+        ]
+        .into_iter()
+        .map(|name| (name.to_string(), PathBuf::from(name)))
+        .collect();
+        assert!(assert_no_conflicts(&migrations).is_ok());
+    }
+
+    #[test]
+    fn seeds_have_their_own_versions() {
+        let (prefix, version, name) = parse_migration_name("S1__add_persons").unwrap();
+        assert_eq!(Type::Seed, prefix);
+        assert_eq!(1, version);
+        assert_eq!("add_persons", name);
+
+        let migrations: Vec<(String, PathBuf)> = ["V1__first", "S1__add_persons"]
+            .into_iter()
+            .map(|name| (name.to_string(), PathBuf::from(name)))
+            .collect();
+        assert!(assert_no_conflicts(&migrations).is_ok());
+    }
+
+    #[test]
+    fn detects_conflicting_dialect_variants() {
+        let migrations: Vec<(String, PathBuf)> =
+            ["V2__second", "V2__second.postgres", "V2__other.postgres"]
+                .into_iter()
+                .map(|name| (name.to_string(), PathBuf::from(name)))
+                .collect();
+        let err = assert_no_conflicts(&migrations).unwrap_err();
+        assert!(matches!(err.kind(), Kind::ConflictingMigrations(_, _)));
+    }
+
+    #[test]
+    fn parses_header_comment_block() {
+        let sql = "\n-- author: alice\n-- Adds the users table\n--ticket:TICKET-123\n-- description: users: the table\nCREATE TABLE users(id int);\n-- note: not in header";
+        let header: Vec<(&str, &str)> = parse_header(sql).collect();
+        assert_eq!(
+            vec![
+                ("author", "alice"),
+                ("ticket", "TICKET-123"),
+                ("description", "users: the table")
+            ],
+            header
+        );
+    }
+
+    #[test]
+    fn parses_empty_header_without_comment_block() {
+        let sql = "CREATE TABLE users(id int);\n-- author: alice";
+        assert!(parse_header(sql).next().is_none());
+    }
+
+    #[test]
+    fn splits_check_section() {
+        let sql = "UPDATE users SET active = true;\n--  Refinery: check\n-- every user is active\nSELECT NOT EXISTS (SELECT 1 FROM users WHERE NOT active);\n\nSELECT count(*) > 0 FROM users -- not empty\n;\n-- done\n";
+        let (migration, checks) = split_checks(sql).unwrap();
+        assert_eq!("UPDATE users SET active = true;\n", migration);
+        assert_eq!(
+            vec![
+                "-- every user is active\nSELECT NOT EXISTS (SELECT 1 FROM users WHERE NOT active)",
+                "SELECT count(*) > 0 FROM users -- not empty",
+            ],
+            checks
+        );
+    }
+
+    #[test]
+    fn doesnt_split_migration_without_check_section() {
+        assert!(split_checks("-- refinery:skip\nCREATE TABLE users(id int);").is_none());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(Some(Duration::from_millis(500)), parse_duration("500ms"));
+        assert_eq!(Some(Duration::from_secs(5)), parse_duration("5s"));
+        assert_eq!(Some(Duration::from_secs(120)), parse_duration("2m"));
+        assert_eq!(Some(Duration::from_secs(3600)), parse_duration("1h"));
+        assert_eq!(None, parse_duration("5"));
+        assert_eq!(None, parse_duration("s"));
+        assert_eq!(None, parse_duration("5 s"));
+        assert_eq!(None, parse_duration("-5s"));
+    }
+
+    #[test]
+    fn splits_header() {
+        let sql =
+            "\n-- refinery:timeout=5s\n-- author: alice\nCREATE TABLE users(id int);\n-- note";
+        assert_eq!(
+            (
+                "\n-- refinery:timeout=5s\n-- author: alice\n",
+                "CREATE TABLE users(id int);\n-- note"
+            ),
+            split_header(sql)
+        );
+        assert_eq!(("", "SELECT 1;"), split_header("SELECT 1;"));
+    }
 }
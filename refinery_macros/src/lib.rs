@@ -3,13 +3,20 @@
 
 use heck::ToUpperCamelCase;
 use proc_macro::TokenStream;
-use proc_macro2::{Span as Span2, TokenStream as TokenStream2};
-use quote::quote;
+use proc_macro2::{Delimiter, Span as Span2, TokenStream as TokenStream2, TokenTree};
 use quote::ToTokens;
-use refinery_core::{find_migration_files, MigrationType};
+use quote::{format_ident, quote};
+use refinery_core::{
+    assert_no_conflicts, find_migration_files_with_depth, manifest::locations_source_hash,
+    migration_file_name, parse_migration_dialect, parse_migration_name, parse_migration_naming,
+    strip_down_section, ChecksumAlgorithm, Migration, MigrationNaming, MigrationType, Runner, Type,
+};
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::{env, fs};
-use syn::{parse_macro_input, Ident, LitStr};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Ident, ItemFn, LitBool, LitInt, LitStr, Token};
 
 pub(crate) fn crate_root() -> PathBuf {
     let crate_root = env::var("CARGO_MANIFEST_DIR")
@@ -21,29 +28,149 @@ fn migration_fn_quoted<T: ToTokens>(_migrations: Vec<T>) -> TokenStream2 {
     let result = quote! {
         use refinery::{Migration, Runner};
         pub fn runner() -> Runner {
-            let quoted_migrations: Vec<(&str, String)> = vec![#(#_migrations),*];
-            let mut migrations: Vec<Migration> = Vec::new();
-            for module in quoted_migrations.into_iter() {
-                migrations.push(Migration::unapplied(module.0, &module.1).unwrap());
-            }
+            let migrations: Vec<Migration> = vec![#(#_migrations),*];
             Runner::new(&migrations)
         }
     };
     result
 }
 
+fn manifest_fn_quoted(entries: &[(String, u32, Option<u64>)], source_hash: u64) -> TokenStream2 {
+    let entries = entries.iter().map(|(name, version, checksum)| {
+        let checksum = match checksum {
+            Some(checksum) => quote! { Some(#checksum) },
+            None => quote! { None },
+        };
+        quote! {
+            ManifestEntry { name: #name.into(), version: #version, checksum: #checksum }
+        }
+    });
+    quote! {
+        pub fn manifest() -> refinery::manifest::Manifest {
+            use refinery::manifest::{Manifest, ManifestEntry};
+            Manifest {
+                migrations: vec![#(#entries),*],
+                source_hash: #source_hash,
+            }
+        }
+    }
+}
+
+// Rust migrations either have a `migration()` function returning the sql,
+// or a `migration(dialect: Dialect)` one generating it for the dialect of the connection
+fn migration_takes_dialect(rs_content: &TokenStream2) -> bool {
+    let mut tokens = rs_content.clone().into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let is_migration_fn = matches!(&token, TokenTree::Ident(ident) if ident == "fn")
+            && matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == "migration");
+        if is_migration_fn {
+            tokens.next();
+            if let Some(TokenTree::Group(args)) = tokens.next() {
+                return !args.stream().is_empty();
+            }
+        }
+    }
+    false
+}
+
+// `migration(dialect: Dialect)` functions can return a `Result<String, Error>`, for dialects they can't generate the sql
+// for, i.e. with `sea_query::MigrationBuilder`
+fn migration_returns_result(rs_content: &TokenStream2) -> bool {
+    let mut tokens = rs_content.clone().into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let is_migration_fn = matches!(&token, TokenTree::Ident(ident) if ident == "fn")
+            && matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == "migration");
+        if is_migration_fn {
+            // the last path segment of the return type before its generic arguments, i.e. `refinery::Result`
+            let mut return_type = None;
+            for token in tokens.by_ref() {
+                match token {
+                    TokenTree::Ident(ident) => return_type = Some(ident.to_string()),
+                    TokenTree::Punct(punct) if punct.as_char() == '<' => break,
+                    TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => break,
+                    _ => {}
+                }
+            }
+            return return_type.as_deref() == Some("Result");
+        }
+    }
+    false
+}
+
+// async Rust migrations have an `async fn migrate(executor: &mut dyn AsyncExecutor)` function
+// run on the connection instead
+fn migration_is_async(rs_content: &TokenStream2) -> bool {
+    let tokens: Vec<_> = rs_content.clone().into_iter().collect();
+    tokens.windows(3).any(|window| {
+        matches!(
+            window,
+            [TokenTree::Ident(a), TokenTree::Ident(f), TokenTree::Ident(name)]
+                if a == "async" && f == "fn" && name == "migrate"
+        )
+    })
+}
+
+// the first gap between the versions of the versioned migrations, as the versions before and after it,
+// timestamp migrations always have gaps between them
+fn version_gap(migration_names: &[impl AsRef<str>]) -> Option<(i32, i32)> {
+    let mut versions: Vec<i32> = migration_names
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|name| parse_migration_naming(name) == Some(MigrationNaming::Versioned))
+        .filter_map(|name| match parse_migration_name(name) {
+            Ok((Type::Versioned, version, _)) => Some(version),
+            _ => None,
+        })
+        .collect();
+    versions.sort_unstable();
+    versions.dedup();
+    versions
+        .windows(2)
+        .find(|window| window[1] > window[0] + 1)
+        .map(|window| (window[0], window[1]))
+}
+
+// a valid identifier for the name of a migration, as names can have dashes and Unicode word characters that
+// identifiers can't, which are replaced with `_`, and can start with a digit
+fn identifier(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| {
+            if unicode_ident::is_xid_continue(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match ident.chars().next() {
+        Some(first) if first == '_' || unicode_ident::is_xid_start(first) => ident,
+        _ => format!("_{}", ident),
+    }
+}
+
 fn migration_enum_quoted(migration_names: &[impl AsRef<str>]) -> TokenStream2 {
     if cfg!(feature = "enums") {
         let mut variants = Vec::new();
         let mut discriminants = Vec::new();
+        let mut versions_of = Vec::new();
+        let mut names_of = Vec::new();
+        let mut versions = HashSet::new();
 
         for m in migration_names {
             let m = m.as_ref();
-            let (_, version, name) = refinery_core::parse_migration_name(m)
+            let (_, version, name) = parse_migration_name(m)
                 .unwrap_or_else(|e| panic!("Couldn't parse migration filename '{}': {:?}", m, e));
-            let variant = Ident::new(name.to_upper_camel_case().as_str(), Span2::call_site());
+            // dialect variants of a migration share the same enum variant
+            if !versions.insert(version) {
+                continue;
+            }
+            let variant = Ident::new(&identifier(&name.to_upper_camel_case()), Span2::call_site());
+            let unsigned_version = version as u32;
             variants.push(quote! { #variant(Migration) = #version });
             discriminants.push(quote! { #version => Self::#variant(migration) });
+            versions_of.push(quote! { Self::#variant(_) => #unsigned_version });
+            names_of.push(quote! { Self::#variant(_) => #name });
         }
         discriminants.push(quote! { v => panic!("Invalid migration version '{}'", v) });
 
@@ -54,6 +181,30 @@ fn migration_enum_quoted(migration_names: &[impl AsRef<str>]) -> TokenStream2 {
                 #(#variants),*
             }
 
+            impl EmbeddedMigration {
+                /// Returns every embedded migration, one for each version, ordered by version
+                pub fn all() -> Vec<EmbeddedMigration> {
+                    let mut migrations = runner().into_migrations();
+                    migrations.sort();
+                    migrations.dedup_by_key(|migration| migration.version());
+                    migrations.into_iter().map(EmbeddedMigration::from).collect()
+                }
+
+                /// Returns the version of the migration
+                pub fn version(&self) -> u32 {
+                    match self {
+                        #(#versions_of),*
+                    }
+                }
+
+                /// Returns the name of the migration
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        #(#names_of),*
+                    }
+                }
+            }
+
             impl From<Migration> for EmbeddedMigration {
                 fn from(migration: Migration) -> Self {
                     match migration.version() as i32 {
@@ -61,6 +212,18 @@ fn migration_enum_quoted(migration_names: &[impl AsRef<str>]) -> TokenStream2 {
                     }
                 }
             }
+
+            impl ::std::convert::TryFrom<i64> for EmbeddedMigration {
+                /// The version, which has no embedded migration
+                type Error = i64;
+
+                fn try_from(version: i64) -> Result<Self, Self::Error> {
+                    EmbeddedMigration::all()
+                        .into_iter()
+                        .find(|migration| i64::from(migration.version()) == version)
+                        .ok_or(version)
+                }
+            }
         };
         result
     } else {
@@ -68,6 +231,63 @@ fn migration_enum_quoted(migration_names: &[impl AsRef<str>]) -> TokenStream2 {
     }
 }
 
+// the arguments of `embed_migrations!`, optional locations followed by optional `max_depth = {number}`,
+// `naming = "versioned" | "timestamp"`, `strict_sequence = {bool}` and `omit_down = {bool}` options
+struct EmbedArgs {
+    locations: Vec<LitStr>,
+    max_depth: Option<usize>,
+    naming: Option<MigrationNaming>,
+    strict_sequence: bool,
+    omit_down: bool,
+}
+
+impl Parse for EmbedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut locations = Vec::new();
+        while input.peek(LitStr) {
+            locations.push(input.parse()?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let mut args = EmbedArgs {
+            locations,
+            max_depth: None,
+            naming: None,
+            strict_sequence: false,
+            omit_down: false,
+        };
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "max_depth" {
+                args.max_depth = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if key == "naming" {
+                let naming: LitStr = input.parse()?;
+                args.naming = Some(
+                    naming
+                        .value()
+                        .parse()
+                        .map_err(|err| syn::Error::new(naming.span(), err))?,
+                );
+            } else if key == "strict_sequence" {
+                args.strict_sequence = input.parse::<LitBool>()?.value;
+            } else if key == "omit_down" {
+                args.omit_down = input.parse::<LitBool>()?.value;
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected `max_depth`, `naming`, `strict_sequence` or `omit_down`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(args)
+    }
+}
+
 /// Interpret Rust or SQL migrations and inserts a function called runner that when called returns a [`Runner`] instance with the collected migration modules.
 ///
 /// When called without arguments `embed_migrations` searches for migration files on a directory called `migrations` at the root level of your crate.
@@ -75,69 +295,392 @@ fn migration_enum_quoted(migration_names: &[impl AsRef<str>]) -> TokenStream2 {
 ///
 /// To be a valid migration module, it has to be named in the format `V{1}__{2}.{3} ` where `{1}` represents the migration version and `{2}` the name and `{3} is "rs" or "sql".
 /// For the name alphanumeric characters plus "_" are supported.
-/// The Rust migration file must have a function named `migration()` that returns a [`std::string::String`].
+/// SQL migrations can have variants for a database dialect named in the format `V{1}__{2}.{dialect}.sql`, i.e. `V7__add_fulltext.postgres.sql`,
+/// the [`Runner`] runs the variant for the dialect of the connection, or the migration without dialect if there's none.
+/// SQL migrations on a directory named after a dialect i.e. `migrations/postgres/V7__add_fulltext.sql` are also variants for it,
+/// so migrations common to every database can be shared on another directory, i.e. `migrations/common`.
+/// Migrations are found on every subdirectory of the location, i.e. `migrations/2023/V12__foo.sql` and `migrations/payments/V13__bar.sql`,
+/// `embed_migrations!("./migrations", max_depth = 1)` limits how many levels of subdirectories are descended, `0` only embeds the
+/// migrations directly on the location.
+/// Compilation fails if there's more than one migration for the same version and dialect, even on different subdirectories.
+/// Migrations can be embedded from several locations merged by version, i.e. `embed_migrations!("migrations/core", "migrations/auth")`
+/// to organize them by domain on a workspace, compilation also fails if a version is repeated on different locations.
+/// `embed_migrations!("./migrations", strict_sequence = true)` also fails compilation when there are gaps between the
+/// versions of the versioned migrations, i.e. `V3` is missing between `V2__foo.sql` and `V4__bar.sql`.
+/// `embed_migrations!("./migrations", omit_down = true)` strips the `-- refinery:down` section of SQL migrations from the
+/// generated code, for binaries that never roll back. Their checksums are still formed from the whole file, the legacy
+/// Sip13 one with the endianness of the machine compiling the crate. [`Migration::down_sql`] returns `None` for them
+/// and rolling them back fails.
+///
+/// Migrations can instead be named after the UTC time they were created at, in the format `{YYYYMMDD}_{HHMMSS}_{2}.{3}`,
+/// i.e. `20250501_000000_initial.sql`, see [`MigrationNaming::Timestamp`], the modules of Rust ones are prefixed with `_`.
+/// Compilation fails if the migrations are named with different schemes, `embed_migrations!("./migrations", naming = "timestamp")`
+/// also requires them to be named with the given one.
+/// The Rust migration file must have a function named `migration()` that returns a [`std::string::String`],
+/// or a function named `migration(dialect: Dialect)` to generate the sql for the [`Dialect`] of the connection it's run on.
+/// It can return a `Result<String, Error>` instead, the Error fails the run before the migration is applied.
+/// Async Rust migrations instead have an `async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), Error>` function,
+/// run on the [`AsyncExecutor`] of the connection to query and transform data with the driver, their checksum is formed
+/// from the source of the file.
 /// The SQL migration file must have valid sql instructions for the database you want it to run on.
 ///
+/// Only the migration files found when the macro is expanded are tracked, so adding one doesn't trigger a recompilation
+/// by itself. Call `refinery::rerun_if_migrations_changed` with the migrations directory from the build script of the
+/// crate to rebuild it when they change.
+///
+/// A `manifest()` function is also inserted, returning the [`Manifest`] of the embedded migrations with their versions
+/// and checksums and the hash of the migrations directory sources, to report the migration set a binary was built with.
+///
+/// With the `enums` feature an `EmbeddedMigration` enum with a variant for each migration version is also inserted,
+/// built from a [`Migration`] with `From` or from a version with `TryFrom<i64>`, and `EmbeddedMigration::all()`
+/// enumerates the embedded migrations, whose `version()` and `name()` are known statically.
+///
 /// [`Runner`]: https://docs.rs/refinery/latest/refinery/struct.Runner.html
+/// [`Dialect`]: https://docs.rs/refinery/latest/refinery/enum.Dialect.html
+/// [`AsyncExecutor`]: https://docs.rs/refinery/latest/refinery/trait.AsyncExecutor.html
+/// [`Manifest`]: https://docs.rs/refinery/latest/refinery/manifest/struct.Manifest.html
+/// [`Migration`]: https://docs.rs/refinery/latest/refinery/struct.Migration.html
+/// [`Migration::down_sql`]: https://docs.rs/refinery/latest/refinery/struct.Migration.html#method.down_sql
+/// [`MigrationNaming::Timestamp`]: https://docs.rs/refinery/latest/refinery/enum.MigrationNaming.html#variant.Timestamp
 #[proc_macro]
 pub fn embed_migrations(input: TokenStream) -> TokenStream {
-    let location = if input.is_empty() {
-        crate_root().join("migrations")
-    } else {
-        let location: LitStr = parse_macro_input!(input);
-        crate_root().join(location.value())
+    let args = parse_macro_input!(input as EmbedArgs);
+    let locations = match args.locations.as_slice() {
+        [] => vec![crate_root().join("migrations")],
+        locations => locations
+            .iter()
+            .map(|location| crate_root().join(location.value()))
+            .collect(),
     };
 
-    let migration_files =
-        find_migration_files(location, MigrationType::All).expect("error getting migration files");
+    // the migrations of every location, named relative to theirs
+    let mut migration_files = Vec::new();
+    for location in &locations {
+        let files = find_migration_files_with_depth(location, MigrationType::All, args.max_depth)
+            .expect("error getting migration files");
+        migration_files.extend(files.map(|migration| (location, migration)));
+    }
 
     let mut migrations_mods = Vec::new();
     let mut _migrations = Vec::new();
     let mut migration_filenames = Vec::new();
+    let mut migration_paths = Vec::new();
+    let mut manifest_entries = Vec::new();
 
-    for migration in migration_files {
-        // safe to call unwrap as find_migration_filenames returns canonical paths
-        let filename = migration
-            .file_stem()
-            .and_then(|file| file.to_os_string().into_string().ok())
-            .unwrap();
+    for (location, migration) in migration_files {
+        let filename = migration_file_name(location, &migration);
         let path = migration.display().to_string();
         let extension = migration.extension().unwrap();
+        if let Some(naming) = args.naming {
+            if parse_migration_naming(&filename) != Some(naming) {
+                panic!(
+                    "migration {} is not named with the {:?} naming",
+                    path, naming
+                );
+            }
+        }
         migration_filenames.push(filename.clone());
+        migration_paths.push((filename.clone(), migration.clone()));
 
         if extension == "sql" {
-            _migrations.push(quote! {(#filename, include_str!(#path).to_string())});
+            // fail compilation on invalid directives, instead of when the runner is created
+            let sql = fs::read_to_string(&migration).unwrap();
+            let unapplied = Migration::unapplied(&filename, &sql)
+                .unwrap_or_else(|err| panic!("invalid migration {}: {}", path, err));
+            manifest_entries.push((
+                filename.clone(),
+                unapplied.version(),
+                Some(unapplied.checksum()),
+            ));
+            if args.omit_down && unapplied.down_sql().is_some() {
+                // the checksums are formed from the sql with the down section, which isn't embedded
+                let up = strip_down_section(&sql);
+                let checksums = [
+                    ChecksumAlgorithm::Sip13Legacy,
+                    ChecksumAlgorithm::Sha256,
+                    ChecksumAlgorithm::Crc32,
+                ]
+                .map(|algorithm| {
+                    Runner::new(std::slice::from_ref(&unapplied))
+                        .set_checksum_algorithm(algorithm)
+                        .get_migrations()[0]
+                        .checksum()
+                });
+                _migrations.push(quote! {{
+                    // the file is still included so we trigger recompilation if it changes
+                    const _: &str = include_str!(#path);
+                    Migration::unapplied_without_down(#filename, #up, [#(#checksums),*]).unwrap()
+                }});
+            } else {
+                _migrations.push(
+                    quote! {Migration::unapplied_from(#filename, include_str!(#path)).unwrap()},
+                );
+            }
         } else if extension == "rs" {
-            let rs_content = fs::read_to_string(&path)
-                .unwrap()
-                .parse::<TokenStream2>()
-                .unwrap();
-            let ident = Ident::new(&filename, Span2::call_site());
+            if parse_migration_dialect(&filename).is_some() {
+                panic!(
+                    "Rust migration {} can't be a variant for a database dialect, its migration function can take the Dialect instead",
+                    path
+                );
+            }
+            let (_, version, _) = parse_migration_name(&filename).unwrap_or_else(|e| {
+                panic!("Couldn't parse migration filename '{}': {:?}", filename, e)
+            });
+            let source = fs::read_to_string(&path).unwrap();
+            let rs_content = source.parse::<TokenStream2>().unwrap();
+            // identifiers can't start with the digits of timestamp migrations
+            let ident = match parse_migration_naming(&filename) {
+                Some(MigrationNaming::Timestamp) => format_ident!("_{}", identifier(&filename)),
+                _ => Ident::new(&identifier(&filename), Span2::call_site()),
+            };
+            if migration_is_async(&rs_content) {
+                // the checksum of async migrations is formed from their source, so it's known when they're embedded
+                let unapplied =
+                    Migration::unapplied_async(&filename, &source, |_| Box::pin(async { Ok(()) }))
+                        .unwrap_or_else(|err| panic!("invalid migration {}: {}", path, err));
+                manifest_entries.push((
+                    filename.clone(),
+                    version as u32,
+                    Some(unapplied.checksum()),
+                ));
+                _migrations.push(quote! {
+                    Migration::unapplied_async(#filename, include_str!(#path), |executor| {
+                        Box::pin(#ident::migrate(executor))
+                    })
+                    .unwrap()
+                });
+            } else if migration_takes_dialect(&rs_content) {
+                manifest_entries.push((filename.clone(), version as u32, None));
+                if migration_returns_result(&rs_content) {
+                    _migrations.push(
+                        quote! {Migration::try_unapplied_for_dialect(#filename, #ident::migration).unwrap()},
+                    );
+                } else {
+                    _migrations.push(
+                        quote! {Migration::unapplied_for_dialect(#filename, #ident::migration).unwrap()},
+                    );
+                }
+            } else {
+                manifest_entries.push((filename.clone(), version as u32, None));
+                _migrations.push(
+                    quote! {Migration::unapplied_from(#filename, #ident::migration()).unwrap()},
+                );
+            }
             let mig_mod = quote! {pub mod #ident {
                 #rs_content
                 // also include the file as str so we trigger recompilation if it changes
                 const _RECOMPILE_IF_CHANGED: &str = include_str!(#path);
             }};
-            _migrations.push(quote! {(#filename, #ident::migration())});
             migrations_mods.push(mig_mod);
         }
     }
 
+    // fail the build pointing at the locations of the migrations
+    let span = args
+        .locations
+        .first()
+        .map_or_else(Span2::call_site, LitStr::span);
+    if let Err(err) = assert_no_conflicts(&migration_paths) {
+        return syn::Error::new(span, err).into_compile_error().into();
+    }
+    if args.strict_sequence {
+        if let Some((previous, next)) = version_gap(&migration_filenames) {
+            let missing = match (previous + 1, next - 1) {
+                (first, last) if first == last => format!("migration version {} is missing", first),
+                (first, last) => format!("migration versions {} to {} are missing", first, last),
+            };
+            return syn::Error::new(
+                span,
+                format!(
+                    "{}, versions must be contiguous with `strict_sequence = true`",
+                    missing
+                ),
+            )
+            .into_compile_error()
+            .into();
+        }
+    }
     let fnq = migration_fn_quoted(_migrations);
     let enums = migration_enum_quoted(migration_filenames.as_slice());
+    manifest_entries.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    let source_hash = locations_source_hash(&locations).expect("error hashing migration files");
+    let manifest = manifest_fn_quoted(&manifest_entries, source_hash);
     (quote! {
         pub mod migrations {
             #(#migrations_mods)*
             #fnq
+            #manifest
             #enums
         }
     })
     .into()
 }
 
+// the arguments of `#[migration]`, `version = {number}` followed by an optional `name = "{name}"`,
+// the name of the function by default
+struct MigrationArgs {
+    version: LitInt,
+    name: Option<LitStr>,
+}
+
+impl Parse for MigrationArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut version = None;
+        let mut name = None;
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "version" {
+                version = Some(input.parse()?);
+            } else if key == "name" {
+                name = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(key.span(), "expected `version` or `name`"));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let version = version
+            .ok_or_else(|| syn::Error::new(Span2::call_site(), "expected `version = {number}`"))?;
+        Ok(MigrationArgs { version, name })
+    }
+}
+
+// whether the return type of a `#[migration]` function is a `Result`, i.e. `Result<String, Error>`
+fn returns_result(output: &syn::ReturnType) -> bool {
+    match output {
+        syn::ReturnType::Type(_, ty) => matches!(
+            &**ty,
+            syn::Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Result")
+        ),
+        syn::ReturnType::Default => false,
+    }
+}
+
+fn migration_attribute_quoted(args: MigrationArgs, item: ItemFn) -> syn::Result<TokenStream2> {
+    let version: u32 = args.version.base10_parse()?;
+    if let Some(receiver) = item.sig.receiver() {
+        return Err(syn::Error::new(
+            receiver.span(),
+            "migration functions can't take self",
+        ));
+    }
+    let ident = &item.sig.ident;
+    let name = args
+        .name
+        .as_ref()
+        .map(LitStr::value)
+        .unwrap_or_else(|| ident.to_string());
+    let filename = format!("V{}__{}", version, name);
+    let migration = match (item.sig.asyncness, item.sig.inputs.len()) {
+        // the checksum of async migrations is formed from their source, as for the ones embedded from files
+        (Some(_), 1) => {
+            let source = item.to_token_stream().to_string();
+            Migration::unapplied_async(&filename, &source, |_| Box::pin(async { Ok(()) }))
+                .map_err(|err| syn::Error::new(args.version.span(), err))?;
+            quote! {
+                refinery::Migration::unapplied_async(#filename, #source, |executor| Box::pin(#ident(executor)))
+            }
+        }
+        (None, 0) => quote! { refinery::Migration::unapplied_from(#filename, #ident()) },
+        (None, 1) if returns_result(&item.sig.output) => {
+            quote! { refinery::Migration::try_unapplied_for_dialect(#filename, #ident) }
+        }
+        (None, 1) => quote! { refinery::Migration::unapplied_for_dialect(#filename, #ident) },
+        _ => {
+            return Err(syn::Error::new(
+                item.sig.span(),
+                "migration functions must be `fn()` or `fn(dialect: Dialect)` returning the sql, or `async fn(executor: &mut dyn AsyncExecutor)`",
+            ))
+        }
+    };
+    parse_migration_name(&filename).map_err(|err| syn::Error::new(args.version.span(), err))?;
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            fn __refinery_migration() -> refinery::Migration {
+                #migration.unwrap()
+            }
+            refinery::registry::inventory::submit! {
+                refinery::registry::RegisteredMigration::new(__refinery_migration)
+            }
+        };
+    })
+}
+
+/// Register a Rust migration declared on any module of the crate, to be gathered with [`collect_migrations!`] instead
+/// of embedded from a file of the migrations directory:
+///
+/// ```rust,ignore
+/// use refinery::{migration, AsyncExecutor, Dialect, Error};
+///
+/// #[migration(version = 7, name = "add_users")]
+/// fn add_users() -> String {
+///     "CREATE TABLE users(id int);".into()
+/// }
+///
+/// #[migration(version = 8)]
+/// fn add_user_emails(dialect: Dialect) -> String {
+///     format!("ALTER TABLE users ADD email {};", if dialect == Dialect::Mysql { "varchar(255)" } else { "text" })
+/// }
+///
+/// #[migration(version = 9)]
+/// async fn backfill_emails(executor: &mut dyn AsyncExecutor) -> Result<(), Error> {
+///     executor.execute("UPDATE users SET email = ''").await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// The migration is named `V{version}__{name}`, after the function if there's no `name`. As the `migration()` and
+/// `migrate(executor)` functions of Rust migration files, the function either returns the sql, generates it for the
+/// [`Dialect`] of the connection, or is an async migration run on the [`AsyncExecutor`] of the connection whose
+/// checksum is formed from its source.
+///
+/// [`Dialect`]: https://docs.rs/refinery/latest/refinery/enum.Dialect.html
+/// [`AsyncExecutor`]: https://docs.rs/refinery/latest/refinery/trait.AsyncExecutor.html
+#[proc_macro_attribute]
+pub fn migration(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as MigrationArgs);
+    let item = parse_macro_input!(item as ItemFn);
+    migration_attribute_quoted(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Gather the migrations registered with the [`macro@migration`] attribute on every crate linked into the binary,
+/// ordered by version, to be run with a [`Runner`]:
+///
+/// ```rust,ignore
+/// let runner = refinery::Runner::new(&refinery::collect_migrations!());
+/// // or along with the embedded ones
+/// let runner = embedded::migrations::runner().add_migrations(&refinery::collect_migrations!())?;
+/// ```
+///
+/// [`Runner`]: https://docs.rs/refinery/latest/refinery/struct.Runner.html
+#[proc_macro]
+pub fn collect_migrations(input: TokenStream) -> TokenStream {
+    let input = TokenStream2::from(input);
+    if !input.is_empty() {
+        return syn::Error::new_spanned(input, "collect_migrations! takes no arguments")
+            .into_compile_error()
+            .into();
+    }
+    (quote! {
+        refinery::registry::registered_migrations()
+    })
+    .into()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{migration_fn_quoted, quote};
+    use super::{
+        manifest_fn_quoted, migration_attribute_quoted, migration_fn_quoted, migration_is_async,
+        migration_returns_result, migration_takes_dialect, quote, version_gap, EmbedArgs, LitStr,
+        MigrationArgs, MigrationNaming, TokenStream2,
+    };
 
     #[test]
     #[cfg(feature = "enums")]
@@ -148,31 +691,250 @@ mod tests {
             "Foo (Migration) = 1i32 , ",
             "BarBaz (Migration) = 3i32 ",
             "} ",
+            "impl EmbeddedMigration { ",
+            "# [doc = r\" Returns every embedded migration, one for each version, ordered by version\"] ",
+            "pub fn all () -> Vec < EmbeddedMigration > { ",
+            "let mut migrations = runner () . into_migrations () ; ",
+            "migrations . sort () ; ",
+            "migrations . dedup_by_key (| migration | migration . version ()) ; ",
+            "migrations . into_iter () . map (EmbeddedMigration :: from) . collect () ",
+            "} ",
+            "# [doc = r\" Returns the version of the migration\"] ",
+            "pub fn version (& self) -> u32 { ",
+            "match self { Self :: Foo (_) => 1u32 , Self :: BarBaz (_) => 3u32 } ",
+            "} ",
+            "# [doc = r\" Returns the name of the migration\"] ",
+            "pub fn name (& self) -> & 'static str { ",
+            "match self { Self :: Foo (_) => \"foo\" , Self :: BarBaz (_) => \"barBAZ\" } ",
+            "} ",
+            "} ",
             "impl From < Migration > for EmbeddedMigration { ",
             "fn from (migration : Migration) -> Self { ",
             "match migration . version () as i32 { ",
             "1i32 => Self :: Foo (migration) , ",
             "3i32 => Self :: BarBaz (migration) , ",
             "v => panic ! (\"Invalid migration version '{}'\" , v) ",
-            "} } }"
+            "} } } ",
+            "impl :: std :: convert :: TryFrom < i64 > for EmbeddedMigration { ",
+            "# [doc = r\" The version, which has no embedded migration\"] ",
+            "type Error = i64 ; ",
+            "fn try_from (version : i64) -> Result < Self , Self :: Error > { ",
+            "EmbeddedMigration :: all () . into_iter () ",
+            ". find (| migration | i64 :: from (migration . version ()) == version) ",
+            ". ok_or (version) ",
+            "} }"
         };
-        let enums = super::migration_enum_quoted(&["V1__foo", "U3__barBAZ"]).to_string();
+        let enums = super::migration_enum_quoted(&["V1__foo", "V1__foo.postgres", "U3__barBAZ"])
+            .to_string();
         assert_eq!(expected, enums);
     }
 
+    #[test]
+    #[cfg(feature = "enums")]
+    fn test_enum_fn_sanitizes_variants() {
+        let enums = super::migration_enum_quoted(&[
+            "V1__añadir-usuarios",
+            "V2__添加用户",
+            "V3__add-2fa__TICKET-12",
+            "V4__2fa",
+            "20250501_000000_drop-users",
+        ])
+        .to_string();
+        assert!(enums.contains(concat!(
+            "pub enum EmbeddedMigration { ",
+            "AñadirUsuarios (Migration) = 1i32 , ",
+            "添加用户 (Migration) = 2i32 , ",
+            "Add2faTicket12 (Migration) = 3i32 , ",
+            "_2fa (Migration) = 4i32 , ",
+            "DropUsers (Migration) = 1746057600i32 ",
+            "}"
+        )));
+        assert!(enums.contains(concat!(
+            "match self { ",
+            "Self :: AñadirUsuarios (_) => \"añadir-usuarios\" , ",
+            "Self :: 添加用户 (_) => \"添加用户\" , ",
+            "Self :: Add2faTicket12 (_) => \"add-2fa__TICKET-12\" , ",
+            "Self :: _2fa (_) => \"2fa\" , ",
+            "Self :: DropUsers (_) => \"drop-users\" }"
+        )));
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!("V1__add_users", super::identifier("V1__add-users"));
+        assert_eq!(
+            "V2__añadir_usuarios",
+            super::identifier("V2__añadir-usuarios")
+        );
+        assert_eq!("_2fa", super::identifier("2fa"));
+        assert_eq!(
+            "_20250501_000000_x_y",
+            super::identifier("20250501_000000_x-y")
+        );
+    }
+
     #[test]
     fn test_quote_fn() {
-        let migs = vec![quote!("V1__first", "valid_sql_file")];
+        let migs = vec![quote!(Migration::unapplied_from(
+            "V1__first",
+            "valid_sql_file"
+        )
+        .unwrap())];
         let expected = concat! {
             "use refinery :: { Migration , Runner } ; ",
             "pub fn runner () -> Runner { ",
-            "let quoted_migrations : Vec < (& str , String) > = vec ! [\"V1__first\" , \"valid_sql_file\"] ; ",
-            "let mut migrations : Vec < Migration > = Vec :: new () ; ",
-            "for module in quoted_migrations . into_iter () { ",
-            "migrations . push (Migration :: unapplied (module . 0 , & module . 1) . unwrap ()) ; ",
-            "} ",
+            "let migrations : Vec < Migration > = vec ! [Migration :: unapplied_from (\"V1__first\" , \"valid_sql_file\") . unwrap ()] ; ",
             "Runner :: new (& migrations) }"
         };
         assert_eq!(expected, migration_fn_quoted(migs).to_string());
     }
+
+    #[test]
+    fn test_manifest_fn() {
+        let expected = concat! {
+            "pub fn manifest () -> refinery :: manifest :: Manifest { ",
+            "use refinery :: manifest :: { Manifest , ManifestEntry } ; ",
+            "Manifest { migrations : vec ! [",
+            "ManifestEntry { name : \"V1__first\" . into () , version : 1u32 , checksum : Some (42u64) } , ",
+            "ManifestEntry { name : \"V2__second\" . into () , version : 2u32 , checksum : None }",
+            "] , source_hash : 7u64 , } }"
+        };
+        let entries = [
+            ("V1__first".to_string(), 1, Some(42)),
+            ("V2__second".to_string(), 2, None),
+        ];
+        assert_eq!(expected, manifest_fn_quoted(&entries, 7).to_string());
+    }
+
+    #[test]
+    fn test_migration_takes_dialect() {
+        let rs_content: TokenStream2 = "use refinery::Dialect; pub fn migration(dialect: Dialect) -> String { dialect.to_string() }"
+            .parse()
+            .unwrap();
+        assert!(migration_takes_dialect(&rs_content));
+
+        let rs_content: TokenStream2 = "pub fn migration() -> String { String::new() }"
+            .parse()
+            .unwrap();
+        assert!(!migration_takes_dialect(&rs_content));
+    }
+
+    #[test]
+    fn test_migration_returns_result() {
+        let rs_content: TokenStream2 = "use refinery::{Dialect, Error}; pub fn migration(dialect: Dialect) -> Result<String, Error> { Ok(dialect.to_string()) }"
+            .parse()
+            .unwrap();
+        assert!(migration_returns_result(&rs_content));
+
+        let rs_content: TokenStream2 = "pub fn migration(dialect: Dialect) -> String { let _: Result<(), ()> = Ok(()); String::new() }"
+            .parse()
+            .unwrap();
+        assert!(!migration_returns_result(&rs_content));
+    }
+
+    #[test]
+    fn test_migration_is_async() {
+        let rs_content: TokenStream2 = "use refinery::{AsyncExecutor, Error}; pub async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), Error> { Ok(()) }"
+            .parse()
+            .unwrap();
+        assert!(migration_is_async(&rs_content));
+
+        let rs_content: TokenStream2 = "pub fn migration() -> String { String::new() }"
+            .parse()
+            .unwrap();
+        assert!(!migration_is_async(&rs_content));
+    }
+
+    #[test]
+    fn test_parse_embed_args() {
+        let args: EmbedArgs = syn::parse_str("").unwrap();
+        assert!(args.locations.is_empty());
+        assert_eq!(None, args.max_depth);
+
+        let args: EmbedArgs = syn::parse_str("\"./migrations\", max_depth = 1").unwrap();
+        assert_eq!("./migrations", args.locations[0].value());
+        assert_eq!(Some(1), args.max_depth);
+
+        let args: EmbedArgs = syn::parse_str("max_depth = 0").unwrap();
+        assert!(args.locations.is_empty());
+        assert_eq!(Some(0), args.max_depth);
+
+        let args: EmbedArgs =
+            syn::parse_str("\"./migrations\", naming = \"timestamp\", max_depth = 2,").unwrap();
+        assert_eq!(Some(MigrationNaming::Timestamp), args.naming);
+        assert_eq!(Some(2), args.max_depth);
+
+        let args: EmbedArgs =
+            syn::parse_str("\"migrations/core\", \"migrations/auth\", max_depth = 0").unwrap();
+        let locations: Vec<String> = args.locations.iter().map(LitStr::value).collect();
+        assert_eq!(vec!["migrations/core", "migrations/auth"], locations);
+        assert_eq!(Some(0), args.max_depth);
+
+        let args: EmbedArgs = syn::parse_str("\"./migrations\", strict_sequence = true").unwrap();
+        assert!(args.strict_sequence);
+        assert!(!syn::parse_str::<EmbedArgs>("").unwrap().strict_sequence);
+        assert!(syn::parse_str::<EmbedArgs>("strict_sequence = 1").is_err());
+
+        let args: EmbedArgs = syn::parse_str("\"./migrations\", omit_down = true").unwrap();
+        assert!(args.omit_down);
+        assert!(!syn::parse_str::<EmbedArgs>("").unwrap().omit_down);
+
+        assert!(syn::parse_str::<EmbedArgs>("\"./migrations\", depth = 1").is_err());
+        assert!(syn::parse_str::<EmbedArgs>("naming = \"semver\"").is_err());
+    }
+
+    #[test]
+    fn test_version_gap() {
+        let names = [
+            "V1__initial",
+            "V2__cars",
+            "V2__cars.postgres",
+            "U7__fix",
+            "S9__seed",
+        ];
+        assert_eq!(None, version_gap(&names));
+        assert_eq!(
+            Some((2, 5)),
+            version_gap(&["V5__colour", "V1__initial", "V2__cars", "V6__doors"])
+        );
+        assert_eq!(
+            None,
+            version_gap(&["20250501_000000_initial", "20250614_093000_add_cars_table"])
+        );
+    }
+
+    #[test]
+    fn test_migration_attribute() {
+        let args: MigrationArgs = syn::parse_str("version = 7, name = \"add_users\"").unwrap();
+        let item =
+            syn::parse_str("fn users(dialect: Dialect) -> String { String::new() }").unwrap();
+        let quoted = migration_attribute_quoted(args, item).unwrap().to_string();
+        assert!(quoted.contains(
+            "refinery :: Migration :: unapplied_for_dialect (\"V7__add_users\" , users) . unwrap ()"
+        ));
+
+        let args: MigrationArgs = syn::parse_str("version = 7, name = \"add_users\"").unwrap();
+        let item = syn::parse_str(
+            "fn users(dialect: Dialect) -> Result<String, Error> { Ok(String::new()) }",
+        )
+        .unwrap();
+        let quoted = migration_attribute_quoted(args, item).unwrap().to_string();
+        assert!(quoted.contains(
+            "refinery :: Migration :: try_unapplied_for_dialect (\"V7__add_users\" , users) . unwrap ()"
+        ));
+
+        let args: MigrationArgs = syn::parse_str("version = 8").unwrap();
+        let item = syn::parse_str("fn add_emails() -> String { String::new() }").unwrap();
+        let quoted = migration_attribute_quoted(args, item).unwrap().to_string();
+        assert!(quoted.contains(
+            "refinery :: Migration :: unapplied_from (\"V8__add_emails\" , add_emails ())"
+        ));
+
+        let args: MigrationArgs = syn::parse_str("version = 9").unwrap();
+        let item = syn::parse_str("fn users(a: u32, b: u32) -> String { String::new() }").unwrap();
+        assert!(migration_attribute_quoted(args, item).is_err());
+
+        assert!(syn::parse_str::<MigrationArgs>("name = \"add_users\"").is_err());
+        assert!(syn::parse_str::<MigrationArgs>("version = 7, prefix = \"U\"").is_err());
+    }
 }